@@ -11,4 +11,7 @@ pub use x86_64::init;
 pub use x86_64::NAME;
 
 #[cfg(target_arch = "x86_64")]
-pub use x86_64::CpuContext;
+pub use x86_64::{
+    is_user_context, new_kernel_context, new_user_context, reschedule, set_kernel_stack,
+    CpuContext, TICK_INTERVAL,
+};
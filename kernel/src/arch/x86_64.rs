@@ -6,8 +6,13 @@ mod init;
 mod interrupts;
 pub mod mp;
 pub mod shutdown;
+mod syscall;
 pub use init::init;
 
 pub const NAME: &str = "x86_64";
 
-pub use interrupts::CpuContext;
+pub use interrupts::{
+    io_apic, irq, is_user_context, new_kernel_context, new_user_context, reschedule, CpuContext,
+    TICK_INTERVAL,
+};
+pub use syscall::set_kernel_stack;
@@ -10,6 +10,7 @@ pub enum AcpiError {
     MapRspdError(NewMapError),
     MapRsdtError(NewMapError),
     MapEntryError(NewMapError),
+    MapIoApicError(NewMapError),
 }
 
 impl Debug for AcpiError {
@@ -38,6 +39,9 @@ impl Debug for AcpiError {
                     "Could not map the Root system description table ({inner:?})"
                 )
             }
+            AcpiError::MapIoApicError(inner) => {
+                write!(f, "Could not map the registers of an I/O APIC ({inner:?})")
+            }
         }
     }
 }
@@ -13,18 +13,99 @@ use super::AcpiError;
 
 pub struct AcpiProcessor {
     id: u8,
+    apic_id: u8,
+}
+
+pub struct AcpiIoApic {
+    id: u8,
+    address: PhysicalAddress,
+    gsi_base: u32,
+}
+
+impl AcpiIoApic {
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// The (identity mapped) address of the registers.
+    pub fn address(&self) -> PhysicalAddress {
+        self.address
+    }
+
+    pub fn gsi_base(&self) -> u32 {
+        self.gsi_base
+    }
+}
+
+/// The global system interrupt that an ISA interrupt is connected to, and how it is signaled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AcpiIsaInterrupt {
+    pub irq: u8,
+    pub gsi: u32,
+    pub active_low: bool,
+    pub level_triggered: bool,
+}
+
+impl AcpiIsaInterrupt {
+    /// ISA interrupts are active high and edge triggered, unless they are overridden.
+    const fn identity(irq: u8) -> Self {
+        Self {
+            irq,
+            gsi: irq as u32,
+            active_low: false,
+            level_triggered: false,
+        }
+    }
 }
 
 pub struct AcpiInfo {
     oem_id: Option<&'static str>,
     local_apic_ptr: Option<PhysicalAddress>,
     processors: Box<[AcpiProcessor]>,
+    io_apics: Box<[AcpiIoApic]>,
+    isa_overrides: Box<[AcpiIsaInterrupt]>,
 }
 
 impl AcpiInfo {
     pub fn processor_count(&self) -> usize {
         self.processors.len()
     }
+
+    /// The local APIC ids of the processors, the index of a processor is its position.
+    pub fn apic_ids(&self) -> impl Iterator<Item = u8> + '_ {
+        self.processors.iter().map(|processor| processor.apic_id)
+    }
+
+    /// The index of the processor with `apic_id`.
+    pub fn processor_index(&self, apic_id: u8) -> Option<usize> {
+        self.apic_ids().position(|id| id == apic_id)
+    }
+
+    pub fn io_apics(&self) -> &[AcpiIoApic] {
+        &self.io_apics
+    }
+
+    /// How the ISA interrupt `irq` is connected to the I/O APIC.
+    pub fn isa_interrupt(&self, irq: u8) -> AcpiIsaInterrupt {
+        self.isa_overrides
+            .iter()
+            .find(|isa_override| isa_override.irq == irq)
+            .copied()
+            .unwrap_or(AcpiIsaInterrupt::identity(irq))
+    }
+
+    /// The ISA interrupt that is connected to `gsi`, if any.
+    pub fn gsi_isa_interrupt(&self, gsi: u32) -> Option<AcpiIsaInterrupt> {
+        if let Some(isa_override) = self.isa_overrides.iter().find(|x| x.gsi == gsi) {
+            return Some(*isa_override);
+        }
+
+        // An ISA interrupt that is overridden no longer uses its identity GSI.
+        let irq = u8::try_from(gsi).ok().filter(|irq| *irq < 16)?;
+        let interrupt = self.isa_interrupt(irq);
+
+        (interrupt.gsi == gsi).then_some(interrupt)
+    }
 }
 
 impl Display for AcpiInfo {
@@ -32,6 +113,7 @@ impl Display for AcpiInfo {
         writeln!(f, "Acpi info:")?;
 
         writeln!(f, "\tProcessor count: {:?}", self.processor_count())?;
+        writeln!(f, "\tI/O APIC count:  {:?}", self.io_apics.len())?;
 
         if let Some(oem) = self.oem_id {
             writeln!(f, "\tOEM:             {oem}")?;
@@ -84,6 +166,8 @@ pub unsafe fn parse_acpi(
 
     let mut local_apic_ptr = None;
     let mut processors = Vec::with_capacity(64);
+    let mut io_apics = Vec::new();
+    let mut isa_overrides = Vec::new();
 
     for entry_ptr in sdt_root.entries() {
         // Its not garanteed that the entry is mapped.
@@ -112,7 +196,14 @@ pub unsafe fn parse_acpi(
 
         match entry.kind() {
             RSDTEntryKind::Madt(madt) => {
-                parse_madt(madt, &mut processors, &mut local_apic_ptr)?;
+                parse_madt(
+                    madt,
+                    mapper,
+                    &mut processors,
+                    &mut io_apics,
+                    &mut isa_overrides,
+                    &mut local_apic_ptr,
+                )?;
             }
             RSDTEntryKind::Other(_) => {}
         }
@@ -122,12 +213,17 @@ pub unsafe fn parse_acpi(
         oem_id: header.oem_id(),
         local_apic_ptr,
         processors: processors.into_boxed_slice(),
+        io_apics: io_apics.into_boxed_slice(),
+        isa_overrides: isa_overrides.into_boxed_slice(),
     })
 }
 
 unsafe fn parse_madt(
     madt: &MADT,
+    mapper: &mut MemoryMapper,
     processors: &mut Vec<AcpiProcessor>,
+    io_apics: &mut Vec<AcpiIoApic>,
+    isa_overrides: &mut Vec<AcpiIsaInterrupt>,
     local_apic_ptr: &mut Option<PhysicalAddress>,
 ) -> Result<(), AcpiError> {
     for madt_entry in madt.entries() {
@@ -139,6 +235,34 @@ unsafe fn parse_madt(
 
                 processors.push(AcpiProcessor {
                     id: proc_local.processor_id(),
+                    apic_id: proc_local.apic_id(),
+                });
+            }
+            MADTEntryKind::IoApic(io_apic) => {
+                mapper
+                    .identity_map(
+                        io_apic.address(),
+                        MemoryMapper::PAGE_SIZE,
+                        MemoryProperties::MMIO_PAGE,
+                    )
+                    .map_err(AcpiError::MapIoApicError)?;
+
+                io_apics.push(AcpiIoApic {
+                    id: io_apic.io_apic_id(),
+                    address: io_apic.address(),
+                    gsi_base: io_apic.gsi_base(),
+                });
+            }
+            MADTEntryKind::InterruptSourceOverride(isa_override) => {
+                let identity = AcpiIsaInterrupt::identity(isa_override.source());
+
+                isa_overrides.push(AcpiIsaInterrupt {
+                    irq: isa_override.source(),
+                    gsi: isa_override.gsi(),
+                    active_low: isa_override.active_low().unwrap_or(identity.active_low),
+                    level_triggered: isa_override
+                        .level_triggered()
+                        .unwrap_or(identity.level_triggered),
                 });
             }
             MADTEntryKind::Other(_) => {}
@@ -148,3 +272,41 @@ unsafe fn parse_madt(
     *local_apic_ptr = Some(madt.local_apic());
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info_with_overrides(isa_overrides: Vec<AcpiIsaInterrupt>) -> AcpiInfo {
+        AcpiInfo {
+            oem_id: None,
+            local_apic_ptr: None,
+            processors: Box::new([]),
+            io_apics: Box::new([]),
+            isa_overrides: isa_overrides.into_boxed_slice(),
+        }
+    }
+
+    #[test_case]
+    fn test_isa_interrupt_overrides() {
+        let timer = AcpiIsaInterrupt {
+            irq: 0,
+            gsi: 2,
+            active_low: false,
+            level_triggered: false,
+        };
+
+        let info = info_with_overrides(alloc::vec![timer]);
+
+        assert_eq!(timer, info.isa_interrupt(0));
+        assert_eq!(AcpiIsaInterrupt::identity(4), info.isa_interrupt(4));
+
+        assert_eq!(Some(timer), info.gsi_isa_interrupt(2));
+        assert_eq!(None, info.gsi_isa_interrupt(0));
+        assert_eq!(
+            Some(AcpiIsaInterrupt::identity(4)),
+            info.gsi_isa_interrupt(4)
+        );
+        assert_eq!(None, info.gsi_isa_interrupt(20));
+    }
+}
@@ -1,26 +1,39 @@
 use core::ptr::addr_of_mut;
 
+use alloc::{boxed::Box, vec};
 use essentials::spin::Singleton;
 use x86_64::{segmentation::*, PrivilegeLevel};
 
 pub const DOUBLE_FAULT_IST_INDEX: usize = 0;
 
-fn init_tss() -> TaskStateSegment {
-    let mut tss = TaskStateSegment::new();
+const STACK_SIZE: usize = 4096 * 16;
 
-    static mut DOUBLE_FAULT_STACK: [u8; 4096 * 16] = [0; 4096 * 16];
+fn new_tss(
+    double_fault_stack: &'static mut [u8],
+    privileged_stack: &'static mut [u8],
+) -> TaskStateSegment {
+    let mut tss = TaskStateSegment::new();
 
     tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX] =
-        TssStackPointer::from_slice(unsafe { &mut *addr_of_mut!(DOUBLE_FAULT_STACK) });
-
-    static mut PRIVILEGED_STACK: [u8; 4096 * 16] = [0; 4096 * 16]; // TODO: dynamically allocate this.
+        TssStackPointer::from_slice(double_fault_stack);
 
-    tss.privilege_stack_table[0] =
-        TssStackPointer::from_slice(unsafe { &mut *addr_of_mut!(PRIVILEGED_STACK) });
+    tss.privilege_stack_table[0] = TssStackPointer::from_slice(privileged_stack);
 
     tss
 }
 
+fn init_tss() -> TaskStateSegment {
+    static mut DOUBLE_FAULT_STACK: [u8; STACK_SIZE] = [0; STACK_SIZE];
+    static mut PRIVILEGED_STACK: [u8; STACK_SIZE] = [0; STACK_SIZE]; // TODO: dynamically allocate this.
+
+    unsafe {
+        new_tss(
+            &mut *addr_of_mut!(DOUBLE_FAULT_STACK),
+            &mut *addr_of_mut!(PRIVILEGED_STACK),
+        )
+    }
+}
+
 pub static TSS: Singleton<TaskStateSegment> = Singleton::new(init_tss);
 
 pub struct FullGdt {
@@ -35,6 +48,16 @@ pub struct FullGdt {
 }
 
 impl FullGdt {
+    /// Create the GDT of an application processor, which needs its own TSS and stacks.
+    ///
+    /// The selectors are the same as the ones of [`GDT`]. The GDT is never deallocated.
+    pub fn new_for_slave() -> &'static Self {
+        let new_stack = || Box::leak(vec![0; STACK_SIZE].into_boxed_slice());
+        let tss = Box::leak(Box::new(new_tss(new_stack(), new_stack())));
+
+        Box::leak(Box::new(new_gdt(tss)))
+    }
+
     /// Loads the GDT and sets all segments (as kernel) to point to the GDT.
     pub fn load(&'static self) {
         self.table.load();
@@ -42,15 +65,15 @@ impl FullGdt {
         // Safety: we know that the segments point to a valid GDT as we loaded it just above.
         // And since self is static, we know it will remain that way
         unsafe {
-            GDT.kernel_code.load_into_cs();
-            GDT.kernel_data.load_into_ss();
-            GDT.kernel_data.load_into_ds();
-            GDT.tss.load_into_tss();
+            self.kernel_code.load_into_cs();
+            self.kernel_data.load_into_ss();
+            self.kernel_data.load_into_ds();
+            self.tss.load_into_tss();
         }
     }
 }
 
-fn init_gdt() -> FullGdt {
+fn new_gdt(tss: &'static TaskStateSegment) -> FullGdt {
     let mut table = GlobalDescriptorTable::new();
 
     let kernel_code = table.add_entry(SegmentDescriptor::KERNEL_CODE).unwrap();
@@ -62,7 +85,7 @@ fn init_gdt() -> FullGdt {
     // User code is required by sysret to be the next entry after user data.
     let user_code = table.add_entry(SegmentDescriptor::USER_CODE).unwrap();
 
-    let tss = table.add_entry(SegmentDescriptor::new_tss(&TSS)).unwrap();
+    let tss = table.add_entry(SegmentDescriptor::new_tss(tss)).unwrap();
 
     FullGdt {
         table,
@@ -76,4 +99,8 @@ fn init_gdt() -> FullGdt {
     }
 }
 
+fn init_gdt() -> FullGdt {
+    new_gdt(&TSS)
+}
+
 pub static GDT: Singleton<FullGdt> = Singleton::new(init_gdt);
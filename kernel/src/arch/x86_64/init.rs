@@ -1,12 +1,14 @@
+use alloc::sync::Arc;
 use bootinfo::BootInfo;
 use x86_64::cpuid;
 
 use crate::{info_print, info_println, memory::map::MemoryMapper, warning_println};
 
 use super::acpi::ACPI_INFO;
-use super::interrupts::init_interrupt_control;
+use super::interrupts::irq::{register_irq, IrqSharing};
 use super::interrupts::IDT;
-use super::{acpi::init_acpi, gdt::GDT};
+use super::interrupts::{init_interrupt_control, uart_status_change, UART_IRQ};
+use super::{acpi::init_acpi, gdt::GDT, syscall};
 
 // Initialize x86_64 specific stuff.
 pub unsafe fn init(bootinfo: &BootInfo, mapper: &mut MemoryMapper) {
@@ -24,6 +26,22 @@ pub unsafe fn init(bootinfo: &BootInfo, mapper: &mut MemoryMapper) {
 
     init_interrupt_control();
 
+    // The id of the processor is known once the interrupt control is initialized.
+    #[cfg(feature = "lock_debug")]
+    {
+        essentials::spin::set_processor_id(super::mp::processor_id);
+        essentials::spin::set_interrupts_enabled(|| x86_64::RFlags::read().interrupts_enabled());
+    }
+
     GDT.load();
     IDT.load();
+
+    register_irq(
+        UART_IRQ as u8,
+        IrqSharing::Shared,
+        Arc::new(uart_status_change),
+    )
+    .expect("registering the UART interrupt should never fail in init()");
+
+    syscall::init();
 }
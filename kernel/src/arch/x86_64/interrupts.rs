@@ -1,10 +1,13 @@
 mod cpu_ctx;
 mod handlers;
 mod int_control;
+pub mod io_apic;
+pub mod irq;
 pub mod isr_wrapper;
 
 use super::gdt::*;
 pub use cpu_ctx::*;
+pub use handlers::uart_status_change;
 use handlers::*;
 pub use int_control::*;
 
@@ -13,6 +16,11 @@ use x86_64::interrupt::*;
 
 pub const IRQ_START: usize = InterruptDescriptorTable::STANDARD_INTERRUPTS_COUNT;
 pub const TIMER_IRQ: usize = IRQ_START;
+/// The ISA interrupt of the first serial port.
+pub const UART_ISA_IRQ: u8 = 0x04;
+pub const UART_IRQ: usize = IRQ_START + UART_ISA_IRQ as usize;
+/// The software interrupt that switches to the next thread, see [`reschedule`].
+pub const RESCHEDULE_VECTOR: usize = 0xfe;
 
 fn init_idt() -> InterruptDescriptorTable {
     let kernel_segment = GDT.kernel_code;
@@ -31,12 +39,22 @@ fn init_idt() -> InterruptDescriptorTable {
     idt.page_fault
         .set_handler(kernel_segment, page_fault_handler);
 
-    idt[InterruptDescriptorTable::STANDARD_INTERRUPTS_COUNT + 0x04]
-        .set_handler(kernel_segment, uart_status_change_isr);
+    irq::set_dispatch_handlers(&mut idt);
 
     idt[TIMER_IRQ].set_handler(kernel_segment, tick_isr);
+    idt[RESCHEDULE_VECTOR].set_handler(kernel_segment, reschedule_isr);
 
     idt
 }
 
 pub static IDT: Singleton<InterruptDescriptorTable> = Singleton::new(init_idt);
+
+/// Let the scheduler switch to the next thread, the current thread continues once it is scheduled
+/// again. This also works while interrupts are disabled.
+///
+/// Must not be called while handling a system call, since the entry of system calls cannot be
+/// interrupted by a context switch.
+pub fn reschedule() {
+    // Safety: the handler of the vector only switches the context.
+    unsafe { core::arch::asm!("int {vector}", vector = const RESCHEDULE_VECTOR) };
+}
@@ -1,3 +1,46 @@
-use x86_64::interrupt::InterruptedContext;
+use essentials::address::VirtualAddress;
+use x86_64::{
+    interrupt::{InterruptStackFrame, InterruptedContext},
+    PrivilegeLevel, RFlags,
+};
+
+use crate::arch::x86_64::gdt::GDT;
 
 pub type CpuContext = InterruptedContext;
+
+/// The context of a new thread that starts executing user code at `entry` with `stack`.
+pub fn new_user_context(entry: VirtualAddress, stack: VirtualAddress) -> CpuContext {
+    // Safety: the user selectors point to valid ring 3 segments in the GDT.
+    unsafe {
+        CpuContext::start_new(InterruptStackFrame::new(
+            entry.as_u64(),
+            stack.as_u64(),
+            RFlags::INTERRUPTS_ENABLED,
+            GDT.user_code,
+            GDT.user_data,
+        ))
+    }
+}
+
+/// The context of a new kernel thread that starts executing `entry` with `stack`, as if `entry`
+/// was called.
+pub fn new_kernel_context(entry: extern "C" fn() -> !, stack: VirtualAddress) -> CpuContext {
+    // The return address that a call would push.
+    let stack = stack.align_down(16) - 8usize;
+
+    // Safety: the kernel selectors point to valid ring 0 segments in the GDT.
+    unsafe {
+        CpuContext::start_new(InterruptStackFrame::new(
+            entry as usize as u64,
+            stack.as_u64(),
+            RFlags::INTERRUPTS_ENABLED,
+            GDT.kernel_code,
+            GDT.kernel_data,
+        ))
+    }
+}
+
+/// Whether `ctx` was executing user code, rather than the kernel, when it was interrupted.
+pub fn is_user_context(ctx: &CpuContext) -> bool {
+    ctx.interrupt_stack_frame().code_segment & 3 == PrivilegeLevel::Ring3 as u64
+}
@@ -1,4 +1,4 @@
-use super::{InterruptControl, INTERRUPT_CONTROL};
+use super::INTERRUPT_CONTROL;
 use crate::{
     interface::interrupts as kernel_interface,
     memory::map::{MemoryAccess, MemoryViolation, PageFault},
@@ -45,15 +45,16 @@ pub fn page_fault(ctx: &InterruptErrorContext<PageFaultErrorCode>) -> Option<Int
         addr,
         access,
         violation,
+        user_mode: ctx.error.user_mode(),
     };
 
-    kernel_interface::page_fault(fault)
+    kernel_interface::page_fault(fault, ctx.context.clone())
 }
 
-fn uart_status_change(_ctx: &InterruptedContext) -> Option<InterruptedContext> {
+pub fn uart_status_change() -> bool {
     kernel_interface::uart_status_change();
 
-    None
+    true
 }
 
 fn unhandled(_ctx: &InterruptedContext) -> Option<InterruptedContext> {
@@ -63,21 +64,19 @@ fn unhandled(_ctx: &InterruptedContext) -> Option<InterruptedContext> {
 }
 
 fn tick(ctx: &InterruptedContext) -> Option<InterruptedContext> {
-    let new_ctx = kernel_interface::tick(ctx.clone());
+    let new_ctx = kernel_interface::tick(ctx.clone(), super::is_user_context(ctx));
 
-    match &*INTERRUPT_CONTROL {
-        InterruptControl::Pic(pic) => {
-            pic.end_of_interrupt(super::TIMER_IRQ as u8);
-        }
-        InterruptControl::Apic(apic) => {
-            apic.end_of_interrupt();
-        }
-    }
+    INTERRUPT_CONTROL.end_of_interrupt(super::TIMER_IRQ as u8);
 
     Some(new_ctx)
 }
 
-crate::wrap_isr!(uart_status_change, uart_status_change_isr);
+/// The reschedule interrupt is raised by software, so it has no end of interrupt.
+fn reschedule(ctx: &InterruptedContext) -> Option<InterruptedContext> {
+    Some(kernel_interface::reschedule(ctx.clone()))
+}
+
 crate::wrap_isr!(unhandled, unhandled_isr);
 crate::wrap_isr!(tick, tick_isr);
+crate::wrap_isr!(reschedule, reschedule_isr);
 crate::wrap_error_isr!(page_fault, page_fault_handler, PageFaultErrorCode);
@@ -1,4 +1,7 @@
-use core::time::Duration;
+use core::{
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
 
 use essentials::PanicOnce;
 use x86_64::{
@@ -8,22 +11,44 @@ use x86_64::{
     interrupt::*,
 };
 
-use crate::arch::x86_64::{
-    acpi::{AcpiInfo, ACPI_INFO},
-    gdt::GDT,
-    interrupts::TIMER_IRQ,
+use crate::{
+    arch::x86_64::{
+        acpi::{AcpiInfo, ACPI_INFO},
+        gdt::GDT,
+        interrupts::{TIMER_IRQ, UART_IRQ, UART_ISA_IRQ},
+        mp::processor_id,
+    },
+    warning_println,
 };
 
+use super::io_apic::{init_io_apics, route_isa_irq};
+
 pub enum InterruptControl {
     Pic(ChainedPic8259),
     Apic(Apic),
 }
 
-const TIME_SLICE: Duration = Duration::from_millis(4);
+/// The time between two ticks of the timer.
+pub const TICK_INTERVAL: Duration = Duration::from_millis(4);
 const CALIBRATION_TIME: Duration = Duration::from_micros(500);
+const APIC_DIVIDER: u32 = 3;
 
 pub static INTERRUPT_CONTROL: PanicOnce<InterruptControl> = PanicOnce::new();
 
+impl InterruptControl {
+    /// Signal the end of the interrupt `irq` to the controller that delivered it.
+    pub fn end_of_interrupt(&self, irq: u8) {
+        match self {
+            InterruptControl::Pic(pic) => pic.end_of_interrupt(irq),
+            InterruptControl::Apic(apic) => apic.end_of_interrupt(),
+        }
+    }
+}
+
+/// The initial count of the APIC timer for a tick, as calibrated by the bootstrap
+/// processor. The other processors use the same count.
+static APIC_TIMER_COUNT: AtomicU32 = AtomicU32::new(0);
+
 unsafe fn wait_pit_tick(pic: &mut ChainedPic8259, mut before: impl FnMut()) {
     static PIC: PanicOnce<&'static mut ChainedPic8259> = PanicOnce::new();
 
@@ -56,12 +81,10 @@ unsafe fn wait_pit_tick(pic: &mut ChainedPic8259, mut before: impl FnMut()) {
 }
 
 unsafe fn init_apic(_acpi_info: &AcpiInfo, pic: &mut ChainedPic8259, pit: &mut Pit) -> Apic {
-    let calibration_ratio = (TIME_SLICE.as_nanos() / CALIBRATION_TIME.as_nanos()) as u32;
-
-    let divider = 3;
+    let calibration_ratio = (TICK_INTERVAL.as_nanos() / CALIBRATION_TIME.as_nanos()) as u32;
 
     let mut apic = Apic::from_msr();
-    apic.enable(divider);
+    apic.enable(APIC_DIVIDER);
 
     let start_count = u32::MAX;
 
@@ -74,10 +97,10 @@ unsafe fn init_apic(_acpi_info: &AcpiInfo, pic: &mut ChainedPic8259, pit: &mut P
 
     pic.allow_none();
 
-    apic.set_periodic_mode(
-        TIMER_IRQ as u32,
-        (start_count - end_count) * calibration_ratio,
-    );
+    let timer_count = (start_count - end_count) * calibration_ratio;
+    APIC_TIMER_COUNT.store(timer_count, Ordering::Relaxed);
+
+    apic.set_periodic_mode(TIMER_IRQ as u32, timer_count);
 
     apic
 }
@@ -101,10 +124,36 @@ pub unsafe fn init_interrupt_control() {
             let apic = init_apic(info, &mut pic, &mut pit);
 
             INTERRUPT_CONTROL.initialize_with(InterruptControl::Apic(apic));
+
+            // The legacy PIC is masked, so the devices have to be routed through the I/O APIC.
+            init_io_apics(info);
+
+            if let Err(err) = route_isa_irq(UART_ISA_IRQ, UART_IRQ as u8, processor_id()) {
+                warning_println!("Could not route the UART interrupt: {err:?}");
+            }
+
             return;
         }
     }
 
-    pit.set_interval(TIME_SLICE);
+    pit.set_interval(TICK_INTERVAL);
     INTERRUPT_CONTROL.initialize_with(InterruptControl::Pic(pic));
 }
+
+/// Initialize the local APIC of an application processor, with the same timer as the bootstrap
+/// processor.
+///
+/// # Safety
+///
+/// [`init_interrupt_control`] must have initialized the APIC of the bootstrap processor, and
+/// interrupts must be disabled until the IDT is loaded.
+pub unsafe fn init_slave_interrupt_control() {
+    assert!(
+        matches!(&*INTERRUPT_CONTROL, InterruptControl::Apic(_)),
+        "application processors can only be started with an APIC"
+    );
+
+    let mut apic = Apic::from_msr();
+    apic.enable(APIC_DIVIDER);
+    apic.set_periodic_mode(TIMER_IRQ as u32, APIC_TIMER_COUNT.load(Ordering::Relaxed));
+}
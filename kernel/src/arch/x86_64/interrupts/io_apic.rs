@@ -0,0 +1,136 @@
+//! Routing of device interrupts through the I/O APICs, when the interrupts are controlled by the
+//! APIC.
+//!
+//! Devices are identified by their global system interrupt (GSI). Every GSI starts masked, until
+//! it is routed to a vector of a processor.
+
+mod error;
+
+use alloc::{boxed::Box, vec::Vec};
+use essentials::{spin::SpinLock, PanicOnce};
+use x86_64::device::{IoApic, RedirectionEntry};
+
+use crate::{
+    arch::x86_64::acpi::{AcpiInfo, ACPI_INFO},
+    utils::InterruptGuard,
+};
+
+pub use error::IoApicError;
+
+struct IoApicEntry {
+    gsi_base: u32,
+    count: u32,
+    io_apic: InterruptGuard<SpinLock<IoApic>>,
+}
+
+static IO_APICS: PanicOnce<Box<[IoApicEntry]>> = PanicOnce::new();
+
+/// Mask every interrupt of the I/O APICs in `info`.
+///
+/// # Safety
+///
+/// The registers of the I/O APICs must be identity mapped.
+pub(super) unsafe fn init_io_apics(info: &AcpiInfo) {
+    let io_apics: Vec<_> = info
+        .io_apics()
+        .iter()
+        .map(|io_apic| {
+            let mut device = IoApic::new(io_apic.address());
+            let count = device.redirection_count();
+
+            for index in 0..count {
+                let mut entry = device.redirection(index);
+                entry.masked = true;
+                device.set_redirection(index, entry);
+            }
+
+            IoApicEntry {
+                gsi_base: io_apic.gsi_base(),
+                count,
+                io_apic: InterruptGuard::new_lock(device),
+            }
+        })
+        .collect();
+
+    IO_APICS.initialize_with(io_apics.into_boxed_slice());
+}
+
+/// Route `gsi` to `vector` on the processor with id `processor`, and unmask it.
+///
+/// The polarity and trigger mode come from the ACPI tables for ISA interrupts, other interrupts
+/// are assumed to be PCI interrupts, which are active low and level triggered.
+pub fn route_gsi(gsi: u32, vector: u8, processor: usize) -> Result<(), IoApicError> {
+    let info = ACPI_INFO.as_ref().ok_or(IoApicError::Unavailable)?;
+    let destination = apic_id(info, processor)?;
+
+    let (active_low, level_triggered) = match info.gsi_isa_interrupt(gsi) {
+        Some(isa) => (isa.active_low, isa.level_triggered),
+        None => (true, true),
+    };
+
+    modify_gsi(gsi, |entry| {
+        *entry = RedirectionEntry {
+            vector,
+            destination,
+            active_low,
+            level_triggered,
+            masked: false,
+        }
+    })
+}
+
+/// Route the ISA interrupt `irq` to `vector` on the processor with id `processor`, and unmask it.
+///
+/// Returns the GSI of the interrupt, which can differ from `irq`.
+pub fn route_isa_irq(irq: u8, vector: u8, processor: usize) -> Result<u32, IoApicError> {
+    let info = ACPI_INFO.as_ref().ok_or(IoApicError::Unavailable)?;
+    let gsi = info.isa_interrupt(irq).gsi;
+
+    route_gsi(gsi, vector, processor)?;
+
+    Ok(gsi)
+}
+
+pub fn mask_gsi(gsi: u32) -> Result<(), IoApicError> {
+    modify_gsi(gsi, |entry| entry.masked = true)
+}
+
+pub fn unmask_gsi(gsi: u32) -> Result<(), IoApicError> {
+    modify_gsi(gsi, |entry| entry.masked = false)
+}
+
+/// Deliver `gsi` to the processor with id `processor` from now on.
+pub fn set_gsi_processor(gsi: u32, processor: usize) -> Result<(), IoApicError> {
+    let info = ACPI_INFO.as_ref().ok_or(IoApicError::Unavailable)?;
+    let destination = apic_id(info, processor)?;
+
+    modify_gsi(gsi, |entry| entry.destination = destination)
+}
+
+fn apic_id(info: &AcpiInfo, processor: usize) -> Result<u8, IoApicError> {
+    info.apic_ids()
+        .nth(processor)
+        .ok_or(IoApicError::InvalidProcessor(processor))
+}
+
+fn modify_gsi(gsi: u32, modify: impl FnOnce(&mut RedirectionEntry)) -> Result<(), IoApicError> {
+    if !IO_APICS.is_initialized() {
+        return Err(IoApicError::Unavailable);
+    }
+
+    let io_apic = IO_APICS
+        .iter()
+        .find(|io_apic| (io_apic.gsi_base..io_apic.gsi_base + io_apic.count).contains(&gsi))
+        .ok_or(IoApicError::InvalidGsi(gsi))?;
+
+    let index = gsi - io_apic.gsi_base;
+
+    let guard = io_apic.io_apic.guard();
+    let mut device = guard.lock();
+
+    let mut entry = device.redirection(index);
+    modify(&mut entry);
+    device.set_redirection(index, entry);
+
+    Ok(())
+}
@@ -0,0 +1,9 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoApicError {
+    /// The interrupts are controlled by the legacy PIC.
+    Unavailable,
+    /// None of the I/O APICs handles the global system interrupt.
+    InvalidGsi(u32),
+    /// There is no processor with the id.
+    InvalidProcessor(usize),
+}
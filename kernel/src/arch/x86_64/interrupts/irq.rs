@@ -0,0 +1,269 @@
+//! Registration of the interrupt handlers of drivers.
+//!
+//! Every vector after the timer has an interrupt handler that calls the handlers registered for
+//! it, and signals the end of the interrupt afterwards, for both the PIC and the APIC. A vector
+//! can be claimed by a single handler, or shared by several handlers that each check whether
+//! their device raised the interrupt.
+
+mod error;
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use alloc::{sync::Arc, vec::Vec};
+use essentials::{address::VirtualAddress, spin::SpinLock};
+use x86_64::interrupt::{InterruptDescriptorTable, InterruptErrorContext, InterruptedContext};
+
+use crate::{interface::interrupts as kernel_interface, utils::InterruptGuard};
+
+use super::{INTERRUPT_CONTROL, IRQ_START, RESCHEDULE_VECTOR, TIMER_IRQ};
+
+pub use error::IrqError;
+
+/// The handler of a device interrupt.
+pub trait IrqHandler: Send + Sync {
+    /// Handle the interrupt, returns whether the device of the handler raised it.
+    fn handle_irq(&self) -> bool;
+}
+
+impl<F> IrqHandler for F
+where
+    F: Fn() -> bool + Send + Sync,
+{
+    fn handle_irq(&self) -> bool {
+        self()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrqSharing {
+    /// The handler is the only handler of the vector.
+    Exclusive,
+    /// Other shared handlers may be registered for the same vector.
+    Shared,
+}
+
+/// Identifies a registered handler, to unregister it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct IrqRegistration {
+    vector: u8,
+    id: u64,
+}
+
+impl IrqRegistration {
+    pub fn vector(&self) -> u8 {
+        self.vector
+    }
+}
+
+#[derive(Clone)]
+struct RegisteredHandler {
+    id: u64,
+    sharing: IrqSharing,
+    handler: Arc<dyn IrqHandler>,
+}
+
+/// The first vector that can be registered, the vectors before it are exceptions or the timer.
+pub const FIRST_DYNAMIC_VECTOR: u8 = TIMER_IRQ as u8 + 1;
+/// The first vector that [`allocate_irq`] hands out, the vectors before it are left to the legacy
+/// ISA interrupts.
+const FIRST_ALLOCATED_VECTOR: u8 = IRQ_START as u8 + 16;
+/// The vectors from here are reserved for rescheduling and the spurious interrupts of the APIC.
+const RESERVED_VECTORS_START: u8 = RESCHEDULE_VECTOR as u8;
+
+const VECTOR_COUNT: usize = 256;
+
+/// The handlers of a vector, they are replaced as a whole, so the interrupt handler only holds the
+/// lock while it clones the `Arc`.
+type VectorHandlers = Option<Arc<[RegisteredHandler]>>;
+
+static HANDLERS: [InterruptGuard<SpinLock<VectorHandlers>>; VECTOR_COUNT] =
+    [const { InterruptGuard::new_lock(None) }; VECTOR_COUNT];
+
+static ID_AUTOINCREMENT: AtomicU64 = AtomicU64::new(0);
+
+/// Register `handler` for `vector`.
+pub fn register_irq(
+    vector: u8,
+    sharing: IrqSharing,
+    handler: Arc<dyn IrqHandler>,
+) -> Result<IrqRegistration, IrqError> {
+    if !(FIRST_DYNAMIC_VECTOR..RESERVED_VECTORS_START).contains(&vector) {
+        return Err(IrqError::ReservedVector(vector));
+    }
+
+    let guard = HANDLERS[vector as usize].guard();
+    let mut handlers = guard.lock();
+
+    let current = handlers.as_deref().unwrap_or_default();
+
+    let conflicts = current.iter().any(|registered| {
+        registered.sharing == IrqSharing::Exclusive || sharing == IrqSharing::Exclusive
+    });
+
+    if conflicts {
+        return Err(IrqError::VectorInUse(vector));
+    }
+
+    let id = ID_AUTOINCREMENT.fetch_add(1, Ordering::Relaxed);
+
+    let mut new_handlers: Vec<_> = current.to_vec();
+    new_handlers.push(RegisteredHandler {
+        id,
+        sharing,
+        handler,
+    });
+
+    *handlers = Some(new_handlers.into());
+
+    Ok(IrqRegistration { vector, id })
+}
+
+/// Claim a vector that has no handlers yet, for a device that can be programmed with any vector.
+pub fn allocate_irq(handler: Arc<dyn IrqHandler>) -> Result<IrqRegistration, IrqError> {
+    for vector in FIRST_ALLOCATED_VECTOR..RESERVED_VECTORS_START {
+        match register_irq(vector, IrqSharing::Exclusive, handler.clone()) {
+            Err(IrqError::VectorInUse(_)) => continue,
+            result => return result,
+        }
+    }
+
+    Err(IrqError::OutOfVectors)
+}
+
+pub fn unregister_irq(registration: IrqRegistration) -> Result<(), IrqError> {
+    let guard = HANDLERS[registration.vector as usize].guard();
+    let mut handlers = guard.lock();
+
+    let current = handlers.as_deref().unwrap_or_default();
+
+    if !current
+        .iter()
+        .any(|registered| registered.id == registration.id)
+    {
+        return Err(IrqError::NotRegistered);
+    }
+
+    let remaining: Vec<_> = current
+        .iter()
+        .filter(|registered| registered.id != registration.id)
+        .cloned()
+        .collect();
+
+    *handlers = (!remaining.is_empty()).then(|| remaining.into());
+
+    Ok(())
+}
+
+/// The vector that the stub of a vector pushes in place of an error code, so every vector can
+/// share [`dispatch_isr`].
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct IrqVector {
+    value: u64,
+}
+
+fn dispatch(ctx: &InterruptErrorContext<IrqVector>) -> Option<InterruptedContext> {
+    let vector = ctx.error.value as u8;
+
+    let handlers = {
+        let guard = HANDLERS[vector as usize].guard();
+        let handlers = guard.lock();
+        handlers.clone()
+    };
+
+    let mut handled = false;
+
+    for registered in handlers.as_deref().unwrap_or_default() {
+        handled |= registered.handler.handle_irq();
+    }
+
+    if !handled {
+        kernel_interface::unhandled_irq();
+    }
+
+    INTERRUPT_CONTROL.end_of_interrupt(vector);
+
+    None
+}
+
+crate::wrap_error_isr!(dispatch, dispatch_isr, IrqVector);
+
+/// Every stub is aligned to 16 bytes.
+const STUB_SIZE: usize = 16;
+
+extern "C" {
+    static irq_dispatch_stubs: u8;
+}
+
+// A stub for every vector from `FIRST_DYNAMIC_VECTOR`, which pushes the vector and jumps to
+// `dispatch_isr`.
+core::arch::global_asm!(
+    ".p2align 4",
+    ".global irq_dispatch_stubs",
+    "irq_dispatch_stubs:",
+    ".set irq_stub_vector, {first}",
+    ".rept {count}",
+    ".p2align 4",
+    // push imm32, the vectors above 0x7f do not fit in a sign extended imm8.
+    ".byte 0x68",
+    ".long irq_stub_vector",
+    "jmp {dispatch}",
+    ".set irq_stub_vector, irq_stub_vector + 1",
+    ".endr",
+    first = const FIRST_DYNAMIC_VECTOR,
+    count = const RESERVED_VECTORS_START - FIRST_DYNAMIC_VECTOR,
+    dispatch = sym dispatch_isr,
+);
+
+/// Point every vector from [`FIRST_DYNAMIC_VECTOR`] to the handler that calls the registered
+/// handlers.
+pub(super) fn set_dispatch_handlers(idt: &mut InterruptDescriptorTable) {
+    let kernel_segment = super::GDT.kernel_code;
+    let stubs = VirtualAddress::from(unsafe { &irq_dispatch_stubs as *const u8 });
+
+    for vector in FIRST_DYNAMIC_VECTOR..RESERVED_VECTORS_START {
+        let stub = stubs + (vector - FIRST_DYNAMIC_VECTOR) as usize * STUB_SIZE;
+
+        // Safety: the stub pushes a fake error code and jumps to an error interrupt handler.
+        unsafe { idt[vector as usize].set_handler_at(kernel_segment, stub) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_VECTOR: u8 = RESERVED_VECTORS_START - 1;
+
+    #[test_case]
+    fn test_register_and_unregister() {
+        let handler: Arc<dyn IrqHandler> = Arc::new(|| true);
+
+        assert_eq!(
+            Err(IrqError::ReservedVector(TIMER_IRQ as u8)),
+            register_irq(TIMER_IRQ as u8, IrqSharing::Shared, handler.clone())
+        );
+
+        let first = register_irq(TEST_VECTOR, IrqSharing::Shared, handler.clone()).unwrap();
+        let second = register_irq(TEST_VECTOR, IrqSharing::Shared, handler.clone()).unwrap();
+
+        assert_eq!(
+            Err(IrqError::VectorInUse(TEST_VECTOR)),
+            register_irq(TEST_VECTOR, IrqSharing::Exclusive, handler.clone())
+        );
+
+        let first_id = first.id;
+        assert_eq!(Ok(()), unregister_irq(first));
+        assert_eq!(
+            Err(IrqError::NotRegistered),
+            unregister_irq(IrqRegistration {
+                vector: TEST_VECTOR,
+                id: first_id
+            })
+        );
+        assert_eq!(Ok(()), unregister_irq(second));
+
+        let exclusive = register_irq(TEST_VECTOR, IrqSharing::Exclusive, handler).unwrap();
+        assert_eq!(Ok(()), unregister_irq(exclusive));
+    }
+}
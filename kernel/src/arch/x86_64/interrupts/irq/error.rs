@@ -0,0 +1,11 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrqError {
+    /// The vector is an exception, or is reserved by the kernel.
+    ReservedVector(u8),
+    /// The vector is claimed by a handler that does not share it.
+    VectorInUse(u8),
+    /// Every vector that can be allocated is in use.
+    OutOfVectors,
+    /// The handler has already been unregistered.
+    NotRegistered,
+}
@@ -50,8 +50,18 @@ macro_rules! wrap_error_isr {
                 }
             }
 
+            // The error code is pushed below the interrupt stack frame, so the frame is copied
+            // below the error code to get the layout of `InterruptErrorContext`. `iretq` uses the
+            // copy and restores the stack pointer, which also discards the error code.
             unsafe {
-                $crate::_saved_handler_asm!(inner);
+                $crate::_saved_handler_asm!(
+                    inner,
+                    "push qword ptr [rsp + 40]",
+                    "push qword ptr [rsp + 40]",
+                    "push qword ptr [rsp + 40]",
+                    "push qword ptr [rsp + 40]",
+                    "push qword ptr [rsp + 40]"
+                );
             }
         }
     };
@@ -59,8 +69,9 @@ macro_rules! wrap_error_isr {
 
 #[macro_export]
 macro_rules! _saved_handler_asm {
-    ($inner:ident) => {
+    ($inner:ident $(, $prelude:literal)*) => {
         core::arch::asm!(
+            $($prelude,)*
             // save
             "push rax",
             "push rbx",
@@ -1,5 +1,38 @@
+mod trampoline;
+
+use core::{
+    sync::atomic::{AtomicU8, Ordering},
+    time::Duration,
+};
+
+use alloc::vec;
+use bootinfo::AP_TRAMPOLINE_ADDR;
+use essentials::PanicOnce;
+use x86_64::{device::Pit, halt_loop, paging::cr3};
+
+use crate::{memory::alloc::FRAME_ALLOC, warning_println};
+
 use super::acpi::ACPI_INFO;
-use super::interrupts::{InterruptControl, INTERRUPT_CONTROL};
+use super::gdt::FullGdt;
+use super::interrupts::{init_slave_interrupt_control, InterruptControl, IDT, INTERRUPT_CONTROL};
+use super::syscall;
+
+use trampoline::TrampolineData;
+
+/// The stack an application processor starts with, it becomes the stack of its first thread.
+const SLAVE_STACK_SIZE: usize = 4096 * 16;
+
+/// How long the bootstrap processor waits for an application processor to start.
+const STARTUP_TIMEOUT: Duration = Duration::from_millis(100);
+
+static SLAVE_MAIN: PanicOnce<fn(usize) -> !> = PanicOnce::new();
+static SLAVE_STATE: AtomicU8 = AtomicU8::new(SLAVE_WAITING);
+
+/// The bootstrap processor is waiting for the application processor to start.
+const SLAVE_WAITING: u8 = 0;
+const SLAVE_STARTED: u8 = 1;
+/// The application processor did not start in time, and the trampoline is used for the next one.
+const SLAVE_ABANDONED: u8 = 2;
 
 pub fn processor_count() -> usize {
     match &*ACPI_INFO {
@@ -11,8 +44,127 @@ pub fn processor_count() -> usize {
 pub fn processor_id() -> usize {
     match &*INTERRUPT_CONTROL {
         InterruptControl::Pic(_) => 0,
-        InterruptControl::Apic(apic) => apic.id() as usize,
+        InterruptControl::Apic(apic) => ACPI_INFO
+            .as_ref()
+            .and_then(|info| info.processor_index(apic.id() as u8))
+            .expect("the APIC id of a processor should be in the MADT"),
+    }
+}
+
+/// Start every processor besides the current one, one at a time, with INIT-SIPI-SIPI.
+///
+/// Every processor calls `slave_main` with its id, once it has its own GDT, TSS and APIC timer.
+/// Interrupts are disabled when `slave_main` is called.
+pub fn start_slave_processors(slave_main: fn(usize) -> !) {
+    let (Some(info), InterruptControl::Apic(apic)) = (&*ACPI_INFO, &*INTERRUPT_CONTROL) else {
+        return;
+    };
+
+    SLAVE_MAIN.initialize_with(slave_main);
+
+    let cr3 = cr3::active_page().as_u64();
+    assert!(
+        cr3 < 1 << 32,
+        "the trampoline can only load a cr3 below 4 GiB"
+    );
+
+    let trampoline =
+        (AP_TRAMPOLINE_ADDR as usize + FRAME_ALLOC.physical_memory_offset()) as *mut u8;
+    let code = trampoline::code();
+
+    // Safety: the page of the trampoline is reserved by the pre-kernel.
+    unsafe { core::ptr::copy_nonoverlapping(code.as_ptr(), trampoline, code.len()) };
+
+    let mut pit = unsafe { Pit::new() };
+    let own_apic_id = apic.id() as u8;
+
+    for apic_id in info.apic_ids().filter(|id| *id != own_apic_id) {
+        let stack = vec![0u8; SLAVE_STACK_SIZE].leak();
+        let stack_top = (stack.as_ptr_range().end as u64) & !0xf;
+
+        // Safety: the previous processor no longer uses the data once it has started.
+        unsafe {
+            trampoline
+                .add(trampoline::DATA_OFFSET)
+                .cast::<TrampolineData>()
+                .write_unaligned(TrampolineData::new(cr3, stack_top, slave_entry));
+        }
+
+        SLAVE_STATE.store(SLAVE_WAITING, Ordering::Release);
+
+        let page = (AP_TRAMPOLINE_ADDR >> 12) as u8;
+
+        apic.send_init(apic_id as u32);
+        pit.sleep(Duration::from_millis(10));
+
+        apic.send_startup(apic_id as u32, page);
+        pit.sleep(Duration::from_micros(200));
+
+        // The second SIPI is only needed when the first one was missed.
+        if SLAVE_STATE.load(Ordering::Acquire) != SLAVE_STARTED {
+            apic.send_startup(apic_id as u32, page);
+        }
+
+        if !wait_for_slave(&mut pit) {
+            // The processor may still start late, and would then run on the stack of the next
+            // processor. It never claimed its start, so it holds no locks and can be parked with
+            // another INIT before the trampoline is reused.
+            apic.send_init(apic_id as u32);
+            pit.sleep(Duration::from_millis(10));
+
+            warning_println!("Processor with APIC id {apic_id} did not start");
+        }
     }
 }
 
-pub fn start_slave_processors(_startup: impl Fn(usize)) {}
+/// Wait until the processor started, or abandon it when it takes too long. Returns whether it
+/// started.
+fn wait_for_slave(pit: &mut Pit) -> bool {
+    let step = Duration::from_millis(1);
+
+    for _ in 0..(STARTUP_TIMEOUT.as_millis() / step.as_millis()) {
+        if SLAVE_STATE.load(Ordering::Acquire) == SLAVE_STARTED {
+            return true;
+        }
+
+        pit.sleep(step);
+    }
+
+    // The processor either claims its start first, or finds out it was abandoned.
+    SLAVE_STATE
+        .compare_exchange(
+            SLAVE_WAITING,
+            SLAVE_ABANDONED,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        )
+        .is_err()
+}
+
+/// The entry of an application processor, called by the trampoline in long mode.
+extern "C" fn slave_entry() -> ! {
+    // A processor that started too late shares its stack with the next processor, it stops before
+    // taking any lock, until it is parked by the bootstrap processor.
+    if SLAVE_STATE
+        .compare_exchange(
+            SLAVE_WAITING,
+            SLAVE_STARTED,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        )
+        .is_err()
+    {
+        halt_loop();
+    }
+
+    // Safety: interrupts are disabled since the trampoline, and the GDT is never deallocated.
+    unsafe {
+        FullGdt::new_for_slave().load();
+        IDT.load();
+
+        init_slave_interrupt_control();
+        syscall::init();
+    }
+
+    SLAVE_MAIN(processor_id())
+}
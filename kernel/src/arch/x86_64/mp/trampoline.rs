@@ -0,0 +1,115 @@
+//! The real mode code that an application processor starts with.
+//!
+//! It is copied to [`AP_TRAMPOLINE_ADDR`], which the pre-kernel identity maps. The processor
+//! enables long mode with the page table of the bootstrap processor, switches to the stack in the
+//! [`TrampolineData`] and calls its entry.
+
+use core::mem::offset_of;
+
+use bootinfo::AP_TRAMPOLINE_ADDR;
+
+/// The data that the trampoline needs, it is written right after the first jump of the code.
+#[repr(C)]
+pub struct TrampolineData {
+    /// A temporary GDT with a null, a 64-bit code and a data segment.
+    pub gdt: [u64; 3],
+    /// The level 4 page table, it has to be below 4 GiB.
+    pub cr3: u64,
+    pub stack: u64,
+    pub entry: u64,
+    /// The limit and the 32-bit base of `gdt`.
+    pub gdt_pointer: [u16; 3],
+}
+
+/// The offset of [`TrampolineData`] within the trampoline.
+pub const DATA_OFFSET: usize = 8;
+
+const DATA_ADDR: usize = AP_TRAMPOLINE_ADDR as usize + DATA_OFFSET;
+
+impl TrampolineData {
+    pub fn new(cr3: u64, stack: u64, entry: extern "C" fn() -> !) -> Self {
+        let gdt_base = (DATA_ADDR + offset_of!(TrampolineData, gdt)) as u32;
+
+        Self {
+            gdt: [0, 0x00af_9a00_0000_ffff, 0x00cf_9200_0000_ffff],
+            cr3,
+            stack,
+            entry: entry as usize as u64,
+            gdt_pointer: [
+                (core::mem::size_of::<[u64; 3]>() - 1) as u16,
+                gdt_base as u16,
+                (gdt_base >> 16) as u16,
+            ],
+        }
+    }
+}
+
+extern "C" {
+    static ap_trampoline_start: u8;
+    static ap_trampoline_end: u8;
+}
+
+/// The code of the trampoline, which has to be copied to [`AP_TRAMPOLINE_ADDR`].
+pub fn code() -> &'static [u8] {
+    unsafe {
+        let start = core::ptr::addr_of!(ap_trampoline_start);
+        let end = core::ptr::addr_of!(ap_trampoline_end);
+
+        core::slice::from_raw_parts(start, end as usize - start as usize)
+    }
+}
+
+// The processor starts at `AP_TRAMPOLINE_ADDR` with `cs` set to the page, so until the far jump
+// the data is addressed relative to the start of the trampoline.
+core::arch::global_asm!(
+    ".global ap_trampoline_start",
+    ".global ap_trampoline_end",
+    ".code16",
+    "ap_trampoline_start:",
+    "cli",
+    "cld",
+    // jump over the data, `jmp rel8` ends at offset 4.
+    ".byte 0xeb, {data_offset} - 4 + {data_size}",
+    ".skip {data_offset} - 4 + {data_size}",
+    "mov ax, cs",
+    "mov ds, ax",
+    // PAE and PGE
+    "mov eax, 0xa0",
+    "mov cr4, eax",
+    "mov eax, dword ptr [{cr3}]",
+    "mov cr3, eax",
+    // LME and NXE in EFER
+    "mov ecx, 0xc0000080",
+    "rdmsr",
+    "or eax, 0x900",
+    "wrmsr",
+    "lgdt [{gdt_pointer}]",
+    // PE, WP and PG
+    "mov eax, cr0",
+    "or eax, 0x80010001",
+    "mov cr0, eax",
+    // jmp far dword 0x08:5f
+    ".byte 0x66, 0xea",
+    ".long 5f - ap_trampoline_start + {base}",
+    ".word 0x08",
+    ".code64",
+    "5:",
+    "mov ax, 0x10",
+    "mov ds, ax",
+    "mov es, ax",
+    "mov ss, ax",
+    "mov rsp, qword ptr [{stack}]",
+    "mov rax, qword ptr [{entry}]",
+    "call rax",
+    "6:",
+    "hlt",
+    "jmp 6b",
+    "ap_trampoline_end:",
+    data_offset = const DATA_OFFSET,
+    data_size = const core::mem::size_of::<TrampolineData>(),
+    cr3 = const DATA_OFFSET + offset_of!(TrampolineData, cr3),
+    gdt_pointer = const DATA_OFFSET + offset_of!(TrampolineData, gdt_pointer),
+    stack = const DATA_ADDR + offset_of!(TrampolineData, stack),
+    entry = const DATA_ADDR + offset_of!(TrampolineData, entry),
+    base = const AP_TRAMPOLINE_ADDR,
+);
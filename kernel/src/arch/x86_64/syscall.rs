@@ -0,0 +1,168 @@
+use core::{
+    mem::offset_of,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use essentials::{address::VirtualAddress, PanicOnce};
+use x86_64::{wrmsr, RFlags};
+
+use crate::{arch::CpuContext, interface::syscalls, utils::ProcLocal};
+
+use super::gdt::GDT;
+
+/// The data that the system call entry needs, before it can use its own stack.
+///
+/// While in user mode, `KERNEL_GS_BASE` points to the entry of the current processor. The entry
+/// uses `swapgs` to access it, and swaps back before it calls the handler.
+#[repr(C)]
+struct SyscallCpuData {
+    /// The top of the kernel stack of the thread that is running.
+    kernel_stack: AtomicU64,
+    /// Scratch space for the stack pointer of the user.
+    user_stack: AtomicU64,
+    user_code: AtomicU64,
+    user_data: AtomicU64,
+}
+
+/// The length of the `syscall` instruction, to make a system call again.
+const SYSCALL_INSTRUCTION_LEN: u64 = 2;
+
+static CPU_DATA: PanicOnce<ProcLocal<SyscallCpuData>> = PanicOnce::new();
+
+/// Setup `syscall` and `sysret` for the current processor.
+///
+/// # Safety
+///
+/// The GDT must be loaded and the interrupt control must be initialized.
+pub unsafe fn init() {
+    if !CPU_DATA.is_initialized() {
+        CPU_DATA.initialize_with(ProcLocal::new(|| SyscallCpuData {
+            kernel_stack: AtomicU64::new(0),
+            user_stack: AtomicU64::new(0),
+            user_code: AtomicU64::new(GDT.user_code.as_u16() as u64),
+            user_data: AtomicU64::new(GDT.user_data.as_u16() as u64),
+        }));
+    }
+
+    let cpu_data: &SyscallCpuData = &CPU_DATA;
+
+    wrmsr::write_kernel_gs_base(VirtualAddress::from(cpu_data as *const SyscallCpuData));
+    wrmsr::write_star(GDT.syscall, GDT.sysret);
+    wrmsr::write_lstar(VirtualAddress::new(syscall_entry as *const () as usize));
+    // Interrupts are disabled while the entry uses `swapgs`, which the interrupt handlers do not
+    // know about. System calls that take long enable them once the entry is done with it.
+    wrmsr::write_sfmask(
+        RFlags::INTERRUPTS_ENABLED | RFlags::TRAP | RFlags::DIRECTION | RFlags::ALIGNMENT_CHECK,
+    );
+    wrmsr::enable_syscall();
+}
+
+/// Set the stack that is used when the next system call is made on the current processor.
+pub fn set_kernel_stack(top: VirtualAddress) {
+    CPU_DATA.kernel_stack.store(top.as_u64(), Ordering::Relaxed);
+}
+
+/// Handles the system call and returns whether it is safe to return with `sysret`.
+///
+/// Interrupts have to be disabled again when this returns, since the entry restores the stack of
+/// the user before `sysret`.
+unsafe extern "C" fn handle_syscall(ctx: *mut CpuContext) -> bool {
+    let ctx = &mut *ctx;
+    let registers = ctx.registers_mut();
+
+    let args = [
+        registers.rdi,
+        registers.rsi,
+        registers.rdx,
+        registers.r10,
+        registers.r8,
+        registers.r9,
+    ];
+
+    match syscalls::syscall(registers.rax, args) {
+        Some(result) => registers.rax = result,
+        // `rax` still holds the number, so the thread makes the system call again once it is woken.
+        None => ctx.interrupt_stack_frame_mut().instruction_pointer -= SYSCALL_INSTRUCTION_LEN,
+    }
+
+    // The context might not belong to a user thread, so `iretq` has to be used.
+    if let Some(next_context) = syscalls::next_context(ctx.clone()) {
+        *ctx = next_context;
+        return false;
+    }
+
+    // `sysret` faults in ring 0 with the user stack when the return address is not canonical.
+    let instruction_pointer = ctx.interrupt_stack_frame().instruction_pointer;
+    VirtualAddress::new(instruction_pointer as usize).as_u64() == instruction_pointer
+}
+
+/// The entry point of `syscall`.
+///
+/// Builds a [`CpuContext`] on the kernel stack, with the same layout as the interrupt handlers,
+/// and returns with `sysret` or `iretq` when `sysret` is not safe.
+#[naked]
+unsafe extern "C" fn syscall_entry() {
+    core::arch::asm!(
+        "swapgs",
+        "mov gs:[{user_stack}], rsp",
+        "mov rsp, gs:[{kernel_stack}]",
+        // interrupt stack frame
+        "push qword ptr gs:[{user_data}]",
+        "push qword ptr gs:[{user_stack}]",
+        "push r11",
+        "push qword ptr gs:[{user_code}]",
+        "push rcx",
+        // save
+        "push rax",
+        "push rbx",
+        "push rcx",
+        "push rdx",
+        "push rdi",
+        "push rsi",
+        "push rbp",
+        "push r8",
+        "push r9",
+        "push r10",
+        "push r11",
+        "push r12",
+        "push r13",
+        "push r14",
+        "push r15",
+        // the user's gs is restored, so an interrupt can switch to another thread
+        "swapgs",
+        // ctx ptr
+        "mov rdi, rsp",
+        // call to handler
+        "call {handler}",
+        "test al, al",
+        // restore, pop leaves the flags alone
+        "pop r15",
+        "pop r14",
+        "pop r13",
+        "pop r12",
+        "pop r11",
+        "pop r10",
+        "pop r9",
+        "pop r8",
+        "pop rbp",
+        "pop rsi",
+        "pop rdi",
+        "pop rdx",
+        "pop rcx",
+        "pop rbx",
+        "pop rax",
+        "jz 2f",
+        "mov rcx, [rsp]",
+        "mov r11, [rsp + 16]",
+        "mov rsp, [rsp + 24]",
+        "sysretq",
+        "2:",
+        "iretq",
+        user_stack = const offset_of!(SyscallCpuData, user_stack),
+        kernel_stack = const offset_of!(SyscallCpuData, kernel_stack),
+        user_code = const offset_of!(SyscallCpuData, user_code),
+        user_data = const offset_of!(SyscallCpuData, user_data),
+        handler = sym handle_syscall,
+        options(noreturn)
+    );
+}
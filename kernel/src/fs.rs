@@ -1,7 +1,64 @@
 pub mod fat;
+pub mod initrd;
+pub mod ramfs;
 
+mod error;
+mod file;
 mod vfs;
 
+use alloc::{string::String, vec::Vec};
+
+pub use error::FsError;
+pub use file::File;
 pub use vfs::*;
 
-pub trait FileSystem {}
+/// Identifies a file or directory within a single filesystem.
+pub type InodeId = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    File,
+    Directory,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata {
+    pub inode: InodeId,
+    pub kind: FileKind,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    pub name: String,
+    pub inode: InodeId,
+    pub kind: FileKind,
+}
+
+/// A filesystem that can be mounted in the [`Vfs`].
+///
+/// Filesystems are shared between processes, so every operation takes `&self` and the
+/// implementation is responsible for its own locking.
+pub trait FileSystem: Send + Sync {
+    fn root(&self) -> InodeId;
+
+    /// Find an entry within a directory by its name.
+    fn lookup(&self, dir: InodeId, name: &str) -> Result<InodeId, FsError>;
+
+    fn stat(&self, inode: InodeId) -> Result<Metadata, FsError>;
+
+    /// Read from a file, returns the amount of bytes read which is only less than `buf.len()` at
+    /// the end of the file.
+    fn read(&self, inode: InodeId, offset: u64, buf: &mut [u8]) -> Result<usize, FsError>;
+
+    /// Write to a file, growing it when writing past its end.
+    fn write(&self, inode: InodeId, offset: u64, buf: &[u8]) -> Result<usize, FsError>;
+
+    /// List the entries of a directory, without `.` and `..`.
+    fn readdir(&self, dir: InodeId) -> Result<Vec<DirEntry>, FsError>;
+
+    fn create(&self, dir: InodeId, name: &str, kind: FileKind) -> Result<InodeId, FsError>;
+
+    /// Remove a file or an empty directory.
+    fn unlink(&self, dir: InodeId, name: &str) -> Result<(), FsError>;
+}
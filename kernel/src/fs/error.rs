@@ -0,0 +1,15 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsError {
+    NotFound,
+    NotADirectory,
+    IsADirectory,
+    AlreadyExists,
+    NotEmpty,
+    /// The name cannot be stored by the filesystem.
+    InvalidName,
+    NoSpace,
+    /// The filesystem structures are inconsistent.
+    Corrupt,
+    /// The underlying storage failed.
+    Io,
+}
@@ -1 +1,161 @@
-pub struct FatFileSystem {}
+use alloc::vec::Vec;
+use essentials::spin::SpinLock;
+use fat::{FatError, FatVolume, Storage};
+
+use crate::{
+    fs::{DirEntry, FileKind, FileSystem, FsError, InodeId, Metadata},
+    utils::InterruptGuard,
+};
+
+/// A FAT12/16/32 filesystem, the on-disk format is handled by the `fat` library.
+pub struct FatFileSystem<S> {
+    volume: InterruptGuard<SpinLock<FatVolume<S>>>,
+}
+
+impl<S: Storage> FatFileSystem<S> {
+    pub fn new(storage: S) -> Result<Self, FsError> {
+        Ok(Self {
+            volume: InterruptGuard::new_lock(FatVolume::open(storage)?),
+        })
+    }
+}
+
+impl From<FatError> for FsError {
+    fn from(value: FatError) -> Self {
+        match value {
+            FatError::Storage(_) => FsError::Io,
+            FatError::InvalidBootSector
+            | FatError::UnsupportedSectorSize
+            | FatError::InvalidVolumeSize
+            | FatError::Corrupt => FsError::Corrupt,
+            FatError::NotFound => FsError::NotFound,
+            FatError::NotADirectory => FsError::NotADirectory,
+            FatError::IsADirectory => FsError::IsADirectory,
+            FatError::AlreadyExists => FsError::AlreadyExists,
+            FatError::NotEmpty => FsError::NotEmpty,
+            FatError::InvalidName => FsError::InvalidName,
+            FatError::NoSpace => FsError::NoSpace,
+        }
+    }
+}
+
+impl From<fat::FileKind> for FileKind {
+    fn from(value: fat::FileKind) -> Self {
+        match value {
+            fat::FileKind::File => FileKind::File,
+            fat::FileKind::Directory => FileKind::Directory,
+        }
+    }
+}
+
+impl From<FileKind> for fat::FileKind {
+    fn from(value: FileKind) -> Self {
+        match value {
+            FileKind::File => fat::FileKind::File,
+            FileKind::Directory => fat::FileKind::Directory,
+        }
+    }
+}
+
+impl<S: Storage + Send + Sync> FileSystem for FatFileSystem<S> {
+    fn root(&self) -> InodeId {
+        FatVolume::<S>::ROOT
+    }
+
+    fn lookup(&self, dir: InodeId, name: &str) -> Result<InodeId, FsError> {
+        let guard = self.volume.guard();
+        let volume = guard.lock();
+        Ok(volume.lookup(dir, name)?)
+    }
+
+    fn stat(&self, inode: InodeId) -> Result<Metadata, FsError> {
+        let guard = self.volume.guard();
+        let volume = guard.lock();
+        let metadata = volume.stat(inode)?;
+
+        Ok(Metadata {
+            inode: metadata.inode,
+            kind: metadata.kind.into(),
+            size: metadata.size,
+        })
+    }
+
+    fn read(&self, inode: InodeId, offset: u64, buf: &mut [u8]) -> Result<usize, FsError> {
+        let guard = self.volume.guard();
+        let volume = guard.lock();
+        Ok(volume.read(inode, offset, buf)?)
+    }
+
+    fn write(&self, inode: InodeId, offset: u64, buf: &[u8]) -> Result<usize, FsError> {
+        let guard = self.volume.guard();
+        let mut volume = guard.lock();
+        Ok(volume.write(inode, offset, buf)?)
+    }
+
+    fn readdir(&self, dir: InodeId) -> Result<Vec<DirEntry>, FsError> {
+        let guard = self.volume.guard();
+        let volume = guard.lock();
+
+        Ok(volume
+            .readdir(dir)?
+            .into_iter()
+            .map(|entry| DirEntry {
+                name: entry.name,
+                inode: entry.inode,
+                kind: entry.kind.into(),
+            })
+            .collect())
+    }
+
+    fn create(&self, dir: InodeId, name: &str, kind: FileKind) -> Result<InodeId, FsError> {
+        let guard = self.volume.guard();
+        let mut volume = guard.lock();
+        Ok(volume.create(dir, name, kind.into())?)
+    }
+
+    fn unlink(&self, dir: InodeId, name: &str) -> Result<(), FsError> {
+        let guard = self.volume.guard();
+        let mut volume = guard.lock();
+        Ok(volume.unlink(dir, name)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    fn fat_fs() -> FatFileSystem<Vec<u8>> {
+        let mut storage = vec![0u8; 1440 * 1024];
+        fat::format(&mut storage, &Default::default()).unwrap();
+        FatFileSystem::new(storage).unwrap()
+    }
+
+    #[test_case]
+    fn test_fat_create_and_read() {
+        let fs = fat_fs();
+        let root = fs.root();
+
+        let dir = fs.create(root, "boot", FileKind::Directory).unwrap();
+        let file = fs.create(dir, "kernel.elf", FileKind::File).unwrap();
+        fs.write(file, 0, b"\x7fELF").unwrap();
+
+        let mut buf = [0u8; 4];
+        assert_eq!(
+            4,
+            fs.read(fs.lookup(dir, "KERNEL.ELF").unwrap(), 0, &mut buf)
+                .unwrap()
+        );
+        assert_eq!(b"\x7fELF", &buf);
+        assert_eq!(FileKind::Directory, fs.stat(dir).unwrap().kind);
+    }
+
+    #[test_case]
+    fn test_fat_errors() {
+        let fs = fat_fs();
+
+        assert_eq!(Err(FsError::NotFound), fs.lookup(fs.root(), "missing"));
+        assert!(FatFileSystem::new(vec![0u8; 512]).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use alloc::sync::Arc;
+
+use super::{FileKind, FileSystem, FsError, InodeId, VfsPath};
+
+/// A regular file that has been opened through the [`Vfs`](super::Vfs).
+///
+/// A file has no position of its own, it is read and written at an explicit offset. So every
+/// handle to the same file can use it without getting in the way of the others.
+pub struct File {
+    fs: Arc<dyn FileSystem>,
+    inode: InodeId,
+}
+
+impl File {
+    /// Look up the file that `path` refers to.
+    pub fn open(path: &VfsPath) -> Result<Self, FsError> {
+        let inode = path.lookup()?;
+
+        if path.fs.stat(inode)?.kind != FileKind::File {
+            return Err(FsError::IsADirectory);
+        }
+
+        Ok(Self {
+            fs: path.fs.clone(),
+            inode,
+        })
+    }
+
+    pub fn size(&self) -> Result<u64, FsError> {
+        Ok(self.fs.stat(self.inode)?.size)
+    }
+
+    /// Read from `offset` into `buf`, and return how many bytes were read.
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, FsError> {
+        self.fs.read(self.inode, offset, buf)
+    }
+
+    /// Write `data` at `offset`, and return how many bytes were written.
+    pub fn write_at(&self, offset: u64, data: &[u8]) -> Result<usize, FsError> {
+        self.fs.write(self.inode, offset, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use path::{Path, PathBuf};
+
+    use super::*;
+    use crate::fs::ramfs::RamFs;
+
+    fn vfs_path(fs: &Arc<RamFs>, path: &str) -> VfsPath {
+        VfsPath {
+            fs: fs.clone(),
+            path: PathBuf::from(Path::new(path).unwrap()),
+            write_access: true,
+        }
+    }
+
+    #[test_case]
+    fn test_read_write_at() {
+        let fs = Arc::new(RamFs::new(0x1000));
+        fs.create(fs.root(), "data", FileKind::File).unwrap();
+        fs.create(fs.root(), "dir", FileKind::Directory).unwrap();
+
+        assert_eq!(
+            Some(FsError::IsADirectory),
+            File::open(&vfs_path(&fs, "/dir")).err()
+        );
+        assert_eq!(
+            Some(FsError::NotFound),
+            File::open(&vfs_path(&fs, "/missing")).err()
+        );
+
+        let file = File::open(&vfs_path(&fs, "/data")).unwrap();
+        assert_eq!(Ok(4), file.write_at(2, b"file"));
+        assert_eq!(Ok(6), file.size());
+
+        let mut buf = [0xFF; 8];
+        assert_eq!(Ok(3), file.read_at(3, &mut buf));
+        assert_eq!(b"ile", &buf[..3]);
+    }
+}
@@ -0,0 +1,327 @@
+//! Unpacking the initial ramdisk into a filesystem.
+//!
+//! The initrd is an archive that the bootloader loads as a module. Both the ustar (`tar
+//! --format=ustar`) and the newc cpio (`cpio -H newc`) formats are supported, with only regular
+//! files and directories being extracted.
+
+mod error;
+
+use alloc::string::String;
+use path::{Component, Path};
+
+use crate::fs::{FileKind, FileSystem, FsError, InodeId};
+
+pub use error::InitrdError;
+
+const USTAR_BLOCK_SIZE: usize = 512;
+const USTAR_MAGIC_OFFSET: usize = 257;
+const USTAR_MAGIC: &[u8] = b"ustar";
+
+const NEWC_MAGIC: &[u8] = b"070701";
+const NEWC_CRC_MAGIC: &[u8] = b"070702";
+const NEWC_HEADER_SIZE: usize = 110;
+const NEWC_TRAILER: &str = "TRAILER!!!";
+
+const MODE_TYPE_MASK: u32 = 0o170000;
+const MODE_DIRECTORY: u32 = 0o040000;
+const MODE_REGULAR: u32 = 0o100000;
+
+struct ArchiveEntry<'a> {
+    path: String,
+    /// `None` for entries that are skipped, like symbolic links and device files.
+    kind: Option<FileKind>,
+    data: &'a [u8],
+}
+
+fn parse_number(field: &[u8], radix: u32) -> Result<usize, InitrdError> {
+    let text = core::str::from_utf8(field).map_err(|_| InitrdError::InvalidHeader)?;
+    let text = text.trim_matches(|c: char| c == '\0' || c == ' ');
+
+    if text.is_empty() {
+        return Ok(0);
+    }
+
+    usize::from_str_radix(text, radix).map_err(|_| InitrdError::InvalidHeader)
+}
+
+fn c_str(field: &[u8]) -> Result<&str, InitrdError> {
+    let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    core::str::from_utf8(&field[..len]).map_err(|_| InitrdError::InvalidHeader)
+}
+
+fn slice(archive: &[u8], start: usize, len: usize) -> Result<&[u8], InitrdError> {
+    start
+        .checked_add(len)
+        .and_then(|end| archive.get(start..end))
+        .ok_or(InitrdError::Truncated)
+}
+
+/// Walk the entries of a ustar archive.
+fn for_each_ustar<'a>(
+    archive: &'a [u8],
+    mut f: impl FnMut(ArchiveEntry<'a>) -> Result<(), InitrdError>,
+) -> Result<(), InitrdError> {
+    let mut offset = 0;
+
+    loop {
+        let header = slice(archive, offset, USTAR_BLOCK_SIZE)?;
+
+        // The archive ends with two zeroed blocks.
+        if header.iter().all(|&b| b == 0) {
+            return Ok(());
+        }
+
+        let size = parse_number(&header[124..136], 8)?;
+        let data = slice(archive, offset + USTAR_BLOCK_SIZE, size)?;
+
+        let name = c_str(&header[0..100])?;
+        let prefix = c_str(&header[345..500])?;
+
+        let mut path = String::from(prefix);
+        if !path.is_empty() {
+            path.push('/');
+        }
+        path.push_str(name);
+
+        let kind = match header[156] {
+            b'0' | b'\0' => Some(FileKind::File),
+            b'5' => Some(FileKind::Directory),
+            _ => None,
+        };
+
+        f(ArchiveEntry { path, kind, data })?;
+
+        offset += USTAR_BLOCK_SIZE + size.next_multiple_of(USTAR_BLOCK_SIZE);
+    }
+}
+
+/// Walk the entries of a newc cpio archive.
+fn for_each_newc<'a>(
+    archive: &'a [u8],
+    mut f: impl FnMut(ArchiveEntry<'a>) -> Result<(), InitrdError>,
+) -> Result<(), InitrdError> {
+    let mut offset = 0;
+
+    loop {
+        let header = slice(archive, offset, NEWC_HEADER_SIZE)?;
+
+        if &header[0..6] != NEWC_MAGIC && &header[0..6] != NEWC_CRC_MAGIC {
+            return Err(InitrdError::InvalidHeader);
+        }
+
+        let field = |index: usize| parse_number(&header[6 + index * 8..14 + index * 8], 16);
+
+        let mode = field(1)? as u32;
+        let size = field(6)?;
+        let name_size = field(11)?;
+
+        let name = c_str(slice(archive, offset + NEWC_HEADER_SIZE, name_size)?)?;
+
+        if name == NEWC_TRAILER {
+            return Ok(());
+        }
+
+        let data_offset = (offset + NEWC_HEADER_SIZE + name_size).next_multiple_of(4);
+        let data = slice(archive, data_offset, size)?;
+
+        let kind = match mode & MODE_TYPE_MASK {
+            MODE_REGULAR => Some(FileKind::File),
+            MODE_DIRECTORY => Some(FileKind::Directory),
+            _ => None,
+        };
+
+        f(ArchiveEntry {
+            path: String::from(name),
+            kind,
+            data,
+        })?;
+
+        offset = (data_offset + size).next_multiple_of(4);
+    }
+}
+
+/// Find `name` in `dir`, or create it when it does not exist yet.
+fn lookup_or_create(
+    fs: &dyn FileSystem,
+    dir: InodeId,
+    name: &str,
+    kind: FileKind,
+) -> Result<InodeId, FsError> {
+    match fs.lookup(dir, name) {
+        Ok(inode) if fs.stat(inode)?.kind == kind => Ok(inode),
+        Ok(_) => Err(FsError::AlreadyExists),
+        Err(FsError::NotFound) => fs.create(dir, name, kind),
+        Err(e) => Err(e),
+    }
+}
+
+fn extract(fs: &dyn FileSystem, entry: ArchiveEntry) -> Result<bool, InitrdError> {
+    let Some(kind) = entry.kind else {
+        return Ok(false);
+    };
+
+    // Archives store relative paths like `./bin/init`, which are always placed at the root.
+    let path = Path::new(&entry.path).map_err(|_| InitrdError::InvalidPath(entry.path.clone()))?;
+
+    let path = Path::ROOT.combine(path);
+    let Some(file_name) = path.file_name() else {
+        // The root itself, which always exists.
+        return Ok(false);
+    };
+
+    let mut dir = fs.root();
+
+    for component in path.parent().into_iter().flat_map(Path::components) {
+        if let Component::Name(name) = component {
+            dir = lookup_or_create(fs, dir, name, FileKind::Directory)?;
+        }
+    }
+
+    let inode = lookup_or_create(fs, dir, file_name, kind)?;
+
+    if kind == FileKind::File && !entry.data.is_empty() {
+        fs.write(inode, 0, entry.data)?;
+    }
+
+    Ok(true)
+}
+
+/// Extract all files and directories of an archive into `fs`, returns the amount of extracted
+/// entries.
+pub fn unpack(archive: &[u8], fs: &dyn FileSystem) -> Result<usize, InitrdError> {
+    let mut count = 0;
+
+    let mut extract = |entry| {
+        count += extract(fs, entry)? as usize;
+        Ok(())
+    };
+
+    if archive.starts_with(NEWC_MAGIC) || archive.starts_with(NEWC_CRC_MAGIC) {
+        for_each_newc(archive, &mut extract)?;
+    } else if archive.get(USTAR_MAGIC_OFFSET..USTAR_MAGIC_OFFSET + USTAR_MAGIC.len())
+        == Some(USTAR_MAGIC)
+    {
+        for_each_ustar(archive, &mut extract)?;
+    } else {
+        return Err(InitrdError::UnknownFormat);
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, vec::Vec};
+
+    use super::*;
+    use crate::fs::ramfs::RamFs;
+
+    fn ustar_entry(archive: &mut Vec<u8>, name: &str, typeflag: u8, data: &[u8]) {
+        let mut header = [0u8; USTAR_BLOCK_SIZE];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[124..135].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+        header[156] = typeflag;
+        header[257..263].copy_from_slice(b"ustar\0");
+
+        archive.extend_from_slice(&header);
+        archive.extend_from_slice(data);
+        archive.resize(archive.len().next_multiple_of(USTAR_BLOCK_SIZE), 0);
+    }
+
+    fn newc_entry(archive: &mut Vec<u8>, name: &str, mode: u32, data: &[u8]) {
+        let fields = [
+            0,
+            mode,
+            0,
+            0,
+            1,
+            0,
+            data.len() as u32,
+            0,
+            0,
+            0,
+            0,
+            name.len() as u32 + 1,
+            0,
+        ];
+
+        archive.extend_from_slice(NEWC_MAGIC);
+        for field in fields {
+            archive.extend_from_slice(format!("{field:08X}").as_bytes());
+        }
+
+        archive.extend_from_slice(name.as_bytes());
+        archive.push(0);
+        archive.resize(archive.len().next_multiple_of(4), 0);
+        archive.extend_from_slice(data);
+        archive.resize(archive.len().next_multiple_of(4), 0);
+    }
+
+    fn read_file(fs: &dyn FileSystem, path: &[&str]) -> Vec<u8> {
+        let inode = path
+            .iter()
+            .try_fold(fs.root(), |dir, name| fs.lookup(dir, name))
+            .unwrap();
+
+        let mut buf = alloc::vec![0; fs.stat(inode).unwrap().size as usize];
+        fs.read(inode, 0, &mut buf).unwrap();
+        buf
+    }
+
+    #[test_case]
+    fn test_unpack_ustar() {
+        let mut archive = Vec::new();
+        ustar_entry(&mut archive, "./bin/", b'5', &[]);
+        ustar_entry(&mut archive, "./bin/init", b'0', b"\x7fELF");
+        ustar_entry(&mut archive, "etc/motd", b'0', &[b'x'; 600]);
+        ustar_entry(&mut archive, "link", b'2', &[]);
+        archive.resize(archive.len() + USTAR_BLOCK_SIZE * 2, 0);
+
+        let fs = RamFs::new(64 * 1024);
+        assert_eq!(Ok(3), unpack(&archive, &fs));
+
+        assert_eq!(b"\x7fELF", &read_file(&fs, &["bin", "init"])[..]);
+        assert_eq!(600, read_file(&fs, &["etc", "motd"]).len());
+        assert_eq!(Err(FsError::NotFound), fs.lookup(fs.root(), "link"));
+    }
+
+    #[test_case]
+    fn test_unpack_newc() {
+        let mut archive = Vec::new();
+        newc_entry(&mut archive, ".", MODE_DIRECTORY | 0o755, &[]);
+        newc_entry(&mut archive, "bin", MODE_DIRECTORY | 0o755, &[]);
+        newc_entry(&mut archive, "bin/init", MODE_REGULAR | 0o755, b"hello");
+        newc_entry(&mut archive, NEWC_TRAILER, 0, &[]);
+
+        let fs = RamFs::new(64 * 1024);
+        assert_eq!(Ok(2), unpack(&archive, &fs));
+
+        assert_eq!(b"hello", &read_file(&fs, &["bin", "init"])[..]);
+    }
+
+    #[test_case]
+    fn test_unpack_invalid() {
+        let fs = RamFs::new(64 * 1024);
+
+        assert_eq!(Err(InitrdError::UnknownFormat), unpack(&[0; 1024], &fs));
+
+        let mut archive = Vec::new();
+        ustar_entry(&mut archive, "file", b'0', &[1; 100]);
+        archive.truncate(USTAR_BLOCK_SIZE + 10);
+
+        assert_eq!(Err(InitrdError::Truncated), unpack(&archive, &fs));
+    }
+
+    #[test_case]
+    fn test_unpack_dotted_name() {
+        let mut archive = Vec::new();
+        ustar_entry(&mut archive, "./bin/init.elf", b'0', b"\x7fELF");
+        archive.resize(archive.len() + USTAR_BLOCK_SIZE * 2, 0);
+
+        let fs = RamFs::new(64 * 1024);
+        assert_eq!(
+            Err(InitrdError::InvalidPath("./bin/init.elf".into())),
+            unpack(&archive, &fs)
+        );
+    }
+}
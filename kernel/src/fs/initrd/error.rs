@@ -0,0 +1,21 @@
+use alloc::string::String;
+
+use crate::fs::FsError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InitrdError {
+    /// The archive is neither a ustar nor a newc cpio archive.
+    UnknownFormat,
+    /// An entry extends past the end of the archive.
+    Truncated,
+    InvalidHeader,
+    /// The path of an entry is not a valid path, for example because a name contains a dot.
+    InvalidPath(String),
+    Fs(FsError),
+}
+
+impl From<FsError> for InitrdError {
+    fn from(value: FsError) -> Self {
+        InitrdError::Fs(value)
+    }
+}
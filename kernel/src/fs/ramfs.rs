@@ -0,0 +1,382 @@
+//! A filesystem that lives entirely on the kernel heap.
+//!
+//! The ramfs is available before any block device driver is, which makes it the default root
+//! mount. Because the data is stored in the [`KERNEL_ALLOC`](crate::memory::alloc::kernel_alloc::KERNEL_ALLOC),
+//! each ramfs is given a limit on how many bytes it may use so it cannot starve the kernel.
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::mem::size_of;
+use essentials::spin::SpinLock;
+
+use crate::{
+    fs::{DirEntry, FileKind, FileSystem, FsError, InodeId, Metadata, MAX_FILE_NAME},
+    utils::InterruptGuard,
+};
+
+const ROOT_INODE: InodeId = 1;
+
+enum InodeData {
+    File(Vec<u8>),
+    Directory(BTreeMap<String, InodeId>),
+}
+
+struct Inode {
+    parent: InodeId,
+    data: InodeData,
+}
+
+impl Inode {
+    fn kind(&self) -> FileKind {
+        match self.data {
+            InodeData::File(_) => FileKind::File,
+            InodeData::Directory(_) => FileKind::Directory,
+        }
+    }
+
+    fn size(&self) -> u64 {
+        match &self.data {
+            InodeData::File(data) => data.len() as u64,
+            InodeData::Directory(entries) => entries.len() as u64,
+        }
+    }
+}
+
+struct RamFsData {
+    inodes: BTreeMap<InodeId, Inode>,
+    next_inode: InodeId,
+    used: usize,
+    limit: usize,
+}
+
+impl RamFsData {
+    /// The bytes accounted for an entry in a directory, including the inode it refers to.
+    fn entry_cost(name: &str) -> usize {
+        size_of::<Inode>() + size_of::<InodeId>() * 2 + name.len()
+    }
+
+    fn reserve(&mut self, bytes: usize) -> Result<(), FsError> {
+        let used = self
+            .used
+            .checked_add(bytes)
+            .filter(|&used| used <= self.limit)
+            .ok_or(FsError::NoSpace)?;
+
+        self.used = used;
+        Ok(())
+    }
+
+    fn release(&mut self, bytes: usize) {
+        self.used -= bytes;
+    }
+
+    fn inode(&self, inode: InodeId) -> Result<&Inode, FsError> {
+        self.inodes.get(&inode).ok_or(FsError::NotFound)
+    }
+
+    fn dir(&self, inode: InodeId) -> Result<&BTreeMap<String, InodeId>, FsError> {
+        match &self.inode(inode)?.data {
+            InodeData::Directory(entries) => Ok(entries),
+            InodeData::File(_) => Err(FsError::NotADirectory),
+        }
+    }
+
+    fn dir_mut(&mut self, inode: InodeId) -> Result<&mut BTreeMap<String, InodeId>, FsError> {
+        match &mut self.inodes.get_mut(&inode).ok_or(FsError::NotFound)?.data {
+            InodeData::Directory(entries) => Ok(entries),
+            InodeData::File(_) => Err(FsError::NotADirectory),
+        }
+    }
+
+    fn file_mut(&mut self, inode: InodeId) -> Result<&mut Vec<u8>, FsError> {
+        match &mut self.inodes.get_mut(&inode).ok_or(FsError::NotFound)?.data {
+            InodeData::File(data) => Ok(data),
+            InodeData::Directory(_) => Err(FsError::IsADirectory),
+        }
+    }
+}
+
+pub struct RamFs {
+    data: InterruptGuard<SpinLock<RamFsData>>,
+}
+
+impl RamFs {
+    /// Create an empty ramfs that stores at most `limit` bytes, including the bookkeeping of
+    /// files and directories.
+    pub fn new(limit: usize) -> Self {
+        let mut inodes = BTreeMap::new();
+        inodes.insert(
+            ROOT_INODE,
+            Inode {
+                parent: ROOT_INODE,
+                data: InodeData::Directory(BTreeMap::new()),
+            },
+        );
+
+        Self {
+            data: InterruptGuard::new_lock(RamFsData {
+                inodes,
+                next_inode: ROOT_INODE + 1,
+                used: 0,
+                limit,
+            }),
+        }
+    }
+
+    /// The amount of bytes that is currently accounted to this filesystem.
+    pub fn used(&self) -> usize {
+        self.data.guard().lock().used
+    }
+
+    pub fn limit(&self) -> usize {
+        self.data.guard().lock().limit
+    }
+}
+
+impl FileSystem for RamFs {
+    fn root(&self) -> InodeId {
+        ROOT_INODE
+    }
+
+    fn lookup(&self, dir: InodeId, name: &str) -> Result<InodeId, FsError> {
+        let guard = self.data.guard();
+        let data = guard.lock();
+
+        match name {
+            "." => data.dir(dir).map(|_| dir),
+            ".." => data.dir(dir).map(|_| data.inodes[&dir].parent),
+            name => data.dir(dir)?.get(name).copied().ok_or(FsError::NotFound),
+        }
+    }
+
+    fn stat(&self, inode: InodeId) -> Result<Metadata, FsError> {
+        let guard = self.data.guard();
+        let data = guard.lock();
+        let node = data.inode(inode)?;
+
+        Ok(Metadata {
+            inode,
+            kind: node.kind(),
+            size: node.size(),
+        })
+    }
+
+    fn read(&self, inode: InodeId, offset: u64, buf: &mut [u8]) -> Result<usize, FsError> {
+        let guard = self.data.guard();
+        let data = guard.lock();
+
+        let file = match &data.inode(inode)?.data {
+            InodeData::File(file) => file,
+            InodeData::Directory(_) => return Err(FsError::IsADirectory),
+        };
+
+        let Some(rest) = usize::try_from(offset).ok().and_then(|o| file.get(o..)) else {
+            return Ok(0);
+        };
+
+        let len = rest.len().min(buf.len());
+        buf[..len].copy_from_slice(&rest[..len]);
+
+        Ok(len)
+    }
+
+    fn write(&self, inode: InodeId, offset: u64, buf: &[u8]) -> Result<usize, FsError> {
+        let guard = self.data.guard();
+        let mut data = guard.lock();
+
+        let start = usize::try_from(offset).map_err(|_| FsError::NoSpace)?;
+        let end = start.checked_add(buf.len()).ok_or(FsError::NoSpace)?;
+
+        let len = data.file_mut(inode)?.len();
+        let grow = end.saturating_sub(len);
+
+        data.reserve(grow)?;
+
+        let file = data.file_mut(inode)?;
+
+        if file.try_reserve(grow).is_err() {
+            data.release(grow);
+            return Err(FsError::NoSpace);
+        }
+
+        if end > file.len() {
+            file.resize(end, 0);
+        }
+
+        file[start..end].copy_from_slice(buf);
+
+        Ok(buf.len())
+    }
+
+    fn readdir(&self, dir: InodeId) -> Result<Vec<DirEntry>, FsError> {
+        let guard = self.data.guard();
+        let data = guard.lock();
+
+        Ok(data
+            .dir(dir)?
+            .iter()
+            .map(|(name, &inode)| DirEntry {
+                name: name.clone(),
+                inode,
+                kind: data.inodes[&inode].kind(),
+            })
+            .collect())
+    }
+
+    fn create(&self, dir: InodeId, name: &str, kind: FileKind) -> Result<InodeId, FsError> {
+        if name.is_empty() || name.len() > MAX_FILE_NAME || name.contains('/') {
+            return Err(FsError::InvalidName);
+        }
+
+        if name == "." || name == ".." {
+            return Err(FsError::AlreadyExists);
+        }
+
+        let guard = self.data.guard();
+        let mut data = guard.lock();
+
+        if data.dir(dir)?.contains_key(name) {
+            return Err(FsError::AlreadyExists);
+        }
+
+        data.reserve(RamFsData::entry_cost(name))?;
+
+        let inode = data.next_inode;
+        data.next_inode += 1;
+
+        let node = Inode {
+            parent: dir,
+            data: match kind {
+                FileKind::File => InodeData::File(Vec::new()),
+                FileKind::Directory => InodeData::Directory(BTreeMap::new()),
+            },
+        };
+
+        data.inodes.insert(inode, node);
+        data.dir_mut(dir)?.insert(String::from(name), inode);
+
+        Ok(inode)
+    }
+
+    fn unlink(&self, dir: InodeId, name: &str) -> Result<(), FsError> {
+        let guard = self.data.guard();
+        let mut data = guard.lock();
+
+        let inode = *data.dir(dir)?.get(name).ok_or(FsError::NotFound)?;
+
+        let freed = match &data.inode(inode)?.data {
+            InodeData::Directory(entries) if !entries.is_empty() => return Err(FsError::NotEmpty),
+            InodeData::Directory(_) => 0,
+            InodeData::File(file) => file.len(),
+        };
+
+        data.dir_mut(dir)?.remove(name);
+        data.inodes.remove(&inode);
+        data.release(freed + RamFsData::entry_cost(name));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{sync::Arc, vec};
+    use path::Path;
+
+    use super::*;
+    use crate::{
+        fs::{OverLay, Vfs},
+        multitasking::process::Permissions,
+    };
+
+    const LIMIT: usize = 64 * 1024;
+
+    #[test_case]
+    fn test_ramfs_create_write_read() {
+        let fs = RamFs::new(LIMIT);
+        let file = fs.create(fs.root(), "file", FileKind::File).unwrap();
+
+        assert_eq!(5, fs.write(file, 0, b"hello").unwrap());
+        assert_eq!(3, fs.write(file, 7, b"!!!").unwrap());
+
+        let mut buf = [0xFF; 16];
+        assert_eq!(10, fs.read(file, 0, &mut buf).unwrap());
+        assert_eq!(b"hello\0\0!!!", &buf[..10]);
+        assert_eq!(0, fs.read(file, 10, &mut buf).unwrap());
+        assert_eq!(10, fs.stat(file).unwrap().size);
+    }
+
+    #[test_case]
+    fn test_ramfs_directories() {
+        let fs = RamFs::new(LIMIT);
+        let root = fs.root();
+
+        let dir = fs.create(root, "dir", FileKind::Directory).unwrap();
+        let file = fs.create(dir, "file", FileKind::File).unwrap();
+
+        assert_eq!(file, fs.lookup(dir, "file").unwrap());
+        assert_eq!(root, fs.lookup(dir, "..").unwrap());
+        assert_eq!(root, fs.lookup(root, "..").unwrap());
+        assert_eq!(Err(FsError::NotADirectory), fs.lookup(file, "x"));
+        assert_eq!(
+            Err(FsError::AlreadyExists),
+            fs.create(dir, "file", FileKind::File)
+        );
+
+        let entries = fs.readdir(root).unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!("dir", entries[0].name);
+        assert_eq!(FileKind::Directory, entries[0].kind);
+    }
+
+    #[test_case]
+    fn test_ramfs_unlink() {
+        let fs = RamFs::new(LIMIT);
+        let root = fs.root();
+
+        let dir = fs.create(root, "dir", FileKind::Directory).unwrap();
+        let file = fs.create(dir, "file", FileKind::File).unwrap();
+        fs.write(file, 0, &[1; 100]).unwrap();
+
+        assert_eq!(Err(FsError::NotEmpty), fs.unlink(root, "dir"));
+
+        fs.unlink(dir, "file").unwrap();
+        fs.unlink(root, "dir").unwrap();
+
+        assert_eq!(Err(FsError::NotFound), fs.stat(file));
+        assert_eq!(0, fs.used());
+    }
+
+    #[test_case]
+    fn test_ramfs_limit() {
+        let fs = RamFs::new(1024);
+        let file = fs.create(fs.root(), "file", FileKind::File).unwrap();
+
+        assert_eq!(Err(FsError::NoSpace), fs.write(file, 0, &vec![0; 1024]));
+        assert_eq!(0, fs.stat(file).unwrap().size);
+
+        fs.write(file, 0, &[0; 512]).unwrap();
+        assert!(fs.used() <= fs.limit());
+    }
+
+    #[test_case]
+    fn test_ramfs_through_vfs() {
+        let vfs = Vfs::new();
+        let fs = Arc::new(RamFs::new(LIMIT));
+
+        let etc = fs.create(fs.root(), "etc", FileKind::Directory).unwrap();
+        let hosts = fs.create(etc, "hosts", FileKind::File).unwrap();
+
+        vfs.mount(Path::ROOT, fs).unwrap();
+
+        let opened = vfs
+            .open(
+                &OverLay::ROOT,
+                &Permissions::all(),
+                Path::new("/etc/../etc/hosts").unwrap(),
+                false,
+            )
+            .unwrap();
+
+        assert_eq!(hosts, opened.lookup().unwrap());
+    }
+}
@@ -1,29 +1,76 @@
+//! The virtual filesystem (VFS) glues mounted filesystems together into one tree, and gives every
+//! process its own view of that tree through an [`OverLay`].
+//!
+//! # Overlays
+//!
+//! Each process holds an `Arc<OverLay>` that it inherits from its parent. An overlay translates
+//! the paths that a process sees into the paths that its parent sees. This is done either with a
+//! mount point, that remaps the root, or with mappings that remap a specific directory. For
+//! example, spawning a child with:
+//!
+//! - Nothing: the child shares the root with the parent.
+//! - `/home/anon` as `/`: the child sees `/home/anon` of the parent as its root.
+//! - `/home/anon/config` as `/config`: the child sees `/home/anon/config` of the parent at
+//!   `/config`, and everything else like the parent does.
+//!
+//! Because every path is translated through the entire chain of overlays, a child can never see
+//! more than its parent can. The same goes for write access, which is only granted when every
+//! overlay in the chain grants it.
+
+mod error;
+
 use alloc::{sync::Arc, vec::Vec};
-use path::Path;
+use essentials::spin::SpinLock;
+use path::{Component, Path, PathBuf};
 
-const MAX_FILE_NAME: usize = 256;
+use crate::{
+    fs::{FileSystem, FsError, InodeId},
+    multitasking::process::Permissions,
+    utils::InterruptGuard,
+};
 
-struct Inode {
-    filesize: u64,
-}
+pub use error::VfsError;
+
+/// The longest name of a single path component that filesystems have to support.
+pub const MAX_FILE_NAME: usize = 256;
 
 pub struct OverlayMap {
     to: Arc<Path>,
     write_access: bool,
 }
 
-struct OverLay {
-    // if no mappings are found, also check this.
+impl OverlayMap {
+    pub fn new(to: &Path, write_access: bool) -> Result<Self, VfsError> {
+        if to.is_relative() {
+            return Err(VfsError::RelativePath);
+        }
+
+        Ok(Self {
+            to: Arc::from(&*to.normalize()),
+            write_access,
+        })
+    }
+}
+
+/// A path as seen by the filesystem, after resolving it through an [`OverLay`].
+#[derive(Debug)]
+pub struct ResolvedPath {
+    pub path: PathBuf,
+    pub write_access: bool,
+}
+
+pub struct OverLay {
+    // Resolved paths are handed to the parent, which resolves them further.
     parent: Option<Arc<OverLay>>,
 
     // example: /home/anon
-    // none: check inherits, else root
+    // none: the root is the same as the parent's root.
     mount_point: Option<OverlayMap>,
 
     // example:
     // - /exe /exe
     // Key: the path relative to the current overlay
-    // Value: the absolute path
+    // Value: the absolute path in the parent overlay
     // Its important that this vec is ordered from specific to less specific
     overlay_mappings: Vec<(Arc<Path>, OverlayMap)>,
 }
@@ -35,49 +82,307 @@ impl OverLay {
         overlay_mappings: Vec::new(),
     };
 
-    fn mapping_with_start_of(&self, path: &Path) -> Option<&OverlayMap> {
+    /// Create a child overlay of `parent`.
+    ///
+    /// Each mapping consists of the path in the parent overlay, the path in the child overlay and
+    /// whenever the child may write. Mapping to `/` sets the mount point of the child.
+    pub fn inherit<'a>(
+        parent: &Arc<OverLay>,
+        mappings: impl IntoIterator<Item = (&'a Path, &'a Path, bool)>,
+    ) -> Result<Arc<Self>, VfsError> {
+        let mut overlay = OverLay {
+            parent: Some(parent.clone()),
+            mount_point: None,
+            overlay_mappings: Vec::new(),
+        };
+
+        for (parent_path, child_path, write_access) in mappings {
+            if child_path.is_relative() {
+                return Err(VfsError::RelativePath);
+            }
+
+            let map = OverlayMap::new(parent_path, write_access)?;
+            let child_path = child_path.normalize();
+
+            if child_path.is_root() {
+                overlay.mount_point = Some(map);
+            } else {
+                overlay
+                    .overlay_mappings
+                    .push((Arc::from(&*child_path), map));
+            }
+        }
+
+        overlay
+            .overlay_mappings
+            .sort_by_key(|(from, _)| core::cmp::Reverse(from.components().count()));
+
+        Ok(Arc::new(overlay))
+    }
+
+    fn mapping_with_start_of(&self, path: &Path) -> Option<(&Path, &OverlayMap)> {
         self.overlay_mappings
             .iter()
             .find(|(from, _)| path.starts_with(from))
-            .map(|(_, to)| to)
-            .or_else(|| {
-                self.parent
-                    .as_ref()
-                    .and_then(|p| p.mapping_with_start_of(path))
+            .map(|(from, to)| (&**from, to))
+    }
+
+    /// Translate a path of this overlay into the path of the root overlay.
+    pub fn resolve_path(&self, path: &Path) -> Result<ResolvedPath, VfsError> {
+        if path.is_relative() {
+            return Err(VfsError::RelativePath);
+        }
+
+        // Normalizing first makes sure that `..` cannot be used to escape a mapping.
+        let path = path.normalize();
+
+        let (from, mapping) = match self.mapping_with_start_of(&path) {
+            Some(found) => found,
+            None => match &self.mount_point {
+                Some(mount_point) => (Path::ROOT, mount_point),
+                None => {
+                    return self.resolve_in_parent(ResolvedPath {
+                        path,
+                        write_access: true,
+                    })
+                }
+            },
+        };
+
+        let rest = path
+            .strip_prefix(from)
+            .expect("the mapping should be a prefix of the path");
+
+        self.resolve_in_parent(ResolvedPath {
+            path: mapping.to.combine(rest),
+            write_access: mapping.write_access,
+        })
+    }
+
+    fn resolve_in_parent(&self, resolved: ResolvedPath) -> Result<ResolvedPath, VfsError> {
+        let Some(parent) = &self.parent else {
+            return Ok(resolved);
+        };
+
+        let mut parent_resolved = parent.resolve_path(&resolved.path)?;
+        parent_resolved.write_access &= resolved.write_access;
+
+        Ok(parent_resolved)
+    }
+}
+
+struct Mount {
+    path: PathBuf,
+    fs: Arc<dyn FileSystem>,
+}
+
+/// A path within a mounted filesystem.
+pub struct VfsPath {
+    pub fs: Arc<dyn FileSystem>,
+    /// The path relative to the root of `fs`.
+    pub path: PathBuf,
+    pub write_access: bool,
+}
+
+impl VfsPath {
+    /// Walk the path from the root of the filesystem to find the inode it refers to.
+    pub fn lookup(&self) -> Result<InodeId, FsError> {
+        self.path
+            .components()
+            .try_fold(self.fs.root(), |inode, component| match component {
+                Component::Name(name) => self.fs.lookup(inode, name),
+                _ => Ok(inode),
             })
     }
+}
+
+pub struct Vfs {
+    // Ordered from specific to less specific, just like the overlay mappings.
+    mounts: InterruptGuard<SpinLock<Vec<Mount>>>,
+}
+
+impl Vfs {
+    pub const fn new() -> Self {
+        Self {
+            mounts: InterruptGuard::new_lock(Vec::new()),
+        }
+    }
+
+    pub fn mount(&self, path: &Path, fs: Arc<dyn FileSystem>) -> Result<(), VfsError> {
+        if path.is_relative() {
+            return Err(VfsError::RelativePath);
+        }
+
+        let path = path.normalize();
+
+        let guard = self.mounts.guard();
+        let mut mounts = guard.lock();
+
+        if mounts.iter().any(|m| m.path == path) {
+            return Err(VfsError::AlreadyMounted);
+        }
+
+        mounts.push(Mount { path, fs });
+        mounts.sort_by_key(|m| core::cmp::Reverse(m.path.components().count()));
 
-    fn mount_point(&self) -> Option<&OverlayMap> {
-        self.mount_point
-            .as_ref()
-            .or_else(|| self.parent.as_ref().and_then(|p| p.mount_point()))
+        Ok(())
     }
 
-    pub fn resolve_path<'a>(&'a self, path: &'a Path) -> (&Path, &Path) {
-        assert!(path.is_absolute());
+    pub fn unmount(&self, path: &Path) -> Result<Arc<dyn FileSystem>, VfsError> {
+        let path = path.normalize();
+
+        let guard = self.mounts.guard();
+        let mut mounts = guard.lock();
+
+        let index = mounts
+            .iter()
+            .position(|m| m.path == path)
+            .ok_or(VfsError::NotMounted)?;
+
+        Ok(mounts.remove(index).fs)
+    }
 
-        let Some(mapping) = self
-            .mapping_with_start_of(path)
-            .or_else(|| self.mount_point())
-        else {
-            return (Path::ROOT, path);
+    /// Resolve `path` as seen through `overlay` to the filesystem it lives on.
+    ///
+    /// When `write` is requested, the overlay chain must grant write access to the path and
+    /// `permissions` must allow modifying it. Otherwise `permissions` must allow reading it.
+    pub fn open(
+        &self,
+        overlay: &OverLay,
+        permissions: &Permissions,
+        path: &Path,
+        write: bool,
+    ) -> Result<VfsPath, VfsError> {
+        let resolved = overlay.resolve_path(path)?;
+
+        if write && !resolved.write_access {
+            return Err(VfsError::ReadOnly);
+        }
+
+        let allowed = match write {
+            true => permissions.allows_modify(&resolved.path),
+            false => permissions.allows_read(&resolved.path),
         };
 
-        mapping.to.combine(path)
+        if !allowed {
+            return Err(VfsError::PermissionDenied);
+        }
+
+        let guard = self.mounts.guard();
+        let mounts = guard.lock();
+
+        let (mount, rest) = mounts
+            .iter()
+            .find_map(|m| resolved.path.strip_prefix(&m.path).map(|rest| (m, rest)))
+            .ok_or(VfsError::NotMounted)?;
+
+        Ok(VfsPath {
+            fs: mount.fs.clone(),
+            path: Path::ROOT.combine(rest),
+            write_access: resolved.write_access,
+        })
     }
 }
 
-pub struct Vfs {}
+impl Default for Vfs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-/*
+pub static VFS: Vfs = Vfs::new();
 
-ieder proces heeft een root Overlay
-en een "overlay"
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::multitasking::process::Permission;
 
-ik wil dit kunnen doen:
-spawn() -> zelfde root
-spawn(["/home/anon", "/"])
-spawn(["/home/anon/config", "/config"])
+    fn path(value: &str) -> &Path {
+        Path::new(value).unwrap()
+    }
 
+    fn root() -> Arc<OverLay> {
+        Arc::new(OverLay::ROOT)
+    }
 
-*/
+    #[test_case]
+    fn test_root_overlay_resolves_to_itself() {
+        let resolved = root().resolve_path(path("/home/../etc")).unwrap();
+        assert_eq!(path("/etc"), &*resolved.path);
+        assert!(resolved.write_access);
+    }
+
+    #[test_case]
+    fn test_mount_point_remaps_root() {
+        let child = OverLay::inherit(&root(), [(path("/home/anon"), path("/"), true)]).unwrap();
+
+        let resolved = child.resolve_path(path("/config")).unwrap();
+        assert_eq!(path("/home/anon/config"), &*resolved.path);
+    }
+
+    #[test_case]
+    fn test_mount_point_cannot_be_escaped() {
+        let child = OverLay::inherit(&root(), [(path("/home/anon"), path("/"), true)]).unwrap();
+
+        let resolved = child.resolve_path(path("/../../etc")).unwrap();
+        assert_eq!(path("/home/anon/etc"), &*resolved.path);
+    }
+
+    #[test_case]
+    fn test_specific_mapping_wins() {
+        let child = OverLay::inherit(
+            &root(),
+            [
+                (path("/home/anon"), path("/"), true),
+                (path("/home/anon/config"), path("/config"), false),
+                (path("/bin"), path("/config/bin"), false),
+            ],
+        )
+        .unwrap();
+
+        let resolved = child.resolve_path(path("/config/bin/sh")).unwrap();
+        assert_eq!(path("/bin/sh"), &*resolved.path);
+        assert!(!resolved.write_access);
+    }
+
+    #[test_case]
+    fn test_write_access_is_inherited() {
+        let child = OverLay::inherit(&root(), [(path("/home/anon"), path("/"), false)]).unwrap();
+        let grandchild = OverLay::inherit(&child, [(path("/config"), path("/"), true)]).unwrap();
+
+        let resolved = grandchild.resolve_path(path("/file")).unwrap();
+        assert_eq!(path("/home/anon/config/file"), &*resolved.path);
+        assert!(!resolved.write_access);
+    }
+
+    #[test_case]
+    fn test_open_requires_permission() {
+        let vfs = Vfs::new();
+        let permissions = Permissions::all()
+            .inherit([Permission::Read(path("/etc").to_path_buf())])
+            .unwrap();
+
+        let open = |p: &str, write: bool| vfs.open(&OverLay::ROOT, &permissions, path(p), write);
+
+        assert!(matches!(
+            open("/etc/hosts", false),
+            Err(VfsError::NotMounted)
+        ));
+        assert!(matches!(
+            open("/etc/hosts", true),
+            Err(VfsError::PermissionDenied)
+        ));
+        assert!(matches!(
+            open("/home", false),
+            Err(VfsError::PermissionDenied)
+        ));
+    }
+
+    #[test_case]
+    fn test_relative_path_is_rejected() {
+        assert_eq!(
+            VfsError::RelativePath,
+            root().resolve_path(path("etc")).unwrap_err()
+        );
+    }
+}
@@ -0,0 +1,13 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VfsError {
+    /// Overlays only accept absolute paths.
+    RelativePath,
+    /// The overlay does not grant write access to the path.
+    ReadOnly,
+    /// The process has no permission to read or modify the path.
+    PermissionDenied,
+    /// There is no filesystem mounted that contains the path.
+    NotMounted,
+    /// A filesystem is already mounted at the path.
+    AlreadyMounted,
+}
@@ -0,0 +1,20 @@
+//! Handles, through which processes use kernel objects.
+//!
+//! Every process has a [`HandleTable`] that maps small integers to reference counted
+//! [`KernelObject`]s: files, channel ends, ports, shared memory, threads and child processes. A
+//! handle carries the [`Rights`] that the process has on its object. Rights can only be taken
+//! away: a duplicate of a handle never has more rights than the original.
+//!
+//! Handles with the [`Rights::TRANSFER`] right are moved to other processes in the messages of
+//! channels. A spawned process inherits every such handle of its parent under the same number,
+//! except the handles that are marked to be closed on exec.
+
+mod error;
+mod object;
+mod rights;
+mod table;
+
+pub use error::HandleError;
+pub use object::{Capability, KernelObject};
+pub use rights::Rights;
+pub use table::{Handle, HandleTable};
@@ -0,0 +1,7 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandleError {
+    /// There is no object with the handle, or it is of another kind.
+    BadHandle,
+    /// The handle lacks a right that the operation requires.
+    MissingRights,
+}
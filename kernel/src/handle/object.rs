@@ -0,0 +1,43 @@
+use alloc::sync::Arc;
+
+use crate::{
+    fs::File,
+    ipc::{ChannelEnd, Port},
+    memory::shared::SharedMemory,
+    multitasking::{ids::ThreadId, process::ProcessId},
+};
+
+use super::Rights;
+
+/// An object that a process can hold a handle to.
+///
+/// Objects are reference counted, an object is closed once the last handle to it is closed.
+/// Threads and processes are referred to by their id instead, since they are never reused.
+#[derive(Clone)]
+pub enum KernelObject {
+    /// Reading and writing read and write the file.
+    File(Arc<File>),
+    /// Reading receives messages, writing sends them.
+    Channel(Arc<ChannelEnd>),
+    /// Reading accepts connections.
+    Port(Arc<Port>),
+    /// Reading maps the memory, writing allows to map it writable.
+    SharedMemory(Arc<SharedMemory>),
+    /// A thread of the process.
+    Thread(ThreadId),
+    /// A child of the process, reading waits for it to exit.
+    Process(ProcessId),
+}
+
+/// An object, and the rights that the holder of the handle has on it.
+#[derive(Clone)]
+pub struct Capability {
+    pub object: KernelObject,
+    pub rights: Rights,
+}
+
+impl Capability {
+    pub fn new(object: KernelObject, rights: Rights) -> Self {
+        Self { object, rights }
+    }
+}
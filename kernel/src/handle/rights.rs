@@ -0,0 +1,45 @@
+/// What the holder of a handle may do with its object.
+///
+/// The meaning of reading and writing depends on the kind of object, see
+/// [`KernelObject`](super::KernelObject).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rights {
+    bits: u32,
+}
+
+impl Rights {
+    pub const NONE: Self = Self { bits: 0 };
+    pub const READ: Self = Self { bits: 1 << 0 };
+    pub const WRITE: Self = Self { bits: 1 << 1 };
+    /// The handle can be moved to another process through a channel.
+    pub const TRANSFER: Self = Self { bits: 1 << 2 };
+    /// The handle can be duplicated, with the same or fewer rights.
+    pub const DUPLICATE: Self = Self { bits: 1 << 3 };
+    pub const ALL: Self = Self { bits: (1 << 4) - 1 };
+
+    /// The rights of `bits`, or `None` when it holds bits that are not a right.
+    pub const fn from_bits(bits: u32) -> Option<Self> {
+        match bits & !Self::ALL.bits {
+            0 => Some(Self { bits }),
+            _ => None,
+        }
+    }
+
+    pub const fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    pub const fn contains(&self, other: Rights) -> bool {
+        self.bits & other.bits == other.bits
+    }
+}
+
+impl core::ops::BitOr for Rights {
+    type Output = Rights;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self {
+            bits: self.bits | rhs.bits,
+        }
+    }
+}
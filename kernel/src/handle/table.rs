@@ -0,0 +1,297 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use alloc::{collections::BTreeMap, sync::Arc};
+use essentials::spin::SpinLock;
+
+use crate::{
+    fs::File,
+    ipc::{ChannelEnd, Port},
+    memory::shared::SharedMemory,
+    multitasking::process::ProcessId,
+    utils::InterruptGuard,
+};
+
+use super::{Capability, HandleError, KernelObject, Rights};
+
+/// The number by which a process refers to one of its objects.
+pub type Handle = u32;
+
+#[derive(Clone)]
+struct Entry {
+    capability: Capability,
+    /// The handle is not inherited by the processes that are spawned, even when it could be.
+    close_on_exec: bool,
+}
+
+/// The objects of a process, by handle.
+///
+/// Handles are never reused, so a stale handle never refers to another object.
+pub struct HandleTable {
+    entries: InterruptGuard<SpinLock<BTreeMap<Handle, Entry>>>,
+    next_handle: AtomicU32,
+}
+
+impl HandleTable {
+    pub const fn new() -> Self {
+        Self {
+            entries: InterruptGuard::new_lock(BTreeMap::new()),
+            next_handle: AtomicU32::new(1),
+        }
+    }
+
+    /// The table of a process that is spawned by the process of this table. Every handle with
+    /// the [`Rights::TRANSFER`] right that is not closed on exec is inherited with the same
+    /// number. Other handles, like those of ports and child processes, stay with their process.
+    pub fn inherit(&self) -> HandleTable {
+        let guard = self.entries.guard();
+        let entries = guard.lock();
+
+        let inherited = entries
+            .iter()
+            .filter(|(_, entry)| {
+                entry.capability.rights.contains(Rights::TRANSFER) && !entry.close_on_exec
+            })
+            .map(|(handle, entry)| (*handle, entry.clone()))
+            .collect();
+
+        HandleTable {
+            entries: InterruptGuard::new_lock(inherited),
+            next_handle: AtomicU32::new(self.next_handle.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Insert a handle to `capability`, which is inherited by spawned processes.
+    pub fn insert(&self, capability: Capability) -> Handle {
+        let handle = self.next_handle.fetch_add(1, Ordering::Relaxed);
+
+        let guard = self.entries.guard();
+        guard.lock().insert(
+            handle,
+            Entry {
+                capability,
+                close_on_exec: false,
+            },
+        );
+
+        handle
+    }
+
+    /// The capability of `handle`, when it holds every right of `required`.
+    pub fn get(&self, handle: Handle, required: Rights) -> Result<Capability, HandleError> {
+        let guard = self.entries.guard();
+        let entries = guard.lock();
+
+        let entry = entries.get(&handle).ok_or(HandleError::BadHandle)?;
+
+        if !entry.capability.rights.contains(required) {
+            return Err(HandleError::MissingRights);
+        }
+
+        Ok(entry.capability.clone())
+    }
+
+    pub fn file(&self, handle: Handle, required: Rights) -> Result<Arc<File>, HandleError> {
+        self.get_as(handle, required, |object| match object {
+            KernelObject::File(file) => Some(file),
+            _ => None,
+        })
+    }
+
+    pub fn channel(
+        &self,
+        handle: Handle,
+        required: Rights,
+    ) -> Result<Arc<ChannelEnd>, HandleError> {
+        self.get_as(handle, required, |object| match object {
+            KernelObject::Channel(end) => Some(end),
+            _ => None,
+        })
+    }
+
+    pub fn port(&self, handle: Handle, required: Rights) -> Result<Arc<Port>, HandleError> {
+        self.get_as(handle, required, |object| match object {
+            KernelObject::Port(port) => Some(port),
+            _ => None,
+        })
+    }
+
+    pub fn shared_memory(
+        &self,
+        handle: Handle,
+        required: Rights,
+    ) -> Result<Arc<SharedMemory>, HandleError> {
+        self.get_as(handle, required, |object| match object {
+            KernelObject::SharedMemory(memory) => Some(memory),
+            _ => None,
+        })
+    }
+
+    pub fn process(&self, handle: Handle, required: Rights) -> Result<ProcessId, HandleError> {
+        self.get_as(handle, required, |object| match object {
+            KernelObject::Process(process_id) => Some(process_id),
+            _ => None,
+        })
+    }
+
+    /// Insert a new handle to the object of `handle`, with `rights`. The handle needs the
+    /// [`Rights::DUPLICATE`] right, and the new handle cannot have rights that it does not have.
+    pub fn duplicate(&self, handle: Handle, rights: Rights) -> Result<Handle, HandleError> {
+        let capability = self.get(handle, Rights::DUPLICATE)?;
+
+        if !capability.rights.contains(rights) {
+            return Err(HandleError::MissingRights);
+        }
+
+        Ok(self.insert(Capability::new(capability.object, rights)))
+    }
+
+    /// Set whether `handle` is closed in the processes that are spawned, instead of inherited.
+    pub fn set_close_on_exec(
+        &self,
+        handle: Handle,
+        close_on_exec: bool,
+    ) -> Result<(), HandleError> {
+        let guard = self.entries.guard();
+        let mut entries = guard.lock();
+
+        let entry = entries.get_mut(&handle).ok_or(HandleError::BadHandle)?;
+        entry.close_on_exec = close_on_exec;
+
+        Ok(())
+    }
+
+    /// Remove the handle, its object is closed when this was its last reference.
+    pub fn remove(&self, handle: Handle) -> Option<Capability> {
+        let guard = self.entries.guard();
+        let removed = guard.lock().remove(&handle);
+
+        removed.map(|entry| entry.capability)
+    }
+
+    /// Remove every handle, once the process exits.
+    pub fn clear(&self) {
+        let entries = {
+            let guard = self.entries.guard();
+            let mut entries = guard.lock();
+
+            core::mem::take(&mut *entries)
+        };
+
+        // Closing an object wakes its waiters, which takes other locks.
+        drop(entries);
+    }
+
+    /// The object of `handle` as the kind that `as_kind` selects, when it holds every right of
+    /// `required`.
+    fn get_as<T>(
+        &self,
+        handle: Handle,
+        required: Rights,
+        as_kind: impl FnOnce(KernelObject) -> Option<T>,
+    ) -> Result<T, HandleError> {
+        let capability = self.get(handle, Rights::NONE)?;
+        let object = as_kind(capability.object).ok_or(HandleError::BadHandle)?;
+
+        if !capability.rights.contains(required) {
+            return Err(HandleError::MissingRights);
+        }
+
+        Ok(object)
+    }
+}
+
+impl Default for HandleTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ipc::{channel, listen};
+
+    use super::*;
+
+    fn channel_capability(rights: Rights) -> Capability {
+        let (end, _) = channel().unwrap();
+        Capability::new(KernelObject::Channel(Arc::new(end)), rights)
+    }
+
+    #[test_case]
+    fn test_rights() {
+        let table = HandleTable::new();
+        let handle = table.insert(channel_capability(Rights::READ | Rights::DUPLICATE));
+
+        assert!(table.channel(handle, Rights::READ).is_ok());
+        assert_eq!(
+            Some(HandleError::MissingRights),
+            table.channel(handle, Rights::WRITE).err()
+        );
+        assert_eq!(
+            Some(HandleError::BadHandle),
+            table.port(handle, Rights::NONE).err()
+        );
+
+        assert_eq!(
+            Err(HandleError::MissingRights),
+            table.duplicate(handle, Rights::READ | Rights::WRITE)
+        );
+
+        let duplicate = table.duplicate(handle, Rights::NONE).unwrap();
+        assert_ne!(handle, duplicate);
+        assert_eq!(
+            Err(HandleError::MissingRights),
+            table.duplicate(duplicate, Rights::NONE)
+        );
+
+        assert!(table.remove(handle).is_some());
+        assert!(table.remove(handle).is_none());
+        assert!(table.channel(duplicate, Rights::NONE).is_ok());
+    }
+
+    #[test_case]
+    fn test_close_on_exec() {
+        let table = HandleTable::new();
+        let kept = table.insert(channel_capability(Rights::ALL));
+        let closed = table.insert(channel_capability(Rights::ALL));
+
+        table.set_close_on_exec(closed, true).unwrap();
+        assert_eq!(
+            Err(HandleError::BadHandle),
+            table.set_close_on_exec(closed + 1, true)
+        );
+
+        let inherited = table.inherit();
+        assert!(inherited.channel(kept, Rights::ALL).is_ok());
+        assert_eq!(
+            Some(HandleError::BadHandle),
+            inherited.channel(closed, Rights::NONE).err()
+        );
+
+        // New handles never collide with the inherited ones.
+        let new = inherited.insert(channel_capability(Rights::ALL));
+        assert!(new > closed);
+    }
+
+    #[test_case]
+    fn test_inherit_without_transfer() {
+        let table = HandleTable::new();
+        let port = Capability::new(
+            KernelObject::Port(listen(40_005).unwrap()),
+            Rights::READ | Rights::DUPLICATE,
+        );
+        let port = table.insert(port);
+        let channel = table.insert(channel_capability(Rights::READ));
+
+        // Only the processes that were permitted to listen can accept connections.
+        let inherited = table.inherit();
+        assert_eq!(
+            Some(HandleError::BadHandle),
+            inherited.port(port, Rights::NONE).err()
+        );
+        assert_eq!(
+            Some(HandleError::BadHandle),
+            inherited.channel(channel, Rights::NONE).err()
+        );
+    }
+}
@@ -1,7 +1,11 @@
+use alloc::sync::Arc;
 use bootinfo::BootInfo;
+use path::Path;
+use x86_64::{halt_loop, interrupt::enable_interrupts};
 
 use crate::{
     arch::x86_64::mp,
+    fs::{initrd, ramfs::RamFs, FileSystem, VFS},
     memory::{
         alloc::{kernel_alloc::KERNEL_ALLOC, FRAME_ALLOC},
         map::MemoryMapper,
@@ -9,7 +13,10 @@ use crate::{
     multitasking::{scheduler::LOWEST_PRIORITY, PROCESS_TABLE, SCHEDULER},
 };
 
-use crate::{arch, debug_println};
+use crate::{arch, debug_println, warning_println};
+
+/// The root ramfs may use up to `1 / ROOT_RAMFS_HEAP_SHARE` of the kernel heap.
+const ROOT_RAMFS_HEAP_SHARE: usize = 4;
 
 /// Initialize and start the operating system.
 ///
@@ -35,6 +42,17 @@ pub unsafe fn init(boot_info: &BootInfo) {
 
     debug_println!("FRAME_ALLOC initialized");
 
+    let root_fs = Arc::new(RamFs::new(
+        KERNEL_ALLOC.backing_size() / ROOT_RAMFS_HEAP_SHARE,
+    ));
+
+    VFS.mount(Path::ROOT, root_fs.clone())
+        .expect("mounting the root filesystem should never fail in init()");
+
+    debug_println!("VFS initialized; ramfs mounted at /");
+
+    unpack_initrd(boot_info, &*root_fs);
+
     let mut kernel_mem = MemoryMapper::new_root_mapper(boot_info.physycal_memory_offset());
 
     arch::init(boot_info, &mut kernel_mem);
@@ -53,5 +71,35 @@ pub unsafe fn init(boot_info: &BootInfo) {
     PROCESS_TABLE.init();
     debug_println!("PROCESS_TABLE initialized");
 
-    mp::start_slave_processors(|processor_id| debug_println!("Processor #{processor_id} online"));
+    mp::start_slave_processors(slave_main);
+}
+
+/// Every other processor joins the scheduler with its own kernel thread.
+fn slave_main(processor_id: usize) -> ! {
+    let kernel_tid = SCHEDULER
+        .current_as_kernel_thread(LOWEST_PRIORITY)
+        .expect("calling current_as_kernel_thread should never fail in slave_main()");
+
+    debug_println!("Processor #{processor_id} online; kernel thread id: {kernel_tid}");
+
+    enable_interrupts();
+    halt_loop()
+}
+
+/// Every module besides the kernel is expected to be an archive that is unpacked into the root
+/// filesystem.
+unsafe fn unpack_initrd(boot_info: &BootInfo, root_fs: &dyn FileSystem) {
+    for module in boot_info.modules() {
+        let archive = core::slice::from_raw_parts(
+            (module.region.start as usize + boot_info.physycal_memory_offset()) as *const u8,
+            module.region.size as usize,
+        );
+
+        let name = module.cmdline().unwrap_or("<unnamed>");
+
+        match initrd::unpack(archive, root_fs) {
+            Ok(count) => debug_println!("Initrd module {name} unpacked; {count} entries"),
+            Err(e) => warning_println!("Failed to unpack initrd module {name}: {e:?}"),
+        }
+    }
 }
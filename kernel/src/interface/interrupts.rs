@@ -1,22 +1,93 @@
-use crate::{arch::CpuContext, memory::map::PageFault, multitasking::SCHEDULER};
+use crate::{
+    arch::CpuContext,
+    memory::map::PageFault,
+    multitasking::{process::ExitCode, PROCESS_TABLE, SCHEDULER},
+};
 use crate::{log, warning_println};
 
+/// The exit code of a process that is killed because of a page fault it could not recover from.
+pub const PAGE_FAULT_EXIT_CODE: ExitCode = -11;
+/// The exit code of a process that is killed because it used up its CPU time.
+pub const CPU_TIME_EXIT_CODE: ExitCode = -24;
+
 pub fn uart_status_change() {
     log::flush_availible();
 }
 
-pub fn tick(current_context: CpuContext) -> CpuContext {
-    if let Some(next_context) = SCHEDULER.next_ctx(current_context) {
-        return next_context;
+/// A tick of the timer, `user_mode` is whether the processor was running user code.
+pub fn tick(current_context: CpuContext, user_mode: bool) -> CpuContext {
+    // In user mode the process holds no kernel locks, otherwise it is killed on a later tick.
+    if user_mode {
+        kill_over_cpu_time();
+    }
+
+    SCHEDULER.tick(current_context)
+}
+
+/// Kill the current process when it used more CPU time than it is allowed, the scheduler switches
+/// away from its thread right after.
+fn kill_over_cpu_time() {
+    let Some(process) = SCHEDULER
+        .current_ids()
+        .0
+        .and_then(|id| PROCESS_TABLE.get(id))
+    else {
+        return;
+    };
+
+    if !process.exceeds_cpu_time() {
+        return;
     }
 
-    todo!("Idle task is not implemented")
+    warning_println!(
+        "Killing process {}: used up its CPU time ({:?})",
+        process.process_id(),
+        process.cpu_time(),
+    );
+
+    // The process can only have exited in the meantime, which is what we want anyway.
+    let _ = PROCESS_TABLE.exit(process.process_id(), CPU_TIME_EXIT_CODE);
+}
+
+/// The current thread gives up the processor, or blocks.
+pub fn reschedule(current_context: CpuContext) -> CpuContext {
+    SCHEDULER.next_ctx(current_context)
 }
 
 pub fn unhandled_irq() {
     warning_println!("Unhandled IRQ");
 }
 
-pub fn page_fault(fault: PageFault) -> Option<CpuContext> {
-    panic!("{fault:?}");
+/// Resolve a page fault, faults in user mode that cannot be resolved only kill the process.
+pub fn page_fault(fault: PageFault, current_context: CpuContext) -> Option<CpuContext> {
+    let (process_id, thread_id) = SCHEDULER.current_ids();
+
+    if !fault.user_mode {
+        panic!(
+            "Page fault in kernel mode (process {process_id:?}, thread {thread_id:?}): {fault:#?}"
+        );
+    }
+
+    let Some(process) = process_id.and_then(|id| PROCESS_TABLE.get(id)) else {
+        // The process exited while the thread was running.
+        return Some(SCHEDULER.next_ctx(current_context));
+    };
+
+    let Err(err) = process.manager().handle_page_fault(fault) else {
+        return None;
+    };
+
+    warning_println!(
+        "Killing process {} (thread {thread_id:?}): {:?} at {:?} ({:?})",
+        process.process_id(),
+        err.kind,
+        err.fault.addr,
+        err.fault.instruction_pointer,
+    );
+
+    // The process can only have exited in the meantime, which is what we want anyway.
+    let _ = PROCESS_TABLE.exit(process.process_id(), PAGE_FAULT_EXIT_CODE);
+    drop(process);
+
+    Some(SCHEDULER.next_ctx(current_context))
 }
@@ -0,0 +1,1478 @@
+//! The system calls that user processes can make.
+//!
+//! A system call is identified by its number, and takes up to six arguments. The result is
+//! returned as a single `u64`, where errors are encoded as the negated [`SyscallError`] code.
+//!
+//! A process may only make the syscalls that its permissions allow, except for [`SYS_REQUIRE`]
+//! which is always allowed so that programs can probe their permissions.
+
+mod error;
+
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
+use essentials::address::VirtualAddress;
+use path::PathBuf;
+use x86_64::{
+    interrupt::{disable_interrupts, enable_interrupts},
+    RFlags,
+};
+
+use crate::{
+    arch::{self, CpuContext},
+    fs::{File, OverLay, VFS},
+    handle::{Capability, Handle, KernelObject, Rights},
+    info_println,
+    ipc::{self, ChannelEnd, IpcError, Message, MAX_MESSAGE_HANDLES, MAX_MESSAGE_LEN},
+    memory::{
+        map::{AreaKind, MemoryArea, MemoryMapper, MemoryProperties},
+        shared::SharedMemory,
+    },
+    multitasking::{
+        exec::exec_path,
+        process::{ExitCode, Permission, Process},
+        scheduler::LOWEST_PRIORITY,
+        PROCESS_TABLE, SCHEDULER,
+    },
+};
+
+pub use error::SyscallError;
+
+pub type SyscallArgs = [u64; 6];
+type SyscallHandler = fn(SyscallArgs) -> Result<u64, SyscallError>;
+
+/// Write a message to the kernel log: `log(addr, len)`.
+pub const SYS_LOG: u64 = 0;
+/// Get the id of the calling process: `process_id()`.
+pub const SYS_PROCESS_ID: u64 = 1;
+/// Get the id of the calling thread: `thread_id()`.
+pub const SYS_THREAD_ID: u64 = 2;
+/// Check whether the calling process holds a permission: `require(kind, a, b)`.
+///
+/// The `kind` is one of the `REQUIRE_*` constants. Paths are passed as `(addr, len)` and are
+/// resolved through the overlay of the process, all other kinds only use `a`. Returns 0 when the
+/// permission is held.
+pub const SYS_REQUIRE: u64 = 3;
+/// Exit the calling process, this never returns: `exit(code)`.
+pub const SYS_EXIT: u64 = 4;
+/// Create a channel, and write the handles of both ends to `addr` as two `u32`: `channel(addr)`.
+pub const SYS_CHANNEL: u64 = 5;
+/// Send a message at a channel end: `send(handle, addr, len, handles_addr, handle_count, flags)`.
+///
+/// The handles at `handles_addr` are `u32` and need the [`Rights::TRANSFER`] right, they are
+/// moved to the receiver with their rights. Ports cannot be sent. Blocks while the channel is
+/// full, unless `flags` holds [`IPC_NONBLOCK`].
+pub const SYS_SEND: u64 = 6;
+/// Receive a message at a channel end:
+/// `receive(handle, addr, len, handles_addr, handles_len, flags)`.
+///
+/// The data is written to `addr`, and the handles of the objects that came with it to
+/// `handles_addr` as `u32`. Returns the length of the data, with the number of handles in the
+/// upper 32 bits. A message that does not fit is not received. Blocks until there is a message,
+/// unless `flags` holds [`IPC_NONBLOCK`].
+pub const SYS_RECEIVE: u64 = 7;
+/// Close a handle: `close(handle)`.
+///
+/// The object is closed once its last handle is closed, shared memory stays mapped until it is
+/// unmapped.
+pub const SYS_CLOSE: u64 = 8;
+/// Listen on a port, and get the handle of the port: `listen(port)`.
+pub const SYS_LISTEN: u64 = 9;
+/// Connect to the listener of a port, and get the handle of a channel end: `connect(port)`.
+pub const SYS_CONNECT: u64 = 10;
+/// Accept a connection to a port, and get the handle of a channel end: `accept(handle, flags)`.
+///
+/// Blocks until there is a connection, unless `flags` holds [`IPC_NONBLOCK`].
+pub const SYS_ACCEPT: u64 = 11;
+/// Create zero filled shared memory of at least `size` bytes, and get its handle:
+/// `shm_create(size)`.
+///
+/// The memory is charged to the process until it is dropped, together with the rest of its memory
+/// it has to fit within its permission to use memory.
+pub const SYS_SHM_CREATE: u64 = 12;
+/// Map shared memory at the page aligned `addr`, and get its size: `shm_map(handle, addr, flags)`.
+///
+/// The memory is mapped writable when `flags` holds [`SHM_WRITE`], which the handle has to allow.
+pub const SYS_SHM_MAP: u64 = 13;
+/// Unmap the shared memory that was mapped at `addr`: `shm_unmap(addr)`.
+pub const SYS_SHM_UNMAP: u64 = 14;
+/// Get a new handle to the object of a handle, with the same or fewer rights:
+/// `duplicate(handle, rights)`.
+///
+/// The `rights` are the bits of [`Rights`], the handle needs the [`Rights::DUPLICATE`] right.
+pub const SYS_DUPLICATE: u64 = 15;
+/// Set whether a handle is closed in the processes that are spawned, instead of inherited:
+/// `close_on_exec(handle, enabled)`.
+pub const SYS_CLOSE_ON_EXEC: u64 = 16;
+/// Open the file at a path, and get its handle: `open(addr, len, flags)`.
+///
+/// The file is opened for writing when `flags` holds [`OPEN_WRITE`], which requires the
+/// permission to modify it. Otherwise reading it requires the permission to read it.
+pub const SYS_OPEN: u64 = 17;
+/// Read a file at `offset`, and get the number of bytes that were read:
+/// `read(handle, addr, len, offset)`.
+pub const SYS_READ: u64 = 18;
+/// Write a file at `offset`, and get the number of bytes that were written:
+/// `write(handle, addr, len, offset)`.
+pub const SYS_WRITE: u64 = 19;
+/// Execute the file at a path as a child process, and get the handle of the child:
+/// `spawn(addr, len, grants_addr, grant_count, mounts_addr, mount_count)`.
+///
+/// Each grant is three `u64` that are passed like the arguments of [`SYS_REQUIRE`], the child only
+/// gets the permissions that are granted and held by the caller, besides the CPU time, memory and
+/// syscalls that are not granted explicitly. Each mount is five `u64`:
+/// `(parent_addr, parent_len, child_addr, child_len, flags)`, which map a path of the caller to a
+/// path of the child, writable when `flags` holds [`MOUNT_WRITE`]. Without mounts the child sees
+/// the same files as the caller. The child inherits the handles that can be transferred and are
+/// not closed on exec.
+pub const SYS_SPAWN: u64 = 20;
+/// Wait for a child process to exit, and get its exit code: `wait(handle, flags)`.
+///
+/// Blocks until the child exits, unless `flags` holds [`IPC_NONBLOCK`].
+pub const SYS_WAIT: u64 = 21;
+/// Start a new thread of the calling process, and get its handle:
+/// `spawn_thread(entry, stack_pointer)`.
+///
+/// The entry and the word below the stack pointer have to be in user space.
+pub const SYS_SPAWN_THREAD: u64 = 22;
+
+pub const REQUIRE_READ: u64 = 0;
+pub const REQUIRE_MODIFY: u64 = 1;
+pub const REQUIRE_CPU_TIME: u64 = 2;
+pub const REQUIRE_MEMORY: u64 = 3;
+pub const REQUIRE_SYSCALL: u64 = 4;
+pub const REQUIRE_LISTEN: u64 = 5;
+pub const REQUIRE_REQUEST: u64 = 6;
+
+/// Fail with [`SyscallError::WouldBlock`] instead of blocking.
+pub const IPC_NONBLOCK: u64 = 1;
+
+/// Map shared memory writable.
+pub const SHM_WRITE: u64 = 1;
+
+/// Open a file for writing.
+pub const OPEN_WRITE: u64 = 1;
+
+/// Let a spawned process write to a mounted path.
+pub const MOUNT_WRITE: u64 = 1;
+
+/// The handlers, indexed by system call number.
+const SYSCALLS: &[SyscallHandler] = &[
+    sys_log,
+    sys_process_id,
+    sys_thread_id,
+    sys_require,
+    sys_exit,
+    sys_channel,
+    sys_send,
+    sys_receive,
+    sys_close,
+    sys_listen,
+    sys_connect,
+    sys_accept,
+    sys_shm_create,
+    sys_shm_map,
+    sys_shm_unmap,
+    sys_duplicate,
+    sys_close_on_exec,
+    sys_open,
+    sys_read,
+    sys_write,
+    sys_spawn,
+    sys_wait,
+    sys_spawn_thread,
+];
+
+/// The maximum length of a single log message.
+const MAX_LOG_LEN: usize = 1024;
+/// The maximum length of a path argument.
+const MAX_PATH_LEN: usize = 4096;
+/// The maximum number of bytes that a single read or write of a file transfers.
+const MAX_IO_LEN: usize = 64 * 1024;
+/// The maximum number of permissions that a spawned process is granted.
+const MAX_SPAWN_GRANTS: usize = 64;
+/// The maximum number of paths that are mounted for a spawned process.
+const MAX_SPAWN_MOUNTS: usize = 16;
+
+/// Returned by a system call that blocks the calling thread, the result is never seen since the
+/// system call is made again once the thread is woken.
+const BLOCKED: Result<u64, SyscallError> = Err(SyscallError::WouldBlock);
+
+/// Dispatch system call `number`, this is called by the architecture specific entry.
+///
+/// Returns `None` when the calling thread blocks, the entry has to make the system call again once
+/// the thread is woken.
+pub fn syscall(number: u64, args: SyscallArgs) -> Option<u64> {
+    let result = usize::try_from(number)
+        .ok()
+        .and_then(|number| SYSCALLS.get(number))
+        .ok_or(SyscallError::UnknownSyscall)
+        .and_then(|handler| {
+            check_permission(number)?;
+            handler(args)
+        });
+
+    if SCHEDULER.is_blocking() {
+        return None;
+    }
+
+    match result {
+        Ok(value) => Some(value),
+        Err(err) => Some(err.encode()),
+    }
+}
+
+/// Called by the architecture specific entry before returning to the user, returns the context to
+/// switch to when the calling thread can no longer run.
+pub fn next_context(current: CpuContext) -> Option<CpuContext> {
+    let (Some(process_id), _) = SCHEDULER.current_ids() else {
+        return None;
+    };
+
+    if !SCHEDULER.is_blocking() && PROCESS_TABLE.get(process_id).is_some() {
+        return None;
+    }
+
+    Some(SCHEDULER.next_ctx(current))
+}
+
+/// Syscalls made by kernel threads, that do not belong to a process, are always allowed.
+fn check_permission(number: u64) -> Result<(), SyscallError> {
+    if number == SYS_REQUIRE {
+        return Ok(());
+    }
+
+    match current_process() {
+        Ok(process) if !process.permissions().allows_syscall(number) => {
+            Err(SyscallError::PermissionDenied)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Run the part of a system call that takes long with interrupts enabled, so that it does not
+/// delay the timer and the other threads.
+///
+/// The entry disables interrupts, they are disabled again once `work` is done. `work` must not
+/// block the calling thread.
+fn interruptible<R>(work: impl FnOnce() -> R) -> R {
+    // Kernel threads make system calls with interrupts enabled.
+    if RFlags::read().interrupts_enabled() {
+        return work();
+    }
+
+    enable_interrupts();
+    let result = work();
+    disable_interrupts();
+
+    result
+}
+
+fn current_process() -> Result<Arc<Process>, SyscallError> {
+    let process_id = SCHEDULER.current_ids().0.ok_or(SyscallError::NoProcess)?;
+
+    PROCESS_TABLE.get(process_id).ok_or(SyscallError::NoProcess)
+}
+
+/// The address of `len` bytes at `addr`, when they are within the user space.
+fn user_address(addr: u64, len: usize) -> Result<VirtualAddress, SyscallError> {
+    let start = addr as usize;
+    let end = start.checked_add(len).ok_or(SyscallError::BadAddress)?;
+
+    if start < MemoryMapper::USER_SPACE_START || end > MemoryMapper::USER_SPACE_END {
+        return Err(SyscallError::BadAddress);
+    }
+
+    Ok(VirtualAddress::new(start))
+}
+
+/// Copy `buf.len()` bytes at `addr` from the user space of `process`.
+fn read_user(process: &Process, addr: u64, buf: &mut [u8]) -> Result<(), SyscallError> {
+    // Nothing is read, so the address does not matter.
+    if buf.is_empty() {
+        return Ok(());
+    }
+
+    process
+        .manager()
+        .read(user_address(addr, buf.len())?, buf)
+        .map_err(|_| SyscallError::BadAddress)
+}
+
+/// Copy `data` to `addr` in the user space of `process`.
+fn write_user(process: &Process, addr: u64, data: &[u8]) -> Result<(), SyscallError> {
+    if data.is_empty() {
+        return Ok(());
+    }
+
+    process
+        .manager()
+        .write(user_address(addr, data.len())?, data)
+        .map_err(|_| SyscallError::BadAddress)
+}
+
+fn read_user_handles(
+    process: &Process,
+    addr: u64,
+    count: usize,
+) -> Result<Vec<Handle>, SyscallError> {
+    let mut bytes = vec![0u8; count * size_of::<Handle>()];
+    read_user(process, addr, &mut bytes)?;
+
+    let (chunks, _) = bytes.as_chunks::<{ size_of::<Handle>() }>();
+
+    Ok(chunks
+        .iter()
+        .map(|chunk| Handle::from_le_bytes(*chunk))
+        .collect())
+}
+
+/// Read `count` words from user space, where system calls take arrays of arguments.
+fn read_user_words(process: &Process, addr: u64, count: usize) -> Result<Vec<u64>, SyscallError> {
+    let mut bytes = vec![0u8; count * size_of::<u64>()];
+    read_user(process, addr, &mut bytes)?;
+
+    let (chunks, _) = bytes.as_chunks::<{ size_of::<u64>() }>();
+
+    Ok(chunks
+        .iter()
+        .map(|chunk| u64::from_le_bytes(*chunk))
+        .collect())
+}
+
+fn write_user_handles(
+    process: &Process,
+    addr: u64,
+    handles: &[Handle],
+) -> Result<(), SyscallError> {
+    let bytes: Vec<u8> = handles
+        .iter()
+        .flat_map(|handle| handle.to_le_bytes())
+        .collect();
+
+    write_user(process, addr, &bytes)
+}
+
+fn handle_arg(value: u64) -> Result<Handle, SyscallError> {
+    Handle::try_from(value).map_err(|_| SyscallError::BadHandle)
+}
+
+fn port_arg(value: u64) -> Result<u16, SyscallError> {
+    u16::try_from(value).map_err(|_| SyscallError::InvalidArgument)
+}
+
+fn insert_channel(process: &Process, end: ChannelEnd) -> Result<Handle, SyscallError> {
+    let end = Arc::try_new(end).map_err(|_| SyscallError::OutOfMemory)?;
+
+    let capability = Capability::new(KernelObject::Channel(end), Rights::ALL);
+
+    Ok(process.handles().insert(capability))
+}
+
+fn sys_log(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let [addr, len, ..] = args;
+    let len = len as usize;
+
+    if len > MAX_LOG_LEN {
+        return Err(SyscallError::InvalidArgument);
+    }
+
+    let process = current_process()?;
+
+    let mut message = vec![0u8; len];
+    read_user(&process, addr, &mut message)?;
+
+    let message = core::str::from_utf8(&message).map_err(|_| SyscallError::InvalidArgument)?;
+
+    info_println!("[{}] {message}", process.process_id());
+
+    Ok(len as u64)
+}
+
+fn sys_process_id(_args: SyscallArgs) -> Result<u64, SyscallError> {
+    let (process_id, _) = SCHEDULER.current_ids();
+
+    process_id
+        .map(|id| id as u64)
+        .ok_or(SyscallError::NoProcess)
+}
+
+fn sys_thread_id(_args: SyscallArgs) -> Result<u64, SyscallError> {
+    let (_, thread_id) = SCHEDULER.current_ids();
+
+    thread_id.map(|id| id as u64).ok_or(SyscallError::NoProcess)
+}
+
+/// Read a permission that is passed as `(kind, a, b)`, see [`SYS_REQUIRE`].
+fn read_user_permission(
+    process: &Process,
+    kind: u64,
+    a: u64,
+    b: u64,
+) -> Result<Permission, SyscallError> {
+    let port = || u16::try_from(a).map_err(|_| SyscallError::InvalidArgument);
+
+    Ok(match kind {
+        REQUIRE_READ => Permission::Read(read_user_path(process, a, b)?),
+        REQUIRE_MODIFY => Permission::Modify(read_user_path(process, a, b)?),
+        REQUIRE_CPU_TIME => Permission::CpuTime(a),
+        REQUIRE_MEMORY => Permission::Memory(a),
+        REQUIRE_SYSCALL => Permission::Syscall(a),
+        REQUIRE_LISTEN => Permission::Listen(port()?),
+        REQUIRE_REQUEST => Permission::Request(port()?),
+        _ => return Err(SyscallError::InvalidArgument),
+    })
+}
+
+fn sys_require(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let [kind, a, b, ..] = args;
+    let process = current_process()?;
+
+    let permission = read_user_permission(&process, kind, a, b)?;
+
+    match process.permissions().allows(&permission) {
+        true => Ok(0),
+        false => Err(SyscallError::PermissionDenied),
+    }
+}
+
+fn sys_exit(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let process = current_process()?;
+
+    PROCESS_TABLE
+        .exit(process.process_id(), args[0] as ExitCode)
+        .map_err(|_| SyscallError::NoProcess)?;
+
+    Ok(0)
+}
+
+fn sys_channel(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let process = current_process()?;
+    let handles = process.handles();
+
+    let (first, second) = ipc::channel()?;
+    let first = insert_channel(&process, first)?;
+
+    let second = match insert_channel(&process, second) {
+        Ok(second) => second,
+        Err(err) => {
+            handles.remove(first);
+            return Err(err);
+        }
+    };
+
+    if let Err(err) = write_user_handles(&process, args[0], &[first, second]) {
+        handles.remove(first);
+        handles.remove(second);
+        return Err(err);
+    }
+
+    Ok(0)
+}
+
+fn sys_send(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let [handle, addr, len, handles_addr, handle_count, flags] = args;
+    let (len, handle_count) = (len as usize, handle_count as usize);
+
+    if len > MAX_MESSAGE_LEN {
+        return Err(SyscallError::MessageTooLarge);
+    }
+
+    if handle_count > MAX_MESSAGE_HANDLES {
+        return Err(SyscallError::MessageTooLarge);
+    }
+
+    let process = current_process()?;
+    let handles = process.handles();
+    let end = handles.channel(handle_arg(handle)?, Rights::WRITE)?;
+
+    let mut data = vec![0u8; len];
+    read_user(&process, addr, &mut data)?;
+
+    let transferred = read_user_handles(&process, handles_addr, handle_count)?;
+
+    // A handle that is listed twice would give the receiver two capabilities for one object,
+    // which only a handle with the duplicate right may do.
+    let repeated = transferred
+        .iter()
+        .enumerate()
+        .any(|(index, handle)| transferred[..index].contains(handle));
+
+    if repeated {
+        return Err(SyscallError::InvalidArgument);
+    }
+
+    let capabilities = transferred
+        .iter()
+        .map(|handle| handles.get(*handle, Rights::TRANSFER))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut message = Message::new(data, capabilities)?;
+
+    loop {
+        match end.try_send(message) {
+            Ok(()) => break,
+            Err((IpcError::WouldBlock, returned)) if flags & IPC_NONBLOCK == 0 => {
+                if end.block_send_after_syscall() {
+                    return BLOCKED;
+                }
+
+                message = returned;
+            }
+            Err((err, _)) => return Err(err.into()),
+        }
+    }
+
+    // The objects now belong to the receiver.
+    for handle in transferred {
+        handles.remove(handle);
+    }
+
+    Ok(0)
+}
+
+fn sys_receive(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let [handle, addr, len, handles_addr, handles_len, flags] = args;
+    let (len, handles_len) = (len as usize, handles_len as usize);
+
+    let process = current_process()?;
+    let handles = process.handles();
+    let end = handles.channel(handle_arg(handle)?, Rights::READ)?;
+
+    // The data is written, and the handle buffer is checked, before the message is taken, so it is
+    // not lost when a buffer is not mapped.
+    let accept = |message: &Message| {
+        if message.data().len() > len || message.capabilities().len() > handles_len {
+            return Err(SyscallError::MessageTooLarge);
+        }
+
+        write_user(&process, addr, message.data())?;
+        write_user_handles(
+            &process,
+            handles_addr,
+            &vec![0; message.capabilities().len()],
+        )
+    };
+
+    let message = loop {
+        match end.try_receive_if(accept) {
+            Err(SyscallError::WouldBlock) if flags & IPC_NONBLOCK == 0 => {
+                if end.block_receive_after_syscall() {
+                    return BLOCKED;
+                }
+            }
+            result => break result?,
+        }
+    };
+
+    let (data, capabilities) = message.into_parts();
+
+    let received: Vec<Handle> = capabilities
+        .into_iter()
+        .map(|capability| handles.insert(capability))
+        .collect();
+
+    // The buffer may have been unmapped by another thread since, the objects are closed instead
+    // of kept under handles the process never learns about.
+    if let Err(err) = write_user_handles(&process, handles_addr, &received) {
+        for handle in received {
+            handles.remove(handle);
+        }
+
+        return Err(err);
+    }
+
+    Ok(data.len() as u64 | (received.len() as u64) << 32)
+}
+
+fn sys_close(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let process = current_process()?;
+
+    process
+        .handles()
+        .remove(handle_arg(args[0])?)
+        .ok_or(SyscallError::BadHandle)?;
+
+    Ok(0)
+}
+
+fn sys_listen(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let port = port_arg(args[0])?;
+    let process = current_process()?;
+
+    if !process.permissions().allows(&Permission::Listen(port)) {
+        return Err(SyscallError::PermissionDenied);
+    }
+
+    let listener = ipc::listen(port)?;
+
+    // Only the listener can accept connections, so the port cannot be transferred.
+    let capability = Capability::new(
+        KernelObject::Port(listener),
+        Rights::READ | Rights::DUPLICATE,
+    );
+
+    Ok(process.handles().insert(capability) as u64)
+}
+
+fn sys_connect(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let port = port_arg(args[0])?;
+    let process = current_process()?;
+
+    if !process.permissions().allows(&Permission::Request(port)) {
+        return Err(SyscallError::PermissionDenied);
+    }
+
+    let end = ipc::connect(port)?;
+
+    insert_channel(&process, end).map(|handle| handle as u64)
+}
+
+fn sys_accept(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let [handle, flags, ..] = args;
+
+    let process = current_process()?;
+    let listener = process.handles().port(handle_arg(handle)?, Rights::READ)?;
+
+    let end = loop {
+        match listener.try_accept() {
+            Err(IpcError::WouldBlock) if flags & IPC_NONBLOCK == 0 => {
+                if listener.block_accept_after_syscall() {
+                    return BLOCKED;
+                }
+            }
+            result => break result?,
+        }
+    };
+
+    insert_channel(&process, end).map(|handle| handle as u64)
+}
+
+fn sys_shm_create(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let size = usize::try_from(args[0]).map_err(|_| SyscallError::InvalidArgument)?;
+    let process = current_process()?;
+
+    // The memory counts towards the quota of the process, even when other processes map it.
+    let memory = SharedMemory::new_charged(size, process.manager().quota())?;
+    let memory = Arc::try_new(memory).map_err(|_| SyscallError::OutOfMemory)?;
+
+    let capability = Capability::new(KernelObject::SharedMemory(memory), Rights::ALL);
+
+    Ok(process.handles().insert(capability) as u64)
+}
+
+fn sys_shm_map(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let [handle, addr, flags, ..] = args;
+    let writable = flags & SHM_WRITE != 0;
+
+    let required = match writable {
+        true => Rights::READ | Rights::WRITE,
+        false => Rights::READ,
+    };
+
+    let process = current_process()?;
+    let memory = process
+        .handles()
+        .shared_memory(handle_arg(handle)?, required)?;
+
+    let size = memory.size();
+    let start = user_address(addr, size)?;
+    let properties = MemoryProperties::new(writable, true, false, false, false);
+
+    process.manager().reserve(MemoryArea::new(
+        start,
+        size,
+        properties,
+        AreaKind::Shared(memory),
+    ))?;
+
+    Ok(size as u64)
+}
+
+fn sys_shm_unmap(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let process = current_process()?;
+    let start = user_address(args[0], 0)?;
+
+    // Only shared memory can be unmapped, the other areas belong to the program itself.
+    match process.manager().area(start) {
+        Some(area) if area.start() == start && matches!(area.kind(), AreaKind::Shared(_)) => {}
+        _ => return Err(SyscallError::BadAddress),
+    }
+
+    process.manager().release(start)?;
+
+    Ok(0)
+}
+
+fn sys_duplicate(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let [handle, rights, ..] = args;
+
+    let rights = u32::try_from(rights)
+        .ok()
+        .and_then(Rights::from_bits)
+        .ok_or(SyscallError::InvalidArgument)?;
+
+    let process = current_process()?;
+    let duplicate = process.handles().duplicate(handle_arg(handle)?, rights)?;
+
+    Ok(duplicate as u64)
+}
+
+fn sys_close_on_exec(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let [handle, enabled, ..] = args;
+    let process = current_process()?;
+
+    process
+        .handles()
+        .set_close_on_exec(handle_arg(handle)?, enabled != 0)?;
+
+    Ok(0)
+}
+
+fn sys_open(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let [addr, len, flags, ..] = args;
+    let writable = flags & OPEN_WRITE != 0;
+
+    let process = current_process()?;
+    let path = read_user_unresolved_path(&process, addr, len)?;
+    let (overlay, permissions) = (process.overlay(), process.permissions());
+
+    let opened = VFS.open(overlay, permissions, &path, writable)?;
+
+    // Opening for writing only requires the permission to modify the file.
+    let readable = !writable || VFS.open(overlay, permissions, &path, false).is_ok();
+
+    let file = Arc::try_new(File::open(&opened)?).map_err(|_| SyscallError::OutOfMemory)?;
+
+    let mut rights = Rights::TRANSFER | Rights::DUPLICATE;
+
+    if readable {
+        rights = rights | Rights::READ;
+    }
+
+    if writable {
+        rights = rights | Rights::WRITE;
+    }
+
+    let capability = Capability::new(KernelObject::File(file), rights);
+
+    Ok(process.handles().insert(capability) as u64)
+}
+
+fn sys_read(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let [handle, addr, len, offset, ..] = args;
+    let len = (len as usize).min(MAX_IO_LEN);
+
+    let process = current_process()?;
+    let file = process.handles().file(handle_arg(handle)?, Rights::READ)?;
+
+    interruptible(|| {
+        let mut buf = vec![0u8; len];
+        let read = file.read_at(offset, &mut buf)?;
+
+        write_user(&process, addr, &buf[..read])?;
+
+        Ok(read as u64)
+    })
+}
+
+fn sys_write(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let [handle, addr, len, offset, ..] = args;
+    let len = (len as usize).min(MAX_IO_LEN);
+
+    let process = current_process()?;
+    let file = process.handles().file(handle_arg(handle)?, Rights::WRITE)?;
+
+    interruptible(|| {
+        let mut data = vec![0u8; len];
+        read_user(&process, addr, &mut data)?;
+
+        Ok(file.write_at(offset, &data)? as u64)
+    })
+}
+
+fn sys_spawn(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let [addr, len, grants_addr, grant_count, mounts_addr, mount_count] = args;
+    let (grant_count, mount_count) = (grant_count as usize, mount_count as usize);
+
+    if grant_count > MAX_SPAWN_GRANTS || mount_count > MAX_SPAWN_MOUNTS {
+        return Err(SyscallError::InvalidArgument);
+    }
+
+    let process = current_process()?;
+    let path = read_user_unresolved_path(&process, addr, len)?;
+
+    let grants = read_user_words(&process, grants_addr, grant_count * 3)?
+        .as_chunks::<3>()
+        .0
+        .iter()
+        .map(|&[kind, a, b]| read_user_permission(&process, kind, a, b))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let permissions = process
+        .permissions()
+        .inherit(grants)
+        .map_err(|_| SyscallError::PermissionDenied)?;
+
+    let overlay = match mount_count {
+        0 => process.overlay().clone(),
+        _ => {
+            let mounts = read_user_words(&process, mounts_addr, mount_count * 5)?
+                .as_chunks::<5>()
+                .0
+                .iter()
+                .map(|&[parent_addr, parent_len, child_addr, child_len, flags]| {
+                    Ok((
+                        read_user_unresolved_path(&process, parent_addr, parent_len)?,
+                        read_user_unresolved_path(&process, child_addr, child_len)?,
+                        flags & MOUNT_WRITE != 0,
+                    ))
+                })
+                .collect::<Result<Vec<_>, SyscallError>>()?;
+
+            OverLay::inherit(
+                process.overlay(),
+                mounts
+                    .iter()
+                    .map(|(parent, child, write)| (&**parent, &**child, *write)),
+            )?
+        }
+    };
+
+    let (child, _) = interruptible(|| {
+        exec_path(
+            &path,
+            &[],
+            &[],
+            Some(process.process_id()),
+            overlay,
+            permissions,
+        )
+    })?;
+
+    // The child can only be waited for by its parent, so the handle cannot be transferred.
+    let capability = Capability::new(
+        KernelObject::Process(child),
+        Rights::READ | Rights::DUPLICATE,
+    );
+
+    Ok(process.handles().insert(capability) as u64)
+}
+
+fn sys_wait(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let [handle, flags, ..] = args;
+
+    let process = current_process()?;
+    let child = process
+        .handles()
+        .process(handle_arg(handle)?, Rights::READ)?;
+
+    loop {
+        match PROCESS_TABLE.try_wait(process.process_id(), child)? {
+            // The exit code is not sign extended, so it never looks like an error.
+            Some(code) => return Ok(code as u32 as u64),
+            None if flags & IPC_NONBLOCK != 0 => return Err(SyscallError::WouldBlock),
+            None => {
+                if PROCESS_TABLE.block_wait_after_syscall(child) {
+                    return BLOCKED;
+                }
+            }
+        }
+    }
+}
+
+fn sys_spawn_thread(args: SyscallArgs) -> Result<u64, SyscallError> {
+    let [entry, stack_pointer, ..] = args;
+
+    // The entry has to hold at least one instruction, and the first push goes below the stack
+    // pointer.
+    let entry = user_address(entry, 1)?;
+    let pushed = stack_pointer
+        .checked_sub(8)
+        .ok_or(SyscallError::BadAddress)?;
+    user_address(pushed, 8)?;
+    let stack_pointer = VirtualAddress::new(stack_pointer as usize);
+
+    let process = current_process()?;
+    let context = arch::new_user_context(entry, stack_pointer);
+
+    let thread_id = SCHEDULER
+        .spawn_thread(LOWEST_PRIORITY, Some(process.clone()), context)
+        .map_err(|_| SyscallError::OutOfMemory)?;
+
+    let capability = Capability::new(KernelObject::Thread(thread_id), Rights::DUPLICATE);
+
+    Ok(process.handles().insert(capability) as u64)
+}
+
+/// Read a path from user space, as the process sees it.
+fn read_user_unresolved_path(
+    process: &Process,
+    addr: u64,
+    len: u64,
+) -> Result<PathBuf, SyscallError> {
+    let len = len as usize;
+
+    if len > MAX_PATH_LEN {
+        return Err(SyscallError::InvalidArgument);
+    }
+
+    let mut bytes = vec![0u8; len];
+    read_user(process, addr, &mut bytes)?;
+
+    let path = String::from_utf8(bytes).map_err(|_| SyscallError::InvalidArgument)?;
+
+    PathBuf::new(path).map_err(|_| SyscallError::InvalidArgument)
+}
+
+/// Read a path from user space, and resolve it through the overlay of `process`.
+fn read_user_path(process: &Process, addr: u64, len: u64) -> Result<PathBuf, SyscallError> {
+    let path = read_user_unresolved_path(process, addr, len)?;
+
+    let resolved = process
+        .overlay()
+        .resolve_path(&path)
+        .map_err(|_| SyscallError::InvalidArgument)?;
+
+    Ok(resolved.path)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{sync::Arc, vec, vec::Vec};
+    use x86_64::{halt, interrupt::without_interrupts};
+
+    use super::*;
+    use crate::{
+        fs::{FileKind, OverLay},
+        interface::interrupts,
+        memory::{alloc::FRAME_ALLOC, map::MemoryManager},
+        multitasking::{
+            exec::{exec, test_utils::*},
+            process::{test_utils::new_process, Permissions, ProcessId},
+        },
+    };
+    use path::Path;
+
+    const CODE_ADDR: u64 = (MemoryMapper::USER_SPACE_START + 0x1000) as u64;
+    const DATA_ADDR: u64 = CODE_ADDR + 0x1000;
+
+    /// Start a process with `code`, and 16 bytes of data a page after the code.
+    fn exec_code(code: Vec<u8>, parent: Option<ProcessId>) -> ProcessId {
+        exec_code_with(code, parent, Permissions::all())
+    }
+
+    /// Like [`exec_code`], but the process gets `permissions`.
+    fn exec_code_with(
+        code: Vec<u8>,
+        parent: Option<ProcessId>,
+        permissions: Permissions,
+    ) -> ProcessId {
+        let (process_id, _) = exec(
+            as_bytes(&code_image(code)),
+            &[],
+            &[],
+            parent,
+            Arc::new(OverLay::ROOT),
+            permissions,
+        )
+        .unwrap();
+
+        process_id
+    }
+
+    /// A process without a parent that runs `code`, see [`exec_code`].
+    struct UserProgram {
+        process_id: ProcessId,
+        process: Arc<Process>,
+    }
+
+    impl UserProgram {
+        fn start(code: Vec<u8>) -> Self {
+            let process_id = exec_code(code, None);
+            let process = PROCESS_TABLE.get(process_id).unwrap();
+
+            Self {
+                process_id,
+                process,
+            }
+        }
+
+        fn read_u64(&self, addr: u64) -> u64 {
+            read_u64(self.process.manager(), addr.into())
+        }
+
+        /// Wait until the program writes something other than 0 to `addr`, and get it.
+        fn wait_for_u64(&self, addr: u64) -> u64 {
+            for _ in 0..1000 {
+                let value = self.read_u64(addr);
+
+                if value != 0 {
+                    return value;
+                }
+
+                halt();
+            }
+
+            self.read_u64(addr)
+        }
+
+        fn exit(self) {
+            PROCESS_TABLE.exit(self.process_id, 0).unwrap();
+        }
+    }
+
+    /// The executable that [`exec_code`] starts.
+    fn code_image(code: Vec<u8>) -> Vec<u64> {
+        elf_image(
+            ET_EXEC,
+            CODE_ADDR,
+            &[
+                Segment {
+                    kind: PT_LOAD,
+                    flags: PF_R | PF_X,
+                    addr: CODE_ADDR,
+                    memory_size: code.len() as u64,
+                    data: code,
+                },
+                Segment {
+                    kind: PT_LOAD,
+                    flags: PF_R | PF_W,
+                    addr: DATA_ADDR,
+                    data: vec![0; 16],
+                    memory_size: 16,
+                },
+            ],
+        )
+    }
+
+    #[test_case]
+    fn test_unknown_syscall() {
+        assert_eq!(
+            Some(SyscallError::UnknownSyscall.encode()),
+            syscall(u64::MAX, [0; 6])
+        );
+        assert_eq!(
+            Some(SyscallError::UnknownSyscall.encode()),
+            syscall(SYSCALLS.len() as u64, [0; 6])
+        );
+    }
+
+    #[test_case]
+    fn test_interruptible() {
+        without_interrupts(|| {
+            assert!(interruptible(|| RFlags::read().interrupts_enabled()));
+            assert!(!RFlags::read().interrupts_enabled());
+        });
+
+        assert!(interruptible(|| RFlags::read().interrupts_enabled()));
+        assert!(RFlags::read().interrupts_enabled());
+    }
+
+    #[test_case]
+    fn test_require_without_process() {
+        assert_eq!(
+            Some(SyscallError::NoProcess.encode()),
+            syscall(SYS_REQUIRE, [REQUIRE_SYSCALL, SYS_LOG, 0, 0, 0, 0])
+        );
+    }
+
+    #[test_case]
+    fn test_log_invalid_arguments() {
+        assert_eq!(
+            Some(SyscallError::NoProcess.encode()),
+            syscall(SYS_LOG, [0, 16, 0, 0, 0, 0])
+        );
+        assert_eq!(
+            Some(SyscallError::InvalidArgument.encode()),
+            syscall(SYS_LOG, [0, MAX_LOG_LEN as u64 + 1, 0, 0, 0, 0])
+        );
+    }
+
+    #[test_case]
+    fn test_syscall_from_user() {
+        // mov eax, SYS_PROCESS_ID; syscall; mov [rip + data], rax; jmp $
+        let code = vec![
+            0xB8, 0x01, 0x00, 0x00, 0x00, 0x0F, 0x05, 0x48, 0x89, 0x05, 0xF2, 0x0F, 0x00, 0x00,
+            0xEB, 0xFE,
+        ];
+
+        let program = UserProgram::start(code);
+
+        assert_eq!(program.process_id as u64, program.wait_for_u64(DATA_ADDR));
+        program.exit();
+    }
+
+    #[test_case]
+    fn test_spawn_thread_bad_address() {
+        let end = MemoryMapper::USER_SPACE_END as u64;
+        let start = MemoryMapper::USER_SPACE_START as u64;
+
+        for [entry, stack_pointer] in [[end, end], [CODE_ADDR, start], [CODE_ADDR, end + 8]] {
+            assert_eq!(
+                Some(SyscallError::BadAddress.encode()),
+                syscall(SYS_SPAWN_THREAD, [entry, stack_pointer, 0, 0, 0, 0])
+            );
+        }
+
+        assert_eq!(
+            Some(SyscallError::NoProcess.encode()),
+            syscall(SYS_SPAWN_THREAD, [CODE_ADDR, end, 0, 0, 0, 0])
+        );
+    }
+
+    #[test_case]
+    fn test_ipc_without_process() {
+        assert_eq!(
+            Some(SyscallError::NoProcess.encode()),
+            syscall(SYS_CHANNEL, [DATA_ADDR, 0, 0, 0, 0, 0])
+        );
+        assert_eq!(
+            Some(SyscallError::MessageTooLarge.encode()),
+            syscall(SYS_SEND, [1, 0, MAX_MESSAGE_LEN as u64 + 1, 0, 0, 0])
+        );
+        assert_eq!(
+            Some(SyscallError::NoProcess.encode()),
+            syscall(SYS_SHM_CREATE, [4096, 0, 0, 0, 0, 0])
+        );
+    }
+
+    #[test_case]
+    fn test_handles_without_process() {
+        assert_eq!(
+            Some(SyscallError::NoProcess.encode()),
+            syscall(SYS_DUPLICATE, [1, Rights::READ.bits() as u64, 0, 0, 0, 0])
+        );
+        assert_eq!(
+            Some(SyscallError::InvalidArgument.encode()),
+            syscall(SYS_DUPLICATE, [1, 1 << 31, 0, 0, 0, 0])
+        );
+        assert_eq!(
+            Some(SyscallError::NoProcess.encode()),
+            syscall(SYS_WAIT, [1, 0, 0, 0, 0, 0])
+        );
+    }
+
+    #[test_case]
+    fn test_ipc_from_user() {
+        let listener = ipc::listen(40_001).unwrap();
+
+        // mov edi, 40001; mov eax, SYS_CONNECT; syscall; mov rbx, rax
+        // mov rdi, rbx; lea rsi, [rip + msg]; mov edx, 4; xor r10d, r10d; xor r8d, r8d;
+        // xor r9d, r9d; mov eax, SYS_SEND; syscall
+        // mov rdi, rbx; lea rsi, [rip + data + 8]; mov edx, 8; mov eax, SYS_RECEIVE; syscall
+        // mov [rip + data], rax; jmp $; msg: "ping"
+        let code = vec![
+            0xBF, 0x41, 0x9C, 0x00, 0x00, 0xB8, 0x0A, 0x00, 0x00, 0x00, 0x0F, 0x05, 0x48, 0x89,
+            0xC3, 0x48, 0x89, 0xDF, 0x48, 0x8D, 0x35, 0x34, 0x00, 0x00, 0x00, 0xBA, 0x04, 0x00,
+            0x00, 0x00, 0x45, 0x31, 0xD2, 0x45, 0x31, 0xC0, 0x45, 0x31, 0xC9, 0xB8, 0x06, 0x00,
+            0x00, 0x00, 0x0F, 0x05, 0x48, 0x89, 0xDF, 0x48, 0x8D, 0x35, 0xD0, 0x0F, 0x00, 0x00,
+            0xBA, 0x08, 0x00, 0x00, 0x00, 0xB8, 0x07, 0x00, 0x00, 0x00, 0x0F, 0x05, 0x48, 0x89,
+            0x05, 0xB5, 0x0F, 0x00, 0x00, 0xEB, 0xFE, 0x70, 0x69, 0x6E, 0x67,
+        ];
+
+        let program = UserProgram::start(code);
+
+        let server = listener.accept();
+        assert_eq!(b"ping", server.receive().ok().unwrap().data());
+
+        // The process blocks in the receive, until there is a reply.
+        SCHEDULER.sleep(crate::arch::TICK_INTERVAL * 2);
+        assert_eq!(0, program.read_u64(DATA_ADDR));
+
+        server
+            .send(Message::new(b"pong".to_vec(), Vec::new()).unwrap())
+            .unwrap();
+
+        assert_eq!(4, program.wait_for_u64(DATA_ADDR));
+        assert_eq!(
+            u32::from_le_bytes(*b"pong") as u64,
+            program.read_u64(DATA_ADDR + 8)
+        );
+
+        program.exit();
+    }
+
+    #[test_case]
+    fn test_receive_with_bad_handle_buffer() {
+        let listener = ipc::listen(40_003).unwrap();
+
+        // mov edi, 40003; mov eax, SYS_CONNECT; syscall; mov rbx, rax
+        // mov rdi, rbx; xor esi, esi; xor edx, edx; xor r10d, r10d; mov r8d, 1; xor r9d, r9d;
+        // mov eax, SYS_RECEIVE; syscall; mov [rip + data], rax
+        // mov rdi, rbx; xor esi, esi; xor edx, edx; lea r10, [rip + data + 8]; mov r8d, 1;
+        // xor r9d, r9d; mov eax, SYS_RECEIVE; syscall; jmp $
+        let code = vec![
+            0xBF, 0x43, 0x9C, 0x00, 0x00, 0xB8, 0x0A, 0x00, 0x00, 0x00, 0x0F, 0x05, 0x48, 0x89,
+            0xC3, 0x48, 0x89, 0xDF, 0x31, 0xF6, 0x31, 0xD2, 0x45, 0x31, 0xD2, 0x41, 0xB8, 0x01,
+            0x00, 0x00, 0x00, 0x45, 0x31, 0xC9, 0xB8, 0x07, 0x00, 0x00, 0x00, 0x0F, 0x05, 0x48,
+            0x89, 0x05, 0xD0, 0x0F, 0x00, 0x00, 0x48, 0x89, 0xDF, 0x31, 0xF6, 0x31, 0xD2, 0x4C,
+            0x8D, 0x15, 0xCA, 0x0F, 0x00, 0x00, 0x41, 0xB8, 0x01, 0x00, 0x00, 0x00, 0x45, 0x31,
+            0xC9, 0xB8, 0x07, 0x00, 0x00, 0x00, 0x0F, 0x05, 0xEB, 0xFE,
+        ];
+
+        let program = UserProgram::start(code);
+
+        let server = listener.accept();
+        let (end, _) = ipc::channel().unwrap();
+        let capability = Capability::new(KernelObject::Channel(Arc::new(end)), Rights::ALL);
+        server
+            .send(Message::new(Vec::new(), vec![capability]).unwrap())
+            .unwrap();
+
+        // The message stays in the channel when the handles cannot be written, so the second
+        // receive gets it.
+        let handle = program.wait_for_u64(DATA_ADDR + 8) as Handle;
+
+        assert_eq!(
+            SyscallError::BadAddress.encode(),
+            program.read_u64(DATA_ADDR)
+        );
+        assert!(program
+            .process
+            .handles()
+            .channel(handle, Rights::READ)
+            .is_ok());
+
+        program.exit();
+    }
+
+    #[test_case]
+    fn test_send_repeated_handle() {
+        let listener = ipc::listen(40_004).unwrap();
+
+        // mov edi, 4096; mov eax, SYS_SHM_CREATE; syscall
+        // mov [rip + data], eax; mov [rip + data + 4], eax
+        // mov edi, 40004; mov eax, SYS_CONNECT; syscall
+        // mov rdi, rax; lea rsi, [rip + data]; xor edx, edx; lea r10, [rip + data]; mov r8d, 2;
+        // xor r9d, r9d; mov eax, SYS_SEND; syscall; mov [rip + data + 8], rax; jmp $
+        let code = vec![
+            0xBF, 0x00, 0x10, 0x00, 0x00, 0xB8, 0x0C, 0x00, 0x00, 0x00, 0x0F, 0x05, 0x89, 0x05,
+            0xEE, 0x0F, 0x00, 0x00, 0x89, 0x05, 0xEC, 0x0F, 0x00, 0x00, 0xBF, 0x44, 0x9C, 0x00,
+            0x00, 0xB8, 0x0A, 0x00, 0x00, 0x00, 0x0F, 0x05, 0x48, 0x89, 0xC7, 0x48, 0x8D, 0x35,
+            0xD2, 0x0F, 0x00, 0x00, 0x31, 0xD2, 0x4C, 0x8D, 0x15, 0xC9, 0x0F, 0x00, 0x00, 0x41,
+            0xB8, 0x02, 0x00, 0x00, 0x00, 0x45, 0x31, 0xC9, 0xB8, 0x06, 0x00, 0x00, 0x00, 0x0F,
+            0x05, 0x48, 0x89, 0x05, 0xBA, 0x0F, 0x00, 0x00, 0xEB, 0xFE,
+        ];
+
+        let program = UserProgram::start(code);
+        let server = listener.accept();
+
+        assert_eq!(
+            SyscallError::InvalidArgument.encode(),
+            program.wait_for_u64(DATA_ADDR + 8)
+        );
+        assert_eq!(Err(IpcError::WouldBlock), server.try_receive().map(|_| ()));
+
+        // The handle is kept, since nothing was sent.
+        let handle = program.read_u64(DATA_ADDR) as Handle;
+        assert!(program
+            .process
+            .handles()
+            .get(handle, Rights::TRANSFER)
+            .is_ok());
+
+        program.exit();
+    }
+
+    #[test_case]
+    fn test_shared_memory_from_user() {
+        let listener = ipc::listen(40_002).unwrap();
+
+        // mov edi, 4096; mov eax, SYS_SHM_CREATE; syscall; mov r12, rax; mov [rip + data], rax
+        // mov rdi, r12; lea rsi, [rip + shm]; mov edx, SHM_WRITE; mov eax, SYS_SHM_MAP; syscall
+        // lea rsi, [rip + shm]; mov dword [rsi], 42
+        // mov edi, 40002; mov eax, SYS_CONNECT; syscall
+        // mov rdi, rax; lea rsi, [rip + data]; xor edx, edx; lea r10, [rip + data]; mov r8d, 1;
+        // xor r9d, r9d; mov eax, SYS_SEND; syscall; mov [rip + data + 8], rax; jmp $
+        // shm: CODE_ADDR + 0x10_0000
+        let code = vec![
+            0xBF, 0x00, 0x10, 0x00, 0x00, 0xB8, 0x0C, 0x00, 0x00, 0x00, 0x0F, 0x05, 0x49, 0x89,
+            0xC4, 0x48, 0x89, 0x05, 0xEA, 0x0F, 0x00, 0x00, 0x4C, 0x89, 0xE7, 0x48, 0x8D, 0x35,
+            0xE0, 0xFF, 0x0F, 0x00, 0xBA, 0x01, 0x00, 0x00, 0x00, 0xB8, 0x0D, 0x00, 0x00, 0x00,
+            0x0F, 0x05, 0x48, 0x8D, 0x35, 0xCD, 0xFF, 0x0F, 0x00, 0xC7, 0x06, 0x2A, 0x00, 0x00,
+            0x00, 0xBF, 0x42, 0x9C, 0x00, 0x00, 0xB8, 0x0A, 0x00, 0x00, 0x00, 0x0F, 0x05, 0x48,
+            0x89, 0xC7, 0x48, 0x8D, 0x35, 0xB1, 0x0F, 0x00, 0x00, 0x31, 0xD2, 0x4C, 0x8D, 0x15,
+            0xA8, 0x0F, 0x00, 0x00, 0x41, 0xB8, 0x01, 0x00, 0x00, 0x00, 0x45, 0x31, 0xC9, 0xB8,
+            0x06, 0x00, 0x00, 0x00, 0x0F, 0x05, 0x48, 0x89, 0x05, 0x99, 0x0F, 0x00, 0x00, 0xEB,
+            0xFE,
+        ];
+
+        let program = UserProgram::start(code);
+
+        let server = listener.accept();
+        let (_, mut capabilities) = server.receive().ok().unwrap().into_parts();
+        let capability = capabilities.pop().unwrap();
+        assert_eq!(Rights::ALL, capability.rights);
+
+        let KernelObject::SharedMemory(memory) = capability.object else {
+            panic!("the shared memory should have been received");
+        };
+
+        // The memory is seen by every address space that maps it.
+        let mapper = MemoryMapper::new_user_mapper(FRAME_ALLOC.physical_memory_offset()).unwrap();
+        let manager = MemoryManager::new(mapper);
+        let read_only = MemoryProperties::new(false, true, false, false, false);
+
+        manager
+            .reserve(MemoryArea::new(
+                DATA_ADDR.into(),
+                memory.size(),
+                read_only,
+                AreaKind::Shared(memory),
+            ))
+            .unwrap();
+
+        assert_eq!(42, read_u64(&manager, DATA_ADDR.into()));
+        program.exit();
+    }
+
+    /// Insert a process without memory into the table, to be the parent of processes in tests.
+    fn new_parent() -> ProcessId {
+        PROCESS_TABLE
+            .insert(new_process(&PROCESS_TABLE), None)
+            .unwrap()
+            .process_id()
+    }
+
+    #[test_case]
+    fn test_exit_from_user() {
+        // Let the threads of processes that exited in earlier tests be retired.
+        SCHEDULER.sleep(crate::arch::TICK_INTERVAL * 2);
+
+        let available = FRAME_ALLOC.available();
+        let parent = new_parent();
+
+        // 1: cmp qword ptr [rip + data], 0; je 1b; mov edi, 7; mov eax, SYS_EXIT; syscall; jmp $
+        let code = vec![
+            0x48, 0x83, 0x3D, 0xF8, 0x0F, 0x00, 0x00, 0x00, 0x74, 0xF6, 0xBF, 0x07, 0x00, 0x00,
+            0x00, 0xB8, 0x04, 0x00, 0x00, 0x00, 0x0F, 0x05, 0xEB, 0xFE,
+        ];
+
+        let child = exec_code(code, Some(parent));
+
+        // The process cannot exit before it is released, so it is still in the table.
+        let process = Arc::downgrade(&PROCESS_TABLE.get(child).unwrap());
+        process
+            .upgrade()
+            .unwrap()
+            .manager()
+            .write(DATA_ADDR.into(), &1u64.to_le_bytes())
+            .unwrap();
+
+        assert_eq!(Ok(7), PROCESS_TABLE.wait(parent, child));
+        PROCESS_TABLE.exit(parent, 0).unwrap();
+
+        // The thread releases the process once it has been retired, which frees its memory.
+        for _ in 0..1000 {
+            if process.strong_count() == 0 {
+                break;
+            }
+
+            halt();
+        }
+
+        assert_eq!(0, process.strong_count());
+        assert_eq!(available, FRAME_ALLOC.available());
+    }
+
+    #[test_case]
+    fn test_page_fault_kills_process() {
+        let parent = new_parent();
+
+        // push rax; mov qword ptr [0], rax; jmp $
+        let code = vec![
+            0x50, 0x48, 0x89, 0x04, 0x25, 0x00, 0x00, 0x00, 0x00, 0xEB, 0xFE,
+        ];
+
+        let child = exec_code(code, Some(parent));
+
+        assert_eq!(
+            Ok(interrupts::PAGE_FAULT_EXIT_CODE),
+            PROCESS_TABLE.wait(parent, child)
+        );
+        PROCESS_TABLE.exit(parent, 0).unwrap();
+    }
+
+    #[test_case]
+    fn test_memory_limit_kills_process() {
+        let parent = new_parent();
+
+        // The code, the data and the arguments on the stack take a page each.
+        let limit = 3 * MemoryMapper::PAGE_SIZE as u64;
+        let permissions = Permissions::all()
+            .inherit([Permission::Memory(limit)])
+            .unwrap();
+
+        // sub rsp, 0x3000; push rax; jmp $
+        let code = vec![0x48, 0x81, 0xEC, 0x00, 0x30, 0x00, 0x00, 0x50, 0xEB, 0xFE];
+        let child = exec_code_with(code, Some(parent), permissions);
+
+        assert_eq!(
+            Ok(interrupts::PAGE_FAULT_EXIT_CODE),
+            PROCESS_TABLE.wait(parent, child)
+        );
+        PROCESS_TABLE.exit(parent, 0).unwrap();
+    }
+
+    #[test_case]
+    fn test_cpu_time_kills_process() {
+        let parent = new_parent();
+        let permissions = Permissions::all()
+            .inherit([Permission::CpuTime(20)])
+            .unwrap();
+
+        // jmp $
+        let child = exec_code_with(vec![0xEB, 0xFE], Some(parent), permissions);
+
+        assert_eq!(
+            Ok(interrupts::CPU_TIME_EXIT_CODE),
+            PROCESS_TABLE.wait(parent, child)
+        );
+        PROCESS_TABLE.exit(parent, 0).unwrap();
+    }
+
+    #[test_case]
+    fn test_spawn_grants_permission() {
+        // mov edi, REQUIRE_LISTEN; mov esi, 40006; mov eax, SYS_REQUIRE; syscall
+        // mov rdi, rax; mov eax, SYS_EXIT; syscall; jmp $
+        let child = vec![
+            0xBF, 0x05, 0x00, 0x00, 0x00, 0xBE, 0x46, 0x9C, 0x00, 0x00, 0xB8, 0x03, 0x00, 0x00,
+            0x00, 0x0F, 0x05, 0x48, 0x89, 0xC7, 0xB8, 0x04, 0x00, 0x00, 0x00, 0x0F, 0x05, 0xEB,
+            0xFE,
+        ];
+
+        let root = VFS
+            .open(&OverLay::ROOT, &Permissions::all(), Path::ROOT, true)
+            .unwrap();
+        let dir = root.lookup().unwrap();
+        let inode = root.fs.create(dir, "spawn_grant", FileKind::File).unwrap();
+        root.fs
+            .write(inode, 0, as_bytes(&code_image(child)))
+            .unwrap();
+
+        // lea rdi, [rip + path]; mov esi, 12; lea rdx, [rip + grant]; mov r10d, 1; xor r8d, r8d;
+        // xor r9d, r9d; mov eax, SYS_SPAWN; syscall
+        // mov rdi, rax; xor esi, esi; mov eax, SYS_WAIT; syscall
+        // mov [rip + data], rax; mov qword ptr [rip + data + 8], 1; jmp $
+        // grant: REQUIRE_LISTEN, 40006, 0; path: "/spawn_grant"
+        let code = vec![
+            0x48, 0x8D, 0x3D, 0x57, 0x00, 0x00, 0x00, 0xBE, 0x0C, 0x00, 0x00, 0x00, 0x48, 0x8D,
+            0x15, 0x33, 0x00, 0x00, 0x00, 0x41, 0xBA, 0x01, 0x00, 0x00, 0x00, 0x45, 0x31, 0xC0,
+            0x45, 0x31, 0xC9, 0xB8, 0x14, 0x00, 0x00, 0x00, 0x0F, 0x05, 0x48, 0x89, 0xC7, 0x31,
+            0xF6, 0xB8, 0x15, 0x00, 0x00, 0x00, 0x0F, 0x05, 0x48, 0x89, 0x05, 0xC7, 0x0F, 0x00,
+            0x00, 0x48, 0xC7, 0x05, 0xC4, 0x0F, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0xEB, 0xFE,
+            0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46, 0x9C, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x2F, 0x73, 0x70, 0x61,
+            0x77, 0x6E, 0x5F, 0x67, 0x72, 0x61, 0x6E, 0x74,
+        ];
+
+        // Ports are never inherited, so without the grant the child could not listen on any.
+        let program = UserProgram::start(code);
+
+        // The child exits with the result of `require`.
+        assert_eq!(1, program.wait_for_u64(DATA_ADDR + 8));
+        assert_eq!(0, program.read_u64(DATA_ADDR));
+
+        program.exit();
+        root.fs.unlink(dir, "spawn_grant").unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{
+    fs::{FsError, VfsError},
+    handle::HandleError,
+    ipc::IpcError,
+    memory::{
+        map::{AreaError, MemoryErrorKind},
+        shared::SharedMemoryError,
+    },
+    multitasking::{exec::ExecError, process::ProcessError},
+};
+
+/// The errors that are returned to the user, the discriminant is the error code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u64)]
+pub enum SyscallError {
+    UnknownSyscall = 1,
+    InvalidArgument = 2,
+    /// A pointer argument does not point to mapped user memory.
+    BadAddress = 3,
+    /// The system call requires a calling process.
+    NoProcess = 4,
+    /// The calling process does not have the required permission, or the handle does not have
+    /// the required rights.
+    PermissionDenied = 5,
+    /// The system call would block, but was asked not to.
+    WouldBlock = 6,
+    /// The handle does not refer to an object of the process, or to one of another kind.
+    BadHandle = 7,
+    /// The other end of the channel has been closed.
+    PeerClosed = 8,
+    /// The message is too large, or does not fit the buffers of the receiver.
+    MessageTooLarge = 9,
+    PortInUse = 10,
+    NotListening = 11,
+    OutOfMemory = 12,
+    /// The memory overlaps with memory that is already reserved.
+    AddressInUse = 13,
+    /// The file does not exist.
+    NotFound = 14,
+    /// The filesystem could not complete the operation.
+    Io = 15,
+}
+
+impl SyscallError {
+    /// Errors are returned as the negated error code, so they never overlap with valid results.
+    pub fn encode(self) -> u64 {
+        (self as u64).wrapping_neg()
+    }
+}
+
+impl From<IpcError> for SyscallError {
+    fn from(value: IpcError) -> Self {
+        match value {
+            IpcError::PeerClosed => SyscallError::PeerClosed,
+            IpcError::WouldBlock => SyscallError::WouldBlock,
+            IpcError::MessageTooLarge | IpcError::TooManyHandles => SyscallError::MessageTooLarge,
+            IpcError::InvalidTransfer => SyscallError::InvalidArgument,
+            IpcError::PortInUse => SyscallError::PortInUse,
+            IpcError::NotListening => SyscallError::NotListening,
+            IpcError::OutOfMemory => SyscallError::OutOfMemory,
+        }
+    }
+}
+
+impl From<AreaError> for SyscallError {
+    fn from(value: AreaError) -> Self {
+        match value {
+            AreaError::InvalidArea | AreaError::NotReserved => SyscallError::BadAddress,
+            AreaError::Overlaps => SyscallError::AddressInUse,
+        }
+    }
+}
+
+impl From<SharedMemoryError> for SyscallError {
+    fn from(value: SharedMemoryError) -> Self {
+        match value {
+            SharedMemoryError::InvalidSize => SyscallError::InvalidArgument,
+            SharedMemoryError::OutOfFrames => SyscallError::OutOfMemory,
+            SharedMemoryError::QuotaExceeded => SyscallError::PermissionDenied,
+        }
+    }
+}
+
+impl From<HandleError> for SyscallError {
+    fn from(value: HandleError) -> Self {
+        match value {
+            HandleError::BadHandle => SyscallError::BadHandle,
+            HandleError::MissingRights => SyscallError::PermissionDenied,
+        }
+    }
+}
+
+impl From<FsError> for SyscallError {
+    fn from(value: FsError) -> Self {
+        match value {
+            FsError::NotFound => SyscallError::NotFound,
+            FsError::NotADirectory
+            | FsError::IsADirectory
+            | FsError::AlreadyExists
+            | FsError::NotEmpty
+            | FsError::InvalidName => SyscallError::InvalidArgument,
+            FsError::NoSpace | FsError::Corrupt | FsError::Io => SyscallError::Io,
+        }
+    }
+}
+
+impl From<VfsError> for SyscallError {
+    fn from(value: VfsError) -> Self {
+        match value {
+            VfsError::RelativePath | VfsError::AlreadyMounted => SyscallError::InvalidArgument,
+            VfsError::ReadOnly | VfsError::PermissionDenied => SyscallError::PermissionDenied,
+            VfsError::NotMounted => SyscallError::NotFound,
+        }
+    }
+}
+
+impl From<ProcessError> for SyscallError {
+    fn from(value: ProcessError) -> Self {
+        match value {
+            // The child has already been reaped, or the handle was not given to its parent.
+            ProcessError::NoSuchProcess | ProcessError::NotAChild => SyscallError::BadHandle,
+            ProcessError::Exited => SyscallError::NoProcess,
+        }
+    }
+}
+
+impl From<ExecError> for SyscallError {
+    fn from(value: ExecError) -> Self {
+        match value {
+            ExecError::Vfs(err) => err.into(),
+            ExecError::Fs(err) => err.into(),
+            ExecError::Process(err) => err.into(),
+            // The child would need more memory than it is permitted to use.
+            ExecError::Memory(MemoryErrorKind::QuotaExceeded) => SyscallError::PermissionDenied,
+            ExecError::Map(_)
+            | ExecError::Write(_)
+            | ExecError::Area(_)
+            | ExecError::Memory(_)
+            | ExecError::Scheduler(_)
+            | ExecError::OutOfMemory => SyscallError::OutOfMemory,
+            // The file is not an executable that can be loaded.
+            _ => SyscallError::InvalidArgument,
+        }
+    }
+}
@@ -0,0 +1,25 @@
+//! Communication between processes.
+//!
+//! A channel is a pair of ends, the messages that are sent at one end are received at the other.
+//! A message holds bytes and the ends of other channels, which are moved to the receiver, so a
+//! process can hand a connection to another process. Messages can carry any object that can be
+//! transferred, see [`handle`](crate::handle), like shared memory so processes can exchange large
+//! amounts of data without copying it.
+//!
+//! Processes find each other through ports. A process with the permission to listen on a port
+//! registers a [`Port`], and a process with the permission to request at the port connects to
+//! it, which creates a new channel of which the listener accepts one end.
+//!
+//! Messages are queued in the lock-free [`BoundedQueue`](essentials::nb::BoundedQueue). Kernel
+//! threads block in [`ChannelEnd::send`], [`ChannelEnd::receive`] and [`Port::accept`], user
+//! threads block in the system calls, which are made again once the thread is woken.
+
+mod channel;
+mod error;
+mod port;
+
+pub use channel::{
+    channel, ChannelEnd, Message, CHANNEL_CAPACITY, MAX_MESSAGE_HANDLES, MAX_MESSAGE_LEN,
+};
+pub use error::IpcError;
+pub use port::{connect, listen, Port, PORT_BACKLOG};
@@ -0,0 +1,442 @@
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use alloc::{sync::Arc, vec::Vec};
+use essentials::{nb::BoundedQueue, spin::SpinLock};
+
+use crate::{
+    handle::{Capability, KernelObject},
+    multitasking::scheduler::WaitQueue,
+    utils::InterruptGuard,
+};
+
+use super::IpcError;
+
+/// The messages that can wait in each direction of a channel, before the sender blocks.
+pub const CHANNEL_CAPACITY: usize = 16;
+pub const MAX_MESSAGE_LEN: usize = 64 * 1024;
+pub const MAX_MESSAGE_HANDLES: usize = 16;
+
+/// Taken while channel ends are sent, so two ends cannot be sent through each other at once.
+static CHANNEL_TRANSFERS: InterruptGuard<SpinLock<()>> = InterruptGuard::new_lock(());
+
+/// The bytes of a message, and the capabilities that are moved to the receiver with it.
+pub struct Message {
+    data: Vec<u8>,
+    capabilities: Vec<Capability>,
+}
+
+impl Message {
+    pub fn new(data: Vec<u8>, capabilities: Vec<Capability>) -> Result<Self, IpcError> {
+        if data.len() > MAX_MESSAGE_LEN {
+            return Err(IpcError::MessageTooLarge);
+        }
+
+        if capabilities.len() > MAX_MESSAGE_HANDLES {
+            return Err(IpcError::TooManyHandles);
+        }
+
+        Ok(Self { data, capabilities })
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn capabilities(&self) -> &[Capability] {
+        &self.capabilities
+    }
+
+    pub fn into_parts(self) -> (Vec<u8>, Vec<Capability>) {
+        (self.data, self.capabilities)
+    }
+
+    fn channel_count(&self) -> usize {
+        self.capabilities
+            .iter()
+            .filter(|capability| matches!(capability.object, KernelObject::Channel(_)))
+            .count()
+    }
+}
+
+/// The messages in one direction of a channel.
+///
+/// Senders push to the queue without locking, receivers take the `unread` lock so a message that
+/// a receiver refuses stays the next message.
+struct Pipe {
+    messages: BoundedQueue<CHANNEL_CAPACITY, Message>,
+    /// A message that was taken from the queue, but was refused by the receiver.
+    unread: InterruptGuard<SpinLock<Option<Message>>>,
+    /// The messages that have not been received, including the unread message. It is raised
+    /// before a message is pushed, so it never falls behind the queue.
+    queued: AtomicUsize,
+    /// The channel ends in the messages that have not been received, it is raised like `queued`.
+    queued_channels: AtomicUsize,
+    /// One of the ends has been closed, nothing can be sent anymore.
+    closed: AtomicBool,
+    receivers: WaitQueue,
+    senders: WaitQueue,
+}
+
+impl Pipe {
+    fn new() -> Self {
+        Self {
+            messages: BoundedQueue::new(),
+            unread: InterruptGuard::new_lock(None),
+            queued: AtomicUsize::new(0),
+            queued_channels: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+            receivers: WaitQueue::new(),
+            senders: WaitQueue::new(),
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    fn is_readable(&self) -> bool {
+        self.queued.load(Ordering::Acquire) > 0 || self.is_closed()
+    }
+
+    fn is_writable(&self) -> bool {
+        self.queued.load(Ordering::Acquire) < CHANNEL_CAPACITY || self.is_closed()
+    }
+
+    fn push(&self, message: Message) -> Result<(), (IpcError, Message)> {
+        if self.is_closed() {
+            return Err((IpcError::PeerClosed, message));
+        }
+
+        let channels = message.channel_count();
+
+        self.queued.fetch_add(1, Ordering::AcqRel);
+        self.queued_channels.fetch_add(channels, Ordering::AcqRel);
+
+        if let Err(message) = self.messages.push(message) {
+            self.queued.fetch_sub(1, Ordering::AcqRel);
+            self.queued_channels.fetch_sub(channels, Ordering::AcqRel);
+            return Err((IpcError::WouldBlock, message));
+        }
+
+        self.receivers.wake_one();
+
+        Ok(())
+    }
+
+    fn pop_if<E: From<IpcError>>(
+        &self,
+        accept: impl FnOnce(&Message) -> Result<(), E>,
+    ) -> Result<Message, E> {
+        let guard = self.unread.guard();
+        let mut unread = guard.lock();
+
+        let message = match unread.take() {
+            Some(message) => message,
+            None => match self.messages.pop() {
+                Some(message) => message,
+                None if self.is_closed() => return Err(IpcError::PeerClosed.into()),
+                None => return Err(IpcError::WouldBlock.into()),
+            },
+        };
+
+        if let Err(err) = accept(&message) {
+            *unread = Some(message);
+            return Err(err);
+        }
+
+        drop(unread);
+        drop(guard);
+
+        self.queued.fetch_sub(1, Ordering::AcqRel);
+        self.queued_channels
+            .fetch_sub(message.channel_count(), Ordering::AcqRel);
+        self.senders.wake_one();
+
+        Ok(message)
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+
+        self.receivers.wake_all();
+        self.senders.wake_all();
+    }
+
+    /// Drop the messages that will never be received.
+    fn drain(&self) {
+        while self.pop_if(|_| Ok::<_, IpcError>(())).is_ok() {}
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        // The queue does not drop the messages that are left.
+        self.drain();
+    }
+}
+
+/// One end of a channel, the messages that are sent at one end are received at the other end in
+/// the same order.
+///
+/// The channel is closed once either end is dropped. The messages that were sent before can
+/// still be received, after that receiving fails with [`IpcError::PeerClosed`].
+pub struct ChannelEnd {
+    outgoing: Arc<Pipe>,
+    incoming: Arc<Pipe>,
+}
+
+/// Create a channel, and return both of its ends.
+pub fn channel() -> Result<(ChannelEnd, ChannelEnd), IpcError> {
+    let first = Arc::try_new(Pipe::new()).map_err(|_| IpcError::OutOfMemory)?;
+    let second = Arc::try_new(Pipe::new()).map_err(|_| IpcError::OutOfMemory)?;
+
+    Ok((
+        ChannelEnd {
+            outgoing: first.clone(),
+            incoming: second.clone(),
+        },
+        ChannelEnd {
+            outgoing: second,
+            incoming: first,
+        },
+    ))
+}
+
+impl ChannelEnd {
+    /// Send `message` without blocking, the message is returned when it cannot be sent.
+    ///
+    /// A channel end cannot be sent while ends of other channels wait to be received by it.
+    /// Otherwise ends could be queued in each other, and keep each other alive after every handle
+    /// to them is closed.
+    pub fn try_send(&self, message: Message) -> Result<(), (IpcError, Message)> {
+        let transferable = |capability: &Capability| match &capability.object {
+            // The channel would keep itself alive while the end waits in it.
+            KernelObject::Channel(end) => {
+                !Arc::ptr_eq(&end.outgoing, &self.outgoing)
+                    && !end.is_peer_of(self)
+                    && end.incoming.queued_channels.load(Ordering::Acquire) == 0
+            }
+            // Only the process that listens may accept connections.
+            KernelObject::Port(_) => false,
+            _ => true,
+        };
+
+        // Every cycle is closed by sending an end that already holds the next end of the cycle,
+        // so checking and queueing the ends has to happen at once.
+        let guard = CHANNEL_TRANSFERS.guard();
+        let _transfer = (message.channel_count() > 0).then(|| guard.lock());
+
+        if !message.capabilities().iter().all(transferable) {
+            return Err((IpcError::InvalidTransfer, message));
+        }
+
+        self.outgoing.push(message)
+    }
+
+    /// Send `message`, blocks while the channel is full.
+    pub fn send(&self, mut message: Message) -> Result<(), IpcError> {
+        loop {
+            match self.try_send(message) {
+                Err((IpcError::WouldBlock, returned)) => message = returned,
+                result => return result.map_err(|(err, _)| err),
+            }
+
+            self.outgoing
+                .senders
+                .wait_until(|| self.outgoing.is_writable());
+        }
+    }
+
+    /// Receive the next message without blocking.
+    pub fn try_receive(&self) -> Result<Message, IpcError> {
+        self.incoming.pop_if(|_| Ok(()))
+    }
+
+    /// Receive the next message when `accept` allows it, otherwise it stays the next message and
+    /// the error of `accept` is returned.
+    pub fn try_receive_if<E: From<IpcError>>(
+        &self,
+        accept: impl FnOnce(&Message) -> Result<(), E>,
+    ) -> Result<Message, E> {
+        self.incoming.pop_if(accept)
+    }
+
+    /// Receive the next message, blocks until there is one.
+    pub fn receive(&self) -> Result<Message, IpcError> {
+        loop {
+            match self.try_receive() {
+                Err(IpcError::WouldBlock) => {}
+                result => return result,
+            }
+
+            self.incoming
+                .receivers
+                .wait_until(|| self.incoming.is_readable());
+        }
+    }
+
+    /// Block the current user thread until a message can be sent, see
+    /// [`WaitQueue::block_after_syscall`].
+    pub fn block_send_after_syscall(&self) -> bool {
+        self.outgoing
+            .senders
+            .block_after_syscall(|| self.outgoing.is_writable())
+    }
+
+    /// Block the current user thread until a message can be received, see
+    /// [`WaitQueue::block_after_syscall`].
+    pub fn block_receive_after_syscall(&self) -> bool {
+        self.incoming
+            .receivers
+            .block_after_syscall(|| self.incoming.is_readable())
+    }
+
+    fn is_peer_of(&self, other: &ChannelEnd) -> bool {
+        Arc::ptr_eq(&self.incoming, &other.outgoing)
+    }
+}
+
+impl Drop for ChannelEnd {
+    fn drop(&mut self) {
+        self.outgoing.close();
+        self.incoming.close();
+
+        // The messages may hold ends of other channels, which should be closed now as well.
+        self.incoming.drain();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use crate::{
+        handle::Rights,
+        multitasking::scheduler::{LOWEST_PRIORITY, SCHEDULER},
+    };
+
+    use super::*;
+
+    fn message(data: &[u8]) -> Message {
+        Message::new(data.to_vec(), Vec::new()).unwrap()
+    }
+
+    fn transfer(end: &Arc<ChannelEnd>) -> Message {
+        let capability = Capability::new(KernelObject::Channel(end.clone()), Rights::ALL);
+        Message::new(vec![], vec![capability]).unwrap()
+    }
+
+    #[test_case]
+    fn test_send_receive() {
+        let (first, second) = channel().unwrap();
+
+        first.try_send(message(b"ping")).ok().unwrap();
+        second.send(message(b"pong")).unwrap();
+
+        assert_eq!(b"ping", second.try_receive().ok().unwrap().data());
+        assert_eq!(b"pong", first.receive().ok().unwrap().data());
+        assert_eq!(Some(IpcError::WouldBlock), first.try_receive().err());
+    }
+
+    #[test_case]
+    fn test_full_and_closed() {
+        let (first, second) = channel().unwrap();
+
+        for _ in 0..CHANNEL_CAPACITY {
+            first.try_send(message(b"")).ok().unwrap();
+        }
+
+        let (err, _) = first.try_send(message(b"")).err().unwrap();
+        assert_eq!(IpcError::WouldBlock, err);
+
+        drop(first);
+
+        assert!(second.try_receive().is_ok());
+        while second.try_receive().is_ok() {}
+
+        assert_eq!(Some(IpcError::PeerClosed), second.try_receive().err());
+
+        let (err, _) = second.try_send(message(b"")).err().unwrap();
+        assert_eq!(IpcError::PeerClosed, err);
+    }
+
+    #[test_case]
+    fn test_refused_message_stays() {
+        let (first, second) = channel().unwrap();
+
+        first.try_send(message(b"long message")).ok().unwrap();
+
+        assert_eq!(
+            Some(IpcError::MessageTooLarge),
+            second
+                .try_receive_if(|_| Err(IpcError::MessageTooLarge))
+                .err()
+        );
+        assert_eq!(b"long message", second.try_receive().ok().unwrap().data());
+    }
+
+    #[test_case]
+    fn test_transfer() {
+        let (first, second) = channel().unwrap();
+        let (third, fourth) = channel().unwrap();
+        let first = Arc::new(first);
+        let fourth = Arc::new(fourth);
+
+        let (err, _) = first.try_send(transfer(&first)).err().unwrap();
+        assert_eq!(IpcError::InvalidTransfer, err);
+
+        first.try_send(transfer(&fourth)).ok().unwrap();
+        drop(fourth);
+
+        let (_, mut capabilities) = second.try_receive().ok().unwrap().into_parts();
+        let Some(KernelObject::Channel(fourth)) = capabilities.pop().map(|c| c.object) else {
+            panic!("the channel end should have been received");
+        };
+
+        third.try_send(message(b"moved")).ok().unwrap();
+        assert_eq!(b"moved", fourth.try_receive().ok().unwrap().data());
+    }
+
+    #[test_case]
+    fn test_transfer_cycle() {
+        let (first, second) = channel().unwrap();
+        let (third, fourth) = channel().unwrap();
+        let (second, fourth) = (Arc::new(second), Arc::new(fourth));
+
+        third.try_send(transfer(&second)).ok().unwrap();
+
+        // The second end would wait in the fourth, and the fourth in the second.
+        let (err, _) = first.try_send(transfer(&fourth)).err().unwrap();
+        assert_eq!(IpcError::InvalidTransfer, err);
+
+        // Once the second end is received, the fourth can be sent.
+        fourth.try_receive().ok().unwrap();
+        first.try_send(transfer(&fourth)).ok().unwrap();
+
+        let weak = Arc::downgrade(&second);
+        drop((first, second, third, fourth));
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test_case]
+    fn test_blocking_receive() {
+        let (first, second) = channel().unwrap();
+
+        let sender = SCHEDULER
+            .spawn_kernel_thread(LOWEST_PRIORITY, move || {
+                for i in 0..(CHANNEL_CAPACITY as u8 * 2) {
+                    first.send(message(&[i])).unwrap();
+                }
+
+                0
+            })
+            .unwrap();
+
+        for i in 0..(CHANNEL_CAPACITY as u8 * 2) {
+            assert_eq!([i], second.receive().ok().unwrap().data());
+        }
+
+        sender.join();
+        assert_eq!(Some(IpcError::PeerClosed), second.receive().err());
+    }
+}
@@ -0,0 +1,19 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpcError {
+    /// The other end of the channel has been closed.
+    PeerClosed,
+    /// The operation would have to wait, for a message, for room in the channel or for a
+    /// connection.
+    WouldBlock,
+    /// The data of the message is too large, or does not fit the buffer of the receiver.
+    MessageTooLarge,
+    TooManyHandles,
+    /// An end of a channel cannot be sent through its own channel, and ports cannot be sent at
+    /// all.
+    InvalidTransfer,
+    /// Another port object is already listening on the port.
+    PortInUse,
+    /// Nothing is listening on the port.
+    NotListening,
+    OutOfMemory,
+}
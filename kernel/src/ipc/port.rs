@@ -0,0 +1,179 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+};
+use essentials::{nb::BoundedQueue, spin::SpinLock};
+
+use crate::{multitasking::scheduler::WaitQueue, utils::InterruptGuard};
+
+use super::{channel, ChannelEnd, IpcError};
+
+/// The connections that can wait until the listener accepts them.
+pub const PORT_BACKLOG: usize = 16;
+
+/// The ports that are listened on, a port is free again once its [`Port`] is dropped.
+static PORTS: InterruptGuard<SpinLock<BTreeMap<u16, Weak<Port>>>> =
+    InterruptGuard::new_lock(BTreeMap::new());
+
+/// A named endpoint, every connection to the port is a new channel of which the listener accepts
+/// one end.
+pub struct Port {
+    port: u16,
+    pending: BoundedQueue<PORT_BACKLOG, ChannelEnd>,
+    /// The connections that have not been accepted, it is raised before a connection is pushed.
+    queued: AtomicUsize,
+    listeners: WaitQueue,
+}
+
+/// Listen on `port`, until the returned port object is dropped.
+pub fn listen(port: u16) -> Result<Arc<Port>, IpcError> {
+    let guard = PORTS.guard();
+    let mut ports = guard.lock();
+
+    if ports
+        .get(&port)
+        .is_some_and(|listener| listener.strong_count() > 0)
+    {
+        return Err(IpcError::PortInUse);
+    }
+
+    let listener = Arc::try_new(Port {
+        port,
+        pending: BoundedQueue::new(),
+        queued: AtomicUsize::new(0),
+        listeners: WaitQueue::new(),
+    })
+    .map_err(|_| IpcError::OutOfMemory)?;
+
+    ports.insert(port, Arc::downgrade(&listener));
+
+    Ok(listener)
+}
+
+/// Connect to the listener of `port`, and return the end of the new channel that does not wait to
+/// be accepted.
+///
+/// Connecting never blocks, it fails with [`IpcError::WouldBlock`] when the backlog of the port
+/// is full.
+pub fn connect(port: u16) -> Result<ChannelEnd, IpcError> {
+    let listener = {
+        let guard = PORTS.guard();
+        let ports = guard.lock();
+
+        ports
+            .get(&port)
+            .and_then(Weak::upgrade)
+            .ok_or(IpcError::NotListening)?
+    };
+
+    let (client, server) = channel()?;
+
+    listener.queued.fetch_add(1, Ordering::AcqRel);
+
+    if listener.pending.push(server).is_err() {
+        listener.queued.fetch_sub(1, Ordering::AcqRel);
+        return Err(IpcError::WouldBlock);
+    }
+
+    listener.listeners.wake_one();
+
+    Ok(client)
+}
+
+impl Port {
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Accept a connection without blocking.
+    pub fn try_accept(&self) -> Result<ChannelEnd, IpcError> {
+        let end = self.pending.pop().ok_or(IpcError::WouldBlock)?;
+        self.queued.fetch_sub(1, Ordering::AcqRel);
+
+        Ok(end)
+    }
+
+    /// Accept a connection, blocks until there is one.
+    pub fn accept(&self) -> ChannelEnd {
+        loop {
+            if let Ok(end) = self.try_accept() {
+                return end;
+            }
+
+            self.listeners.wait_until(|| self.has_pending());
+        }
+    }
+
+    /// Block the current user thread until a connection can be accepted, see
+    /// [`WaitQueue::block_after_syscall`].
+    pub fn block_accept_after_syscall(&self) -> bool {
+        self.listeners.block_after_syscall(|| self.has_pending())
+    }
+
+    fn has_pending(&self) -> bool {
+        self.queued.load(Ordering::Acquire) > 0
+    }
+}
+
+impl Drop for Port {
+    fn drop(&mut self) {
+        {
+            let guard = PORTS.guard();
+            let mut ports = guard.lock();
+
+            // Another port object may be listening already.
+            if ports
+                .get(&self.port)
+                .is_some_and(|listener| listener.strong_count() == 0)
+            {
+                ports.remove(&self.port);
+            }
+        }
+
+        // The clients see that their channel is closed.
+        while self.pending.pop().is_some() {}
+
+        // Threads that blocked in a system call find that the port is gone.
+        self.listeners.wake_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use crate::ipc::Message;
+
+    use super::*;
+
+    /// A port that no other test uses.
+    const TEST_PORT: u16 = 40_000;
+
+    #[test_case]
+    fn test_connect_accept() {
+        assert_eq!(Some(IpcError::NotListening), connect(TEST_PORT).err());
+
+        let listener = listen(TEST_PORT).unwrap();
+        assert_eq!(Some(IpcError::PortInUse), listen(TEST_PORT).err());
+        assert_eq!(Some(IpcError::WouldBlock), listener.try_accept().err());
+
+        let client = connect(TEST_PORT).unwrap();
+        let server = listener.accept();
+
+        client
+            .try_send(Message::new(b"hello".to_vec(), Vec::new()).unwrap())
+            .ok()
+            .unwrap();
+        assert_eq!(b"hello", server.try_receive().ok().unwrap().data());
+
+        let waiting = connect(TEST_PORT).unwrap();
+        drop(listener);
+
+        assert_eq!(Some(IpcError::PeerClosed), waiting.try_receive().err());
+        assert_eq!(Some(IpcError::NotListening), connect(TEST_PORT).err());
+
+        drop(listen(TEST_PORT).unwrap());
+    }
+}
@@ -15,8 +15,10 @@
 extern crate alloc;
 
 pub mod arch;
+pub mod handle;
 pub mod init;
 pub mod interface;
+pub mod ipc;
 pub mod log;
 pub mod memory;
 pub mod multitasking;
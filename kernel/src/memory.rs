@@ -1,2 +1,4 @@
 pub mod alloc;
 pub mod map;
+pub mod quota;
+pub mod shared;
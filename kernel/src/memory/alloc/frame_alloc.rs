@@ -27,6 +27,16 @@
 //!
 //!
 //! For more information on specific operation see: [`FrameAllocator`].
+//!
+//! # Frame descriptors
+//!
+//! Every zone keeps a [`FrameDescriptor`] per frame, in an array at the start of its region. The
+//! descriptor of the first frame of a block holds the reference count and [`FrameFlags`] of the
+//! block. A block normally has a single owner that deallocates it, but it can also be given more
+//! owners with [`FrameAllocator::retain`], after which every owner has to
+//! [`FrameAllocator::release`] it before it is deallocated.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 use alloc::vec::Vec;
 
@@ -39,19 +49,27 @@ use level::Level;
 use essentials::address::PhysicalAddress;
 use essentials::PanicOnce;
 
+#[cfg(doc)]
+use descriptor::FrameDescriptor;
+
+mod descriptor;
 mod level;
 mod zone;
 
+pub use descriptor::FrameFlags;
+
 const MIN_ORDER: u8 = 12; // 4Kib granularity.
 
 pub struct FrameAllocator {
     zones: PanicOnce<Vec<Zone>>, // TODO: add this to the eternal alloc
+    physical_memory_offset: AtomicUsize,
 }
 
 impl FrameAllocator {
     const fn new() -> Self {
         Self {
             zones: PanicOnce::new(),
+            physical_memory_offset: AtomicUsize::new(0),
         }
     }
 
@@ -104,7 +122,7 @@ impl FrameAllocator {
         let usable_regions = map
             .iter() // TODO: align the end & start.
             .filter(|x| x.size >= 2u64.pow(MIN_ORDER as u32))
-            .map(|r| {
+            .filter_map(|r| {
                 Zone::new(
                     (r.start).into(),
                     (r.size + r.start).into(),
@@ -118,6 +136,13 @@ impl FrameAllocator {
         zones.sort_by_key(|b| core::cmp::Reverse(b.size()));
 
         self.zones.initialize_with(zones);
+        self.physical_memory_offset
+            .store(physical_memory_offset, Ordering::Relaxed);
+    }
+
+    /// The offset at which all physical memory is mapped into virtual memory.
+    pub fn physical_memory_offset(&self) -> usize {
+        self.physical_memory_offset.load(Ordering::Relaxed)
     }
 
     /// Allocate a block of physical memory.
@@ -200,13 +225,78 @@ impl FrameAllocator {
     /// - The underlying memory located at `addr` is not read of written after calling this function.
 
     pub unsafe fn deallocate(&self, addr: PhysicalAddress) {
-        let zone = self
-            .zones
-            .iter()
-            .find(|zone| zone.contains(addr))
-            .expect("addr should be within a zone's bounds");
+        self.zone(addr).deallocate(addr);
+    }
+
+    /// Split an allocated block of memory into blocks of `size`, that are deallocated (or
+    /// released) one by one.
+    ///
+    /// Every new block has the same owners and flags as the original block, so every owner of the
+    /// original block has to release all of them.
+    ///
+    /// # Panics
+    ///
+    /// When `addr` is not the start of an allocated block, or the block is smaller than `size`.
+    pub fn split(&self, addr: PhysicalAddress, size: usize) {
+        self.zone(addr).split(addr, size.next_power_of_two());
+    }
+
+    /// Add an owner to an allocated block of memory.
+    ///
+    /// The block is only deallocated once every owner called [`FrameAllocator::release`].
+    pub fn retain(&self, addr: PhysicalAddress) {
+        self.zone(addr).descriptor(addr).retain();
+    }
+
+    /// Add an owner to an allocated block of memory, that is mapped in more than one place.
+    pub fn share(&self, addr: PhysicalAddress) {
+        let descriptor = self.zone(addr).descriptor(addr);
+
+        descriptor.retain();
+        descriptor.insert_flags(FrameFlags::SHARED);
+    }
+
+    /// Add an owner to an allocated block of memory that never releases it, so the block is
+    /// never deallocated.
+    pub fn pin(&self, addr: PhysicalAddress) {
+        let descriptor = self.zone(addr).descriptor(addr);
+
+        descriptor.retain();
+        descriptor.insert_flags(FrameFlags::PINNED);
+    }
+
+    /// Remove an owner from a block of memory, and deallocate it when it was the last owner.
+    ///
+    /// Returns whether the block has been deallocated.
+    ///
+    /// # Safety
+    ///
+    /// The same as [`FrameAllocator::deallocate`], for the caller's ownership of the block.
+    pub unsafe fn release(&self, addr: PhysicalAddress) -> bool {
+        let zone = self.zone(addr);
+
+        if !zone.descriptor(addr).release() {
+            return false;
+        }
 
         zone.deallocate(addr);
+        true
+    }
+
+    /// The amount of owners of an allocated block of memory.
+    pub fn ref_count(&self, addr: PhysicalAddress) -> usize {
+        self.zone(addr).descriptor(addr).ref_count() as usize
+    }
+
+    pub fn flags(&self, addr: PhysicalAddress) -> FrameFlags {
+        self.zone(addr).descriptor(addr).flags()
+    }
+
+    fn zone(&self, addr: PhysicalAddress) -> &Zone {
+        self.zones
+            .iter()
+            .find(|zone| zone.contains(addr))
+            .expect("addr should be within a zone's bounds")
     }
 
     /// Get the total amount of available memory.
@@ -286,6 +376,57 @@ mod tests {
         }
     }
 
+    #[test_case]
+    fn test_shared_frame_is_released_by_last_owner() {
+        let available = FRAME_ALLOC.available();
+        let (addr, _) = FRAME_ALLOC.allocate(FRAME_ALLOC.min_size()).unwrap();
+
+        assert_eq!(1, FRAME_ALLOC.ref_count(addr));
+        assert_eq!(FrameFlags::NONE, FRAME_ALLOC.flags(addr));
+
+        FRAME_ALLOC.share(addr);
+        FRAME_ALLOC.retain(addr);
+        assert_eq!(3, FRAME_ALLOC.ref_count(addr));
+        assert!(FRAME_ALLOC.flags(addr).contains(FrameFlags::SHARED));
+
+        unsafe {
+            assert!(!FRAME_ALLOC.release(addr));
+            assert!(!FRAME_ALLOC.release(addr));
+            assert_eq!(1, FRAME_ALLOC.ref_count(addr));
+            assert!(FRAME_ALLOC.release(addr));
+        }
+
+        assert_eq!(available, FRAME_ALLOC.available());
+
+        // The descriptor is reset when the block is allocated again.
+        let (addr, _) = FRAME_ALLOC.allocate(FRAME_ALLOC.min_size()).unwrap();
+        assert_eq!(1, FRAME_ALLOC.ref_count(addr));
+        assert_eq!(FrameFlags::NONE, FRAME_ALLOC.flags(addr));
+
+        unsafe { FRAME_ALLOC.deallocate(addr) };
+        assert_eq!(available, FRAME_ALLOC.available());
+    }
+
+    #[test_case]
+    fn test_split_blocks_are_deallocated_one_by_one() {
+        let available = FRAME_ALLOC.available();
+        let (addr, size) = FRAME_ALLOC.allocate(FRAME_ALLOC.min_size() * 4).unwrap();
+
+        FRAME_ALLOC.retain(addr);
+        FRAME_ALLOC.split(addr, FRAME_ALLOC.min_size());
+
+        for offset in (0..size).step_by(FRAME_ALLOC.min_size()) {
+            assert_eq!(2, FRAME_ALLOC.ref_count(addr + offset));
+
+            unsafe {
+                assert!(!FRAME_ALLOC.release(addr + offset));
+                assert!(FRAME_ALLOC.release(addr + offset));
+            }
+        }
+
+        assert_eq!(available, FRAME_ALLOC.available());
+    }
+
     #[test_case]
     fn test_alloc_twice_should_not_overlap() {
         let (addr1, size1) = FRAME_ALLOC.allocate(FRAME_ALLOC.min_size()).unwrap();
@@ -0,0 +1,93 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct FrameFlags {
+    value: u32,
+}
+
+impl FrameFlags {
+    pub const NONE: FrameFlags = Self { value: 0 };
+    /// The frame has been given more than one owner with [`super::FrameAllocator::share`].
+    pub const SHARED: FrameFlags = Self { value: 1 << 0 };
+    /// The frame holds a reference that is never released, so it is never deallocated.
+    pub const PINNED: FrameFlags = Self { value: 1 << 1 };
+
+    pub const fn contains(&self, other: FrameFlags) -> bool {
+        self.value & other.value == other.value
+    }
+}
+
+impl core::ops::BitOr for FrameFlags {
+    type Output = FrameFlags;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self {
+            value: self.value | rhs.value,
+        }
+    }
+}
+
+/// The metadata of a single frame of the smallest size, only the descriptor of the first frame of
+/// a block is used.
+///
+/// The descriptors are stored in the memory of their zone, so they can only contain atomics.
+#[repr(C)]
+pub struct FrameDescriptor {
+    ref_count: AtomicU32,
+    flags: AtomicU32,
+}
+
+impl FrameDescriptor {
+    pub const fn new() -> Self {
+        Self {
+            ref_count: AtomicU32::new(0),
+            flags: AtomicU32::new(0),
+        }
+    }
+
+    /// Reset the descriptor for a newly allocated block, with a single owner.
+    pub fn reset(&self) {
+        self.flags.store(0, Ordering::Relaxed);
+        self.ref_count.store(1, Ordering::Release);
+    }
+
+    /// Clear the descriptor of a deallocated block.
+    pub fn clear(&self) {
+        self.ref_count.store(0, Ordering::Release);
+    }
+
+    /// Give the descriptor the same owners and flags as `other`, for a block that is split off
+    /// from the block of `other`.
+    pub fn copy_from(&self, other: &FrameDescriptor) {
+        self.flags
+            .store(other.flags.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.ref_count.store(other.ref_count(), Ordering::Release);
+    }
+
+    pub fn ref_count(&self) -> u32 {
+        self.ref_count.load(Ordering::Acquire)
+    }
+
+    pub fn flags(&self) -> FrameFlags {
+        FrameFlags {
+            value: self.flags.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn insert_flags(&self, flags: FrameFlags) {
+        self.flags.fetch_or(flags.value, Ordering::Relaxed);
+    }
+
+    pub fn retain(&self) {
+        let previous = self.ref_count.fetch_add(1, Ordering::AcqRel);
+        assert!(previous > 0, "retained a frame that is not allocated");
+    }
+
+    /// Remove an owner, returns whether it was the last one.
+    pub fn release(&self) -> bool {
+        let previous = self.ref_count.fetch_sub(1, Ordering::AcqRel);
+        assert!(previous > 0, "released a frame that is not allocated");
+
+        previous == 1
+    }
+}
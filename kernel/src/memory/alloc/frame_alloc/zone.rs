@@ -6,7 +6,9 @@ use crate::{memory::alloc::frame_alloc::level::Level, utils::InterruptGuard};
 use essentials::address::PhysicalAddress;
 use essentials::spin::SpinLock;
 
-use super::MIN_ORDER;
+use super::{descriptor::FrameDescriptor, MIN_ORDER};
+
+const FRAME_SIZE: usize = 2usize.pow(MIN_ORDER as u32);
 
 pub struct Zone {
     addr_start: PhysicalAddress,
@@ -15,16 +17,40 @@ pub struct Zone {
 
     available: AtomicUsize,
     levels: Vec<InterruptGuard<SpinLock<Level>>>, // TODO: add this to the eternal alloc
+    /// A descriptor for every frame of the zone, stored at the start of the region.
+    descriptors: &'static [FrameDescriptor],
 }
 
 impl Zone {
+    /// Create a zone for a region of usable memory, the start of the region is used for the
+    /// descriptors of the frames.
+    ///
+    /// Returns `None` when there is no memory left after the descriptors.
     pub unsafe fn new(
-        addr_start: PhysicalAddress,
-        addr_end: PhysicalAddress,
+        region_start: PhysicalAddress,
+        region_end: PhysicalAddress,
         physical_memory_offset: usize,
-    ) -> Self {
-        assert!(addr_end > addr_start);
-        assert!((addr_end - addr_start).as_usize() >= 2usize.pow(MIN_ORDER as u32));
+    ) -> Option<Self> {
+        assert!(region_end > region_start);
+
+        let frames = (region_end - region_start).as_usize() / FRAME_SIZE;
+        let descriptors_size = (frames * size_of::<FrameDescriptor>()).next_multiple_of(FRAME_SIZE);
+
+        let addr_start = region_start + descriptors_size;
+        let addr_end = region_end;
+
+        if addr_end <= addr_start || (addr_end - addr_start).as_usize() < FRAME_SIZE {
+            return None;
+        }
+
+        let descriptors = core::slice::from_raw_parts_mut(
+            (region_start.as_usize() + physical_memory_offset) as *mut FrameDescriptor,
+            (addr_end - addr_start).as_usize() / FRAME_SIZE,
+        );
+
+        for descriptor in descriptors.iter_mut() {
+            core::ptr::write(descriptor, FrameDescriptor::new());
+        }
 
         let total_size = (addr_end - addr_start).as_usize();
         let largest_order = order_of_two(total_size.next_power_of_two());
@@ -46,13 +72,23 @@ impl Zone {
 
         Self::distribute_unused_memory(addr_start, total_size, largest_order, &mut levels);
 
-        Self {
+        Some(Self {
             physical_memory_offset,
             addr_start,
             addr_end,
             levels,
             available: AtomicUsize::new(total_size),
-        }
+            descriptors,
+        })
+    }
+
+    /// The descriptor of the block that starts at `addr`.
+    pub fn descriptor(&self, addr: PhysicalAddress) -> &FrameDescriptor {
+        assert!(addr.is_aligned_with(FRAME_SIZE), "{addr:?} is not a frame");
+
+        self.descriptors
+            .get((addr - self.addr_start).as_usize() / FRAME_SIZE)
+            .expect("addr should be within the zone")
     }
 
     fn level_allocate(&self, index: usize) -> Option<(PhysicalAddress, usize)> {
@@ -130,6 +166,7 @@ impl Zone {
         self.level_allocate(level_index).map(|(addr, index)| {
             let size = 2usize.pow(Self::level_index_to_order(index) as u32);
             self.available.fetch_sub(size, Ordering::SeqCst);
+            self.descriptor(addr).reset();
             (addr, size)
         })
     }
@@ -137,7 +174,46 @@ impl Zone {
     pub unsafe fn deallocate(&self, addr: PhysicalAddress) {
         assert!(self.contains(addr));
         let level_index = self.find_allocation_level_index(addr);
+
+        self.descriptor(addr).clear();
         self.level_deallocate(level_index, addr);
+
+        let size = 2usize.pow(Self::level_index_to_order(level_index) as u32);
+        self.available.fetch_add(size, Ordering::SeqCst);
+    }
+
+    /// Split the allocated block at `addr` into allocated blocks of `size`, that are deallocated
+    /// one by one. Every block gets the descriptor of the original block.
+    pub fn split(&self, addr: PhysicalAddress, size: usize) {
+        assert!(self.contains(addr));
+
+        let level_index = self.find_allocation_level_index(addr);
+        let target_index = Self::order_to_level_index(max(order_of_two(size), MIN_ORDER));
+        let block_size = 2usize.pow(Self::level_index_to_order(level_index) as u32);
+
+        assert!(addr.is_aligned_with(block_size), "{addr:?} is not a block");
+        assert!(
+            target_index <= level_index,
+            "a block can only be split into smaller blocks"
+        );
+
+        // The halves of a split block are marked as used in every level below it, just like a
+        // block that was split by an allocation.
+        for level in &self.levels[target_index..level_index] {
+            let level_lock = level.guard();
+            let mut level_lock = level_lock.lock();
+
+            for offset in (0..block_size).step_by(level_lock.block_size()) {
+                level_lock.mark_as_used(addr + offset);
+            }
+        }
+
+        let descriptor = self.descriptor(addr);
+        let size = 2usize.pow(Self::level_index_to_order(target_index) as u32);
+
+        for offset in (size..block_size).step_by(size) {
+            self.descriptor(addr + offset).copy_from(descriptor);
+        }
     }
 
     fn find_allocation_level_index(&self, addr: PhysicalAddress) -> usize {
@@ -1,6 +1,41 @@
-use essentials::address::VirtualAddress;
+//! The memory of a single process.
+//!
+//! A process reserves regions of its address space as [`MemoryArea`]s, which are only backed by
+//! frames once they are accessed. The first access to a page of an area causes a page fault, after
+//! which [`MemoryManager::handle_page_fault`] maps and fills the page so the access can be retried.
+//!
+//! Pages that are mapped directly with [`MemoryManager::map`], like the segments of an executable,
+//! do not need an area.
+//!
+//! Every page that the manager backs with a frame of its own is charged to its [`MemoryQuota`],
+//! until the page is released. The pages of shared memory are charged to the process that created
+//! the memory instead.
+//!
+//! An area can also map a [`SharedMemory`](crate::memory::shared::SharedMemory) object, whose
+//! pages are populated with the frames of the object so every process that maps it sees the same
+//! memory. Such pages are not inherited by [`MemoryManager::fork`], the area is, and the new
+//! process maps the pages again when it accesses them.
 
-use crate::memory::map::MemoryMapper;
+mod area;
+mod errors;
+
+use alloc::{collections::BTreeMap, sync::Arc, vec};
+use essentials::{
+    address::{PhysicalAddress, VirtualAddress},
+    spin::SpinLock,
+};
+
+use crate::{
+    fs::FsError,
+    memory::{
+        map::{MemoryMapper, MemoryProperties, ModifyMapError, NewMapError, ReadMapError},
+        quota::MemoryQuota,
+    },
+    utils::InterruptGuard,
+};
+
+pub use area::{AreaKind, MemoryArea};
+pub use errors::AreaError;
 
 #[derive(Debug)]
 pub enum MemoryViolation {
@@ -22,26 +57,287 @@ pub struct PageFault {
     pub instruction_pointer: VirtualAddress,
     pub access: MemoryAccess,
     pub violation: MemoryViolation,
+    /// Whether the fault happened while running in user mode.
+    pub user_mode: bool,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MemoryErrorKind {
     NullPointer,
+    /// The address is not within any reserved area.
+    NotReserved,
+    /// The address is in the guard page of a stack.
+    StackOverflow,
+    /// The access is not allowed by the properties of the memory.
+    AccessViolation,
+    MalformedTable,
+    OutOfFrames,
+    /// The memory would use more than the quota of the process allows.
+    QuotaExceeded,
+    /// The file that backs the memory could not be read.
+    Io(FsError),
 }
 
+#[derive(Debug)]
 pub struct MemoryError {
     pub kind: MemoryErrorKind,
     pub fault: PageFault,
 }
 
-pub struct MemoryManager {
+struct ManagedMemory {
     mapper: MemoryMapper,
+    /// The reserved areas, by their start address.
+    areas: BTreeMap<usize, MemoryArea>,
+    quota: Arc<MemoryQuota>,
+    /// The pages that are charged to the quota.
+    charged_pages: usize,
+}
+
+pub struct MemoryManager {
+    l4_table: PhysicalAddress,
+    memory: InterruptGuard<SpinLock<ManagedMemory>>,
 }
 
 impl MemoryManager {
-    pub const fn new(mapper: MemoryMapper) -> Self {
-        Self { mapper }
+    /// Create a manager whose memory is not limited, see [`Self::with_quota`].
+    pub fn new(mapper: MemoryMapper) -> Self {
+        Self {
+            l4_table: mapper.l4_table(),
+            memory: InterruptGuard::new_lock(ManagedMemory {
+                mapper,
+                areas: BTreeMap::new(),
+                quota: Arc::new(MemoryQuota::unlimited()),
+                charged_pages: 0,
+            }),
+        }
+    }
+
+    /// Charge the pages of the manager to `quota` instead, before any page is mapped.
+    pub fn with_quota(self, quota: Arc<MemoryQuota>) -> Self {
+        {
+            let guard = self.memory.guard();
+            let mut memory = guard.lock();
+
+            assert_eq!(
+                0, memory.charged_pages,
+                "the quota should be set before mapping pages"
+            );
+            memory.quota = quota;
+        }
+
+        self
+    }
+
+    /// The level 4 table of the address space.
+    pub fn l4_table(&self) -> PhysicalAddress {
+        self.l4_table
+    }
+
+    /// The quota that the memory is charged to.
+    pub fn quota(&self) -> Arc<MemoryQuota> {
+        self.memory.guard().lock().quota.clone()
+    }
+
+    /// Access the mapper directly, pages that are mapped this way do not need an area. They are
+    /// not charged to the quota either, use [`Self::map`] for that.
+    pub fn with_mapper<R>(&self, f: impl FnOnce(&mut MemoryMapper) -> R) -> R {
+        let guard = self.memory.guard();
+        let mut memory = guard.lock();
+
+        f(&mut memory.mapper)
+    }
+
+    /// Map the pages that overlap with `start..start + len` directly, without an area. Every new
+    /// page is charged to the quota, pages that are already mapped allow what they allowed before
+    /// as well.
+    pub fn map(
+        &self,
+        start: VirtualAddress,
+        len: usize,
+        props: MemoryProperties,
+    ) -> Result<(), MemoryErrorKind> {
+        let guard = self.memory.guard();
+        let mut memory = guard.lock();
+
+        let end = (start + len).align_up(MemoryMapper::PAGE_SIZE);
+        let mut page = start.align_down(MemoryMapper::PAGE_SIZE);
+
+        while page < end {
+            memory.charge_page()?;
+
+            match memory.mapper.map(page, MemoryMapper::PAGE_SIZE, props) {
+                Ok(_) => {}
+                Err(NewMapError::AlreadyMapped) => {
+                    memory.refund_pages(1);
+
+                    let (mapped, _, _) = memory
+                        .mapper
+                        .mapping_info(page)
+                        .map_err(|_| MemoryErrorKind::MalformedTable)?;
+
+                    memory
+                        .mapper
+                        .protect(page, MemoryMapper::PAGE_SIZE, mapped.union(&props))
+                        .map_err(|_| MemoryErrorKind::AccessViolation)?;
+                }
+                Err(err) => {
+                    memory.refund_pages(1);
+                    return Err(map_error(err));
+                }
+            }
+
+            page += MemoryMapper::PAGE_SIZE;
+        }
+
+        Ok(())
+    }
+
+    /// Create the memory of a new process, that starts out as a copy of this memory. The copy is
+    /// charged to the same quota.
+    ///
+    /// The pages are shared until either process writes to them, see
+    /// [`MemoryMapper::new_inherited_from_shared`].
+    pub fn fork(&self) -> Result<MemoryManager, MemoryErrorKind> {
+        let guard = self.memory.guard();
+        let mut memory = guard.lock();
+
+        let charged = (memory.charged_pages * MemoryMapper::PAGE_SIZE) as u64;
+        memory
+            .quota
+            .charge(charged)
+            .map_err(|_| MemoryErrorKind::QuotaExceeded)?;
+
+        let mapper = match memory.mapper.new_inherited_from_shared() {
+            Ok(mapper) => mapper,
+            Err(err) => {
+                memory.quota.refund(charged);
+                return Err(map_error(err));
+            }
+        };
+
+        let forked = MemoryManager::new(mapper);
+
+        {
+            let guard = forked.memory.guard();
+            let mut forked = guard.lock();
+
+            forked.areas = memory.areas.clone();
+            forked.quota = memory.quota.clone();
+            forked.charged_pages = memory.charged_pages;
+        }
+
+        Ok(forked)
     }
 
+    /// Reserve an area of user space, without backing it by any frames yet.
+    pub fn reserve(&self, area: MemoryArea) -> Result<(), AreaError> {
+        let start = area.start().as_usize();
+        let end = start
+            .checked_add(area.size())
+            .ok_or(AreaError::InvalidArea)?;
+
+        // A stack needs at least a single page next to its guard page.
+        let min_size = match area.kind() {
+            AreaKind::Stack => 2 * MemoryMapper::PAGE_SIZE,
+            _ => MemoryMapper::PAGE_SIZE,
+        };
+
+        let fits = match area.kind() {
+            AreaKind::Shared(memory) => area.size() <= memory.size(),
+            _ => true,
+        };
+
+        if area.size() < min_size
+            || !fits
+            || !area.start().is_aligned_with(MemoryMapper::PAGE_SIZE)
+            || !area.size().is_multiple_of(MemoryMapper::PAGE_SIZE)
+            || start < MemoryMapper::USER_SPACE_START
+            || end > MemoryMapper::USER_SPACE_END
+            || !area.properties().user()
+        {
+            return Err(AreaError::InvalidArea);
+        }
+
+        let guard = self.memory.guard();
+        let mut memory = guard.lock();
+
+        if let Some((_, previous)) = memory.areas.range(..end).next_back() {
+            if previous.end() > area.start() {
+                return Err(AreaError::Overlaps);
+            }
+        }
+
+        // Like the segments of an executable, which would otherwise be used instead of the area.
+        if !memory.mapper.is_unmapped(area.start(), area.size()) {
+            return Err(AreaError::Overlaps);
+        }
+
+        memory.areas.insert(start, area);
+
+        Ok(())
+    }
+
+    /// Release the area that starts at `start`, including the frames that back it.
+    pub fn release(&self, start: VirtualAddress) -> Result<MemoryArea, AreaError> {
+        let guard = self.memory.guard();
+        let mut memory = guard.lock();
+
+        let area = memory
+            .areas
+            .remove(&start.as_usize())
+            .ok_or(AreaError::NotReserved)?;
+
+        let mut page = area.start();
+        let mut released = 0;
+
+        while page < area.end() {
+            match memory.mapper.unmap(page, MemoryMapper::PAGE_SIZE) {
+                Ok(()) => released += 1,
+                Err(ModifyMapError::NotMapped) => {}
+                // Areas are only ever backed by pages that were mapped by `populate`.
+                Err(err) => panic!("Failed to release page {page:?} of {area:?}: {err:?}"),
+            }
+
+            page += MemoryMapper::PAGE_SIZE;
+        }
+
+        // The pages of shared memory are charged to the process that created it.
+        if !matches!(area.kind(), AreaKind::Shared(_)) {
+            memory.refund_pages(released);
+        }
+
+        Ok(area)
+    }
+
+    /// The area that contains `addr`.
+    pub fn area(&self, addr: VirtualAddress) -> Option<MemoryArea> {
+        self.memory.guard().lock().area(addr).cloned()
+    }
+
+    /// Read user memory into `buf`, like the process would. Pages that are reserved but not yet
+    /// backed are populated first.
+    pub fn read(&self, addr: VirtualAddress, buf: &mut [u8]) -> Result<(), MemoryErrorKind> {
+        self.for_each_page(
+            addr,
+            buf.len(),
+            MemoryAccess::Read,
+            |mapper, page, range| mapper.read(page, &mut buf[range]).map_err(Into::into),
+        )
+    }
+
+    /// Write `data` to user memory, like the process would. Pages that are reserved but not yet
+    /// backed are populated first.
+    pub fn write(&self, addr: VirtualAddress, data: &[u8]) -> Result<(), MemoryErrorKind> {
+        self.for_each_page(
+            addr,
+            data.len(),
+            MemoryAccess::Write,
+            |mapper, page, range| mapper.write(page, &data[range]),
+        )
+    }
+
+    /// Resolve a page fault of the process, by backing the page with a frame when it is within a
+    /// reserved area. The faulting instruction can be retried when this succeeds.
     pub fn handle_page_fault(&self, fault: PageFault) -> Result<(), MemoryError> {
         if fault.addr.is_null() {
             return Err(MemoryError {
@@ -50,6 +346,552 @@ impl MemoryManager {
             });
         }
 
-        todo!()
+        let result = match fault.violation {
+            MemoryViolation::MalformedTable => Err(MemoryErrorKind::MalformedTable),
+            MemoryViolation::InsufficientPrivilge => match fault.access {
+                MemoryAccess::Write => {
+                    let guard = self.memory.guard();
+                    let mut memory = guard.lock();
+
+                    memory.copy_on_write(fault.addr)
+                }
+                _ => Err(MemoryErrorKind::AccessViolation),
+            },
+            MemoryViolation::NotMapped => {
+                let guard = self.memory.guard();
+                let mut memory = guard.lock();
+
+                memory.populate(fault.addr, &fault.access)
+            }
+        };
+
+        result.map_err(|kind| MemoryError { kind, fault })
+    }
+
+    /// Call `apply` for every page that overlaps with the region, with the address within the page
+    /// and the range of the region that is within the page.
+    fn for_each_page(
+        &self,
+        addr: VirtualAddress,
+        len: usize,
+        access: MemoryAccess,
+        mut apply: impl FnMut(
+            &mut MemoryMapper,
+            VirtualAddress,
+            core::ops::Range<usize>,
+        ) -> Result<(), ModifyMapError>,
+    ) -> Result<(), MemoryErrorKind> {
+        let guard = self.memory.guard();
+        let mut memory = guard.lock();
+
+        let mut done = 0;
+
+        while done < len {
+            let current = addr + done;
+            let page_left = MemoryMapper::PAGE_SIZE - current.as_usize() % MemoryMapper::PAGE_SIZE;
+            let chunk = page_left.min(len - done);
+
+            match memory.mapper.mapping_info(current) {
+                Ok((props, _, _)) if props.user() && allows(props, &access) => {}
+                Ok((props, _, _)) if props.user() && matches!(access, MemoryAccess::Write) => {
+                    memory.copy_on_write(current)?
+                }
+                Ok(_) => return Err(MemoryErrorKind::AccessViolation),
+                Err(ReadMapError::NotMapped) => memory.populate(current, &access)?,
+            }
+
+            apply(&mut memory.mapper, current, done..done + chunk)
+                .map_err(|_| MemoryErrorKind::AccessViolation)?;
+
+            done += chunk;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for MemoryManager {
+    fn drop(&mut self) {
+        let guard = self.memory.guard();
+        let mut memory = guard.lock();
+
+        let charged = memory.charged_pages;
+        memory.refund_pages(charged);
+    }
+}
+
+impl ManagedMemory {
+    /// Charge a page to the quota, before it is backed by a frame.
+    fn charge_page(&mut self) -> Result<(), MemoryErrorKind> {
+        self.quota
+            .charge(MemoryMapper::PAGE_SIZE as u64)
+            .map_err(|_| MemoryErrorKind::QuotaExceeded)?;
+
+        self.charged_pages += 1;
+
+        Ok(())
+    }
+
+    fn refund_pages(&mut self, pages: usize) {
+        self.quota.refund((pages * MemoryMapper::PAGE_SIZE) as u64);
+        self.charged_pages -= pages;
+    }
+
+    fn area(&self, addr: VirtualAddress) -> Option<&MemoryArea> {
+        self.areas
+            .range(..=addr.as_usize())
+            .next_back()
+            .map(|(_, area)| area)
+            .filter(|area| area.contains(addr))
+    }
+
+    /// Give the copy-on-write page that contains `addr` its own frame.
+    fn copy_on_write(&mut self, addr: VirtualAddress) -> Result<(), MemoryErrorKind> {
+        match self.mapper.copy_on_write(addr) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(MemoryErrorKind::AccessViolation),
+            Err(NewMapError::OutOfFrames) => Err(MemoryErrorKind::OutOfFrames),
+            Err(_) => Err(MemoryErrorKind::AccessViolation),
+        }
+    }
+
+    /// Back the page that contains `addr` with a frame, and fill it according to its area.
+    fn populate(
+        &mut self,
+        addr: VirtualAddress,
+        access: &MemoryAccess,
+    ) -> Result<(), MemoryErrorKind> {
+        let area = self.area(addr).ok_or(MemoryErrorKind::NotReserved)?.clone();
+
+        if area.is_guard_page(addr) {
+            return Err(MemoryErrorKind::StackOverflow);
+        }
+
+        if !area.allows(access) {
+            return Err(MemoryErrorKind::AccessViolation);
+        }
+
+        let page = addr.align_down(MemoryMapper::PAGE_SIZE);
+
+        let mapped = match area.kind() {
+            AreaKind::Shared(memory) => {
+                let frame = memory
+                    .frame((page - area.start()).as_usize())
+                    .expect("a shared area should never be larger than its memory");
+
+                match self.mapper.map_shared(page, frame, area.properties()) {
+                    // Only the frame of the memory itself is a mapping of the area.
+                    Err(NewMapError::AlreadyMapped)
+                        if self
+                            .mapper
+                            .mapping_info(page)
+                            .is_ok_and(|(_, mapped, _)| mapped != frame) =>
+                    {
+                        Err(NewMapError::NotOwned)
+                    }
+                    result => result,
+                }
+            }
+            _ => {
+                self.charge_page()?;
+
+                self.mapper
+                    .map(page, MemoryMapper::PAGE_SIZE, area.properties())
+                    .map(|_| ())
+                    .inspect_err(|_| self.refund_pages(1))
+            }
+        };
+
+        match mapped {
+            Ok(()) => {}
+            // Another thread of the process got to it first.
+            Err(NewMapError::AlreadyMapped) => return Ok(()),
+            Err(err) => return Err(map_error(err)),
+        }
+
+        // New pages are already zeroed, so only file backed pages have to be filled.
+        if let AreaKind::File {
+            fs,
+            inode,
+            offset,
+            size,
+        } = area.kind()
+        {
+            let area_offset = (page - area.start()).as_usize() as u64;
+
+            if area_offset < *size {
+                let len = (*size - area_offset).min(MemoryMapper::PAGE_SIZE as u64) as usize;
+                let mut buf = vec![0u8; len];
+
+                let read = fs
+                    .read(*inode, offset + area_offset, &mut buf)
+                    .map_err(MemoryErrorKind::Io)?;
+
+                self.mapper
+                    .write(page, &buf[..read])
+                    .map_err(|_| MemoryErrorKind::AccessViolation)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn map_error(err: NewMapError) -> MemoryErrorKind {
+    match err {
+        NewMapError::OutOfFrames => MemoryErrorKind::OutOfFrames,
+        NewMapError::AlreadyMapped | NewMapError::NotOwned => MemoryErrorKind::AccessViolation,
+    }
+}
+
+fn allows(properties: MemoryProperties, access: &MemoryAccess) -> bool {
+    match access {
+        MemoryAccess::Read => properties.readable(),
+        MemoryAccess::Write => properties.writable(),
+        MemoryAccess::InstructionFetch => properties.executable(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+
+    use super::*;
+    use crate::{
+        fs::{ramfs::RamFs, FileKind, FileSystem},
+        memory::{alloc::FRAME_ALLOC, shared::SharedMemory},
+    };
+
+    const START: usize = MemoryMapper::USER_SPACE_START + 0x10_0000;
+    const PAGE: usize = MemoryMapper::PAGE_SIZE;
+
+    fn manager() -> MemoryManager {
+        MemoryManager::new(
+            MemoryMapper::new_user_mapper(FRAME_ALLOC.physical_memory_offset()).unwrap(),
+        )
+    }
+
+    fn props(writable: bool) -> MemoryProperties {
+        MemoryProperties::new(writable, true, false, false, false)
+    }
+
+    fn fault(addr: usize, access: MemoryAccess) -> PageFault {
+        PageFault {
+            addr: addr.into(),
+            instruction_pointer: VirtualAddress::null(),
+            access,
+            violation: MemoryViolation::NotMapped,
+            user_mode: true,
+        }
+    }
+
+    fn fault_kind(manager: &MemoryManager, fault: PageFault) -> Option<MemoryErrorKind> {
+        manager.handle_page_fault(fault).err().map(|err| err.kind)
+    }
+
+    fn is_mapped(manager: &MemoryManager, addr: usize) -> bool {
+        manager.with_mapper(|mapper| mapper.mapping_info(addr.into()).is_ok())
+    }
+
+    #[test_case]
+    fn test_anonymous_on_demand() {
+        let manager = manager();
+        let area = MemoryArea::new(START.into(), 4 * PAGE, props(true), AreaKind::Anonymous);
+        manager.reserve(area).unwrap();
+
+        assert!(!is_mapped(&manager, START + PAGE));
+        assert_eq!(
+            None,
+            fault_kind(&manager, fault(START + PAGE + 8, MemoryAccess::Write))
+        );
+        assert!(is_mapped(&manager, START + PAGE));
+        assert!(!is_mapped(&manager, START));
+
+        let mut data = [0xFFu8; 16];
+        manager
+            .read((START + 2 * PAGE - 8).into(), &mut data)
+            .unwrap();
+        assert_eq!([0; 16], data);
+        assert!(is_mapped(&manager, START + 2 * PAGE));
+
+        manager
+            .write((START + 3 * PAGE).into(), &[1, 2, 3])
+            .unwrap();
+        manager
+            .read((START + 3 * PAGE).into(), &mut data[..3])
+            .unwrap();
+        assert_eq!([1, 2, 3], data[..3]);
+    }
+
+    #[test_case]
+    fn test_faults_outside_areas() {
+        let manager = manager();
+        let read_only = MemoryArea::new(START.into(), PAGE, props(false), AreaKind::Anonymous);
+        manager.reserve(read_only).unwrap();
+
+        assert_eq!(
+            Some(MemoryErrorKind::NullPointer),
+            fault_kind(&manager, fault(0, MemoryAccess::Read))
+        );
+        assert_eq!(
+            Some(MemoryErrorKind::NotReserved),
+            fault_kind(&manager, fault(START + PAGE, MemoryAccess::Read))
+        );
+        assert_eq!(
+            Some(MemoryErrorKind::AccessViolation),
+            fault_kind(&manager, fault(START, MemoryAccess::Write))
+        );
+        assert_eq!(
+            Err(MemoryErrorKind::AccessViolation),
+            manager.write(START.into(), &[1])
+        );
+        assert!(!is_mapped(&manager, START));
+    }
+
+    #[test_case]
+    fn test_stack_guard_page() {
+        let manager = manager();
+        let stack = MemoryArea::new(START.into(), 4 * PAGE, props(true), AreaKind::Stack);
+        manager.reserve(stack).unwrap();
+
+        assert_eq!(
+            None,
+            fault_kind(&manager, fault(START + 4 * PAGE - 8, MemoryAccess::Write))
+        );
+        assert_eq!(
+            None,
+            fault_kind(&manager, fault(START + PAGE, MemoryAccess::Write))
+        );
+        assert_eq!(
+            Some(MemoryErrorKind::StackOverflow),
+            fault_kind(&manager, fault(START + PAGE - 8, MemoryAccess::Write))
+        );
+        assert!(!is_mapped(&manager, START));
+    }
+
+    #[test_case]
+    fn test_file_backed() {
+        let fs = Arc::new(RamFs::new(0x10000));
+        let inode = fs.create(fs.root(), "data", FileKind::File).unwrap();
+        fs.write(inode, 0, &[0xAA; PAGE + 16]).unwrap();
+
+        let manager = manager();
+        let kind = AreaKind::File {
+            fs,
+            inode,
+            offset: 8,
+            size: PAGE as u64,
+        };
+        manager
+            .reserve(MemoryArea::new(START.into(), 2 * PAGE, props(false), kind))
+            .unwrap();
+
+        let mut data = [0u8; 16];
+        manager.read((START + PAGE - 8).into(), &mut data).unwrap();
+        assert_eq!([0xAA; 8], data[..8]);
+        assert_eq!([0; 8], data[8..]);
+    }
+
+    #[test_case]
+    fn test_fork_copies_on_write() {
+        let available = FRAME_ALLOC.available();
+        let parent = manager();
+        let area = MemoryArea::new(START.into(), 2 * PAGE, props(true), AreaKind::Anonymous);
+        parent.reserve(area).unwrap();
+        parent.write(START.into(), &[1, 2]).unwrap();
+
+        let child = parent.fork().unwrap();
+        let frame = |manager: &MemoryManager| {
+            manager.with_mapper(|mapper| mapper.mapping_info(START.into()).unwrap().1)
+        };
+
+        assert_eq!(frame(&parent), frame(&child));
+        assert_eq!(2, FRAME_ALLOC.ref_count(frame(&parent)));
+
+        let mut write_fault = fault(START, MemoryAccess::Write);
+        write_fault.violation = MemoryViolation::InsufficientPrivilge;
+        assert_eq!(None, fault_kind(&child, write_fault));
+        assert_ne!(frame(&parent), frame(&child));
+        assert_eq!(1, FRAME_ALLOC.ref_count(frame(&parent)));
+
+        child.write(START.into(), &[3]).unwrap();
+        parent.write((START + 1).into(), &[4]).unwrap();
+
+        let mut data = [0u8; 2];
+        parent.read(START.into(), &mut data).unwrap();
+        assert_eq!([1, 4], data);
+        child.read(START.into(), &mut data).unwrap();
+        assert_eq!([3, 2], data);
+
+        // Pages that were never touched are still populated on demand in both.
+        child.write((START + PAGE).into(), &[5]).unwrap();
+        assert!(!is_mapped(&parent, START + PAGE));
+
+        drop(parent);
+        drop(child);
+        assert_eq!(available, FRAME_ALLOC.available());
+    }
+
+    #[test_case]
+    fn test_quota() {
+        let quota = Arc::new(MemoryQuota::new(Some(2 * PAGE as u64)));
+        let manager = manager().with_quota(quota.clone());
+        let area = MemoryArea::new(START.into(), 4 * PAGE, props(true), AreaKind::Anonymous);
+        manager.reserve(area).unwrap();
+
+        manager.write(START.into(), &[1]).unwrap();
+        manager
+            .map((START + 4 * PAGE).into(), 1, props(true))
+            .unwrap();
+        assert_eq!(2 * PAGE as u64, quota.used());
+
+        assert_eq!(
+            Some(MemoryErrorKind::QuotaExceeded),
+            fault_kind(&manager, fault(START + PAGE, MemoryAccess::Write))
+        );
+        assert!(!is_mapped(&manager, START + PAGE));
+
+        // The copy is charged as well, even though it shares the frames.
+        assert_eq!(Some(MemoryErrorKind::QuotaExceeded), manager.fork().err());
+
+        manager.release(START.into()).unwrap();
+        assert_eq!(PAGE as u64, quota.used());
+
+        drop(manager);
+        assert_eq!(0, quota.used());
+    }
+
+    #[test_case]
+    fn test_reserve_over_mapped_pages() {
+        let manager = manager();
+
+        manager
+            .with_mapper(|mapper| mapper.map((START + PAGE).into(), PAGE, props(true)))
+            .unwrap();
+
+        assert_eq!(
+            Err(AreaError::Overlaps),
+            manager.reserve(MemoryArea::new(
+                START.into(),
+                2 * PAGE,
+                props(true),
+                AreaKind::Anonymous
+            ))
+        );
+        assert_eq!(
+            Ok(()),
+            manager.reserve(MemoryArea::new(
+                (START + 2 * PAGE).into(),
+                PAGE,
+                props(true),
+                AreaKind::Anonymous
+            ))
+        );
+    }
+
+    #[test_case]
+    fn test_shared_memory() {
+        let available = FRAME_ALLOC.available();
+        let memory = Arc::new(SharedMemory::new(2 * PAGE).unwrap());
+        let shared = |writable| {
+            MemoryArea::new(
+                START.into(),
+                2 * PAGE,
+                props(writable),
+                AreaKind::Shared(memory.clone()),
+            )
+        };
+
+        let writer = manager();
+        let reader = manager();
+        writer.reserve(shared(true)).unwrap();
+        reader.reserve(shared(false)).unwrap();
+
+        assert_eq!(
+            Err(AreaError::InvalidArea),
+            reader.reserve(MemoryArea::new(
+                (START + 4 * PAGE).into(),
+                3 * PAGE,
+                props(false),
+                AreaKind::Shared(memory.clone()),
+            ))
+        );
+
+        writer.write((START + PAGE).into(), &[1, 2]).unwrap();
+        assert_eq!(
+            Err(MemoryErrorKind::AccessViolation),
+            reader.write((START + PAGE).into(), &[3])
+        );
+
+        let forked = writer.fork().unwrap();
+        forked.write((START + PAGE + 1).into(), &[4]).unwrap();
+
+        let mut data = [0u8; 2];
+        reader.read((START + PAGE).into(), &mut data).unwrap();
+        assert_eq!([1, 4], data);
+
+        let frame = memory.frame(PAGE).unwrap();
+        assert_eq!(4, FRAME_ALLOC.ref_count(frame));
+
+        writer.release(START.into()).unwrap();
+        drop(forked);
+        drop(memory);
+        assert_eq!(2, FRAME_ALLOC.ref_count(frame));
+
+        // The frames stay allocated until the last mapping of them is gone.
+        drop(reader);
+        drop(writer);
+        assert_eq!(available, FRAME_ALLOC.available());
+    }
+
+    #[test_case]
+    fn test_reserve_and_release() {
+        let available = FRAME_ALLOC.available();
+        let manager = manager();
+        let area = |start: usize, size: usize| {
+            MemoryArea::new(start.into(), size, props(true), AreaKind::Anonymous)
+        };
+
+        manager.reserve(area(START, 2 * PAGE)).unwrap();
+
+        assert_eq!(
+            Err(AreaError::Overlaps),
+            manager.reserve(area(START - PAGE, 2 * PAGE))
+        );
+        assert_eq!(
+            Err(AreaError::Overlaps),
+            manager.reserve(area(START + PAGE, PAGE))
+        );
+        assert_eq!(
+            Err(AreaError::InvalidArea),
+            manager.reserve(area(START + 8, PAGE))
+        );
+        assert_eq!(
+            Err(AreaError::InvalidArea),
+            manager.reserve(area(PAGE, PAGE))
+        );
+        assert_eq!(
+            Err(AreaError::InvalidArea),
+            manager.reserve(MemoryArea::new(
+                (START + 2 * PAGE).into(),
+                PAGE,
+                MemoryProperties::KERNEL_READ_ONLY,
+                AreaKind::Anonymous,
+            ))
+        );
+
+        manager.write((START + PAGE).into(), &[1]).unwrap();
+        assert!(is_mapped(&manager, START + PAGE));
+
+        manager.release(START.into()).unwrap();
+        assert!(!is_mapped(&manager, START + PAGE));
+        assert_eq!(
+            Err(AreaError::NotReserved),
+            manager.release(START.into()).map(|_| ())
+        );
+        assert!(manager.area(START.into()).is_none());
+
+        drop(manager);
+        assert_eq!(available, FRAME_ALLOC.available());
     }
 }
@@ -0,0 +1,114 @@
+use alloc::sync::Arc;
+use essentials::address::VirtualAddress;
+
+use crate::{
+    fs::{FileSystem, InodeId},
+    memory::{
+        map::{MemoryAccess, MemoryMapper, MemoryProperties},
+        shared::SharedMemory,
+    },
+};
+
+/// What a page of a [`MemoryArea`] is filled with when it is first accessed.
+#[derive(Clone)]
+pub enum AreaKind {
+    /// Zero filled memory.
+    Anonymous,
+    /// Memory filled with `size` bytes of a file, starting at `offset` in the file. Whatever is
+    /// left of the area is zero filled.
+    File {
+        fs: Arc<dyn FileSystem>,
+        inode: InodeId,
+        offset: u64,
+        size: u64,
+    },
+    /// Zero filled memory that grows down, the lowest page is a guard page that is never mapped.
+    Stack,
+    /// The frames of a shared memory object, starting at its first page. The area is never larger
+    /// than the object.
+    Shared(Arc<SharedMemory>),
+}
+
+impl core::fmt::Debug for AreaKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            AreaKind::Anonymous => write!(f, "Anonymous"),
+            AreaKind::File {
+                inode,
+                offset,
+                size,
+                ..
+            } => f
+                .debug_struct("File")
+                .field("inode", inode)
+                .field("offset", offset)
+                .field("size", size)
+                .finish(),
+            AreaKind::Stack => write!(f, "Stack"),
+            AreaKind::Shared(memory) => f
+                .debug_struct("Shared")
+                .field("size", &memory.size())
+                .finish(),
+        }
+    }
+}
+
+/// A region of virtual memory that is reserved by a process, but only backed by frames once it
+/// is accessed.
+#[derive(Debug, Clone)]
+pub struct MemoryArea {
+    start: VirtualAddress,
+    size: usize,
+    properties: MemoryProperties,
+    kind: AreaKind,
+}
+
+impl MemoryArea {
+    pub fn new(
+        start: VirtualAddress,
+        size: usize,
+        properties: MemoryProperties,
+        kind: AreaKind,
+    ) -> Self {
+        Self {
+            start,
+            size,
+            properties,
+            kind,
+        }
+    }
+
+    pub fn start(&self) -> VirtualAddress {
+        self.start
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The (exclusive) end of the area.
+    pub fn end(&self) -> VirtualAddress {
+        self.start + self.size
+    }
+
+    pub fn properties(&self) -> MemoryProperties {
+        self.properties
+    }
+
+    pub fn kind(&self) -> &AreaKind {
+        &self.kind
+    }
+
+    pub fn contains(&self, addr: VirtualAddress) -> bool {
+        (self.start..self.end()).contains(&addr)
+    }
+
+    pub fn is_guard_page(&self, addr: VirtualAddress) -> bool {
+        matches!(self.kind, AreaKind::Stack)
+            && (self.start..self.start + MemoryMapper::PAGE_SIZE).contains(&addr)
+    }
+
+    pub fn allows(&self, access: &MemoryAccess) -> bool {
+        super::allows(self.properties, access)
+    }
+}
@@ -0,0 +1,10 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AreaError {
+    /// The area is empty, not page aligned or not within the user space.
+    InvalidArea,
+    /// The area overlaps with an area that is already reserved, or with pages that are mapped
+    /// without an area.
+    Overlaps,
+    /// There is no area that starts at the address.
+    NotReserved,
+}
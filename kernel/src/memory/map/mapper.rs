@@ -32,6 +32,12 @@ struct NavigateCtx {
 
 const BORROW_BIT: u64 = 0;
 const ALLOCATED_BIT: u64 = 1;
+/// The frame of the page is shared with other mappers, and released through [`FRAME_ALLOC`].
+const SHARED_BIT: u64 = 2;
+/// The page was writable before it was shared, so it is copied on the first write.
+const COPY_ON_WRITE_BIT: u64 = 3;
+/// The frame belongs to a shared memory object, so the page stays shared when it is written.
+const SHARED_MEMORY_BIT: u64 = 4;
 
 /// The `MemoryMapper` struct manages the low-level mappings between physical and virtual addresses.
 ///
@@ -43,21 +49,35 @@ const ALLOCATED_BIT: u64 = 1;
 ///
 /// The MemoryMapper adheres to the rust's concept of ownership. This is implemented by including a
 /// single bit within each page that indicates whenether a page is owned (0) or borrowed (1).
-/// Becaues of memory constraints it is not possible to refrence count these shared pages, this
-/// means each shared page is inadvertently a "memory leak".
+/// Borrowed pages are never deallocated by the mapper, so only memory that outlives every mapper
+/// is borrowed: the kernel, and memory that is not allocated by [`FRAME_ALLOC`].
 ///
 /// The positives of this model are that owned memory is automatically cleaned up after dropping an
 /// instance of MemoryMapper. Futhermore, there might be some concurrency optmizations possible
 /// because the MemoryMapper leaves this problem up to the caller.
+///
+/// Pages can also be owned by more than one mapper, these are reference counted by
+/// [`FRAME_ALLOC`] and released by the last mapper that drops them. The user space of a mapper
+/// can be cloned this way with [`Self::new_inherited_from_shared`], where writable pages are
+/// copied on the first write with [`Self::copy_on_write`].
 pub struct MemoryMapper {
     l4_table: PhysicalAddress,
     global_offset: usize,
     root: bool,
+    owns_l4_table: bool,
 }
 
 impl MemoryMapper {
     pub const PAGE_SIZE: usize = 4096;
 
+    /// The start of the virtual memory that is reserved for user processes.
+    ///
+    /// The kernel lives in the first level 4 entry and the physical memory mapping starts at 60
+    /// TiB, so the user space is placed in between.
+    pub const USER_SPACE_START: usize = 0x0000_0080_0000_0000; // 512 GiB
+    /// The (exclusive) end of the virtual memory that is reserved for user processes.
+    pub const USER_SPACE_END: usize = 0x0000_1000_0000_0000; // 16 TiB
+
     /// Create a new `MemoryMapper` instance from the current `CR3` register value.
     ///
     /// # Safety
@@ -71,19 +91,80 @@ impl MemoryMapper {
             global_offset,
             l4_table,
             root: true,
+            owns_l4_table: false,
+        }
+    }
+
+    /// Create a new `MemoryMapper` with its own level 4 table, meant for a user process.
+    ///
+    /// Every level 4 entry outside of the user space is borrowed from the active table, this way
+    /// the kernel remains mapped while the new table is active.
+    pub fn new_user_mapper(global_offset: usize) -> Result<Self, NewMapError> {
+        let (l4_table, _) = FRAME_ALLOC
+            .allocate_zeroed(Self::PAGE_SIZE)
+            .ok_or(NewMapError::OutOfFrames)?;
+
+        let mut mapper = Self {
+            global_offset,
+            l4_table,
+            root: false,
+            owns_l4_table: true,
+        };
+
+        let user_space = VirtualAddress::from(Self::USER_SPACE_START).indices()[0] as usize
+            ..VirtualAddress::from(Self::USER_SPACE_END).indices()[0] as usize;
+
+        // Safety: the active table is valid, and the new table is owned by `mapper`.
+        let (active, table) = unsafe {
+            (
+                mapper.deref_page_table(cr3::active_page()),
+                mapper.deref_l4_table_mut(),
+            )
+        };
+
+        for (index, (entry, active_entry)) in table.iter_mut().zip(active.iter()).enumerate() {
+            if user_space.contains(&index) || !active_entry.flags().present() {
+                continue;
+            }
+
+            let mut borrowed = *active_entry;
+            borrowed.set_flags(borrowed.flags().set_custom::<BORROW_BIT>(true));
+            *entry = borrowed;
+        }
+
+        Ok(mapper)
+    }
+
+    /// The physical address of the level 4 table.
+    pub fn l4_table(&self) -> PhysicalAddress {
+        self.l4_table
+    }
+
+    /// The physical address of the level 4 table that is active on the current processor.
+    pub fn active_l4_table() -> PhysicalAddress {
+        cr3::active_page()
+    }
+
+    /// Make the level 4 table at `l4_table` the active one, if it is not already.
+    ///
+    /// # Safety
+    ///
+    /// The table must remain valid while it is active, and it must map the kernel.
+    pub unsafe fn activate(l4_table: PhysicalAddress) {
+        if cr3::active_page() != l4_table {
+            cr3::set_active_page(l4_table);
         }
     }
 
     /// Make all owned memory shared.
     ///
-    /// # Memory Leak
+    /// # Pinned memory
     ///
-    /// Prefoming this operation effectivly leaks all previously owned memory because it will not get dealloced
-    /// after the `MemoryMapper` gets dropped.
+    /// Every owned page becomes borrowed, and its frame is pinned in [`FRAME_ALLOC`] so that it is
+    /// never deallocated, not even after the `MemoryMapper` gets dropped.
     ///
     /// This is not a problem however, because the function intended use is to be called with only
-    /// kernel memory. Because this memory never needs to be deallocated, this leaked memory will
-    /// never cause any problem.
+    /// kernel memory, which never needs to be deallocated.
     pub fn share_all(&mut self) {
         self.root = false;
 
@@ -96,6 +177,10 @@ impl MemoryMapper {
                 return Ok(None);
             }
 
+            if entry.flags().custom::<ALLOCATED_BIT>() {
+                FRAME_ALLOC.pin(entry.addr());
+            }
+
             entry.set_flags(entry.flags().set_custom::<BORROW_BIT>(true));
 
             Ok(Some(entry))
@@ -115,7 +200,185 @@ impl MemoryMapper {
         }
     }
 
-    pub fn new_inherited_from_shared(&self) {}
+    /// Create a new user mapper that inherits the user space of `self`.
+    ///
+    /// No memory is copied: every owned page becomes shared between both mappers, and writable
+    /// pages become read-only until they are copied by [`Self::copy_on_write`]. Pages that are
+    /// not owned by `self` are borrowed by the new mapper. Pages of shared memory objects are not
+    /// inherited, they have to be mapped again with [`Self::map_shared`].
+    pub fn new_inherited_from_shared(&mut self) -> Result<Self, NewMapError> {
+        let mut mapper = Self::new_user_mapper(self.global_offset)?;
+
+        let user_space = VirtualAddress::from(Self::USER_SPACE_START).indices()[0] as usize
+            ..VirtualAddress::from(Self::USER_SPACE_END).indices()[0] as usize;
+
+        // Safety: both tables are valid level 4 tables, and the new one is owned by `mapper`.
+        let result = unsafe {
+            let (table, inherited) = (self.deref_l4_table_mut(), mapper.deref_l4_table_mut());
+            self.inherit_table(table, inherited, 4, user_space)
+        };
+
+        // The pages that became read-only might still be writable in the TLB.
+        if cr3::active_page() == self.l4_table {
+            unsafe { cr3::set_active_page(self.l4_table) };
+        }
+
+        result.map(|_| mapper)
+    }
+
+    /// Copy the entries within `range` of `table` into `inherited`, which is a table at `level`.
+    unsafe fn inherit_table(
+        &mut self,
+        table: &mut PageTable,
+        inherited: &mut PageTable,
+        level: u8,
+        range: core::ops::Range<usize>,
+    ) -> Result<(), NewMapError> {
+        for index in range {
+            let entry = table[index];
+            let flags = entry.flags();
+
+            if !flags.present()
+                || flags.custom::<BORROW_BIT>()
+                || flags.custom::<SHARED_MEMORY_BIT>()
+            {
+                continue;
+            }
+
+            if level > 1 && !flags.huge() {
+                let (frame, _) = FRAME_ALLOC
+                    .allocate_zeroed(Self::PAGE_SIZE)
+                    .ok_or(NewMapError::OutOfFrames)?;
+
+                inherited[index] = PageTableEntry::new(flags, frame);
+
+                let (table, inherited) = (
+                    self.deref_page_table_mut(entry.addr()),
+                    self.deref_page_table_mut(frame),
+                );
+                self.inherit_table(table, inherited, level - 1, 0..TABLE_ENTRIES)?;
+            } else if flags.custom::<ALLOCATED_BIT>() {
+                FRAME_ALLOC.share(entry.addr());
+
+                let mut shared = flags.set_custom::<SHARED_BIT>(true);
+
+                if shared.writable() {
+                    shared = shared
+                        .set_writable(false)
+                        .set_custom::<COPY_ON_WRITE_BIT>(true);
+                }
+
+                table[index].set_flags(shared);
+                inherited[index] = PageTableEntry::new(shared, entry.addr());
+            } else {
+                inherited[index] =
+                    PageTableEntry::new(flags.set_custom::<BORROW_BIT>(true), entry.addr());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Give a copy-on-write page its own frame, and make it writable again.
+    ///
+    /// Returns `Ok(false)` when the page at `address` is not a copy-on-write page. When the other
+    /// mappers already released the frame, it is made writable without copying.
+    pub fn copy_on_write(&mut self, address: VirtualAddress) -> Result<bool, NewMapError> {
+        let Some((table, index, size)) = self.find_leaf(address) else {
+            return Ok(false);
+        };
+
+        // Safety: `find_leaf` only returns tables that are owned by `self`.
+        let entry = unsafe { &mut self.deref_page_table_mut(table)[index] };
+        let flags = entry.flags();
+
+        if !flags.custom::<COPY_ON_WRITE_BIT>() {
+            return Ok(false);
+        }
+
+        if FRAME_ALLOC.ref_count(entry.addr()) > 1 {
+            let (frame, _) = FRAME_ALLOC.allocate(size).ok_or(NewMapError::OutOfFrames)?;
+
+            // Safety: both frames are mapped through the global offset, and the new frame is not
+            // used by anything else yet.
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    self.translate_table_frame(entry.addr()).as_ptr::<u8>(),
+                    self.translate_table_frame(frame).as_mut_ptr::<u8>(),
+                    size,
+                );
+
+                FRAME_ALLOC.release(entry.addr());
+            }
+
+            entry.set_addr(frame);
+        }
+
+        entry.set_flags(
+            flags
+                .set_writable(true)
+                .set_custom::<SHARED_BIT>(false)
+                .set_custom::<COPY_ON_WRITE_BIT>(false),
+        );
+
+        if cr3::active_page() == self.l4_table {
+            unsafe { cr3::flush_virtual_page(address.align_down(size)) };
+        }
+
+        Ok(true)
+    }
+
+    /// Whether the page at `address` is shared with other mappers.
+    pub fn is_shared(&self, address: VirtualAddress) -> bool {
+        self.find_leaf(address).is_some_and(|(table, index, _)| {
+            let table = unsafe { self.deref_page_table(table) };
+            table[index].flags().custom::<SHARED_BIT>()
+        })
+    }
+
+    /// Whether the page at `address` can only be written by copying it first.
+    fn is_shared_until_copied(&self, address: VirtualAddress) -> bool {
+        self.find_leaf(address).is_some_and(|(table, index, _)| {
+            let flags = unsafe { self.deref_page_table(table) }[index].flags();
+            flags.custom::<SHARED_BIT>() && !flags.custom::<SHARED_MEMORY_BIT>()
+        })
+    }
+
+    /// Whether none of the `size` bytes from `start` are mapped.
+    pub fn is_unmapped(&self, start: VirtualAddress, size: usize) -> bool {
+        self.navigate(start, size, None, |ctx| {
+            if ctx.points_to_backing && ctx.entry.flags().present() {
+                return Err(());
+            }
+
+            Ok(())
+        })
+        .is_ok()
+    }
+
+    /// Find the entry that maps `address` to a frame, as the physical address of its table, its
+    /// index within the table and the size of the page. Borrowed entries are not returned.
+    fn find_leaf(&self, address: VirtualAddress) -> Option<(PhysicalAddress, usize, usize)> {
+        let mut table = self.l4_table;
+        let mut size = Self::PAGE_SIZE * TABLE_ENTRIES.pow(3);
+
+        for (depth, index) in address.indices().into_iter().enumerate() {
+            let entry = unsafe { self.deref_page_table(table) }[index as usize];
+
+            if !entry.flags().present() || entry.flags().custom::<BORROW_BIT>() {
+                return None;
+            }
+
+            if depth == 3 || entry.flags().huge() {
+                return Some((table, index as usize, size));
+            }
+
+            table = entry.addr();
+            size /= TABLE_ENTRIES;
+        }
+
+        None
+    }
 
     pub fn unmap_all_owned(&mut self) {
         if self.root {
@@ -147,6 +410,49 @@ impl MemoryMapper {
         unsafe { self.map_inner(address, size, properties, None) }
     }
 
+    /// Map the page at `address` to `frame`, which is a frame of a shared memory object.
+    ///
+    /// The page holds a reference to the frame until it is unmapped. Unlike the pages that are
+    /// shared by [`Self::new_inherited_from_shared`], the page is never copied: writes are seen by
+    /// every mapper that maps the frame.
+    ///
+    /// # Panics
+    ///
+    /// When `address` is not page aligned.
+    pub fn map_shared(
+        &mut self,
+        address: VirtualAddress,
+        frame: PhysicalAddress,
+        properties: MemoryProperties,
+    ) -> Result<(), NewMapError> {
+        assert!(address.is_aligned_with(Self::PAGE_SIZE));
+
+        if self.mapping_info(address).is_ok() {
+            return Err(NewMapError::AlreadyMapped);
+        }
+
+        // Safety: the frame is allocated by `FRAME_ALLOC`, and the reference that is taken below
+        // keeps it allocated for as long as it is mapped.
+        unsafe { self.map_inner(address, Self::PAGE_SIZE, properties, Some(frame)) }?;
+
+        FRAME_ALLOC.share(frame);
+
+        let (table, index, _) = self
+            .find_leaf(address)
+            .expect("the page should have just been mapped");
+
+        // Safety: `find_leaf` only returns tables that are owned by `self`.
+        let entry = unsafe { &mut self.deref_page_table_mut(table)[index] };
+        entry.set_flags(
+            entry
+                .flags()
+                .set_custom::<SHARED_BIT>(true)
+                .set_custom::<SHARED_MEMORY_BIT>(true),
+        );
+
+        Ok(())
+    }
+
     /// Unmap a region of memory.
     ///
     /// When a operation is stopped due to an error, the original mappings will not be restored.
@@ -165,11 +471,16 @@ impl MemoryMapper {
     ///
     /// * `Err(ModifyMapError::OutOfBounds)` The requested size is smaller than can be possibly
     /// deallocated due to the page structure. Likely because the size is not aligned to the page
-    /// size, or there is a huge page within the region.
+    /// size.
     /// * `Err(ModifyMapError::NotOwned)` The region of memory contains pages that are not owned by
     /// the current MemoryMapper.
     /// * `Err(ModifyMapError::NotMapped)` The region of memory contains pages that are not mapped.
+    /// * `Err(ModifyMapError::OutOfFrames)` A huge page that is partially within the region could
+    /// not be split.
     /// * `Ok(())` The operation was successfull.
+    ///
+    /// Huge pages that are only partially within the region are split into smaller pages, and only
+    /// the pages within the region are unmapped.
     pub fn unmap(&mut self, address: VirtualAddress, size: usize) -> Result<(), ModifyMapError> {
         self.unmap_inner(address, size, false, true)
     }
@@ -181,11 +492,10 @@ impl MemoryMapper {
         lax: bool,
         fail_on_out_of_bounds: bool,
     ) -> Result<(), ModifyMapError> {
-        let mut total_size = 0;
+        let end = address + size;
+        let global_offset = self.global_offset;
 
-        let dealloc = |ctx: NavigateCtx,
-                       huge_size: Option<PageSize>|
-         -> Result<Option<PageTableEntry>, ModifyMapError> {
+        let dealloc = |ctx: NavigateCtx, _| -> Result<Option<PageTableEntry>, ModifyMapError> {
             let mut entry = ctx.entry;
 
             if (!ctx.points_to_backing && !ctx.is_empty)
@@ -195,20 +505,28 @@ impl MemoryMapper {
                 return Ok(None);
             }
 
-            let request_size = huge_size.unwrap_or(PageSize::Size4Kib);
+            if ctx.points_to_backing && entry.flags().huge() {
+                let page_start = ctx.addr.align_down(ctx.size);
 
-            if request_size.as_usize() > size && fail_on_out_of_bounds {
+                // The rest of the huge page stays mapped, the part within the region is unmapped
+                // after descending into the new table.
+                if page_start < address || page_start + ctx.size > end {
+                    return unsafe { Self::split_huge_page(global_offset, entry, ctx.size) }
+                        .map(Some);
+                }
+            } else if ctx.points_to_backing && ctx.size > size && fail_on_out_of_bounds {
                 return Err(ModifyMapError::OutOfBounds);
             }
 
-            if entry.flags().custom::<ALLOCATED_BIT>() {
+            if entry.flags().custom::<SHARED_BIT>() {
+                // Safety: shared pages hold a reference to their frame.
+                unsafe { FRAME_ALLOC.release(entry.addr()) };
+            } else if entry.flags().custom::<ALLOCATED_BIT>() {
                 // Safety:
                 // Owned pages *should* be using allocated using `FRAME_ALLOC`.
                 unsafe { FRAME_ALLOC.deallocate(entry.addr()) };
             }
 
-            total_size += request_size.as_usize();
-
             entry.set_flags(PageTableEntryFlags::null());
             entry.set_addr(PhysicalAddress::null());
 
@@ -220,6 +538,229 @@ impl MemoryMapper {
         Ok(())
     }
 
+    /// Change the properties of a mapped region of memory.
+    ///
+    /// Huge pages that are only partially within the region are split into smaller pages first.
+    /// Shared pages stay read-only when `properties` are writable, and become writable once they
+    /// are copied by [`Self::copy_on_write`]. Pages of shared memory objects are never copied, so
+    /// they become writable right away.
+    ///
+    /// # Returns a `Result` where:
+    ///
+    /// * `Err(ModifyMapError::NotOwned)` The region of memory contains pages that are not owned by
+    /// the current MemoryMapper.
+    /// * `Err(ModifyMapError::NotMapped)` The region of memory contains pages that are not mapped.
+    /// * `Err(ModifyMapError::OutOfFrames)` A huge page that is partially within the region could
+    /// not be split.
+    /// * `Ok(())` The operation was successfull.
+    pub fn protect(
+        &mut self,
+        address: VirtualAddress,
+        size: usize,
+        properties: MemoryProperties,
+    ) -> Result<(), ModifyMapError> {
+        let flags = Self::props_to_flags(properties, true).set_present(true);
+        let end = address + size;
+        let global_offset = self.global_offset;
+
+        let change = |ctx: NavigateCtx, _| -> Result<Option<PageTableEntry>, ModifyMapError> {
+            let entry = ctx.entry;
+            let old_flags = entry.flags();
+
+            if !old_flags.present() {
+                return Ok(None);
+            }
+
+            // A table has to allow everything that the pages within it allow.
+            if !ctx.points_to_backing {
+                let table_flags = (old_flags | flags).set_no_cache(false).set_no_exec(false);
+                return Ok(Some(PageTableEntry::new(table_flags, entry.addr())));
+            }
+
+            let page_start = ctx.addr.align_down(ctx.size);
+
+            if old_flags.huge() && (page_start < address || page_start + ctx.size > end) {
+                return unsafe { Self::split_huge_page(global_offset, entry, ctx.size) }.map(Some);
+            }
+
+            let shared = old_flags.custom::<SHARED_BIT>();
+            let shared_memory = old_flags.custom::<SHARED_MEMORY_BIT>();
+            let copy_on_write = shared && !shared_memory;
+
+            let new_flags = flags
+                .set_huge(old_flags.huge())
+                .set_writable(properties.writable() && !copy_on_write)
+                .set_custom::<ALLOCATED_BIT>(old_flags.custom::<ALLOCATED_BIT>())
+                .set_custom::<SHARED_BIT>(shared)
+                .set_custom::<COPY_ON_WRITE_BIT>(properties.writable() && copy_on_write)
+                .set_custom::<SHARED_MEMORY_BIT>(shared_memory);
+
+            Ok(Some(PageTableEntry::new(new_flags, entry.addr())))
+        };
+
+        unsafe { self.navigate_mut(address, size, None, true, true, false, change) }?;
+
+        Ok(())
+    }
+
+    /// Replace a huge page of `size` bytes with a table of pages of the next smaller size, that
+    /// map the same memory with the same flags. Returns the entry that points to the new table.
+    ///
+    /// # Safety
+    ///
+    /// `entry` must be a huge page of `size` bytes that is not borrowed.
+    unsafe fn split_huge_page(
+        global_offset: usize,
+        entry: PageTableEntry,
+        size: usize,
+    ) -> Result<PageTableEntry, ModifyMapError> {
+        let mut flags = entry.flags();
+        let mut frame = entry.addr();
+
+        let (table, _) = FRAME_ALLOC
+            .allocate(Self::PAGE_SIZE)
+            .ok_or(ModifyMapError::OutOfFrames)?;
+
+        // The frame cannot be split for only one of its owners, so this mapper gets its own copy.
+        if flags.custom::<SHARED_BIT>() && FRAME_ALLOC.ref_count(frame) > 1 {
+            let Some(copy) = Self::allocate_huge_frame(size) else {
+                FRAME_ALLOC.deallocate(table);
+                return Err(ModifyMapError::OutOfFrames);
+            };
+
+            core::ptr::copy_nonoverlapping(
+                (frame.as_usize() + global_offset) as *const u8,
+                (copy.as_usize() + global_offset) as *mut u8,
+                size,
+            );
+
+            FRAME_ALLOC.release(frame);
+            frame = copy;
+
+            flags = flags
+                .set_writable(flags.writable() || flags.custom::<COPY_ON_WRITE_BIT>())
+                .set_custom::<SHARED_BIT>(false)
+                .set_custom::<COPY_ON_WRITE_BIT>(false);
+        }
+
+        let page_size = size / TABLE_ENTRIES;
+        let page_flags = flags.set_huge(page_size > Self::PAGE_SIZE);
+        let pages = &mut *((table.as_usize() + global_offset) as *mut PageTable);
+
+        for (index, page) in pages.iter_mut().enumerate() {
+            *page = PageTableEntry::new(page_flags, frame + index * page_size);
+        }
+
+        if flags.custom::<ALLOCATED_BIT>() {
+            FRAME_ALLOC.split(frame, page_size);
+        }
+
+        let table_flags = PageTableEntryFlags::null()
+            .set_present(true)
+            .set_writable(true)
+            .set_user_accessible(flags.user_accessible())
+            .set_custom::<ALLOCATED_BIT>(true);
+
+        Ok(PageTableEntry::new(table_flags, table))
+    }
+
+    /// Allocate a frame for a huge page, which has to be aligned with the size of the page.
+    fn allocate_huge_frame(size: usize) -> Option<PhysicalAddress> {
+        let (frame, _) = FRAME_ALLOC.allocate(size)?;
+
+        if !frame.is_aligned_with(size) {
+            // Safety: the frame was just allocated, and is not used.
+            unsafe { FRAME_ALLOC.deallocate(frame) };
+            return None;
+        }
+
+        Some(frame)
+    }
+
+    /// Read mapped memory into `buf`, the mapper does not have to be active.
+    pub fn read(&self, address: VirtualAddress, buf: &mut [u8]) -> Result<(), ReadMapError> {
+        self.for_each_backing(address, buf.len(), |_, backing, done| {
+            // Safety: `backing` points to `len` bytes of mapped memory.
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    backing.as_ptr(),
+                    buf[done..].as_mut_ptr(),
+                    backing.len(),
+                )
+            };
+
+            Ok(())
+        })
+    }
+
+    /// Write `data` to mapped user memory, the mapper does not have to be active.
+    ///
+    /// Just like [`Self::unmap`] this is safe because the kernel should never hold refrences to
+    /// memory created from [`Self::map`]. Pages that are shared until they are copied are not
+    /// owned, so they cannot be written. Pages of shared memory objects can be.
+    pub fn write(&mut self, address: VirtualAddress, data: &[u8]) -> Result<(), ModifyMapError> {
+        self.for_each_backing(address, data.len(), |props, backing, done| {
+            if props.kernel() || self.is_shared_until_copied(address + done) {
+                return Err(ModifyMapError::NotOwned);
+            }
+
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    data[done..].as_ptr(),
+                    backing.as_mut_ptr(),
+                    backing.len(),
+                )
+            };
+
+            Ok(())
+        })
+    }
+
+    /// Set `len` bytes of mapped user memory to zero, the mapper does not have to be active.
+    pub fn zero(&mut self, address: VirtualAddress, len: usize) -> Result<(), ModifyMapError> {
+        self.for_each_backing(address, len, |props, backing, done| {
+            if props.kernel() || self.is_shared_until_copied(address + done) {
+                return Err(ModifyMapError::NotOwned);
+            }
+
+            unsafe { core::ptr::write_bytes(backing.as_mut_ptr(), 0, backing.len()) };
+
+            Ok(())
+        })
+    }
+
+    /// Call `apply` for every page within the region with: the properties of the page, the part of
+    /// the page that is within the region (through the global offset), and the amount of bytes
+    /// that came before it.
+    fn for_each_backing<E: From<ReadMapError>>(
+        &self,
+        address: VirtualAddress,
+        len: usize,
+        mut apply: impl FnMut(MemoryProperties, &mut [u8], usize) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut done = 0;
+
+        while done < len {
+            let current = address + done;
+            let (props, phys, page_size) = self.mapping_info(current)?;
+
+            let page_offset = (current - current.align_down(page_size)).as_usize();
+            let chunk_len = (page_size - page_offset).min(len - done);
+
+            let backing = unsafe {
+                core::slice::from_raw_parts_mut(
+                    (phys.as_usize() + page_offset + self.global_offset) as *mut u8,
+                    chunk_len,
+                )
+            };
+
+            apply(props, backing, done)?;
+            done += chunk_len;
+        }
+
+        Ok(())
+    }
+
     unsafe fn map_inner(
         &mut self,
         address: VirtualAddress,
@@ -230,6 +771,8 @@ impl MemoryMapper {
         let flags = Self::props_to_flags(properties, true);
 
         let mut total_size = 0;
+        let global_offset = self.global_offset;
+
         let alloc_missing = |ctx: NavigateCtx,
                              huge_size: Option<PageSize>|
          -> Result<Option<PageTableEntry>, NewMapError> {
@@ -238,8 +781,6 @@ impl MemoryMapper {
                 .set_no_cache(false)
                 .set_no_exec(false);
 
-            let request_size = huge_size.unwrap_or(PageSize::Size4Kib);
-
             if ctx.points_to_backing && entry.flags().present() {
                 let unchanged = phys.map(|phys| phys == entry.addr()).unwrap_or_default();
 
@@ -249,17 +790,53 @@ impl MemoryMapper {
             }
 
             if !entry.flags().present() {
-                if huge_size.is_some() {
-                    flags = flags.set_huge(true);
+                // A huge page has to be aligned with its size in both virtual and physical memory,
+                // otherwise the entry becomes a table of smaller pages.
+                let huge_size = huge_size.filter(|size| {
+                    ctx.addr.is_aligned_with(size.as_usize())
+                        && phys.is_none_or(|phys| phys.is_aligned_with(size.as_usize()))
+                });
+
+                let mut backing = huge_size.and_then(|size| match phys {
+                    Some(phys) => Some((phys, size)),
+                    None => {
+                        let frame = Self::allocate_huge_frame(size.as_usize())?;
+
+                        unsafe {
+                            core::ptr::write_bytes(
+                                (frame.as_usize() + global_offset) as *mut u8,
+                                0,
+                                size.as_usize(),
+                            )
+                        };
+
+                        Some((frame, size))
+                    }
+                });
+
+                if backing.is_none() && ctx.points_to_backing {
+                    let frame = match phys {
+                        Some(phys) => phys,
+                        None => {
+                            FRAME_ALLOC
+                                .allocate_zeroed(Self::PAGE_SIZE)
+                                .ok_or(NewMapError::OutOfFrames)?
+                                .0
+                        }
+                    };
+
+                    backing = Some((frame, PageSize::Size4Kib));
                 }
 
-                let mut frame_addr = None;
+                let frame_addr = match backing {
+                    Some((frame_addr, size)) => {
+                        flags = flags
+                            .set_huge(size != PageSize::Size4Kib)
+                            .set_custom::<ALLOCATED_BIT>(phys.is_none());
 
-                if ctx.points_to_backing {
-                    if let Some(phys) = phys.as_mut() {
-                        frame_addr = Some(*phys);
-                        *phys += request_size.as_usize();
-                        total_size += request_size.as_usize();
+                        if let Some(phys) = phys.as_mut() {
+                            *phys += size.as_usize();
+                        }
 
                         if properties.mmio() {
                             flags = flags.set_no_cache(true);
@@ -268,14 +845,13 @@ impl MemoryMapper {
                         if !properties.executable() {
                             flags = flags.set_no_exec(true);
                         }
-                    }
-                }
 
-                let frame_addr = match frame_addr {
-                    Some(a) => a,
+                        total_size += size.as_usize();
+                        frame_addr
+                    }
                     None => {
                         let (frame_addr, _) = FRAME_ALLOC
-                            .allocate_zeroed(request_size.as_usize())
+                            .allocate_zeroed(Self::PAGE_SIZE)
                             .ok_or(NewMapError::OutOfFrames)?;
 
                         flags = flags.set_custom::<ALLOCATED_BIT>(true);
@@ -499,6 +1075,8 @@ impl MemoryMapper {
         let mut table_stack = FixedVec::<4, (&mut PageTable, PhysicalAddress)>::new();
         table_stack.push((self.deref_l4_table_mut(), self.l4_table));
 
+        let is_active = cr3::active_page() == self.l4_table;
+
         let mut last_entry_index = -1;
         let mut revisit = false;
 
@@ -522,7 +1100,7 @@ impl MemoryMapper {
                 }
             });
 
-            let Some((table, table_phys_addr)) = table_stack.last_mut() else {
+            let Some((table, _)) = table_stack.last_mut() else {
                 break;
             };
 
@@ -588,20 +1166,23 @@ impl MemoryMapper {
 
                     // the entry has been modified in such a way that the tlb needs to be
                     // invalidated
-                    if entry.addr() != new_entry.addr()
-                        || !entry.flags().native_flags_eq(new_entry.flags())
+                    if is_active
+                        && (entry.addr() != new_entry.addr()
+                            || !entry.flags().native_flags_eq(new_entry.flags()))
                     {
-                        cr3::flush_page(*table_phys_addr);
+                        cr3::flush_virtual_page(current_addr);
                     }
                 }
             } else if fail_on_unowned {
                 return Err(ModifyMapError::NotOwned.into());
             }
 
+            // Borrowed tables belong to another mapper, so they are never modified.
             let entry = *entry_ref;
             if entry.flags().present()
                 && table_stack.len() <= max_depth
                 && !entry.flags().huge()
+                && !entry.flags().custom::<BORROW_BIT>()
                 && !revisit
             {
                 let table = self.deref_page_table_mut(entry.addr());
@@ -677,5 +1258,84 @@ impl MemoryMapper {
 impl Drop for MemoryMapper {
     fn drop(&mut self) {
         self.unmap_all_owned();
+
+        if self.owns_l4_table {
+            // Safety: the table was allocated in `new_user_mapper`, and nothing refers to it now
+            // that all owned memory is unmapped.
+            unsafe { FRAME_ALLOC.deallocate(self.l4_table) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USER_DATA: MemoryProperties = MemoryProperties::new(true, true, false, false, false);
+    const HUGE_PAGE: usize = MemoryMapper::PAGE_SIZE * TABLE_ENTRIES;
+
+    #[test_case]
+    fn test_huge_page_is_split_on_partial_unmap() {
+        let available = FRAME_ALLOC.available();
+        let mut mapper =
+            MemoryMapper::new_user_mapper(FRAME_ALLOC.physical_memory_offset()).unwrap();
+        let addr = VirtualAddress::new(MemoryMapper::USER_SPACE_START);
+
+        assert_eq!(HUGE_PAGE, mapper.map(addr, HUGE_PAGE, USER_DATA).unwrap().1);
+        mapper.write(addr + 0x2000, &[42]).unwrap();
+
+        mapper.unmap(addr, MemoryMapper::PAGE_SIZE).unwrap();
+
+        assert!(mapper.mapping_info(addr).is_err());
+        let (_, _, size) = mapper.mapping_info(addr + 0x2000).unwrap();
+        assert_eq!(MemoryMapper::PAGE_SIZE, size);
+
+        let mut buf = [0];
+        mapper.read(addr + 0x2000, &mut buf).unwrap();
+        assert_eq!([42], buf);
+
+        drop(mapper);
+        assert_eq!(available, FRAME_ALLOC.available());
+    }
+
+    #[test_case]
+    fn test_protect_part_of_huge_page() {
+        let mut mapper =
+            MemoryMapper::new_user_mapper(FRAME_ALLOC.physical_memory_offset()).unwrap();
+        let addr = VirtualAddress::new(MemoryMapper::USER_SPACE_START);
+        let read_only = MemoryProperties::new(false, true, false, false, false);
+
+        mapper.map(addr, HUGE_PAGE, USER_DATA).unwrap();
+        mapper
+            .protect(
+                addr + MemoryMapper::PAGE_SIZE,
+                MemoryMapper::PAGE_SIZE,
+                read_only,
+            )
+            .unwrap();
+
+        let (props, _, _) = mapper.mapping_info(addr).unwrap();
+        assert!(props.writable());
+
+        let (props, _, size) = mapper.mapping_info(addr + MemoryMapper::PAGE_SIZE).unwrap();
+        assert!(!props.writable());
+        assert_eq!(MemoryMapper::PAGE_SIZE, size);
+
+        assert!(matches!(
+            mapper.protect(addr + HUGE_PAGE, MemoryMapper::PAGE_SIZE, read_only),
+            Err(ModifyMapError::NotMapped)
+        ));
+    }
+
+    #[test_case]
+    fn test_unaligned_map_uses_small_pages() {
+        let mut mapper =
+            MemoryMapper::new_user_mapper(FRAME_ALLOC.physical_memory_offset()).unwrap();
+        let addr = VirtualAddress::new(MemoryMapper::USER_SPACE_START + MemoryMapper::PAGE_SIZE);
+
+        mapper.map(addr, HUGE_PAGE, USER_DATA).unwrap();
+
+        let (_, _, size) = mapper.mapping_info(addr + HUGE_PAGE / 2).unwrap();
+        assert_eq!(MemoryMapper::PAGE_SIZE, size);
     }
 }
@@ -16,6 +16,7 @@ pub enum ModifyMapError {
     NotOwned,
     NotMapped,
     OutOfBounds,
+    OutOfFrames,
 }
 
 impl From<ModifyMapError> for NewMapError {
@@ -24,6 +25,7 @@ impl From<ModifyMapError> for NewMapError {
             ModifyMapError::NotOwned => NewMapError::NotOwned,
             ModifyMapError::NotMapped => unreachable!(),
             ModifyMapError::OutOfBounds => unreachable!(),
+            ModifyMapError::OutOfFrames => NewMapError::OutOfFrames,
         }
     }
 }
@@ -32,3 +34,11 @@ impl From<ModifyMapError> for NewMapError {
 pub enum ReadMapError {
     NotMapped,
 }
+
+impl From<ReadMapError> for ModifyMapError {
+    fn from(value: ReadMapError) -> Self {
+        match value {
+            ReadMapError::NotMapped => ModifyMapError::NotMapped,
+        }
+    }
+}
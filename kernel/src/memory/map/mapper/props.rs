@@ -50,4 +50,15 @@ impl MemoryProperties {
     pub const fn mmio(&self) -> bool {
         self.mmio
     }
+
+    /// The properties of memory that allows every access that either `self` or `other` allows.
+    pub const fn union(&self, other: &Self) -> Self {
+        Self {
+            writable: self.writable || other.writable,
+            readable: self.readable || other.readable,
+            kernel: self.kernel && other.kernel,
+            executable: self.executable || other.executable,
+            mmio: self.mmio || other.mmio,
+        }
+    }
 }
@@ -120,7 +120,11 @@ impl<'a> Display for MemoryMapTreeDisplay<'a> {
                 ctx.is_last_present_entry,
             )?;
 
-            let backing_indicator = if ctx.points_to_backing { "*" } else { "" };
+            let backing_indicator = match (ctx.points_to_backing, entry.flags().huge()) {
+                (true, true) => " huge*",
+                (true, false) => "*",
+                _ => "",
+            };
             let size = ctx.size;
 
             writeln!(
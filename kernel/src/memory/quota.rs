@@ -0,0 +1,93 @@
+//! The amount of memory that a process may use.
+//!
+//! Every frame that is allocated for a process is charged to its [`MemoryQuota`]: the pages that
+//! its [`MemoryManager`](super::map::MemoryManager) maps, and the
+//! [`SharedMemory`](super::shared::SharedMemory) that it creates. Shared memory stays charged to
+//! the process that created it until the memory is dropped, no matter who maps it.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// The memory would exceed the limit of the quota.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuotaExceeded;
+
+/// The bytes of memory that are charged to a process, and how many it may use.
+#[derive(Debug)]
+pub struct MemoryQuota {
+    /// `None` when the memory is unlimited.
+    limit: Option<u64>,
+    used: AtomicU64,
+}
+
+impl MemoryQuota {
+    pub const fn new(limit: Option<u64>) -> Self {
+        Self {
+            limit,
+            used: AtomicU64::new(0),
+        }
+    }
+
+    pub const fn unlimited() -> Self {
+        Self::new(None)
+    }
+
+    pub fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    /// The bytes that are charged right now.
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Charge `bytes`, unless that would use more than the limit.
+    pub fn charge(&self, bytes: u64) -> Result<(), QuotaExceeded> {
+        let mut used = self.used.load(Ordering::Relaxed);
+
+        loop {
+            let charged = used
+                .checked_add(bytes)
+                .filter(|charged| self.limit.is_none_or(|limit| *charged <= limit))
+                .ok_or(QuotaExceeded)?;
+
+            match self.used.compare_exchange_weak(
+                used,
+                charged,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Ok(()),
+                Err(current) => used = current,
+            }
+        }
+    }
+
+    /// Give back `bytes` that were charged before.
+    pub fn refund(&self, bytes: u64) {
+        let previous = self.used.fetch_sub(bytes, Ordering::Relaxed);
+        debug_assert!(previous >= bytes, "more memory was refunded than charged");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_charge_and_refund() {
+        let quota = MemoryQuota::new(Some(8192));
+
+        assert_eq!(Ok(()), quota.charge(4096));
+        assert_eq!(Ok(()), quota.charge(4096));
+        assert_eq!(Err(QuotaExceeded), quota.charge(1));
+        assert_eq!(8192, quota.used());
+
+        quota.refund(4096);
+        assert_eq!(Ok(()), quota.charge(4096));
+
+        // Even an unlimited quota cannot count more than fits.
+        let unlimited = MemoryQuota::unlimited();
+        assert_eq!(Ok(()), unlimited.charge(u64::MAX));
+        assert_eq!(Err(QuotaExceeded), unlimited.charge(1));
+    }
+}
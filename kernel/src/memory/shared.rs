@@ -0,0 +1,142 @@
+//! Memory that is mapped into the address space of more than one process.
+//!
+//! A [`SharedMemory`] object owns the frames that back it, and is mapped through an area of kind
+//! [`AreaKind::Shared`](super::map::AreaKind::Shared). Every page that is mapped holds its own
+//! reference to the frame, so the frames are only deallocated once the object is dropped and the
+//! last mapping of it is gone.
+//!
+//! The memory that a process creates is charged to its [`MemoryQuota`] until it is dropped.
+
+mod error;
+
+use alloc::{sync::Arc, vec::Vec};
+use essentials::address::{PhysicalAddress, VirtualAddress};
+
+use crate::memory::{alloc::FRAME_ALLOC, map::MemoryMapper, quota::MemoryQuota};
+
+pub use error::SharedMemoryError;
+
+/// Zero filled memory of a fixed size, that is shared by every address space that maps it.
+///
+/// Writes through one mapping are seen by every other mapping, the pages are never copied on
+/// write.
+#[derive(Debug)]
+pub struct SharedMemory {
+    size: usize,
+    frames: Vec<PhysicalAddress>,
+    /// The quota that the size is charged to.
+    quota: Option<Arc<MemoryQuota>>,
+}
+
+impl SharedMemory {
+    /// Allocate the frames for `size` bytes, the size is rounded up to whole pages.
+    pub fn new(size: usize) -> Result<Self, SharedMemoryError> {
+        Self::allocate(size, None)
+    }
+
+    /// Like [`Self::new`], but the memory is charged to `quota` until it is dropped.
+    pub fn new_charged(size: usize, quota: Arc<MemoryQuota>) -> Result<Self, SharedMemoryError> {
+        Self::allocate(size, Some(quota))
+    }
+
+    fn allocate(size: usize, quota: Option<Arc<MemoryQuota>>) -> Result<Self, SharedMemoryError> {
+        if size == 0 || size > MemoryMapper::USER_SPACE_END - MemoryMapper::USER_SPACE_START {
+            return Err(SharedMemoryError::InvalidSize);
+        }
+
+        let size = VirtualAddress::align_ptr_up(size, MemoryMapper::PAGE_SIZE);
+        let pages = size / MemoryMapper::PAGE_SIZE;
+
+        if let Some(quota) = &quota {
+            quota
+                .charge(size as u64)
+                .map_err(|_| SharedMemoryError::QuotaExceeded)?;
+        }
+
+        let mut memory = Self {
+            size,
+            frames: Vec::new(),
+            quota,
+        };
+
+        memory
+            .frames
+            .try_reserve_exact(pages)
+            .map_err(|_| SharedMemoryError::OutOfFrames)?;
+
+        // The frames that were allocated already are released, and the quota is refunded, when
+        // `memory` is dropped.
+        for _ in 0..pages {
+            let (frame, _) = FRAME_ALLOC
+                .allocate_zeroed(MemoryMapper::PAGE_SIZE)
+                .ok_or(SharedMemoryError::OutOfFrames)?;
+
+            memory.frames.push(frame);
+        }
+
+        Ok(memory)
+    }
+
+    /// The size in bytes, which is a multiple of the page size.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The frame that backs the page at `offset` bytes into the memory.
+    pub fn frame(&self, offset: usize) -> Option<PhysicalAddress> {
+        self.frames.get(offset / MemoryMapper::PAGE_SIZE).copied()
+    }
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        for frame in self.frames.drain(..) {
+            // Safety: the frame was allocated in `new`, pages that still map it hold their own
+            // reference.
+            unsafe { FRAME_ALLOC.release(frame) };
+        }
+
+        if let Some(quota) = &self.quota {
+            quota.refund(self.size as u64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_new_shared_memory() {
+        let available = FRAME_ALLOC.available();
+
+        assert_eq!(
+            Some(SharedMemoryError::InvalidSize),
+            SharedMemory::new(0).err()
+        );
+
+        let memory = SharedMemory::new(MemoryMapper::PAGE_SIZE + 1).unwrap();
+        assert_eq!(2 * MemoryMapper::PAGE_SIZE, memory.size());
+        assert!(memory.frame(MemoryMapper::PAGE_SIZE).is_some());
+        assert!(memory.frame(2 * MemoryMapper::PAGE_SIZE).is_none());
+
+        drop(memory);
+        assert_eq!(available, FRAME_ALLOC.available());
+    }
+
+    #[test_case]
+    fn test_charged_shared_memory() {
+        let quota = Arc::new(MemoryQuota::new(Some(2 * MemoryMapper::PAGE_SIZE as u64)));
+
+        let memory = SharedMemory::new_charged(MemoryMapper::PAGE_SIZE + 1, quota.clone()).unwrap();
+        assert_eq!(2 * MemoryMapper::PAGE_SIZE as u64, quota.used());
+        assert_eq!(
+            Some(SharedMemoryError::QuotaExceeded),
+            SharedMemory::new_charged(1, quota.clone()).err()
+        );
+
+        drop(memory);
+        assert_eq!(0, quota.used());
+        assert!(SharedMemory::new_charged(1, quota).is_ok());
+    }
+}
@@ -0,0 +1,8 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharedMemoryError {
+    /// The size is zero, or too large to ever be mapped.
+    InvalidSize,
+    OutOfFrames,
+    /// The memory would use more than the quota of the process allows.
+    QuotaExceeded,
+}
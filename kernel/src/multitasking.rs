@@ -1,6 +1,8 @@
+pub mod exec;
 pub mod ids;
 pub mod process;
 pub mod scheduler;
+pub mod sync;
 
 pub use process::process_table::PROCESS_TABLE;
 pub use scheduler::SCHEDULER;
@@ -0,0 +1,697 @@
+//! Creating user processes from ELF executables.
+//!
+//! Only statically linked executables are supported, either at a fixed address (`ET_EXEC`) or
+//! position independent (`ET_DYN`). Position independent executables are loaded at [`PIE_BASE`],
+//! and their `R_X86_64_RELATIVE` relocations are applied by the loader.
+
+mod error;
+#[cfg(test)]
+pub(crate) mod test_utils;
+
+use alloc::{sync::Arc, vec, vec::Vec};
+use elf::{
+    Arch, ArchHeaderReader, ElfReader, Endianness, ObjectKind, ProgramHeaderReader64,
+    RelocationEntryKind, SectionKind,
+};
+use essentials::address::VirtualAddress;
+use path::Path;
+
+use crate::{
+    arch,
+    fs::{FileKind, FsError, OverLay, VFS},
+    memory::{
+        alloc::FRAME_ALLOC,
+        map::{AreaKind, MemoryArea, MemoryManager, MemoryMapper, MemoryProperties},
+        quota::MemoryQuota,
+    },
+    multitasking::{
+        ids::ThreadId,
+        process::{Permissions, Process, ProcessId},
+        scheduler::LOWEST_PRIORITY,
+        PROCESS_TABLE, SCHEDULER,
+    },
+};
+
+pub use error::ExecError;
+
+/// The address at which position independent executables are loaded.
+pub const PIE_BASE: usize = MemoryMapper::USER_SPACE_START;
+
+/// The size of the stack of the main thread, it is placed at the end of the user space.
+///
+/// The stack is only backed by frames once it is used, and its lowest page is a guard page.
+pub const USER_STACK_SIZE: usize = 8 * 1024 * 1024;
+const USER_STACK_TOP: usize = MemoryMapper::USER_SPACE_END;
+const USER_STACK_BOTTOM: usize = USER_STACK_TOP - USER_STACK_SIZE;
+
+/// The largest executable that [`exec_path`] reads, the whole file is kept in the kernel heap
+/// while it is loaded.
+pub const MAX_IMAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// The size of a single `Elf64_Phdr`.
+const PROGRAM_HEADER_SIZE: u64 = 56;
+
+// Auxiliary vector entry types, as defined by the System V ABI.
+const AT_NULL: u64 = 0;
+const AT_PHDR: u64 = 3;
+const AT_PHENT: u64 = 4;
+const AT_PHNUM: u64 = 5;
+const AT_PAGESZ: u64 = 6;
+const AT_BASE: u64 = 7;
+const AT_ENTRY: u64 = 9;
+
+/// An executable loaded into a new address space, ready to be started.
+pub struct LoadedImage {
+    pub manager: MemoryManager,
+    pub entry: VirtualAddress,
+    /// The initial stack pointer, which points to `argc`.
+    pub stack_pointer: VirtualAddress,
+}
+
+/// Create a new process from an executable image and spawn its main thread.
+///
+/// The main thread starts at the entry point, with `args`, `env` and the auxiliary vector on its
+/// stack as described by the System V ABI. The process becomes a child of `parent`, and its
+/// `permissions` should be created with [`Permissions::inherit`] from those of the parent, its
+/// memory is limited to what they allow. The
+/// handles of the parent that can be transferred are inherited, except those that are closed on
+/// exec.
+pub fn exec(
+    image: &[u8],
+    args: &[&str],
+    env: &[&str],
+    parent: Option<ProcessId>,
+    overlay: Arc<OverLay>,
+    permissions: Permissions,
+) -> Result<(ProcessId, ThreadId), ExecError> {
+    let quota = Arc::new(MemoryQuota::new(permissions.memory_limit()));
+    let loaded = load(image, args, env, quota)?;
+
+    let context = arch::new_user_context(loaded.entry, loaded.stack_pointer);
+
+    let handles = parent
+        .and_then(|parent| PROCESS_TABLE.get(parent))
+        .map(|parent| parent.handles().inherit())
+        .unwrap_or_default();
+
+    let process_id = PROCESS_TABLE.alloc_process_id();
+    let process = PROCESS_TABLE.insert(
+        Process::new(process_id, loaded.manager, overlay, permissions).with_handles(handles),
+        parent,
+    )?;
+
+    let thread_id = SCHEDULER
+        .spawn_thread(LOWEST_PRIORITY, Some(process), context)
+        .inspect_err(|_| {
+            PROCESS_TABLE.remove(process_id);
+        })?;
+
+    Ok((process_id, thread_id))
+}
+
+/// Like [`exec`], but the image is read from `path`, which requires read permission to the file.
+///
+/// The path is opened as `parent` sees it, the child may not be granted access to its own image.
+/// Without a parent it is opened through `overlay` with `permissions`.
+pub fn exec_path(
+    path: &Path,
+    args: &[&str],
+    env: &[&str],
+    parent: Option<ProcessId>,
+    overlay: Arc<OverLay>,
+    permissions: Permissions,
+) -> Result<(ProcessId, ThreadId), ExecError> {
+    let file = match parent.and_then(|parent| PROCESS_TABLE.get(parent)) {
+        Some(parent) => VFS.open(parent.overlay(), parent.permissions(), path, false)?,
+        None => VFS.open(&overlay, &permissions, path, false)?,
+    };
+    let inode = file.lookup()?;
+    let metadata = file.fs.stat(inode)?;
+
+    if metadata.kind != FileKind::File {
+        return Err(FsError::IsADirectory.into());
+    }
+
+    let size = usize::try_from(metadata.size)
+        .ok()
+        .filter(|size| *size <= MAX_IMAGE_SIZE)
+        .ok_or(ExecError::ImageTooLarge)?;
+
+    // The ELF reader requires its structures to be aligned, which a `Vec<u8>` does not guarantee.
+    let mut buffer = Vec::new();
+    buffer
+        .try_reserve_exact(size.div_ceil(8))
+        .map_err(|_| ExecError::OutOfMemory)?;
+    buffer.resize(size.div_ceil(8), 0u64);
+
+    let image = unsafe { core::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, size) };
+
+    // A read may return less than was asked for, the file only ends once nothing is read.
+    let mut read = 0;
+
+    while read < size {
+        match file.fs.read(inode, read as u64, &mut image[read..])? {
+            0 => break,
+            len => read += len,
+        }
+    }
+
+    exec(&image[..read], args, env, parent, overlay, permissions)
+}
+
+/// Load an executable image into a new address space, including the stack of the main thread.
+/// The memory of the image is charged to `quota`.
+pub fn load(
+    image: &[u8],
+    args: &[&str],
+    env: &[&str],
+    quota: Arc<MemoryQuota>,
+) -> Result<LoadedImage, ExecError> {
+    let reader = ElfReader::new(image)?;
+
+    if reader.endianness() != Endianness::Little {
+        return Err(ExecError::UnsupportedArch);
+    }
+
+    let ArchHeaderReader::Bits64(header) = reader.header()? else {
+        return Err(ExecError::UnsupportedArch);
+    };
+
+    if header.arch() != Arch::Archx86_64 {
+        return Err(ExecError::UnsupportedArch);
+    }
+
+    let base = match header.object_kind() {
+        ObjectKind::Executable => 0,
+        ObjectKind::SharedObject => PIE_BASE,
+        _ => return Err(ExecError::NotExecutable),
+    };
+
+    let entry = header.entry_point().ok_or(ExecError::NoEntryPoint)?;
+    let entry = user_address(base, entry, 1)?;
+
+    let mapper = MemoryMapper::new_user_mapper(FRAME_ALLOC.physical_memory_offset())?;
+    let manager = MemoryManager::new(mapper).with_quota(quota);
+
+    // Without a `PT_PHDR` segment, the program headers can still be found in a loaded segment.
+    let program_headers_offset = header
+        .program_headers()?
+        .next()
+        .map(|segment| segment.offset() as u64);
+
+    let mut program_headers_addr = None;
+    let mut program_headers_count = 0;
+
+    for segment in header.program_headers()? {
+        program_headers_count += 1;
+
+        match segment.kind() {
+            SectionKind::Intererp => return Err(ExecError::Interpreter),
+            SectionKind::ProgramHeader => {
+                program_headers_addr = Some(user_address(base, segment.addr(), 1)?);
+            }
+            SectionKind::Load => {
+                load_segment(&manager, base, &segment)?;
+
+                let file_start = segment.data_offset();
+                let file_range = file_start..file_start.saturating_add(segment.file_size());
+
+                match program_headers_offset {
+                    Some(offset)
+                        if program_headers_addr.is_none() && file_range.contains(&offset) =>
+                    {
+                        let addr = segment.addr().wrapping_add(offset - file_start);
+                        program_headers_addr = Some(user_address(base, addr, 1)?);
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if base != 0 {
+        let relocations = header.relocation_table()?.unwrap_or_default();
+        manager.with_mapper(|mapper| apply_relocations(mapper, base, relocations))?;
+    }
+
+    let stack_props = MemoryProperties::new(true, true, false, false, false);
+    let stack = MemoryArea::new(
+        USER_STACK_BOTTOM.into(),
+        USER_STACK_SIZE,
+        stack_props,
+        AreaKind::Stack,
+    );
+    manager.reserve(stack)?;
+
+    let mut auxv = Vec::new();
+
+    if let Some(addr) = program_headers_addr {
+        auxv.push((AT_PHDR, addr.as_u64()));
+    }
+
+    auxv.extend([
+        (AT_PHENT, PROGRAM_HEADER_SIZE),
+        (AT_PHNUM, program_headers_count),
+        (AT_PAGESZ, MemoryMapper::PAGE_SIZE as u64),
+        // There is no interpreter, so there is no base address of one either.
+        (AT_BASE, 0),
+        (AT_ENTRY, entry.as_u64()),
+    ]);
+
+    let (stack_pointer, stack) = build_stack(USER_STACK_TOP, args, env, &auxv)?;
+    manager.write(stack_pointer, &stack)?;
+
+    Ok(LoadedImage {
+        manager,
+        entry,
+        stack_pointer,
+    })
+}
+
+/// Map a `PT_LOAD` segment, copy its contents and zero the rest (the `.bss`).
+fn load_segment(
+    manager: &MemoryManager,
+    base: usize,
+    segment: &ProgramHeaderReader64,
+) -> Result<(), ExecError> {
+    let file_size = segment.file_size() as usize;
+    let memory_size = segment.memory_size() as usize;
+
+    if file_size > memory_size {
+        return Err(ExecError::InvalidSegment);
+    }
+
+    let start = user_address(base, segment.addr(), memory_size)?;
+
+    // x86_64 does not support pages that can be written or executed, but not read.
+    let flags = segment.flags();
+    let props = MemoryProperties::new(flags.writable(), true, false, flags.executable(), false);
+
+    // A page that is shared with a previous segment allows what either segment allows, so a
+    // writable segment that starts on the last page of the code can still be written.
+    manager.map(start, memory_size, props)?;
+
+    manager.with_mapper(|mapper| {
+        mapper.write(start, segment.bytes()?)?;
+
+        // New pages are already zeroed, but a page may be shared with the previous segment.
+        mapper.zero(start + file_size, memory_size - file_size)?;
+
+        Ok(())
+    })
+}
+
+fn apply_relocations(
+    mapper: &mut MemoryMapper,
+    base: usize,
+    relocations: &[elf::RelocationTableEntry<u64>],
+) -> Result<(), ExecError> {
+    for relocation in relocations {
+        if relocation.kind() != RelocationEntryKind::Relative {
+            return Err(ExecError::UnsupportedRelocation);
+        }
+
+        let target = user_address(base, relocation.offset(), size_of::<u64>())?;
+        let value = (base as u64).wrapping_add(relocation.addend());
+
+        mapper.write(target, &value.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Relocate `addr` and make sure that `len` bytes starting there fit below the user stack.
+fn user_address(base: usize, addr: u64, len: usize) -> Result<VirtualAddress, ExecError> {
+    let start = usize::try_from(addr)
+        .ok()
+        .and_then(|addr| addr.checked_add(base))
+        .ok_or(ExecError::InvalidSegment)?;
+
+    let end = start.checked_add(len).ok_or(ExecError::InvalidSegment)?;
+
+    if start < MemoryMapper::USER_SPACE_START || end > USER_STACK_BOTTOM {
+        return Err(ExecError::InvalidSegment);
+    }
+
+    Ok(start.into())
+}
+
+/// Build the initial stack of the main thread, which ends at `stack_top`:
+///
+/// ```txt
+/// stack_top -> | strings of `args` and `env`   |
+///              | padding                       |
+///              | auxiliary vector, AT_NULL     |
+///              | pointers to `env`, NULL       |
+///              | pointers to `args`, NULL      |
+/// sp        -> | argc                          |
+/// ```
+///
+/// Returns the (16 byte aligned) stack pointer, and the contents of the stack from there up to
+/// `stack_top`.
+fn build_stack(
+    stack_top: usize,
+    args: &[&str],
+    env: &[&str],
+    auxv: &[(u64, u64)],
+) -> Result<(VirtualAddress, Vec<u8>), ExecError> {
+    let strings_len: usize = args.iter().chain(env).map(|s| s.len() + 1).sum();
+    let words = 1 + (args.len() + 1) + (env.len() + 1) + 2 * (auxv.len() + 1);
+
+    let size = (strings_len + words * size_of::<u64>()).next_multiple_of(16);
+
+    // Leave at least half of the stack to the program itself.
+    if size > USER_STACK_SIZE / 2 {
+        return Err(ExecError::ArgumentsTooLarge);
+    }
+
+    let stack_pointer = stack_top - size;
+    let mut stack = vec![0u8; size];
+
+    let mut table = Vec::with_capacity(words);
+    table.push(args.len() as u64);
+
+    let mut string_offset = size - strings_len;
+
+    for strings in [args, env] {
+        for string in strings {
+            table.push((stack_pointer + string_offset) as u64);
+
+            stack[string_offset..string_offset + string.len()].copy_from_slice(string.as_bytes());
+            string_offset += string.len() + 1;
+        }
+
+        table.push(0);
+    }
+
+    for (kind, value) in auxv.iter().chain(&[(AT_NULL, 0)]) {
+        table.push(*kind);
+        table.push(*value);
+    }
+
+    for (i, word) in table.iter().enumerate() {
+        let offset = i * size_of::<u64>();
+        stack[offset..offset + size_of::<u64>()].copy_from_slice(&word.to_le_bytes());
+    }
+
+    Ok((stack_pointer.into(), stack))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{test_utils::*, *};
+    use crate::memory::map::MemoryErrorKind;
+
+    #[test_case]
+    fn test_build_stack() {
+        let top = 0x10000;
+        let (sp, stack) =
+            build_stack(top, &["init", "-v"], &["HOME=/"], &[(AT_PAGESZ, 4096)]).unwrap();
+
+        assert_eq!(0, sp.as_usize() % 16);
+        assert_eq!(top, sp.as_usize() + stack.len());
+
+        let word = |i: usize| u64::from_le_bytes(stack[i * 8..i * 8 + 8].try_into().unwrap());
+        let string = |ptr: u64| {
+            let start = ptr as usize - sp.as_usize();
+            let len = stack[start..].iter().position(|b| *b == 0).unwrap();
+            core::str::from_utf8(&stack[start..start + len]).unwrap()
+        };
+
+        assert_eq!(2, word(0));
+        assert_eq!("init", string(word(1)));
+        assert_eq!("-v", string(word(2)));
+        assert_eq!(0, word(3));
+        assert_eq!("HOME=/", string(word(4)));
+        assert_eq!(0, word(5));
+        assert_eq!((AT_PAGESZ, 4096), (word(6), word(7)));
+        assert_eq!((AT_NULL, 0), (word(8), word(9)));
+    }
+
+    #[test_case]
+    fn test_load_invalid() {
+        assert!(matches!(
+            load(b"not an elf", &[], &[], unlimited()),
+            Err(ExecError::Elf(_))
+        ));
+
+        let kernel_address = elf_image(
+            ET_EXEC,
+            0x1000,
+            &[Segment {
+                kind: PT_LOAD,
+                flags: PF_R | PF_X,
+                addr: 0x1000,
+                data: vec![0xEB, 0xFE],
+                memory_size: 2,
+            }],
+        );
+
+        assert!(matches!(
+            load(as_bytes(&kernel_address), &[], &[], unlimited()),
+            Err(ExecError::InvalidSegment)
+        ));
+    }
+
+    #[test_case]
+    fn test_load_executable() {
+        let available = FRAME_ALLOC.available();
+        let addr = (MemoryMapper::USER_SPACE_START + 0x1000) as u64;
+
+        let image = elf_image(
+            ET_EXEC,
+            addr,
+            &[
+                Segment {
+                    kind: PT_LOAD,
+                    flags: PF_R | PF_X,
+                    addr,
+                    data: vec![0xEB, 0xFE],
+                    memory_size: 2,
+                },
+                Segment {
+                    kind: PT_LOAD,
+                    flags: PF_R | PF_W,
+                    addr: addr + 0x1000,
+                    data: vec![0xAA; 16],
+                    memory_size: 0x2000,
+                },
+            ],
+        );
+
+        let loaded = load(as_bytes(&image), &["test"], &[], unlimited()).unwrap();
+        let manager = &loaded.manager;
+
+        assert_eq!(addr, loaded.entry.as_u64());
+
+        let mut code = [0u8; 2];
+        manager.read(loaded.entry, &mut code).unwrap();
+        assert_eq!([0xEB, 0xFE], code);
+
+        let data = VirtualAddress::from(addr as usize + 0x1000);
+        assert_eq!(0xAAAA_AAAA_AAAA_AAAA, read_u64(manager, data));
+        assert_eq!(0, read_u64(manager, data + 0x1ff8));
+
+        manager.with_mapper(|mapper| {
+            let (props, _, _) = mapper.mapping_info(loaded.entry).unwrap();
+            assert!(props.user() && props.executable() && !props.writable());
+
+            let (props, _, _) = mapper.mapping_info(data).unwrap();
+            assert!(props.user() && props.writable() && !props.executable());
+        });
+
+        assert_eq!(1, read_u64(manager, loaded.stack_pointer));
+
+        // Only the part of the stack that holds the arguments is backed.
+        let stack_page = loaded.stack_pointer.align_down(MemoryMapper::PAGE_SIZE);
+        manager.with_mapper(|mapper| {
+            assert!(mapper.mapping_info(stack_page).is_ok());
+            assert!(mapper
+                .mapping_info(stack_page - MemoryMapper::PAGE_SIZE)
+                .is_err());
+        });
+
+        drop(loaded);
+        assert_eq!(available, FRAME_ALLOC.available());
+    }
+
+    #[test_case]
+    fn test_load_segments_sharing_page() {
+        let addr = (MemoryMapper::USER_SPACE_START + 0x1000) as u64;
+
+        let image = elf_image(
+            ET_EXEC,
+            addr,
+            &[
+                Segment {
+                    kind: PT_LOAD,
+                    flags: PF_R | PF_X,
+                    addr,
+                    data: vec![0xEB, 0xFE],
+                    memory_size: 2,
+                },
+                Segment {
+                    kind: PT_LOAD,
+                    flags: PF_R | PF_W,
+                    addr: addr + 0x800,
+                    data: vec![0xAA; 8],
+                    memory_size: 8,
+                },
+            ],
+        );
+
+        let loaded = load(as_bytes(&image), &[], &[], unlimited()).unwrap();
+        let manager = &loaded.manager;
+
+        manager.with_mapper(|mapper| {
+            let (props, _, _) = mapper.mapping_info(loaded.entry).unwrap();
+            assert!(props.user() && props.executable() && props.writable());
+        });
+
+        let data = VirtualAddress::from(addr as usize + 0x800);
+        manager.write(data, &[0xBB]).unwrap();
+        assert_eq!(0xAAAA_AAAA_AAAA_AABB, read_u64(manager, data));
+        assert_eq!(0xFEEB, read_u64(manager, loaded.entry) & 0xFFFF);
+    }
+
+    #[test_case]
+    fn test_load_pie_relocations() {
+        // A relocation table with a single entry, followed by the dynamic section.
+        let mut data = Vec::new();
+
+        for word in [0x100u64, 8, 0x10, 7, 0, 8, 24, 9, 24, 0, 0] {
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+
+        let dynamic = data[24..].to_vec();
+
+        let image = elf_image(
+            ET_DYN,
+            0x180,
+            &[
+                Segment {
+                    kind: PT_LOAD,
+                    flags: PF_R | PF_W | PF_X,
+                    addr: 0,
+                    data,
+                    memory_size: 0x1000,
+                },
+                Segment {
+                    kind: PT_DYNAMIC,
+                    flags: PF_R,
+                    addr: 24,
+                    data: dynamic,
+                    memory_size: 64,
+                },
+            ],
+        );
+
+        let loaded = load(as_bytes(&image), &[], &[], unlimited()).unwrap();
+
+        assert_eq!(PIE_BASE + 0x180, loaded.entry.as_usize());
+        assert_eq!(
+            (PIE_BASE + 0x10) as u64,
+            read_u64(&loaded.manager, (PIE_BASE + 0x100).into())
+        );
+    }
+
+    #[test_case]
+    fn test_load_within_quota() {
+        let addr = (MemoryMapper::USER_SPACE_START + 0x1000) as u64;
+        let image = elf_image(
+            ET_EXEC,
+            addr,
+            &[Segment {
+                kind: PT_LOAD,
+                flags: PF_R | PF_W | PF_X,
+                addr,
+                data: vec![0xEB, 0xFE],
+                memory_size: 0x3000,
+            }],
+        );
+
+        // The segment takes three pages, and the arguments a page of the stack.
+        let pages = |count: usize| (count * MemoryMapper::PAGE_SIZE) as u64;
+        let quota = Arc::new(MemoryQuota::new(Some(pages(3))));
+
+        assert!(matches!(
+            load(as_bytes(&image), &[], &[], quota.clone()),
+            Err(ExecError::Memory(MemoryErrorKind::QuotaExceeded))
+        ));
+        assert_eq!(0, quota.used());
+
+        let quota = Arc::new(MemoryQuota::new(Some(pages(4))));
+        let loaded = load(as_bytes(&image), &[], &[], quota.clone()).unwrap();
+        assert_eq!(pages(4), quota.used());
+
+        drop(loaded);
+        assert_eq!(0, quota.used());
+    }
+
+    #[test_case]
+    fn test_load_overflowing_relocations() {
+        let image = |table_ptr: u64, table_size: u64, note: Segment| {
+            let mut dynamic = Vec::new();
+
+            for word in [7, table_ptr, 8, table_size, 9, 24, 0, 0] {
+                dynamic.extend_from_slice(&word.to_le_bytes());
+            }
+
+            elf_image(
+                ET_DYN,
+                0,
+                &[
+                    Segment {
+                        kind: PT_LOAD,
+                        flags: PF_R | PF_X,
+                        addr: 0,
+                        data: vec![0xEB, 0xFE],
+                        memory_size: 0x1000,
+                    },
+                    Segment {
+                        kind: PT_DYNAMIC,
+                        flags: PF_R,
+                        addr: 0x100,
+                        data: dynamic,
+                        memory_size: 64,
+                    },
+                    note,
+                ],
+            )
+        };
+
+        // A segment that ends past the end of the address space.
+        let wrapping_segment = image(
+            0x2000,
+            24,
+            Segment {
+                kind: PT_NOTE,
+                flags: PF_R,
+                addr: u64::MAX - 7,
+                data: vec![0; 16],
+                memory_size: 16,
+            },
+        );
+
+        // A table that ends past the end of the address space.
+        let wrapping_table = image(
+            0x2010,
+            u64::MAX - 15,
+            Segment {
+                kind: PT_NOTE,
+                flags: PF_R,
+                addr: 0x2000,
+                data: vec![0; 32],
+                memory_size: 32,
+            },
+        );
+
+        for image in [wrapping_segment, wrapping_table] {
+            assert!(matches!(
+                load(as_bytes(&image), &[], &[], unlimited()),
+                Err(ExecError::Elf(elf::ElfReadError::TooSmall))
+            ));
+        }
+    }
+}
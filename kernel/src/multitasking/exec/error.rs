@@ -0,0 +1,91 @@
+use elf::ElfReadError;
+
+use crate::{
+    fs::{FsError, VfsError},
+    memory::map::{AreaError, MemoryErrorKind, ModifyMapError, NewMapError},
+    multitasking::{process::ProcessError, scheduler::SchedulerError},
+};
+
+#[derive(Debug)]
+pub enum ExecError {
+    Elf(ElfReadError),
+    /// The image is not a little endian x86_64 executable.
+    UnsupportedArch,
+    /// The image is neither `ET_EXEC` nor `ET_DYN`.
+    NotExecutable,
+    /// The image requests a program interpreter, dynamic linking is not supported.
+    Interpreter,
+    NoEntryPoint,
+    /// A segment does not fit in the image or in the user space.
+    InvalidSegment,
+    /// Only `R_X86_64_RELATIVE` relocations are supported.
+    UnsupportedRelocation,
+    /// The arguments and environment do not fit on the user stack.
+    ArgumentsTooLarge,
+    /// The file is larger than [`MAX_IMAGE_SIZE`](super::MAX_IMAGE_SIZE).
+    ImageTooLarge,
+    /// The kernel heap has no room for the image.
+    OutOfMemory,
+    Map(NewMapError),
+    Write(ModifyMapError),
+    Area(AreaError),
+    Memory(MemoryErrorKind),
+    Scheduler(SchedulerError),
+    Process(ProcessError),
+    Vfs(VfsError),
+    Fs(FsError),
+}
+
+impl From<ElfReadError> for ExecError {
+    fn from(value: ElfReadError) -> Self {
+        ExecError::Elf(value)
+    }
+}
+
+impl From<NewMapError> for ExecError {
+    fn from(value: NewMapError) -> Self {
+        ExecError::Map(value)
+    }
+}
+
+impl From<ModifyMapError> for ExecError {
+    fn from(value: ModifyMapError) -> Self {
+        ExecError::Write(value)
+    }
+}
+
+impl From<AreaError> for ExecError {
+    fn from(value: AreaError) -> Self {
+        ExecError::Area(value)
+    }
+}
+
+impl From<MemoryErrorKind> for ExecError {
+    fn from(value: MemoryErrorKind) -> Self {
+        ExecError::Memory(value)
+    }
+}
+
+impl From<SchedulerError> for ExecError {
+    fn from(value: SchedulerError) -> Self {
+        ExecError::Scheduler(value)
+    }
+}
+
+impl From<ProcessError> for ExecError {
+    fn from(value: ProcessError) -> Self {
+        ExecError::Process(value)
+    }
+}
+
+impl From<VfsError> for ExecError {
+    fn from(value: VfsError) -> Self {
+        ExecError::Vfs(value)
+    }
+}
+
+impl From<FsError> for ExecError {
+    fn from(value: FsError) -> Self {
+        ExecError::Fs(value)
+    }
+}
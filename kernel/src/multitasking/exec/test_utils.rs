@@ -0,0 +1,84 @@
+//! Helpers to build small ELF images for tests.
+
+use alloc::{sync::Arc, vec, vec::Vec};
+use essentials::address::VirtualAddress;
+
+use crate::memory::{map::MemoryManager, quota::MemoryQuota};
+
+use super::PROGRAM_HEADER_SIZE;
+
+pub const PF_X: u32 = 1;
+pub const PF_W: u32 = 2;
+pub const PF_R: u32 = 4;
+
+pub const PT_LOAD: u32 = 1;
+pub const PT_DYNAMIC: u32 = 2;
+pub const PT_NOTE: u32 = 4;
+
+pub const ET_EXEC: u16 = 2;
+pub const ET_DYN: u16 = 3;
+
+pub struct Segment {
+    pub kind: u32,
+    pub flags: u32,
+    pub addr: u64,
+    pub data: Vec<u8>,
+    pub memory_size: u64,
+}
+
+/// Build an ELF image, backed by a `Vec<u64>` to keep it aligned.
+pub fn elf_image(kind: u16, entry: u64, segments: &[Segment]) -> Vec<u64> {
+    let mut bytes = vec![0u8; 64 + segments.len() * PROGRAM_HEADER_SIZE as usize];
+
+    bytes[0..8].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+    bytes[16..18].copy_from_slice(&kind.to_le_bytes());
+    bytes[18..20].copy_from_slice(&0x3Eu16.to_le_bytes());
+    bytes[20..24].copy_from_slice(&1u32.to_le_bytes());
+    bytes[24..32].copy_from_slice(&entry.to_le_bytes());
+    bytes[32..40].copy_from_slice(&64u64.to_le_bytes());
+    bytes[52..54].copy_from_slice(&64u16.to_le_bytes());
+    bytes[54..56].copy_from_slice(&(PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+    bytes[56..58].copy_from_slice(&(segments.len() as u16).to_le_bytes());
+
+    for (i, segment) in segments.iter().enumerate() {
+        let data_offset = bytes.len().next_multiple_of(8) as u64;
+        bytes.resize(data_offset as usize, 0);
+        bytes.extend_from_slice(&segment.data);
+
+        let header = 64 + i * PROGRAM_HEADER_SIZE as usize;
+        let fields = [
+            segment.kind as u64 | (segment.flags as u64) << 32,
+            data_offset,
+            segment.addr,
+            segment.addr,
+            segment.data.len() as u64,
+            segment.memory_size,
+            8,
+        ];
+
+        for (j, field) in fields.iter().enumerate() {
+            bytes[header + j * 8..header + j * 8 + 8].copy_from_slice(&field.to_le_bytes());
+        }
+    }
+
+    bytes.resize(bytes.len().next_multiple_of(8), 0);
+    (0..bytes.len())
+        .step_by(8)
+        .map(|i| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap()))
+        .collect()
+}
+
+pub fn as_bytes(image: &[u64]) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(image.as_ptr() as *const u8, image.len() * 8) }
+}
+
+/// A quota for images whose memory is not limited.
+pub fn unlimited() -> Arc<MemoryQuota> {
+    Arc::new(MemoryQuota::unlimited())
+}
+
+pub fn read_u64(manager: &MemoryManager, addr: VirtualAddress) -> u64 {
+    let mut bytes = [0u8; 8];
+    manager.read(addr, &mut bytes).unwrap();
+    u64::from_le_bytes(bytes)
+}
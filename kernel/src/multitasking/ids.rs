@@ -29,6 +29,10 @@ impl AtomicProcThreadId {
         Self::decode(self.value.load(order))
     }
 
+    pub fn store(&self, value: (ProcessId, ThreadId), order: Ordering) {
+        self.value.store(Self::encode(value.0, value.1), order);
+    }
+
     pub fn compare_exchange(
         &self,
         current: (ProcessId, ThreadId),
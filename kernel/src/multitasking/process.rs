@@ -1,8 +1,19 @@
+pub mod permissions;
 pub mod process_table;
+#[cfg(test)]
+pub(crate) mod test_utils;
 
-use core::sync::atomic::AtomicUsize;
+use core::{
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
 
-use crate::memory::map::MemoryManager;
+use alloc::sync::Arc;
+
+use crate::{arch, fs::OverLay, handle::HandleTable, memory::map::MemoryManager};
+
+pub use permissions::{Permission, PermissionError, Permissions};
+pub use process_table::{ExitCode, ProcessError};
 
 pub type ProcessId = u32;
 pub type AtomicProcessId = AtomicUsize;
@@ -10,15 +21,90 @@ pub type AtomicProcessId = AtomicUsize;
 pub struct Process {
     process_id: ProcessId,
     manager: MemoryManager,
+    overlay: Arc<OverLay>,
+    permissions: Permissions,
+    handles: HandleTable,
+    /// The ticks of the timer in which a thread of the process was running.
+    cpu_ticks: AtomicU64,
+    /// Set by the process table when the process exits, its threads are never scheduled again.
+    exited: AtomicBool,
 }
 
 impl Process {
-    pub fn new(process_id: ProcessId, manager: MemoryManager) -> Self {
+    /// Create a new process.
+    ///
+    /// The `overlay` is typically the overlay of the parent process, or one created with
+    /// [`OverLay::inherit`] to restrict the filesystem view of the child. Likewise, the
+    /// `permissions` are created with [`Permissions::inherit`] from those of the parent.
+    pub fn new(
+        process_id: ProcessId,
+        manager: MemoryManager,
+        overlay: Arc<OverLay>,
+        permissions: Permissions,
+    ) -> Self {
         assert_ne!(0, process_id);
 
         Self {
             process_id,
             manager,
+            overlay,
+            permissions,
+            handles: HandleTable::new(),
+            cpu_ticks: AtomicU64::new(0),
+            exited: AtomicBool::new(false),
         }
     }
+
+    pub fn process_id(&self) -> ProcessId {
+        self.process_id
+    }
+
+    pub fn manager(&self) -> &MemoryManager {
+        &self.manager
+    }
+
+    /// The overlay through which every path of this process is resolved.
+    pub fn overlay(&self) -> &Arc<OverLay> {
+        &self.overlay
+    }
+
+    pub fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+
+    /// Give the process `handles` instead of an empty table, typically inherited from the
+    /// parent with [`HandleTable::inherit`].
+    pub fn with_handles(mut self, handles: HandleTable) -> Self {
+        self.handles = handles;
+        self
+    }
+
+    /// The objects that the process holds a handle to.
+    pub fn handles(&self) -> &HandleTable {
+        &self.handles
+    }
+
+    /// Count a tick of the timer in which a thread of the process was running.
+    pub fn account_tick(&self) {
+        self.cpu_ticks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The time that the threads of the process have been running, counted in whole ticks.
+    pub fn cpu_time(&self) -> Duration {
+        let ticks = self.cpu_ticks.load(Ordering::Relaxed);
+        let nanos = ticks.saturating_mul(arch::TICK_INTERVAL.as_nanos() as u64);
+
+        Duration::from_nanos(nanos)
+    }
+
+    /// Whether the process has used more CPU time than its permissions allow.
+    pub fn exceeds_cpu_time(&self) -> bool {
+        let used = self.cpu_time().as_millis() as u64;
+        !self.permissions.allows(&Permission::CpuTime(used))
+    }
+
+    /// Whether the process has exited, its memory is released once its last thread is retired.
+    pub fn has_exited(&self) -> bool {
+        self.exited.load(Ordering::Acquire)
+    }
 }
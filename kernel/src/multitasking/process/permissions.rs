@@ -0,0 +1,293 @@
+//! The permissions of a process.
+//!
+//! Permissions are hierarchical: a process receives its permissions from its parent, and can only
+//! receive a subset of them. A child is spawned with a list of grants, where everything that is
+//! not granted is either denied (paths and ports) or inherited from the parent (CPU time, memory
+//! and syscalls).
+//!
+//! Paths are always in the namespace of the root overlay, so they have to be resolved through the
+//! overlay of the process before they are checked.
+
+mod error;
+
+use alloc::{collections::BTreeSet, vec, vec::Vec};
+use path::{Path, PathBuf};
+
+pub use error::PermissionError;
+
+/// A single permission, which is either held or not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Permission {
+    /// Read at and below a path.
+    Read(PathBuf),
+    /// Modify at and below a path.
+    Modify(PathBuf),
+    /// Use the CPU for an amount of milliseconds.
+    CpuTime(u64),
+    /// Use an amount of bytes of RAM.
+    Memory(u64),
+    /// Execute the syscall with a number.
+    Syscall(u64),
+    /// Listen on a port.
+    Listen(u16),
+    /// Send requests to a port.
+    Request(u16),
+}
+
+/// A set of syscall numbers, only the first 64 syscalls can be represented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyscallSet {
+    bits: u64,
+}
+
+impl SyscallSet {
+    pub const ALL: Self = Self { bits: u64::MAX };
+    pub const EMPTY: Self = Self { bits: 0 };
+
+    pub fn contains(&self, number: u64) -> bool {
+        number < u64::BITS as u64 && self.bits & (1 << number) != 0
+    }
+
+    pub fn insert(&mut self, number: u64) {
+        if number < u64::BITS as u64 {
+            self.bits |= 1 << number;
+        }
+    }
+
+    pub fn is_subset_of(&self, other: &SyscallSet) -> bool {
+        self.bits & !other.bits == 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PortSet {
+    All,
+    Some(BTreeSet<u16>),
+}
+
+impl PortSet {
+    pub fn contains(&self, port: u16) -> bool {
+        match self {
+            PortSet::All => true,
+            PortSet::Some(ports) => ports.contains(&port),
+        }
+    }
+
+    fn insert(&mut self, port: u16) {
+        if let PortSet::Some(ports) = self {
+            ports.insert(port);
+        }
+    }
+
+    fn is_subset_of(&self, other: &PortSet) -> bool {
+        match self {
+            PortSet::All => *other == PortSet::All,
+            PortSet::Some(ports) => ports.iter().all(|port| other.contains(*port)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Permissions {
+    read: Vec<PathBuf>,
+    modify: Vec<PathBuf>,
+    /// `None` when the CPU time is unlimited.
+    cpu_time: Option<u64>,
+    /// `None` when the memory is unlimited.
+    memory: Option<u64>,
+    syscalls: SyscallSet,
+    listen: PortSet,
+    request: PortSet,
+}
+
+impl Permissions {
+    /// Every permission, only processes that are started by the kernel should have these.
+    pub fn all() -> Self {
+        Self {
+            read: vec![Path::ROOT.to_path_buf()],
+            modify: vec![Path::ROOT.to_path_buf()],
+            cpu_time: None,
+            memory: None,
+            syscalls: SyscallSet::ALL,
+            listen: PortSet::All,
+            request: PortSet::All,
+        }
+    }
+
+    /// No permissions at all, not even to make syscalls.
+    pub fn none() -> Self {
+        Self {
+            read: Vec::new(),
+            modify: Vec::new(),
+            cpu_time: Some(0),
+            memory: Some(0),
+            syscalls: SyscallSet::EMPTY,
+            listen: PortSet::Some(BTreeSet::new()),
+            request: PortSet::Some(BTreeSet::new()),
+        }
+    }
+
+    /// Create the permissions of a child, that has been granted `grants`.
+    ///
+    /// The CPU time, memory and syscalls are inherited, unless they are granted explicitly. Every
+    /// grant has to be held by `self`, so the child never has more permissions than its parent.
+    pub fn inherit(
+        &self,
+        grants: impl IntoIterator<Item = Permission>,
+    ) -> Result<Self, PermissionError> {
+        let mut child = Self {
+            read: Vec::new(),
+            modify: Vec::new(),
+            cpu_time: self.cpu_time,
+            memory: self.memory,
+            syscalls: self.syscalls,
+            listen: PortSet::Some(BTreeSet::new()),
+            request: PortSet::Some(BTreeSet::new()),
+        };
+
+        let mut granted_syscalls = None;
+
+        for grant in grants {
+            if !self.allows(&grant) {
+                return Err(PermissionError::NotGranted(grant));
+            }
+
+            match grant {
+                Permission::Read(path) => child.read.push(path.normalize()),
+                Permission::Modify(path) => child.modify.push(path.normalize()),
+                Permission::CpuTime(amount) => child.cpu_time = Some(amount),
+                Permission::Memory(amount) => child.memory = Some(amount),
+                Permission::Syscall(number) => granted_syscalls
+                    .get_or_insert(SyscallSet::EMPTY)
+                    .insert(number),
+                Permission::Listen(port) => child.listen.insert(port),
+                Permission::Request(port) => child.request.insert(port),
+            }
+        }
+
+        if let Some(syscalls) = granted_syscalls {
+            child.syscalls = syscalls;
+        }
+
+        debug_assert!(child.is_subset_of(self));
+
+        Ok(child)
+    }
+
+    pub fn allows(&self, permission: &Permission) -> bool {
+        match permission {
+            Permission::Read(path) => self.allows_read(path),
+            Permission::Modify(path) => self.allows_modify(path),
+            Permission::CpuTime(amount) => self.cpu_time.is_none_or(|limit| *amount <= limit),
+            Permission::Memory(amount) => self.memory.is_none_or(|limit| *amount <= limit),
+            Permission::Syscall(number) => self.syscalls.contains(*number),
+            Permission::Listen(port) => self.listen.contains(*port),
+            Permission::Request(port) => self.request.contains(*port),
+        }
+    }
+
+    pub fn allows_read(&self, path: &Path) -> bool {
+        let path = path.normalize();
+        self.read.iter().any(|base| path.starts_with(base))
+    }
+
+    pub fn allows_modify(&self, path: &Path) -> bool {
+        let path = path.normalize();
+        self.modify.iter().any(|base| path.starts_with(base))
+    }
+
+    pub fn allows_syscall(&self, number: u64) -> bool {
+        self.syscalls.contains(number)
+    }
+
+    /// The milliseconds of CPU time that the process may use, `None` when it is unlimited.
+    pub fn cpu_time_limit(&self) -> Option<u64> {
+        self.cpu_time
+    }
+
+    /// The bytes of RAM that the process may use, `None` when it is unlimited.
+    pub fn memory_limit(&self) -> Option<u64> {
+        self.memory
+    }
+
+    pub fn is_subset_of(&self, other: &Permissions) -> bool {
+        let limit_within = |limit: Option<u64>, other: Option<u64>| match (limit, other) {
+            (_, None) => true,
+            (None, Some(_)) => false,
+            (Some(limit), Some(other)) => limit <= other,
+        };
+
+        self.read.iter().all(|path| other.allows_read(path))
+            && self.modify.iter().all(|path| other.allows_modify(path))
+            && limit_within(self.cpu_time, other.cpu_time)
+            && limit_within(self.memory, other.memory)
+            && self.syscalls.is_subset_of(&other.syscalls)
+            && self.listen.is_subset_of(&other.listen)
+            && self.request.is_subset_of(&other.request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(value: &str) -> PathBuf {
+        Path::new(value).unwrap().to_path_buf()
+    }
+
+    #[test_case]
+    fn test_paths_are_granted_below() {
+        let permissions = Permissions::all()
+            .inherit([Permission::Read(path("/home/anon"))])
+            .unwrap();
+
+        assert!(permissions.allows_read(&path("/home/anon")));
+        assert!(permissions.allows_read(&path("/home/anon/config")));
+        assert!(!permissions.allows_read(&path("/home/anon/../root")));
+        assert!(!permissions.allows_read(&path("/home/anonymous")));
+        assert!(!permissions.allows_modify(&path("/home/anon")));
+    }
+
+    #[test_case]
+    fn test_inherit_defaults() {
+        let parent = Permissions::all()
+            .inherit([Permission::Memory(4096), Permission::Syscall(1)])
+            .unwrap();
+        let child = parent.inherit([]).unwrap();
+
+        assert!(child.allows(&Permission::Memory(4096)));
+        assert!(!child.allows(&Permission::Memory(4097)));
+        assert!(child.allows(&Permission::CpuTime(u64::MAX)));
+        assert!(child.allows_syscall(1));
+        assert!(!child.allows_syscall(0));
+        assert!(!child.allows(&Permission::Listen(80)));
+        assert!(child.is_subset_of(&parent));
+    }
+
+    #[test_case]
+    fn test_inherit_cannot_escalate() {
+        let parent = Permissions::all()
+            .inherit([
+                Permission::Modify(path("/tmp")),
+                Permission::Memory(4096),
+                Permission::Request(443),
+            ])
+            .unwrap();
+
+        for grant in [
+            Permission::Modify(path("/")),
+            Permission::Read(path("/tmp")),
+            Permission::Memory(8192),
+            Permission::Request(80),
+            Permission::Listen(443),
+        ] {
+            assert_eq!(
+                Err(PermissionError::NotGranted(grant.clone())),
+                parent.inherit([grant])
+            );
+        }
+
+        assert!(!Permissions::all().is_subset_of(&parent));
+        assert!(Permissions::none().is_subset_of(&parent));
+    }
+}
@@ -0,0 +1,7 @@
+use super::Permission;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PermissionError {
+    /// A child was given a permission that its parent does not have.
+    NotGranted(Permission),
+}
@@ -1,15 +1,58 @@
-use essentials::PanicOnce;
+//! The table of every process, from its creation until it is reaped by its parent.
+//!
+//! When a process exits it becomes a zombie: its threads are no longer scheduled, and the table
+//! only remembers its exit code until the parent waits for it. The memory of the process is
+//! released as soon as the table and all of its threads dropped their reference to it.
+//!
+//! Processes without a parent, and processes whose parent exited, are reaped as soon as they
+//! exit, since nobody can wait for them.
 
-use crate::{multitasking::process::AtomicProcessId, utils::ProcLocal};
+mod error;
+
+use core::sync::atomic::Ordering;
+
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use essentials::{spin::SpinLock, PanicOnce};
+
+use crate::{
+    multitasking::{
+        ids,
+        process::{AtomicProcessId, Process, ProcessId},
+        scheduler::WaitQueue,
+    },
+    utils::{InterruptGuard, ProcLocal},
+};
+
+pub use error::ProcessError;
+
+pub type ExitCode = i32;
+
+enum ProcessState {
+    Alive(Arc<Process>),
+    Zombie(ExitCode),
+}
+
+struct ProcessEntry {
+    parent: Option<ProcessId>,
+    children: Vec<ProcessId>,
+    state: ProcessState,
+}
 
 pub struct ProcessTable {
     current_process: PanicOnce<ProcLocal<AtomicProcessId>>,
+    id_autoincrement: ids::AtomicProcessId,
+    processes: InterruptGuard<SpinLock<BTreeMap<ProcessId, ProcessEntry>>>,
+    /// The user threads that wait for a child to exit.
+    exits: WaitQueue,
 }
 
 impl ProcessTable {
     pub const fn new() -> Self {
         Self {
             current_process: PanicOnce::new(),
+            id_autoincrement: ids::AtomicProcessId::new(0),
+            processes: InterruptGuard::new_lock(BTreeMap::new()),
+            exits: WaitQueue::new(),
         }
     }
 
@@ -17,6 +60,306 @@ impl ProcessTable {
         self.current_process
             .initialize_with(ProcLocal::new(|| AtomicProcessId::new(0)));
     }
+
+    /// Get a process id that has never been used before.
+    pub fn alloc_process_id(&self) -> ProcessId {
+        self.id_autoincrement.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Keep `process` alive until it exits, as a child of `parent`.
+    pub fn insert(
+        &self,
+        process: Process,
+        parent: Option<ProcessId>,
+    ) -> Result<Arc<Process>, ProcessError> {
+        let process = Arc::new(process);
+        let process_id = process.process_id();
+
+        let guard = self.processes.guard();
+        let mut processes = guard.lock();
+
+        if let Some(parent) = parent {
+            let parent_entry = processes
+                .get_mut(&parent)
+                .ok_or(ProcessError::NoSuchProcess)?;
+
+            if let ProcessState::Zombie(_) = parent_entry.state {
+                return Err(ProcessError::Exited);
+            }
+
+            parent_entry.children.push(process_id);
+        }
+
+        processes.insert(
+            process_id,
+            ProcessEntry {
+                parent,
+                children: Vec::new(),
+                state: ProcessState::Alive(process.clone()),
+            },
+        );
+
+        Ok(process)
+    }
+
+    /// Remove a process that never ran, without leaving an exit code behind.
+    pub fn remove(&self, process_id: ProcessId) -> Option<Arc<Process>> {
+        let guard = self.processes.guard();
+        let mut processes = guard.lock();
+
+        let entry = processes.remove(&process_id)?;
+        Self::unlink_from_parent(&mut processes, process_id, entry.parent);
+        Self::orphan_children(&mut processes, &entry.children);
+
+        match entry.state {
+            ProcessState::Alive(process) => Some(process),
+            ProcessState::Zombie(_) => None,
+        }
+    }
+
+    /// Get a process that has not exited.
+    pub fn get(&self, process_id: ProcessId) -> Option<Arc<Process>> {
+        let guard = self.processes.guard();
+        let processes = guard.lock();
+
+        match &processes.get(&process_id)?.state {
+            ProcessState::Alive(process) => Some(process.clone()),
+            ProcessState::Zombie(_) => None,
+        }
+    }
+
+    pub fn parent(&self, process_id: ProcessId) -> Result<Option<ProcessId>, ProcessError> {
+        let guard = self.processes.guard();
+        let processes = guard.lock();
+
+        processes
+            .get(&process_id)
+            .map(|entry| entry.parent)
+            .ok_or(ProcessError::NoSuchProcess)
+    }
+
+    /// The children that have not been reaped yet.
+    pub fn children(&self, process_id: ProcessId) -> Result<Vec<ProcessId>, ProcessError> {
+        let guard = self.processes.guard();
+        let processes = guard.lock();
+
+        processes
+            .get(&process_id)
+            .map(|entry| entry.children.clone())
+            .ok_or(ProcessError::NoSuchProcess)
+    }
+
+    /// Exit a process with `code`.
+    ///
+    /// The threads of the process are retired by the scheduler the next time they would run. The
+    /// process stays in the table as a zombie, until its parent waits for it.
+    pub fn exit(&self, process_id: ProcessId, code: ExitCode) -> Result<(), ProcessError> {
+        let guard = self.processes.guard();
+        let mut processes = guard.lock();
+
+        let entry = processes
+            .get_mut(&process_id)
+            .ok_or(ProcessError::NoSuchProcess)?;
+
+        let ProcessState::Alive(process) = &entry.state else {
+            return Err(ProcessError::Exited);
+        };
+
+        let process = process.clone();
+        process.exited.store(true, Ordering::Release);
+        entry.state = ProcessState::Zombie(code);
+
+        let parent = entry.parent;
+        let children = core::mem::take(&mut entry.children);
+
+        Self::orphan_children(&mut processes, &children);
+
+        if parent.is_none() {
+            processes.remove(&process_id);
+        }
+
+        drop(processes);
+        drop(guard);
+
+        // Threads that are blocked in a system call are woken when their objects are closed, the
+        // scheduler retires them instead of running them.
+        process.handles().clear();
+
+        // Dropping the process outside of the lock, in case this was the last reference.
+        drop(process);
+
+        self.exits.wake_all();
+
+        Ok(())
+    }
+
+    /// Reap `child` if it has exited, returning its exit code.
+    pub fn try_wait(
+        &self,
+        parent: ProcessId,
+        child: ProcessId,
+    ) -> Result<Option<ExitCode>, ProcessError> {
+        let guard = self.processes.guard();
+        let mut processes = guard.lock();
+
+        let entry = processes.get(&child).ok_or(ProcessError::NoSuchProcess)?;
+
+        if entry.parent != Some(parent) {
+            return Err(ProcessError::NotAChild);
+        }
+
+        let ProcessState::Zombie(code) = entry.state else {
+            return Ok(None);
+        };
+
+        processes.remove(&child);
+        Self::unlink_from_parent(&mut processes, child, Some(parent));
+
+        Ok(Some(code))
+    }
+
+    /// Block the current thread until `child` exits and reap it, returning its exit code.
+    pub fn wait(&self, parent: ProcessId, child: ProcessId) -> Result<ExitCode, ProcessError> {
+        loop {
+            if let Some(code) = self.try_wait(parent, child)? {
+                return Ok(code);
+            }
+
+            self.exits.wait_until(|| self.get(child).is_none());
+        }
+    }
+
+    /// Block the current user thread until `child` has exited, see
+    /// [`WaitQueue::block_after_syscall`].
+    pub fn block_wait_after_syscall(&self, child: ProcessId) -> bool {
+        self.exits.block_after_syscall(|| self.get(child).is_none())
+    }
+
+    fn unlink_from_parent(
+        processes: &mut BTreeMap<ProcessId, ProcessEntry>,
+        process_id: ProcessId,
+        parent: Option<ProcessId>,
+    ) {
+        if let Some(parent) = parent.and_then(|parent| processes.get_mut(&parent)) {
+            parent.children.retain(|child| *child != process_id);
+        }
+    }
+
+    /// Children without a parent are reaped as soon as they exit.
+    fn orphan_children(processes: &mut BTreeMap<ProcessId, ProcessEntry>, children: &[ProcessId]) {
+        for child in children {
+            let Some(entry) = processes.get_mut(child) else {
+                continue;
+            };
+
+            entry.parent = None;
+
+            if let ProcessState::Zombie(_) = entry.state {
+                processes.remove(child);
+            }
+        }
+    }
 }
 
 pub static PROCESS_TABLE: ProcessTable = ProcessTable::new();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        memory::alloc::FRAME_ALLOC,
+        multitasking::{
+            process::test_utils::new_process,
+            scheduler::{LOWEST_PRIORITY, SCHEDULER},
+        },
+    };
+
+    #[test_case]
+    fn test_exit_and_wait() {
+        let table = ProcessTable::new();
+        let parent = table.insert(new_process(&table), None).unwrap();
+        let child = table
+            .insert(new_process(&table), Some(parent.process_id()))
+            .unwrap();
+
+        let (parent, child) = (parent.process_id(), child.process_id());
+
+        assert_eq!(Ok(None), table.try_wait(parent, child));
+        assert_eq!(Err(ProcessError::NotAChild), table.try_wait(child, parent));
+
+        table.exit(child, 3).unwrap();
+
+        assert!(table.get(child).is_none());
+        assert_eq!(Err(ProcessError::Exited), table.exit(child, 4));
+        assert_eq!(Ok(3), table.wait(parent, child));
+        assert_eq!(
+            Err(ProcessError::NoSuchProcess),
+            table.try_wait(parent, child)
+        );
+        assert_eq!(Ok(Vec::new()), table.children(parent));
+    }
+
+    #[test_case]
+    fn test_wait_blocks_until_exit() {
+        let table = Arc::new(ProcessTable::new());
+        let parent = table.insert(new_process(&table), None).unwrap();
+        let child = table
+            .insert(new_process(&table), Some(parent.process_id()))
+            .unwrap();
+
+        let (parent, child) = (parent.process_id(), child.process_id());
+
+        let exiter = {
+            let table = table.clone();
+
+            SCHEDULER
+                .spawn_kernel_thread(LOWEST_PRIORITY, move || {
+                    table.exit(child, 5).unwrap();
+                    0
+                })
+                .unwrap()
+        };
+
+        assert_eq!(Ok(5), table.wait(parent, child));
+
+        exiter.join();
+    }
+
+    #[test_case]
+    fn test_orphans_are_reaped() {
+        let table = ProcessTable::new();
+        let parent = table.insert(new_process(&table), None).unwrap();
+        let child = table
+            .insert(new_process(&table), Some(parent.process_id()))
+            .unwrap();
+
+        let (parent, child) = (parent.process_id(), child.process_id());
+
+        table.exit(parent, 0).unwrap();
+        assert_eq!(Err(ProcessError::NoSuchProcess), table.parent(parent));
+        assert_eq!(Ok(None), table.parent(child));
+
+        assert_eq!(
+            Err(ProcessError::NoSuchProcess),
+            table.insert(new_process(&table), Some(parent)).map(|_| ())
+        );
+
+        table.exit(child, 0).unwrap();
+        assert_eq!(Err(ProcessError::NoSuchProcess), table.parent(child));
+    }
+
+    #[test_case]
+    fn test_exit_releases_memory() {
+        let available = FRAME_ALLOC.available();
+
+        let table = ProcessTable::new();
+        let process = table.insert(new_process(&table), None).unwrap();
+        let process_id = process.process_id();
+        drop(process);
+
+        assert!(FRAME_ALLOC.available() < available);
+
+        table.exit(process_id, 0).unwrap();
+        assert_eq!(available, FRAME_ALLOC.available());
+    }
+}
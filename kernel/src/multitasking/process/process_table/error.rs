@@ -0,0 +1,9 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessError {
+    /// There is no process with the id, or it has already been reaped.
+    NoSuchProcess,
+    /// The process has already exited.
+    Exited,
+    /// Only the parent of a process can wait for it.
+    NotAChild,
+}
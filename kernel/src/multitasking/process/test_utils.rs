@@ -0,0 +1,25 @@
+//! Helpers to create processes for tests.
+
+use alloc::sync::Arc;
+
+use crate::{
+    fs::OverLay,
+    memory::{
+        alloc::FRAME_ALLOC,
+        map::{MemoryManager, MemoryMapper},
+    },
+};
+
+use super::{process_table::ProcessTable, Permissions, Process};
+
+/// A process without any memory, with every permission and an id from `table`.
+pub fn new_process(table: &ProcessTable) -> Process {
+    let mapper = MemoryMapper::new_user_mapper(FRAME_ALLOC.physical_memory_offset()).unwrap();
+
+    Process::new(
+        table.alloc_process_id(),
+        MemoryManager::new(mapper),
+        Arc::new(OverLay::ROOT),
+        Permissions::all(),
+    )
+}
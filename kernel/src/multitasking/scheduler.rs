@@ -1,20 +1,32 @@
 mod error;
+mod idle;
+mod join;
 mod thread;
 mod thread_box;
+mod wait_queue;
 
-use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::{
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
 
 use crate::{
-    arch::CpuContext,
+    arch::{self, x86_64::mp::processor_id, CpuContext},
+    memory::map::MemoryMapper,
     multitasking::{
         ids::{AtomicProcThreadId, AtomicThreadId, ThreadId},
-        process::ProcessId,
+        process::{Process, ProcessId},
     },
-    utils::ProcLocal,
+    utils::{InterruptGuard, ProcLocal},
 };
 
-use alloc::boxed::Box;
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
 use essentials::{
+    address::PhysicalAddress,
     nb::{
         queue::{DummyNode, QueueNode},
         Queue,
@@ -22,20 +34,48 @@ use essentials::{
     spin::SpinLock,
     FixedVec, PanicOnce,
 };
+use x86_64::interrupt::without_interrupts;
 
 pub use thread::*;
 use thread_box::new_thread_box;
 
 pub use error::SchedulerError;
+pub use idle::CpuTime;
+use idle::{idle_loop, IdleThread};
+pub use join::{ExitValue, JoinHandle, JoinState, ThreadExit};
 pub use thread_box::ThreadBox;
+pub use wait_queue::WaitQueue;
 
 const MAX_THREADS: usize = 1024 * 10;
-const PRIORITY_LEVELS: usize = 1;
+const PRIORITY_LEVELS: usize = 8;
+
+/// Every `AGING_INTERVAL` ticks (of all processors together), the waiting threads are raised one
+/// level, so the threads with a low priority are never starved.
+const AGING_INTERVAL: u64 = 32;
+
+/// The time slice of the threads in the run queue `level`, threads with a lower priority run
+/// longer once they get to run.
+const fn time_slice(level: usize) -> u64 {
+    level as u64 + 1
+}
+
+/// A thread that blocks, from the moment it prepares to block until it runs again.
+enum BlockedThread {
+    /// The thread is still running, it blocks soon.
+    Blocking,
+    /// The thread was woken before it blocked, so it does not block.
+    Woken,
+    /// The thread is blocked and waits to be woken.
+    Parked(&'static mut QueueNode<Thread>),
+}
 
 // TOOD: nodes and dummy nodes to the eternal alloc.
 
 pub struct Scheduler {
     run_queues: PanicOnce<FixedVec<PRIORITY_LEVELS, Queue<Thread>>>,
+    /// The number of threads in each run queue, the queues themselves cannot tell.
+    queued_threads: [AtomicUsize; PRIORITY_LEVELS],
+    ticks: AtomicU64,
     retired_threads: PanicOnce<Queue<Thread>>,
 
     id_autoincrement: AtomicThreadId,
@@ -44,6 +84,19 @@ pub struct Scheduler {
 
     current_thread: PanicOnce<ProcLocal<SpinLock<Option<&'static mut QueueNode<Thread>>>>>,
     current_thread_id: PanicOnce<ProcLocal<AtomicProcThreadId>>,
+    idle_threads: PanicOnce<ProcLocal<IdleThread>>,
+    /// The thread that stopped while running on the processor, it is retired on the next switch
+    /// since it was still using its stack.
+    stopped_threads: PanicOnce<ProcLocal<SpinLock<Option<&'static mut QueueNode<Thread>>>>>,
+
+    blocked_threads: InterruptGuard<SpinLock<BTreeMap<ThreadId, BlockedThread>>>,
+    /// The sleeping threads, sorted by the tick of the clock at which they wake up.
+    sleeping_threads: InterruptGuard<SpinLock<BTreeSet<(u64, ThreadId)>>>,
+    /// The ticks of the bootstrap processor, which is the clock of sleeping threads.
+    clock_ticks: AtomicU64,
+
+    /// The level 4 table that is active while kernel threads run.
+    kernel_address_space: PanicOnce<PhysicalAddress>,
 }
 
 impl Scheduler {
@@ -51,11 +104,19 @@ impl Scheduler {
         Self {
             id_autoincrement: AtomicThreadId::new(0),
             run_queues: PanicOnce::new(),
+            queued_threads: [const { AtomicUsize::new(0) }; PRIORITY_LEVELS],
+            ticks: AtomicU64::new(0),
             retired_threads: PanicOnce::new(),
             allocated_threads: AtomicUsize::new(0),
             allocation_exceeded: AtomicBool::new(false),
             current_thread: PanicOnce::new(),
             current_thread_id: PanicOnce::new(),
+            idle_threads: PanicOnce::new(),
+            stopped_threads: PanicOnce::new(),
+            blocked_threads: InterruptGuard::new_lock(BTreeMap::new()),
+            sleeping_threads: InterruptGuard::new_lock(BTreeSet::new()),
+            clock_ticks: AtomicU64::new(0),
+            kernel_address_space: PanicOnce::new(),
         }
     }
 
@@ -83,6 +144,40 @@ impl Scheduler {
 
         self.current_thread_id
             .initialize_with(ProcLocal::new(|| AtomicProcThreadId::new(0, 0)));
+
+        #[cfg(feature = "lock_debug")]
+        essentials::spin::set_thread_id(|| SCHEDULER.current_ids().1.unwrap_or_default() as usize);
+
+        self.stopped_threads
+            .initialize_with(ProcLocal::new(|| SpinLock::new(None)));
+
+        self.kernel_address_space
+            .initialize_with(MemoryMapper::active_l4_table());
+
+        self.idle_threads.initialize_with(ProcLocal::new(|| {
+            self.new_idle_thread()
+                .expect("creating an idle thread should never fail in init()")
+        }));
+    }
+
+    fn new_idle_thread(&self) -> Result<IdleThread, SchedulerError> {
+        let node = self.allocate_thread(
+            self.alloc_thread_id(),
+            None,
+            LOWEST_PRIORITY,
+            None,
+            true,
+            Default::default(),
+        )?;
+
+        let stack_top = node
+            .kernel_stack()
+            .expect("the idle thread should have a stack")
+            .top();
+
+        node.save_context(arch::new_kernel_context(idle_loop, stack_top));
+
+        Ok(IdleThread::new(node))
     }
 
     pub fn current_as_kernel_thread(
@@ -101,38 +196,329 @@ impl Scheduler {
             return Err(SchedulerError::SlotTaken);
         }
 
-        let node = self.spawn_id(tid, priority, None, Default::default())?;
+        let node = self.spawn_id(tid, priority, None, false, Default::default())?;
 
         *current_thread_lock = Some(node);
 
         Ok(tid)
     }
 
-    pub fn next_ctx(&self, current: CpuContext) -> Option<CpuContext> {
+    /// Switch to the next thread, or to the idle thread of the processor when no other thread can
+    /// run.
+    pub fn next_ctx(&self, current: CpuContext) -> CpuContext {
+        let idle_thread: &IdleThread = &self.idle_threads;
         let mut current_node_lock = self.current_thread.lock();
 
+        // The address space of a stopped thread may still be active, so it can only be retired
+        // after switching to the next one.
+        let mut stopped_node = None;
+
         if let Some(current_node) = current_node_lock.take() {
+            // Whether the thread is stopped depends on where it was interrupted.
             current_node.save_context(current);
-            self.schedule_node(current_node);
+
+            if current_node.thread_id() == idle_thread.thread_id() {
+                idle_thread.park(current_node);
+            } else if current_node.is_stopped() {
+                stopped_node = Some(current_node);
+            } else {
+                match current_node.state() {
+                    ThreadState::Blocked | ThreadState::Sleeping => self.park(current_node),
+                    _ => self.schedule_node(current_node),
+                }
+            }
         }
 
-        let next_node = self.next_node()?;
+        let next_node = loop {
+            let Some(node) = self.next_node() else {
+                break idle_thread.unpark();
+            };
+
+            if !node.is_stopped() {
+                break node;
+            }
+
+            // The thread is not running, so its address space is not active here.
+            self.deallocate_thread(node);
+        };
+
         let ctx = next_node.context();
 
+        let address_space = next_node
+            .address_space()
+            .unwrap_or(*self.kernel_address_space);
+
+        // Safety: the address space belongs to the thread's process, which outlives the thread.
+        unsafe { MemoryMapper::activate(address_space) };
+
+        if let Some(stopped_node) = stopped_node {
+            stopped_node.finish();
+
+            let previous = self.stopped_threads.lock().replace(stopped_node);
+
+            // The previous thread stopped before the last switch, so its stack is no longer used.
+            if let Some(previous) = previous {
+                self.retired_threads.push(previous);
+            }
+        }
+
+        if let Some(kernel_stack) = next_node.kernel_stack() {
+            arch::set_kernel_stack(kernel_stack.top());
+        }
+
+        self.current_thread_id.store(
+            (
+                next_node.process_id().unwrap_or_default(),
+                next_node.thread_id(),
+            ),
+            Ordering::Relaxed,
+        );
+
+        next_node.set_state(ThreadState::Running);
         *current_node_lock = Some(next_node);
 
-        Some(ctx)
+        ctx
+    }
+
+    /// Called on every tick of the timer, the current thread keeps running until its time slice
+    /// is used up, or a thread with a higher priority is waiting.
+    pub fn tick(&self, current: CpuContext) -> CpuContext {
+        if (self.ticks.fetch_add(1, Ordering::Relaxed) + 1).is_multiple_of(AGING_INTERVAL) {
+            self.age_waiting_threads();
+        }
+
+        if processor_id() == 0 {
+            self.wake_sleeping_threads();
+        }
+
+        let idle_thread: &IdleThread = &self.idle_threads;
+        idle_thread.account_tick(self.current_ids().1);
+
+        let keep_running = match self.current_thread.lock().as_mut() {
+            Some(node) if node.thread_id() == idle_thread.thread_id() => {
+                !self.threads_waiting_above(PRIORITY_LEVELS)
+            }
+            Some(node) => {
+                let slice_used = node.account_tick();
+
+                !slice_used
+                    && !node.is_stopped()
+                    && !self.threads_waiting_above(node.level::<PRIORITY_LEVELS>())
+            }
+            None => false,
+        };
+
+        if keep_running {
+            return current;
+        }
+
+        self.next_ctx(current)
+    }
+
+    /// Prepare to block the current thread, before it can be woken with [`Scheduler::wake`].
+    ///
+    /// A wake up between this call and [`Scheduler::block`] is not lost, the thread simply does
+    /// not block. Either [`Scheduler::block`] or [`Scheduler::cancel_block`] has to follow.
+    pub fn prepare_block(&self) -> ThreadId {
+        let thread_id = self.current_ids().1.expect("only a thread can block");
+
+        let guard = self.blocked_threads.guard();
+        guard.lock().insert(thread_id, BlockedThread::Blocking);
+
+        thread_id
+    }
+
+    /// The current thread does not block after all.
+    pub fn cancel_block(&self) {
+        let Some(thread_id) = self.current_ids().1 else {
+            return;
+        };
+
+        let guard = self.blocked_threads.guard();
+        guard.lock().remove(&thread_id);
+    }
+
+    /// Block the current thread with `state`, after [`Scheduler::prepare_block`], until it is
+    /// woken.
+    ///
+    /// Only kernel threads can block, see [`arch::reschedule`].
+    pub fn block(&self, state: ThreadState) {
+        assert!(
+            matches!(state, ThreadState::Blocked | ThreadState::Sleeping),
+            "a thread cannot block as {state:?}"
+        );
+
+        // A tick between changing the state and rescheduling would block the thread too early.
+        without_interrupts(|| {
+            self.with_current(|thread| {
+                assert!(
+                    thread.process_id().is_none(),
+                    "only kernel threads can block"
+                );
+
+                thread.set_state(state);
+            })
+            .expect("only a thread can block");
+
+            arch::reschedule();
+        });
+    }
+
+    /// Block the current user thread once the system call that it is making returns, after
+    /// [`Scheduler::prepare_block`].
+    ///
+    /// The thread cannot block within the system call, so the system call is made again once the
+    /// thread is woken, see [`syscalls::next_context`](crate::interface::syscalls::next_context).
+    pub fn block_after_syscall(&self) {
+        self.with_current(|thread| {
+            assert!(
+                thread.process_id().is_some(),
+                "only user threads block after a system call"
+            );
+
+            thread.set_state(ThreadState::Blocked);
+        })
+        .expect("only a thread can block");
+    }
+
+    /// Whether the current thread blocks once it is switched out.
+    pub fn is_blocking(&self) -> bool {
+        self.with_current(|thread| {
+            matches!(thread.state(), ThreadState::Blocked | ThreadState::Sleeping)
+        })
+        .unwrap_or(false)
+    }
+
+    /// Wake the thread with id `thread_id`, returns whether it was blocked or about to block.
+    pub fn wake(&self, thread_id: ThreadId) -> bool {
+        let guard = self.blocked_threads.guard();
+        let mut blocked_threads = guard.lock();
+
+        match blocked_threads.get_mut(&thread_id) {
+            None => false,
+            Some(BlockedThread::Parked(_)) => {
+                if let Some(BlockedThread::Parked(node)) = blocked_threads.remove(&thread_id) {
+                    self.schedule_node(node);
+                }
+
+                true
+            }
+            Some(entry) => {
+                *entry = BlockedThread::Woken;
+                true
+            }
+        }
+    }
+
+    /// Block the current thread for at least `duration`.
+    pub fn sleep(&self, duration: Duration) {
+        let ticks = duration
+            .as_nanos()
+            .div_ceil(arch::TICK_INTERVAL.as_nanos())
+            .try_into()
+            .unwrap_or(u64::MAX);
+
+        let wake_tick = self.clock_ticks().saturating_add(ticks);
+
+        // Other threads may wake the thread early.
+        while self.clock_ticks() < wake_tick {
+            let thread_id = self.prepare_block();
+
+            {
+                let guard = self.sleeping_threads.guard();
+                guard.lock().insert((wake_tick, thread_id));
+            }
+
+            self.block(ThreadState::Sleeping);
+
+            let guard = self.sleeping_threads.guard();
+            guard.lock().remove(&(wake_tick, thread_id));
+        }
+    }
+
+    /// The ticks of the clock since the scheduler started, see [`arch::TICK_INTERVAL`].
+    pub fn clock_ticks(&self) -> u64 {
+        self.clock_ticks.load(Ordering::Relaxed)
+    }
+
+    /// The time that the processor with id `processor` spent, and how much of it was idle.
+    pub fn cpu_time(&self, processor: usize) -> Option<CpuTime> {
+        self.idle_threads
+            .get(processor)
+            .map(|idle_thread| idle_thread.cpu_time())
     }
 
+    /// Spawn a new kernel thread that runs `entry` on its own stack, and exits with the value it
+    /// returns.
+    pub fn spawn_kernel_thread<F>(
+        &self,
+        priority: ThreadPriority,
+        entry: F,
+    ) -> Result<JoinHandle, SchedulerError>
+    where
+        F: FnOnce() -> ExitValue + Send + Sync + 'static,
+    {
+        let entry: ThreadEntry = Box::try_new(entry).map_err(|_| SchedulerError::OutOfMemory)?;
+        let join_state = Arc::try_new(JoinState::new()).map_err(|_| SchedulerError::OutOfMemory)?;
+
+        let thread_id = self.alloc_thread_id();
+        let node = self.spawn_id(thread_id, priority, None, true, Default::default())?;
+
+        let stack_top = node
+            .kernel_stack()
+            .expect("a kernel thread with an entry should have a stack")
+            .top();
+
+        node.save_context(arch::new_kernel_context(kernel_thread_entry, stack_top));
+        node.set_entry(entry);
+        node.set_join_state(join_state.clone());
+
+        self.schedule_node(node);
+
+        Ok(JoinHandle::new(thread_id, join_state))
+    }
+
+    /// Exit the current kernel thread with `value`.
+    pub fn exit_current(&self, value: ExitValue) -> ! {
+        without_interrupts(|| {
+            self.with_current(|thread| {
+                assert!(
+                    thread.process_id().is_none(),
+                    "only kernel threads can exit on their own"
+                );
+
+                thread.exit(value);
+            })
+            .expect("only a thread can exit");
+
+            arch::reschedule();
+        });
+
+        unreachable!("an exited thread should never run again")
+    }
+
+    /// Run `f` with the thread that is running on the current processor.
+    fn with_current<R>(&self, f: impl FnOnce(&mut Thread) -> R) -> Option<R> {
+        // A tick while the lock is held would never get it.
+        without_interrupts(|| self.current_thread.lock().as_mut().map(|node| f(node)))
+    }
+
+    /// Spawn a new thread that starts running with `context`.
+    ///
+    /// The thread runs in the address space of `process`, `None` is used for kernel threads. Once
+    /// the process exits, the thread is retired instead of scheduled.
     pub fn spawn_thread(
         &self,
         priority: ThreadPriority,
-        process_id: Option<ProcessId>,
+        process: Option<Arc<Process>>,
         context: CpuContext,
     ) -> Result<ThreadId, SchedulerError> {
         let new_thread_id = self.alloc_thread_id();
 
-        let node = self.spawn_id(new_thread_id, priority, process_id, context)?;
+        // User threads need a stack for when they enter the kernel.
+        let with_kernel_stack = process.is_some();
+
+        let node = self.spawn_id(new_thread_id, priority, process, with_kernel_stack, context)?;
         self.schedule_node(node);
 
         Ok(new_thread_id)
@@ -146,35 +532,107 @@ impl Scheduler {
         &self,
         new_thread_id: ThreadId,
         priority: ThreadPriority,
-        process_id: Option<ProcessId>,
+        process: Option<Arc<Process>>,
+        with_kernel_stack: bool,
         context: CpuContext,
     ) -> Result<&'static mut QueueNode<Thread>, SchedulerError> {
         self.allocate_thread(
             new_thread_id,
             self.current_ids().1,
             priority,
-            process_id,
+            process,
+            with_kernel_stack,
             context,
         )
     }
 
     fn next_node(&self) -> Option<&'static mut QueueNode<Thread>> {
-        self.run_queues.iter().find_map(|q| q.pop())
+        (0..PRIORITY_LEVELS).find_map(|level| self.pop_level(level))
     }
 
     fn schedule_node(&self, thread_node: &'static mut QueueNode<Thread>) {
-        let index = thread_node.priority_index::<PRIORITY_LEVELS>();
+        thread_node.set_state(ThreadState::Ready);
+        thread_node.end_slice();
 
-        let queue = self
-            .run_queues
-            .get(index)
-            .or_else(|| self.run_queues.last())
-            .expect("there should at least be one run queue");
+        let level = thread_node.level::<PRIORITY_LEVELS>();
+        thread_node.start_slice(time_slice(level));
 
-        queue.push(thread_node);
+        self.push_level(level, thread_node);
     }
 
+    fn push_level(&self, level: usize, thread_node: &'static mut QueueNode<Thread>) {
+        self.queued_threads[level].fetch_add(1, Ordering::Relaxed);
+        self.run_queues[level].push(thread_node);
+    }
+
+    fn pop_level(&self, level: usize) -> Option<&'static mut QueueNode<Thread>> {
+        let node = self.run_queues[level].pop()?;
+        self.queued_threads[level].fetch_sub(1, Ordering::Relaxed);
+
+        Some(node)
+    }
+
+    /// Whether a thread is waiting in a run queue before `level`.
+    fn threads_waiting_above(&self, level: usize) -> bool {
+        self.queued_threads[..level]
+            .iter()
+            .any(|count| count.load(Ordering::Relaxed) > 0)
+    }
+
+    /// Raise every waiting thread one level, the threads keep their boost until they use up a
+    /// time slice.
+    fn age_waiting_threads(&self) {
+        for level in 1..PRIORITY_LEVELS {
+            let count = self.queued_threads[level].load(Ordering::Relaxed);
+
+            for _ in 0..count {
+                let Some(node) = self.pop_level(level) else {
+                    break;
+                };
+
+                node.boost();
+                self.push_level(node.level::<PRIORITY_LEVELS>(), node);
+            }
+        }
+    }
+
+    /// Keep the node of a blocked thread until it is woken, it is scheduled right away when it was
+    /// woken already.
+    fn park(&self, thread_node: &'static mut QueueNode<Thread>) {
+        let guard = self.blocked_threads.guard();
+        let mut blocked_threads = guard.lock();
+
+        let thread_id = thread_node.thread_id();
+
+        match blocked_threads.get_mut(&thread_id) {
+            Some(entry @ BlockedThread::Blocking) => *entry = BlockedThread::Parked(thread_node),
+            _ => {
+                blocked_threads.remove(&thread_id);
+                self.schedule_node(thread_node);
+            }
+        }
+    }
+
+    fn wake_sleeping_threads(&self) {
+        let now = self.clock_ticks.fetch_add(1, Ordering::Relaxed) + 1;
+
+        let guard = self.sleeping_threads.guard();
+        let mut sleeping_threads = guard.lock();
+
+        while let Some(&(wake_tick, thread_id)) = sleeping_threads.first() {
+            if wake_tick > now {
+                break;
+            }
+
+            sleeping_threads.pop_first();
+            self.wake(thread_id);
+        }
+    }
+
+    /// Retire a thread that is not running, its node and kernel stack are reused by the next
+    /// thread that is allocated.
     fn deallocate_thread(&self, thread_node: &'static mut QueueNode<Thread>) {
+        thread_node.finish();
         self.retired_threads.push(thread_node);
     }
 
@@ -187,12 +645,30 @@ impl Scheduler {
         thread: ThreadId,
         spawned_by: Option<ThreadId>,
         priority: ThreadPriority,
-        process_id: Option<ProcessId>,
+        process: Option<Arc<Process>>,
+        with_kernel_stack: bool,
         context: CpuContext,
     ) -> Result<&'static mut QueueNode<Thread>, SchedulerError> {
-        let new_thread = Thread::new(thread, spawned_by, priority, process_id, context);
+        let mut retired = self.retired_threads.pop();
+
+        let kernel_stack = match retired.as_mut().and_then(|node| node.take_kernel_stack()) {
+            Some(stack) if with_kernel_stack => Some(stack),
+            _ if with_kernel_stack => match KernelStack::new() {
+                Ok(stack) => Some(stack),
+                Err(err) => {
+                    if let Some(retired) = retired {
+                        self.retired_threads.push(retired);
+                    }
+
+                    return Err(err);
+                }
+            },
+            _ => None,
+        };
 
-        if let Some(retired) = self.retired_threads.pop() {
+        let new_thread = Thread::new(thread, spawned_by, priority, process, kernel_stack, context);
+
+        if let Some(retired) = retired {
             **retired = new_thread;
             return Ok(retired);
         }
@@ -214,7 +690,8 @@ impl Scheduler {
         Ok(Box::leak(new_node_alloc))
     }
 
-    fn current_ids(&self) -> (Option<ProcessId>, Option<ThreadId>) {
+    /// The process and thread that are running on the current processor.
+    pub fn current_ids(&self) -> (Option<ProcessId>, Option<ThreadId>) {
         let (process_id, thread_id) = self.current_thread_id.load(Ordering::Relaxed);
 
         let process_id = match process_id {
@@ -232,3 +709,44 @@ impl Scheduler {
 }
 
 pub static SCHEDULER: Scheduler = Scheduler::new();
+
+/// The first code of a thread spawned with [`Scheduler::spawn_kernel_thread`].
+extern "C" fn kernel_thread_entry() -> ! {
+    let entry = SCHEDULER
+        .with_current(|thread| thread.take_entry())
+        .flatten()
+        .expect("a kernel thread should start with an entry");
+
+    SCHEDULER.exit_current(entry())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_sleep() {
+        let before = SCHEDULER.clock_ticks();
+
+        SCHEDULER.sleep(arch::TICK_INTERVAL * 3);
+
+        assert!(SCHEDULER.clock_ticks() >= before + 3);
+    }
+
+    #[test_case]
+    fn test_idle_while_sleeping() {
+        // The thread might wake up on another processor, so the idle ticks of all are counted.
+        let idle_ticks = || {
+            (0..)
+                .map_while(|processor| SCHEDULER.cpu_time(processor))
+                .map(|cpu_time| cpu_time.idle_ticks)
+                .sum::<u64>()
+        };
+
+        let before = idle_ticks();
+
+        SCHEDULER.sleep(arch::TICK_INTERVAL * 3);
+
+        assert!(idle_ticks() > before);
+    }
+}
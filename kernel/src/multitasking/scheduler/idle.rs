@@ -0,0 +1,81 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use essentials::{nb::queue::QueueNode, spin::SpinLock};
+use x86_64::interrupt::enable_interrupts_and_halt;
+
+use crate::multitasking::{ids::ThreadId, scheduler::Thread};
+
+/// How much time a processor spent, counted in timer ticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuTime {
+    pub ticks: u64,
+    /// The ticks in which the processor was running its idle thread.
+    pub idle_ticks: u64,
+}
+
+/// The thread a processor runs when no other thread can run, it is never placed in the run
+/// queues.
+pub struct IdleThread {
+    thread_id: ThreadId,
+    /// The node of the thread, while it is not running.
+    node: SpinLock<Option<&'static mut QueueNode<Thread>>>,
+
+    ticks: AtomicU64,
+    idle_ticks: AtomicU64,
+}
+
+impl IdleThread {
+    /// The thread runs on the kernel stack of its node.
+    pub fn new(node: &'static mut QueueNode<Thread>) -> Self {
+        Self {
+            thread_id: node.thread_id(),
+            node: SpinLock::new(Some(node)),
+            ticks: AtomicU64::new(0),
+            idle_ticks: AtomicU64::new(0),
+        }
+    }
+
+    pub fn thread_id(&self) -> ThreadId {
+        self.thread_id
+    }
+
+    /// Give the node back once the processor switches to another thread.
+    pub fn park(&self, node: &'static mut QueueNode<Thread>) {
+        assert_eq!(self.thread_id, node.thread_id());
+
+        let mut idle_node = self.node.lock();
+        assert!(idle_node.is_none(), "the idle thread is parked twice");
+
+        *idle_node = Some(node);
+    }
+
+    /// Take the node to run the idle thread.
+    pub fn unpark(&self) -> &'static mut QueueNode<Thread> {
+        self.node
+            .lock()
+            .take()
+            .expect("the idle thread should not be running")
+    }
+
+    /// Count a tick of the timer, `current` is the thread that was interrupted.
+    pub fn account_tick(&self, current: Option<ThreadId>) {
+        self.ticks.fetch_add(1, Ordering::Relaxed);
+
+        if current == Some(self.thread_id) {
+            self.idle_ticks.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn cpu_time(&self) -> CpuTime {
+        CpuTime {
+            ticks: self.ticks.load(Ordering::Relaxed),
+            idle_ticks: self.idle_ticks.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub extern "C" fn idle_loop() -> ! {
+    loop {
+        enable_interrupts_and_halt();
+    }
+}
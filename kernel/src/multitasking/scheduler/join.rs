@@ -0,0 +1,113 @@
+use alloc::sync::Arc;
+use essentials::spin::SpinLock;
+
+use crate::{multitasking::ids::ThreadId, utils::InterruptGuard};
+
+use super::WaitQueue;
+
+/// The value a kernel thread exits with.
+pub type ExitValue = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadExit {
+    /// The thread exited with a value.
+    Exited(ExitValue),
+    /// The thread was stopped, because its process exited.
+    Stopped,
+}
+
+/// Shared by a thread and its [`JoinHandle`].
+pub struct JoinState {
+    exit: InterruptGuard<SpinLock<Option<ThreadExit>>>,
+    waiters: WaitQueue,
+}
+
+impl JoinState {
+    pub const fn new() -> Self {
+        Self {
+            exit: InterruptGuard::new_lock(None),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    /// Called once the thread will never run again.
+    pub fn finish(&self, exit: ThreadExit) {
+        {
+            let guard = self.exit.guard();
+            guard.lock().get_or_insert(exit);
+        }
+
+        self.waiters.wake_all();
+    }
+
+    fn exit(&self) -> Option<ThreadExit> {
+        let guard = self.exit.guard();
+        let exit = *guard.lock();
+
+        exit
+    }
+}
+
+impl Default for JoinState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Waits for a thread to exit, dropping the handle detaches the thread.
+pub struct JoinHandle {
+    thread_id: ThreadId,
+    state: Arc<JoinState>,
+}
+
+impl JoinHandle {
+    pub(super) fn new(thread_id: ThreadId, state: Arc<JoinState>) -> Self {
+        Self { thread_id, state }
+    }
+
+    pub fn thread_id(&self) -> ThreadId {
+        self.thread_id
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.state.exit().is_some()
+    }
+
+    /// Block the current thread until the thread exits.
+    pub fn join(self) -> ThreadExit {
+        self.state
+            .waiters
+            .wait_until(|| self.state.exit().is_some());
+
+        self.state
+            .exit()
+            .expect("the thread should have exited after waiting")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::multitasking::scheduler::{LOWEST_PRIORITY, SCHEDULER};
+
+    use super::*;
+
+    #[test_case]
+    fn test_join_exit_value() {
+        let handle = SCHEDULER
+            .spawn_kernel_thread(LOWEST_PRIORITY, || 42)
+            .unwrap();
+
+        assert_eq!(ThreadExit::Exited(42), handle.join());
+    }
+
+    #[test_case]
+    fn test_exited_threads_are_reused() {
+        for value in 0..4 {
+            let handle = SCHEDULER
+                .spawn_kernel_thread(LOWEST_PRIORITY, move || value)
+                .unwrap();
+
+            assert_eq!(ThreadExit::Exited(value), handle.join());
+        }
+    }
+}
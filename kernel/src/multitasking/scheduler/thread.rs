@@ -1,17 +1,78 @@
+use core::mem::MaybeUninit;
+
+use alloc::{boxed::Box, sync::Arc};
+use essentials::address::{PhysicalAddress, VirtualAddress};
+
 use crate::{
-    arch::CpuContext,
-    multitasking::{ids::ThreadId, process::ProcessId},
+    arch::{self, CpuContext},
+    multitasking::{
+        ids::ThreadId,
+        process::{Process, ProcessId},
+        scheduler::{ExitValue, JoinState, SchedulerError, ThreadExit},
+    },
 };
 
 pub type ThreadPriority = u8;
 
+/// The code that a kernel thread spawned with [`super::Scheduler::spawn_kernel_thread`] runs.
+pub type ThreadEntry = Box<dyn FnOnce() -> ExitValue + Send + Sync>;
+
 pub const LOWEST_PRIORITY: ThreadPriority = ThreadPriority::MIN;
+pub const HIGHEST_PRIORITY: ThreadPriority = ThreadPriority::MAX;
+
+/// The stack that the kernel uses while handling the system calls of a user thread.
+pub struct KernelStack {
+    memory: Box<[MaybeUninit<u8>]>,
+}
+
+impl KernelStack {
+    pub const SIZE: usize = 4096 * 16;
+
+    pub fn new() -> Result<Self, SchedulerError> {
+        let memory =
+            Box::try_new_uninit_slice(Self::SIZE).map_err(|_| SchedulerError::OutOfMemory)?;
+
+        Ok(Self { memory })
+    }
+
+    /// The (16 byte aligned) initial stack pointer.
+    pub fn top(&self) -> VirtualAddress {
+        VirtualAddress::from(self.memory.as_ptr_range().end).align_down(16)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadState {
+    /// Waiting in a run queue.
+    Ready,
+    Running,
+    /// Waiting until another thread wakes it.
+    Blocked,
+    /// Waiting until its sleep is over.
+    Sleeping,
+    /// The thread will never run again.
+    Exited,
+}
 
 pub struct Thread {
     thread_id: ThreadId,
+    state: ThreadState,
     spawned_by: Option<ThreadId>,
     priority: ThreadPriority,
-    process_id: Option<ProcessId>,
+    /// Keeps the memory of the process alive, while the thread may still run.
+    process: Option<Arc<Process>>,
+    kernel_stack: Option<KernelStack>,
+    /// Taken by the thread once it starts running.
+    entry: Option<ThreadEntry>,
+    exit_value: Option<ExitValue>,
+    join_state: Option<Arc<JoinState>>,
+
+    /// How many levels the thread has been raised above its priority, while it was waiting.
+    boost: usize,
+    /// The ticks that are left of the current time slice.
+    remaining_slice: u64,
+    /// The ticks the thread has been running in total.
+    run_ticks: u64,
 
     context: CpuContext,
 }
@@ -21,31 +82,161 @@ impl Thread {
         thread_id: ThreadId,
         spawned_by: Option<ThreadId>,
         priority: ThreadPriority,
-        process_id: Option<ProcessId>,
+        process: Option<Arc<Process>>,
+        kernel_stack: Option<KernelStack>,
         context: CpuContext,
     ) -> Self {
         assert_ne!(0, thread_id);
 
         Self {
             thread_id,
+            state: ThreadState::Ready,
             spawned_by,
-            process_id,
+            process,
             priority,
+            kernel_stack,
+            entry: None,
+            exit_value: None,
+            join_state: None,
+            boost: 0,
+            remaining_slice: 0,
+            run_ticks: 0,
             context,
         }
     }
 
+    pub const fn thread_id(&self) -> ThreadId {
+        self.thread_id
+    }
+
+    pub const fn state(&self) -> ThreadState {
+        self.state
+    }
+
+    pub fn set_state(&mut self, state: ThreadState) {
+        self.state = state;
+    }
+
+    pub fn process_id(&self) -> Option<ProcessId> {
+        self.process.as_ref().map(|process| process.process_id())
+    }
+
     pub const fn priority(&self) -> ThreadPriority {
         self.priority
     }
 
     pub const fn priority_index<const VEC_SIZE: usize>(&self) -> usize {
         assert!(VEC_SIZE.is_power_of_two());
-        let step_size = ThreadPriority::MAX as usize / VEC_SIZE;
+        let step_size = (ThreadPriority::MAX as usize + 1) / VEC_SIZE;
 
         (ThreadPriority::MAX - self.priority()) as usize / step_size
     }
 
+    /// The index of the run queue of the thread, which includes the boost of aging.
+    pub const fn level<const VEC_SIZE: usize>(&self) -> usize {
+        self.priority_index::<VEC_SIZE>().saturating_sub(self.boost)
+    }
+
+    /// Raise the thread one level, because it has been waiting for too long.
+    pub fn boost(&mut self) {
+        self.boost += 1;
+    }
+
+    /// Stop running, the boost is lost when the time slice was used up.
+    pub fn end_slice(&mut self) {
+        if self.remaining_slice == 0 {
+            self.boost = 0;
+        }
+    }
+
+    pub fn start_slice(&mut self, ticks: u64) {
+        self.remaining_slice = ticks;
+    }
+
+    /// Count a tick in which the thread was running, returns whether its time slice is used up.
+    /// The tick counts towards the CPU time of the process as well.
+    pub fn account_tick(&mut self) -> bool {
+        self.run_ticks += 1;
+
+        if let Some(process) = &self.process {
+            process.account_tick();
+        }
+
+        self.remaining_slice = self.remaining_slice.saturating_sub(1);
+
+        self.remaining_slice == 0
+    }
+
+    pub const fn run_ticks(&self) -> u64 {
+        self.run_ticks
+    }
+
+    /// The level 4 table that has to be active while the thread runs, kernel threads run in the
+    /// address space of the kernel and return `None`.
+    pub fn address_space(&self) -> Option<PhysicalAddress> {
+        self.process
+            .as_ref()
+            .map(|process| process.manager().l4_table())
+    }
+
+    /// Whether the thread or its process has exited, in which case it should never run again.
+    ///
+    /// A thread that was interrupted in a system call still finishes it once its process has
+    /// exited, so that everything the system call holds is released.
+    pub fn is_stopped(&self) -> bool {
+        let process_exited = self
+            .process
+            .as_ref()
+            .is_some_and(|process| process.has_exited());
+
+        self.state == ThreadState::Exited
+            || (process_exited && arch::is_user_context(&self.context))
+    }
+
+    /// Exit with `value`, the thread is retired the next time it is switched out.
+    pub fn exit(&mut self, value: ExitValue) {
+        self.exit_value = Some(value);
+        self.state = ThreadState::Exited;
+    }
+
+    /// Release everything besides the kernel stack once the thread will never run again, and
+    /// notify the joiner.
+    pub fn finish(&mut self) {
+        self.state = ThreadState::Exited;
+        self.process = None;
+        self.entry = None;
+
+        if let Some(join_state) = self.join_state.take() {
+            join_state.finish(match self.exit_value {
+                Some(value) => ThreadExit::Exited(value),
+                None => ThreadExit::Stopped,
+            });
+        }
+    }
+
+    pub fn set_entry(&mut self, entry: ThreadEntry) {
+        self.entry = Some(entry);
+    }
+
+    pub fn take_entry(&mut self) -> Option<ThreadEntry> {
+        self.entry.take()
+    }
+
+    pub fn set_join_state(&mut self, join_state: Arc<JoinState>) {
+        self.join_state = Some(join_state);
+    }
+
+    /// User threads use their kernel stack for system calls, kernel threads spawned with an entry
+    /// run on it. Other kernel threads bring their own stack.
+    pub fn kernel_stack(&self) -> Option<&KernelStack> {
+        self.kernel_stack.as_ref()
+    }
+
+    /// Take the kernel stack of a retired thread, so it can be reused.
+    pub fn take_kernel_stack(&mut self) -> Option<KernelStack> {
+        self.kernel_stack.take()
+    }
+
     pub fn save_context(&mut self, ctx: CpuContext) {
         self.context = ctx;
     }
@@ -54,3 +245,43 @@ impl Thread {
         self.context.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_thread(priority: ThreadPriority) -> Thread {
+        Thread::new(1, None, priority, None, None, Default::default())
+    }
+
+    #[test_case]
+    fn test_priority_index() {
+        assert_eq!(0, new_thread(HIGHEST_PRIORITY).priority_index::<8>());
+        assert_eq!(7, new_thread(LOWEST_PRIORITY).priority_index::<8>());
+        assert_eq!(3, new_thread(128).priority_index::<8>());
+        assert_eq!(0, new_thread(LOWEST_PRIORITY).priority_index::<1>());
+    }
+
+    #[test_case]
+    fn test_boost_is_lost_after_a_full_slice() {
+        let mut thread = new_thread(LOWEST_PRIORITY);
+
+        thread.start_slice(2);
+        thread.boost();
+        thread.boost();
+        assert_eq!(5, thread.level::<8>());
+
+        // Preempted before the slice is used up.
+        assert!(!thread.account_tick());
+        thread.end_slice();
+        assert_eq!(5, thread.level::<8>());
+
+        thread.start_slice(2);
+        assert!(!thread.account_tick());
+        assert!(thread.account_tick());
+        thread.end_slice();
+
+        assert_eq!(7, thread.level::<8>());
+        assert_eq!(3, thread.run_ticks());
+    }
+}
@@ -0,0 +1,149 @@
+use alloc::collections::VecDeque;
+use essentials::spin::SpinLock;
+
+use crate::{multitasking::ids::ThreadId, utils::InterruptGuard};
+
+use super::{ThreadState, SCHEDULER};
+
+/// Threads that block until a condition holds, and the threads that change the condition wake
+/// them.
+pub struct WaitQueue {
+    waiters: InterruptGuard<SpinLock<VecDeque<ThreadId>>>,
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self {
+            waiters: InterruptGuard::new_lock(VecDeque::new()),
+        }
+    }
+
+    /// Block the current thread until `condition` holds, it is checked again every time the
+    /// thread is woken.
+    ///
+    /// The condition has to be changed before the queue is woken, otherwise the wake up may be
+    /// missed.
+    pub fn wait_until(&self, mut condition: impl FnMut() -> bool) {
+        while !condition() {
+            let thread_id = SCHEDULER.prepare_block();
+
+            {
+                let guard = self.waiters.guard();
+                guard.lock().push_back(thread_id);
+            }
+
+            if condition() {
+                self.remove(thread_id);
+                SCHEDULER.cancel_block();
+                return;
+            }
+
+            SCHEDULER.block(ThreadState::Blocked);
+
+            // The thread is only still in the queue when it was woken by something else.
+            self.remove(thread_id);
+        }
+    }
+
+    /// Block the current user thread once its system call returns, unless `condition` holds.
+    /// Returns whether the thread blocks.
+    ///
+    /// The system call is made again once the thread is woken, see
+    /// [`Scheduler::block_after_syscall`](super::Scheduler::block_after_syscall). The thread stays
+    /// in the queue when something else wakes it, which only causes a spurious wake up.
+    pub fn block_after_syscall(&self, condition: impl FnOnce() -> bool) -> bool {
+        let thread_id = SCHEDULER.prepare_block();
+
+        {
+            let guard = self.waiters.guard();
+            let mut waiters = guard.lock();
+
+            waiters.retain(|waiter| *waiter != thread_id);
+            waiters.push_back(thread_id);
+        }
+
+        if condition() {
+            self.remove(thread_id);
+            SCHEDULER.cancel_block();
+            return false;
+        }
+
+        SCHEDULER.block_after_syscall();
+
+        true
+    }
+
+    /// Wake the thread that has been waiting the longest, returns whether there was one.
+    pub fn wake_one(&self) -> bool {
+        let guard = self.waiters.guard();
+        let mut waiters = guard.lock();
+
+        // The thread is woken while the queue is locked, so it cannot wait for something else in
+        // the meantime.
+        while let Some(thread_id) = waiters.pop_front() {
+            if SCHEDULER.wake(thread_id) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Wake every waiting thread, returns how many there were.
+    pub fn wake_all(&self) -> usize {
+        let guard = self.waiters.guard();
+        let mut waiters = guard.lock();
+
+        waiters
+            .drain(..)
+            .filter(|thread_id| SCHEDULER.wake(*thread_id))
+            .count()
+    }
+
+    fn remove(&self, thread_id: ThreadId) {
+        let guard = self.waiters.guard();
+        guard.lock().retain(|waiter| *waiter != thread_id);
+    }
+}
+
+impl Default for WaitQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use alloc::sync::Arc;
+
+    use crate::multitasking::scheduler::LOWEST_PRIORITY;
+
+    use super::*;
+
+    #[test_case]
+    fn test_wait_until_woken() {
+        let queue = Arc::new(WaitQueue::new());
+        let done = Arc::new(AtomicBool::new(false));
+
+        let waker = {
+            let queue = queue.clone();
+            let done = done.clone();
+
+            SCHEDULER
+                .spawn_kernel_thread(LOWEST_PRIORITY, move || {
+                    done.store(true, Ordering::Release);
+                    queue.wake_all() as u64
+                })
+                .unwrap()
+        };
+
+        queue.wait_until(|| done.load(Ordering::Acquire));
+
+        assert!(done.load(Ordering::Acquire));
+        assert!(!queue.wake_one());
+
+        waker.join();
+    }
+}
@@ -0,0 +1,16 @@
+//! Synchronization primitives that block the calling thread, instead of spinning until they are
+//! available.
+//!
+//! Only kernel threads can block, see [`Scheduler::block`](super::scheduler::Scheduler::block).
+//! Interrupt handlers and system calls keep using the spin locks of `essentials::spin`, wrapped
+//! in an [`InterruptGuard`](crate::utils::InterruptGuard).
+
+mod condvar;
+mod mutex;
+mod rwlock;
+mod semaphore;
+
+pub use condvar::Condvar;
+pub use mutex::{Mutex, MutexGuard};
+pub use rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use semaphore::Semaphore;
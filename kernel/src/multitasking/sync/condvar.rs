@@ -0,0 +1,109 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::multitasking::scheduler::WaitQueue;
+
+use super::MutexGuard;
+
+/// Threads block on a condition variable until another thread notifies them, while the mutex
+/// that protects the condition is unlocked.
+///
+/// A thread may also wake up without being notified, so the condition has to be checked again,
+/// see [`Condvar::wait_while`].
+pub struct Condvar {
+    /// Incremented by every notification, so a notification between unlocking the mutex and
+    /// blocking is not missed.
+    generation: AtomicU64,
+    waiters: WaitQueue,
+}
+
+impl Condvar {
+    pub const fn new() -> Self {
+        Self {
+            generation: AtomicU64::new(0),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    /// Unlock the mutex of `guard` and block until notified, the mutex is locked again before
+    /// returning.
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        let mutex = guard.mutex();
+        let generation = self.generation.load(Ordering::Acquire);
+
+        drop(guard);
+
+        self.waiters
+            .wait_until(|| self.generation.load(Ordering::Acquire) != generation);
+
+        mutex.lock()
+    }
+
+    /// Block while `condition` holds.
+    pub fn wait_while<'a, T>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: impl FnMut(&mut T) -> bool,
+    ) -> MutexGuard<'a, T> {
+        while condition(&mut guard) {
+            guard = self.wait(guard);
+        }
+
+        guard
+    }
+
+    pub fn notify_one(&self) {
+        self.generation.fetch_add(1, Ordering::Release);
+        self.waiters.wake_one();
+    }
+
+    pub fn notify_all(&self) {
+        self.generation.fetch_add(1, Ordering::Release);
+        self.waiters.wake_all();
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+
+    use crate::multitasking::{
+        scheduler::{LOWEST_PRIORITY, SCHEDULER},
+        sync::Mutex,
+    };
+
+    use super::*;
+
+    #[test_case]
+    fn test_wait_while() {
+        let state = Arc::new((Mutex::new(false), Condvar::new()));
+
+        let handle = {
+            let state = state.clone();
+
+            SCHEDULER
+                .spawn_kernel_thread(LOWEST_PRIORITY, move || {
+                    let (ready, condvar) = &*state;
+
+                    *ready.lock() = true;
+                    condvar.notify_all();
+
+                    0
+                })
+                .unwrap()
+        };
+
+        let (ready, condvar) = &*state;
+        let guard = condvar.wait_while(ready.lock(), |ready| !*ready);
+
+        assert!(*guard);
+        drop(guard);
+
+        handle.join();
+    }
+}
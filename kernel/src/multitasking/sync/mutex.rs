@@ -0,0 +1,144 @@
+use core::{
+    cell::UnsafeCell,
+    fmt::{Debug, Formatter},
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::multitasking::scheduler::WaitQueue;
+
+pub struct MutexGuard<'a, T> {
+    mutex: &'a Mutex<T>,
+}
+
+impl<'a, T> MutexGuard<'a, T> {
+    /// The mutex of the guard, so it can be locked again after unlocking it.
+    pub(super) fn mutex(&self) -> &'a Mutex<T> {
+        self.mutex
+    }
+}
+
+impl<T> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+/// Like a [`SpinLock`](essentials::spin::SpinLock), but the thread blocks until the mutex is
+/// unlocked.
+pub struct Mutex<T> {
+    data: UnsafeCell<T>,
+    is_locked: AtomicBool,
+    waiters: WaitQueue,
+}
+
+impl<T> Mutex<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            data: UnsafeCell::new(data),
+            is_locked: AtomicBool::new(false),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        self.waiters.wait_until(|| self.acquire());
+
+        MutexGuard { mutex: self }
+    }
+
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        self.acquire().then(|| MutexGuard { mutex: self })
+    }
+
+    fn acquire(&self) -> bool {
+        self.is_locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    fn unlock(&self) {
+        self.is_locked.store(false, Ordering::Release);
+        self.waiters.wake_one();
+    }
+}
+
+impl<T> AsMut<T> for Mutex<T> {
+    fn as_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+unsafe impl<T: Send> Sync for Mutex<T> {}
+unsafe impl<T: Send> Send for Mutex<T> {}
+
+impl<T> Debug for Mutex<T>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self.try_lock() {
+            Some(guard) => write!(f, "Mutex({:?})", *guard),
+            None => write!(f, "Mutex(<locked>)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{sync::Arc, vec::Vec};
+
+    use crate::{
+        arch,
+        multitasking::scheduler::{LOWEST_PRIORITY, SCHEDULER},
+    };
+
+    use super::*;
+
+    #[test_case]
+    fn test_contended_lock() {
+        let counter = Arc::new(Mutex::new(0u64));
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let counter = counter.clone();
+
+                SCHEDULER
+                    .spawn_kernel_thread(LOWEST_PRIORITY, move || {
+                        for _ in 0..100 {
+                            let mut count = counter.lock();
+                            let value = *count;
+
+                            // Give the other threads a chance to find the mutex locked.
+                            arch::reschedule();
+                            *count = value + 1;
+                        }
+
+                        0
+                    })
+                    .unwrap()
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join();
+        }
+
+        assert_eq!(400, *counter.lock());
+        assert!(counter.try_lock().is_some());
+    }
+}
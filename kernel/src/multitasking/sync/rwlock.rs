@@ -0,0 +1,179 @@
+use core::{
+    cell::UnsafeCell,
+    fmt::{Debug, Formatter},
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::multitasking::scheduler::WaitQueue;
+
+/// The state of a lock that is held by a writer, otherwise it is the number of readers.
+const WRITER: usize = usize::MAX;
+
+pub struct RwLockReadGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.unlock_read();
+    }
+}
+
+impl<T> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+pub struct RwLockWriteGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.unlock_write();
+    }
+}
+
+impl<T> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+/// Many readers or a single writer, the threads block until the lock is available.
+///
+/// Readers and writers are not queued in order, so a steady stream of readers can starve a
+/// writer.
+pub struct RwLock<T> {
+    data: UnsafeCell<T>,
+    state: AtomicUsize,
+    readers: WaitQueue,
+    writers: WaitQueue,
+}
+
+impl<T> RwLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            data: UnsafeCell::new(data),
+            state: AtomicUsize::new(0),
+            readers: WaitQueue::new(),
+            writers: WaitQueue::new(),
+        }
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.readers.wait_until(|| self.acquire_read());
+
+        RwLockReadGuard { lock: self }
+    }
+
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        self.acquire_read().then(|| RwLockReadGuard { lock: self })
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.writers.wait_until(|| self.acquire_write());
+
+        RwLockWriteGuard { lock: self }
+    }
+
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        self.acquire_write()
+            .then(|| RwLockWriteGuard { lock: self })
+    }
+
+    fn acquire_read(&self) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+
+        // The last count before `WRITER` is never used, so the readers cannot overflow into it.
+        while state < WRITER - 1 {
+            match self.state.compare_exchange_weak(
+                state,
+                state + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(current) => state = current,
+            }
+        }
+
+        false
+    }
+
+    fn acquire_write(&self) -> bool {
+        self.state
+            .compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    fn unlock_read(&self) {
+        if self.state.fetch_sub(1, Ordering::Release) == 1 {
+            self.writers.wake_one();
+        }
+    }
+
+    fn unlock_write(&self) {
+        self.state.store(0, Ordering::Release);
+
+        self.readers.wake_all();
+        self.writers.wake_one();
+    }
+}
+
+impl<T> AsMut<T> for RwLock<T> {
+    fn as_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+unsafe impl<T: Send> Send for RwLock<T> {}
+
+impl<T> Debug for RwLock<T>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self.try_read() {
+            Some(guard) => write!(f, "RwLock({:?})", *guard),
+            None => write!(f, "RwLock(<locked>)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_readers_exclude_writer() {
+        let lock = RwLock::new(1);
+
+        let first = lock.read();
+        let second = lock.try_read().unwrap();
+        assert!(lock.try_write().is_none());
+
+        drop(first);
+        drop(second);
+
+        let mut writer = lock.try_write().unwrap();
+        assert!(lock.try_read().is_none());
+        *writer = 2;
+        drop(writer);
+
+        assert_eq!(2, *lock.read());
+    }
+}
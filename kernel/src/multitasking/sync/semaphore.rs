@@ -0,0 +1,71 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::multitasking::scheduler::WaitQueue;
+
+/// A counter of permits, threads block until a permit is available.
+pub struct Semaphore {
+    permits: AtomicUsize,
+    waiters: WaitQueue,
+}
+
+impl Semaphore {
+    pub const fn new(permits: usize) -> Self {
+        Self {
+            permits: AtomicUsize::new(permits),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    pub fn available_permits(&self) -> usize {
+        self.permits.load(Ordering::Relaxed)
+    }
+
+    /// Take a permit, blocks until one is available.
+    pub fn acquire(&self) {
+        self.waiters.wait_until(|| self.try_acquire());
+    }
+
+    /// Take a permit if one is available, returns whether it was taken.
+    pub fn try_acquire(&self) -> bool {
+        let mut permits = self.permits.load(Ordering::Relaxed);
+
+        while permits > 0 {
+            match self.permits.compare_exchange_weak(
+                permits,
+                permits - 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(current) => permits = current,
+            }
+        }
+
+        false
+    }
+
+    /// Give a permit back, or add a new one.
+    pub fn release(&self) {
+        self.permits.fetch_add(1, Ordering::Release);
+        self.waiters.wake_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_permits() {
+        let semaphore = Semaphore::new(2);
+
+        semaphore.acquire();
+        assert!(semaphore.try_acquire());
+        assert!(!semaphore.try_acquire());
+
+        semaphore.release();
+        assert_eq!(1, semaphore.available_permits());
+        semaphore.acquire();
+        assert_eq!(0, semaphore.available_permits());
+    }
+}
@@ -24,6 +24,11 @@ impl<T> ProcLocal<T> {
             proc_storage: vec.into_boxed_slice(),
         }
     }
+
+    /// The value of the processor with id `processor`, instead of the current one.
+    pub fn get(&self, processor: usize) -> Option<&T> {
+        self.proc_storage.get(processor)
+    }
 }
 
 impl<T> Deref for ProcLocal<T> {
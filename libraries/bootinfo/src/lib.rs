@@ -4,6 +4,11 @@ use core::fmt::Debug;
 
 use essentials::address::VirtualAddress;
 
+/// The physical page that the pre-kernel keeps out of the usable memory and identity maps, for the
+/// trampoline that starts the application processors. They start in real mode, so it has to be
+/// below 1 MiB.
+pub const AP_TRAMPOLINE_ADDR: u64 = 0x8000;
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 pub struct MemoryRegion {
@@ -26,6 +31,32 @@ impl MemoryRegion {
     }
 }
 
+/// A file that the bootloader loaded into memory next to the kernel, like an initial ramdisk.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct BootModule {
+    /// The physical memory that holds the module.
+    pub region: MemoryRegion,
+
+    pub cmdline_addr: u64,
+    pub cmdline_len: u64,
+}
+
+impl BootModule {
+    pub fn cmdline(&self) -> Option<&'static str> {
+        if self.cmdline_addr == 0 || self.cmdline_len == 0 {
+            return None;
+        }
+
+        Some(unsafe {
+            core::str::from_utf8_unchecked(core::slice::from_raw_parts(
+                self.cmdline_addr as *const _,
+                self.cmdline_len as usize,
+            ))
+        })
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Debug)]
 pub struct BootInfoData {
@@ -48,6 +79,9 @@ pub struct BootInfoData {
     pub bootloader_name_len: u64,
 
     pub rsdp_addr: u64,
+
+    pub modules_addr: u64,
+    pub modules_len: u64,
 }
 
 pub struct BootInfo {
@@ -73,6 +107,20 @@ impl BootInfo {
         }
     }
 
+    /// The modules loaded by the bootloader, except for the kernel itself.
+    pub fn modules(&self) -> &'static [BootModule] {
+        if self.data.modules_addr == 0 {
+            return &[];
+        }
+
+        unsafe {
+            core::slice::from_raw_parts(
+                self.data.modules_addr as *const _,
+                self.data.modules_len as usize,
+            )
+        }
+    }
+
     pub fn kernel_stack(&self) -> MemoryRegion {
         self.data.kernel_stack
     }
@@ -136,6 +184,7 @@ impl Debug for BootInfo {
             .field("kernel_arguments", &self.kernel_arguments())
             .field("bootloader_name", &self.bootloader_name())
             .field("rsdp_addr", &self.rsdp_addr())
+            .field("modules", &self.modules())
             .finish()
     }
 }
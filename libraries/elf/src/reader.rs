@@ -13,13 +13,13 @@ pub use error::ElfReadError;
 unsafe fn read_struct<T: Clone>(raw_data: &[u8], offset: usize) -> Result<T, ElfReadError> {
     use core::mem::{align_of, size_of};
 
-    let value_data = &raw_data[offset..];
+    let value_data = raw_data.get(offset..).ok_or(ElfReadError::TooSmall)?;
 
     if value_data.len() < size_of::<T>() {
         return Err(ElfReadError::TooSmall);
     }
 
-    if value_data.as_ptr() as usize % align_of::<T>() != 0 {
+    if !(value_data.as_ptr() as usize).is_multiple_of(align_of::<T>()) {
         return Err(ElfReadError::NotAligned);
     }
 
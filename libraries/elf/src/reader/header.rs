@@ -154,10 +154,12 @@ impl<'a, P: Copy + TryInto<usize> + TryInto<u32>> ElfHeaderReader<'a, P> {
             return Err(ElfReadError::InvalidEntrySize);
         }
 
-        let min_size = offset + entry_size * len;
+        let min_size = offset
+            .checked_add(entry_size * len)
+            .ok_or(ElfReadError::TooSmall)?;
 
         let align = align_of::<T>();
-        if offset % align != 0 {
+        if !offset.is_multiple_of(align) {
             return Err(ElfReadError::NotAligned);
         }
 
@@ -190,7 +192,7 @@ impl<'a, P: Copy + TryInto<usize> + TryInto<u32>> ElfHeaderReader<'a, P> {
             return Err(ElfReadError::InvalidEntrySize);
         }
 
-        if table_size % size_of::<RelocationTableEntry<P>>() != 0 {
+        if !table_size.is_multiple_of(size_of::<RelocationTableEntry<P>>()) {
             return Err(ElfReadError::NotAligned);
         }
 
@@ -201,7 +203,9 @@ impl<'a, P: Copy + TryInto<usize> + TryInto<u32>> ElfHeaderReader<'a, P> {
             let size: Result<usize, _> = header.file_size().try_into();
 
             match (addr, size) {
-                (Ok(addr), Ok(size)) => table_ptr >= addr && table_ptr <= addr + size,
+                (Ok(addr), Ok(size)) => addr
+                    .checked_add(size)
+                    .is_some_and(|end| table_ptr >= addr && table_ptr <= end),
                 _ => false,
             }
         });
@@ -218,7 +222,10 @@ impl<'a, P: Copy + TryInto<usize> + TryInto<u32>> ElfHeaderReader<'a, P> {
         let bytes = table_section.bytes()?;
 
         let table_index = table_ptr - offset_addr;
-        let table_bytes = &bytes[table_index..(table_index + table_size)];
+        let table_bytes = table_index
+            .checked_add(table_size)
+            .and_then(|end| bytes.get(table_index..end))
+            .ok_or(ElfReadError::TooSmall)?;
 
         Ok(Some(unsafe {
             core::slice::from_raw_parts(table_bytes.as_ptr() as *const _, table_len)
@@ -31,7 +31,7 @@ pub struct ElfReader<'a> {
 
 impl<'a> ElfReader<'a> {
     fn magic_matches(raw_data: &[u8]) -> bool {
-        raw_data[0..4] == [0x7f, b'E', b'L', b'F']
+        raw_data.get(0..4) == Some(&[0x7f, b'E', b'L', b'F'])
     }
 
     pub fn new(raw_data: &'a [u8]) -> Result<Self, ElfReadError> {
@@ -23,9 +23,9 @@ pub struct SectionFlags {
 }
 
 impl SectionFlags {
-    const EXEC_BIT: u32 = 1 << 1;
-    const WRITABLE_BIT: u32 = 2 << 1;
-    const READABLE_BIT: u32 = 3 << 1;
+    const EXEC_BIT: u32 = 1 << 0;
+    const WRITABLE_BIT: u32 = 1 << 1;
+    const READABLE_BIT: u32 = 1 << 2;
 
     pub fn executable(&self) -> bool {
         (self.flags & Self::EXEC_BIT) != 0
@@ -175,11 +175,11 @@ impl<'a, P: Copy + TryInto<usize>, F1: Copy, F2: Copy> ProgramHeaderReader<'a, P
 
         let bytes = self.bytes()?;
 
-        if bytes.as_ptr() as usize % align_of::<DynamicEntry<P>>() != 0 {
+        if !(bytes.as_ptr() as usize).is_multiple_of(align_of::<DynamicEntry<P>>()) {
             return Err(ElfReadError::NotAligned);
         }
 
-        if bytes.len() % size_of::<DynamicEntry<P>>() != 0 {
+        if !bytes.len().is_multiple_of(size_of::<DynamicEntry<P>>()) {
             return Err(ElfReadError::NotAligned);
         }
 
@@ -202,9 +202,9 @@ impl<'a, P: Copy + TryInto<usize>, F1: Copy, F2: Copy> ProgramHeaderReader<'a, P
             .try_into()
             .map_err(|_| ElfReadError::TooSmall)?;
 
-        let end = offset + len;
+        let end = usize::checked_add(offset, len).ok_or(ElfReadError::TooSmall)?;
 
-        Ok(&self.raw_data[offset..end])
+        self.raw_data.get(offset..end).ok_or(ElfReadError::TooSmall)
     }
 }
 
@@ -225,3 +225,23 @@ impl<'a, P: Copy + TryInto<usize>> ProgramHeaderReader<'a, P, PhantomData<()>, u
 }
 
 pub type ProgramHeaderReader64<'a> = ProgramHeaderReader<'a, u64, u32, PhantomData<()>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_section_flags() {
+        // PF_R | PF_X, as used by a typical .text segment.
+        let text = SectionFlags { flags: 0b101 };
+        assert!(text.readable());
+        assert!(text.executable());
+        assert!(!text.writable());
+
+        // PF_R | PF_W, as used by a typical .data segment.
+        let data = SectionFlags { flags: 0b110 };
+        assert!(data.readable());
+        assert!(data.writable());
+        assert!(!data.executable());
+    }
+}
@@ -2,10 +2,22 @@
 //!
 //! Spin locking means running a loop (spinning)
 //! that does nothing while the primitive is locked until it is unlocked.
+//!
+//! With the `lock_debug` feature, the locks panic on recursive acquisition and lock order
+//! inversion, see [`set_processor_id`], [`set_thread_id`] and [`set_interrupts_enabled`].
 pub use lock::*;
 pub use once::*;
+pub use rwlock::*;
 pub use singleton::*;
+pub use ticket::*;
+
+#[cfg(feature = "lock_debug")]
+#[doc(cfg(feature = "lock_debug"))]
+pub use debug::{set_interrupts_enabled, set_processor_id, set_thread_id};
 
+mod debug;
 mod lock;
 mod once;
+mod rwlock;
 mod singleton;
+mod ticket;
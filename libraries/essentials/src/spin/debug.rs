@@ -0,0 +1,385 @@
+//! Lock debugging, enabled with the `lock_debug` feature.
+//!
+//! Every lock records the processor and thread that hold it and where it was acquired. Acquiring
+//! a lock that the current thread already holds panics, and so does acquiring a lock that was
+//! acquired on the current processor with interrupts disabled, since the processor would spin
+//! forever. A lock that another thread on the same processor holds with interrupts enabled is
+//! released once that thread runs again. Every processor also keeps track of the locks it holds, to record in
+//! which order locks are acquired. Acquiring two locks in both orders panics, since two
+//! processors that each take one order can deadlock.
+//!
+//! Locks are identified by their address, and a lock that is held while a thread migrates to
+//! another processor is not tracked correctly.
+
+#[cfg(feature = "lock_debug")]
+pub use enabled::*;
+
+#[cfg(not(feature = "lock_debug"))]
+pub use disabled::*;
+
+#[cfg(feature = "lock_debug")]
+mod enabled {
+    use core::{
+        panic::Location,
+        ptr::null_mut,
+        sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering},
+    };
+
+    /// The processors with a higher id are not tracked.
+    const MAX_PROCESSORS: usize = 64;
+    /// The locks a processor can hold at once that are tracked.
+    const MAX_HELD_LOCKS: usize = 16;
+    /// The pairs of locks of which the order is recorded.
+    const MAX_ORDERED_PAIRS: usize = 1024;
+
+    static PROCESSOR_ID: AtomicPtr<()> = AtomicPtr::new(null_mut());
+    static THREAD_ID: AtomicPtr<()> = AtomicPtr::new(null_mut());
+    static INTERRUPTS_ENABLED: AtomicPtr<()> = AtomicPtr::new(null_mut());
+
+    /// Set the function that returns the id of the current processor, the id is 0 until it is
+    /// set.
+    ///
+    /// The function must not acquire a lock.
+    pub fn set_processor_id(processor_id: fn() -> usize) {
+        PROCESSOR_ID.store(processor_id as *mut (), Ordering::Release);
+    }
+
+    /// Set the function that returns the id of the current thread, the id is 0 until it is set.
+    ///
+    /// The function must not acquire a lock.
+    pub fn set_thread_id(thread_id: fn() -> usize) {
+        THREAD_ID.store(thread_id as *mut (), Ordering::Release);
+    }
+
+    /// Set the function that returns whether interrupts are enabled on the current processor,
+    /// they are assumed to be disabled until it is set.
+    ///
+    /// The function must not acquire a lock.
+    pub fn set_interrupts_enabled(interrupts_enabled: fn() -> bool) {
+        INTERRUPTS_ENABLED.store(interrupts_enabled as *mut (), Ordering::Release);
+    }
+
+    fn processor_id() -> usize {
+        let processor_id = PROCESSOR_ID.load(Ordering::Acquire);
+
+        if processor_id.is_null() {
+            return 0;
+        }
+
+        // Safety: only `set_processor_id` stores a pointer, which is a `fn() -> usize`.
+        let processor_id: fn() -> usize = unsafe { core::mem::transmute(processor_id) };
+        processor_id()
+    }
+
+    fn thread_id() -> usize {
+        let thread_id = THREAD_ID.load(Ordering::Acquire);
+
+        if thread_id.is_null() {
+            return 0;
+        }
+
+        // Safety: only `set_thread_id` stores a pointer, which is a `fn() -> usize`.
+        let thread_id: fn() -> usize = unsafe { core::mem::transmute(thread_id) };
+        thread_id()
+    }
+
+    fn interrupts_enabled() -> bool {
+        let interrupts_enabled = INTERRUPTS_ENABLED.load(Ordering::Acquire);
+
+        if interrupts_enabled.is_null() {
+            return false;
+        }
+
+        // Safety: only `set_interrupts_enabled` stores a pointer, which is a `fn() -> bool`.
+        let interrupts_enabled: fn() -> bool = unsafe { core::mem::transmute(interrupts_enabled) };
+        interrupts_enabled()
+    }
+
+    struct HeldLock {
+        lock: AtomicUsize,
+        site: AtomicPtr<Location<'static>>,
+    }
+
+    impl HeldLock {
+        const fn new() -> Self {
+            Self {
+                lock: AtomicUsize::new(0),
+                site: AtomicPtr::new(null_mut()),
+            }
+        }
+    }
+
+    static HELD_LOCKS: [[HeldLock; MAX_HELD_LOCKS]; MAX_PROCESSORS] =
+        [const { [const { HeldLock::new() }; MAX_HELD_LOCKS] }; MAX_PROCESSORS];
+
+    /// The lock `first` was held while `second` was acquired, `second` is 0 for an unused pair.
+    struct OrderedPair {
+        first: AtomicUsize,
+        second: AtomicUsize,
+    }
+
+    static ORDERED_PAIRS: [OrderedPair; MAX_ORDERED_PAIRS] = [const {
+        OrderedPair {
+            first: AtomicUsize::new(0),
+            second: AtomicUsize::new(0),
+        }
+    }; MAX_ORDERED_PAIRS];
+
+    fn is_ordered(first: usize, second: usize) -> bool {
+        ORDERED_PAIRS.iter().any(|pair| {
+            pair.second.load(Ordering::Acquire) == second
+                && pair.first.load(Ordering::Relaxed) == first
+        })
+    }
+
+    fn record_order(first: usize, second: usize) {
+        if is_ordered(first, second) {
+            return;
+        }
+
+        // The order is simply not recorded when the table is full.
+        for pair in ORDERED_PAIRS.iter() {
+            if pair
+                .first
+                .compare_exchange(0, first, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+            {
+                pair.second.store(second, Ordering::Release);
+                return;
+            }
+        }
+    }
+
+    fn site_of(site: *mut Location<'static>) -> &'static Location<'static> {
+        // Safety: only `&'static Location` are stored.
+        unsafe { site.as_ref() }.unwrap_or(Location::caller())
+    }
+
+    /// The debug information of a single lock.
+    pub struct LockDebug {
+        /// The id of the processor that holds the lock plus one, or 0.
+        owner: AtomicUsize,
+        owner_thread: AtomicUsize,
+        /// Whether interrupts were disabled when the owner acquired the lock.
+        owner_interrupts_disabled: AtomicBool,
+        site: AtomicPtr<Location<'static>>,
+    }
+
+    impl LockDebug {
+        pub const fn new() -> Self {
+            Self {
+                owner: AtomicUsize::new(0),
+                owner_thread: AtomicUsize::new(0),
+                owner_interrupts_disabled: AtomicBool::new(false),
+                site: AtomicPtr::new(null_mut()),
+            }
+        }
+
+        /// Called before the lock at `lock` is acquired by spinning.
+        pub fn acquiring(&self, lock: usize, site: &'static Location<'static>) {
+            let (processor, thread) = (processor_id(), thread_id());
+
+            // Another thread on this processor releases the lock once it runs again, which it
+            // cannot while it keeps interrupts disabled.
+            let never_released = self.owner.load(Ordering::Relaxed) == processor + 1
+                && (self.owner_thread.load(Ordering::Relaxed) == thread
+                    || self.owner_interrupts_disabled.load(Ordering::Relaxed));
+
+            if never_released {
+                panic!(
+                    "Recursive acquisition of lock {lock:#x} at {site} on processor {processor} by \
+                     thread {thread}, it was acquired at {}",
+                    site_of(self.site.load(Ordering::Relaxed))
+                );
+            }
+
+            let Some(held_locks) = HELD_LOCKS.get(processor) else {
+                return;
+            };
+
+            for held in held_locks {
+                let held_lock = held.lock.load(Ordering::Relaxed);
+
+                if held_lock == 0 || held_lock == lock {
+                    continue;
+                }
+
+                if is_ordered(lock, held_lock) {
+                    panic!(
+                        "Lock order inversion: lock {lock:#x} is acquired at {site} while \
+                         holding lock {held_lock:#x}, which was acquired at {}, but it was held \
+                         while acquiring that lock before",
+                        site_of(held.site.load(Ordering::Relaxed))
+                    );
+                }
+
+                record_order(held_lock, lock);
+            }
+        }
+
+        /// Called once the lock at `lock` is held, after spinning or trying.
+        pub fn acquired(&self, lock: usize, site: &'static Location<'static>) {
+            let site = site as *const Location<'static> as *mut Location<'static>;
+
+            self.owner.store(processor_id() + 1, Ordering::Relaxed);
+            self.owner_thread.store(thread_id(), Ordering::Relaxed);
+            self.owner_interrupts_disabled
+                .store(!interrupts_enabled(), Ordering::Relaxed);
+            self.site.store(site, Ordering::Relaxed);
+
+            track_held(lock, site);
+        }
+
+        /// Called once a shared lock at `lock` is held, which does not have a single owner.
+        pub fn acquired_shared(&self, lock: usize, site: &'static Location<'static>) {
+            track_held(
+                lock,
+                site as *const Location<'static> as *mut Location<'static>,
+            );
+        }
+
+        /// Called before the lock at `lock` is released.
+        pub fn releasing(&self, lock: usize) {
+            self.owner.store(0, Ordering::Relaxed);
+            forget_held(lock);
+        }
+
+        /// Called before a shared lock at `lock` is released by one of its holders, which does
+        /// not own the lock.
+        pub fn releasing_shared(&self, lock: usize) {
+            forget_held(lock);
+        }
+    }
+
+    fn track_held(lock: usize, site: *mut Location<'static>) {
+        let Some(held_locks) = HELD_LOCKS.get(processor_id()) else {
+            return;
+        };
+
+        // The lock is simply not tracked when the processor holds too many locks.
+        for held in held_locks {
+            if held
+                .lock
+                .compare_exchange(0, lock, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+            {
+                held.site.store(site, Ordering::Relaxed);
+                return;
+            }
+        }
+    }
+
+    fn forget_held(lock: usize) {
+        let Some(held_locks) = HELD_LOCKS.get(processor_id()) else {
+            return;
+        };
+
+        if let Some(held) = held_locks
+            .iter()
+            .find(|held| held.lock.load(Ordering::Relaxed) == lock)
+        {
+            held.lock.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Forget the order of the lock at `lock`, once it is dropped and its address can be reused.
+    pub fn forget_lock(lock: usize) {
+        for pair in ORDERED_PAIRS.iter() {
+            let first = pair.first.load(Ordering::Relaxed);
+
+            if first == lock || pair.second.load(Ordering::Acquire) == lock {
+                pair.second.store(0, Ordering::Relaxed);
+                pair.first.store(0, Ordering::Release);
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "lock_debug"))]
+mod disabled {
+    use core::panic::Location;
+
+    pub struct LockDebug;
+
+    impl LockDebug {
+        pub const fn new() -> Self {
+            Self
+        }
+
+        #[inline(always)]
+        pub fn acquiring(&self, _lock: usize, _site: &'static Location<'static>) {}
+
+        #[inline(always)]
+        pub fn acquired(&self, _lock: usize, _site: &'static Location<'static>) {}
+
+        #[inline(always)]
+        pub fn acquired_shared(&self, _lock: usize, _site: &'static Location<'static>) {}
+
+        #[inline(always)]
+        pub fn releasing(&self, _lock: usize) {}
+
+        #[inline(always)]
+        pub fn releasing_shared(&self, _lock: usize) {}
+    }
+
+    #[inline(always)]
+    pub fn forget_lock(_lock: usize) {}
+}
+
+#[cfg(all(test, feature = "lock_debug"))]
+mod tests {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    use crate::spin::SpinLock;
+
+    #[test_case]
+    fn test_recursive_acquisition_panics() {
+        let lock = SpinLock::new(());
+        let _guard = lock.lock();
+
+        assert!(catch_unwind(AssertUnwindSafe(|| drop(lock.lock()))).is_err());
+    }
+
+    #[test_case]
+    fn test_other_thread_on_processor() {
+        use core::{
+            panic::Location,
+            sync::atomic::{AtomicUsize, Ordering},
+        };
+
+        use super::{set_interrupts_enabled, set_thread_id, LockDebug};
+
+        static THREAD: AtomicUsize = AtomicUsize::new(1);
+
+        set_thread_id(|| THREAD.load(Ordering::Relaxed));
+        set_interrupts_enabled(|| true);
+
+        let debug = LockDebug::new();
+        debug.acquired(1, Location::caller());
+
+        // The owner can still release the lock once it runs again.
+        THREAD.store(2, Ordering::Relaxed);
+        debug.acquiring(1, Location::caller());
+
+        THREAD.store(1, Ordering::Relaxed);
+        assert!(catch_unwind(AssertUnwindSafe(|| debug.acquiring(1, Location::caller()))).is_err());
+
+        debug.releasing(1);
+        set_thread_id(|| 0);
+        set_interrupts_enabled(|| false);
+    }
+
+    #[test_case]
+    fn test_lock_order_inversion_panics() {
+        let first = SpinLock::new(());
+        let second = SpinLock::new(());
+
+        {
+            let _first = first.lock();
+            let _second = second.lock();
+        }
+
+        let _second = second.lock();
+        assert!(catch_unwind(AssertUnwindSafe(|| drop(first.lock()))).is_err());
+    }
+}
@@ -2,8 +2,11 @@ use core::cell::UnsafeCell;
 use core::fmt::{Debug, Formatter};
 use core::hint::spin_loop;
 use core::ops::{Deref, DerefMut};
+use core::panic::Location;
 use core::sync::atomic::{AtomicBool, Ordering};
 
+use super::debug::{forget_lock, LockDebug};
+
 pub struct SpinLockGuard<'a, T> {
     lock: &'a SpinLock<T>,
 }
@@ -29,9 +32,13 @@ impl<T> DerefMut for SpinLockGuard<'_, T> {
 }
 
 /// The spin locking equivalent of `std::sync::Mutex`.
+///
+/// The lock is not fair, a processor can be starved when the lock is contended. Use a
+/// [`TicketLock`](super::TicketLock) for contended locks.
 pub struct SpinLock<T> {
     data: UnsafeCell<T>,
     is_locked: AtomicBool,
+    debug: LockDebug,
 }
 
 impl<T> SpinLock<T> {
@@ -39,24 +46,52 @@ impl<T> SpinLock<T> {
         Self {
             is_locked: AtomicBool::new(false),
             data: UnsafeCell::new(data),
+            debug: LockDebug::new(),
         }
     }
 
+    #[cfg_attr(feature = "lock_debug", track_caller)]
     pub fn lock(&self) -> SpinLockGuard<'_, T> {
-        while self
-            .is_locked
-            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
-            .is_err()
-        {
-            spin_loop();
+        self.debug.acquiring(self.addr(), Location::caller());
+
+        while !self.acquire() {
+            // Only reading while the lock is held keeps the cache line shared.
+            while self.is_locked.load(Ordering::Relaxed) {
+                spin_loop();
+            }
         }
 
+        self.debug.acquired(self.addr(), Location::caller());
+
         SpinLockGuard { lock: self }
     }
 
+    /// Lock without spinning, returns `None` when the lock is held.
+    #[cfg_attr(feature = "lock_debug", track_caller)]
+    pub fn try_lock(&self) -> Option<SpinLockGuard<'_, T>> {
+        if !self.acquire() {
+            return None;
+        }
+
+        self.debug.acquired(self.addr(), Location::caller());
+
+        Some(SpinLockGuard { lock: self })
+    }
+
+    fn acquire(&self) -> bool {
+        self.is_locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
     fn unlock(&self) {
+        self.debug.releasing(self.addr());
         self.is_locked.store(false, Ordering::Release);
     }
+
+    fn addr(&self) -> usize {
+        self as *const Self as usize
+    }
 }
 
 impl<T> AsMut<T> for SpinLock<T> {
@@ -65,6 +100,12 @@ impl<T> AsMut<T> for SpinLock<T> {
     }
 }
 
+impl<T> Drop for SpinLock<T> {
+    fn drop(&mut self) {
+        forget_lock(self.addr());
+    }
+}
+
 unsafe impl<T: Send + Sync> Sync for SpinLock<T> {}
 unsafe impl<T: Send> Send for SpinLock<T> {}
 
@@ -73,7 +114,41 @@ where
     T: Debug,
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
-        let lock = self.lock();
-        write!(f, "{:?}", *lock)
+        // Waiting for the lock would deadlock when the caller holds it.
+        let mut debug = f.debug_struct("SpinLock");
+
+        match self.try_lock() {
+            Some(guard) => debug.field("data", &*guard),
+            None => debug.field("data", &format_args!("<locked>")),
+        };
+
+        debug.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_try_lock() {
+        let lock = SpinLock::new(1);
+
+        let mut guard = lock.try_lock().unwrap();
+        assert!(lock.try_lock().is_none());
+        *guard = 2;
+        drop(guard);
+
+        assert_eq!(2, *lock.lock());
+        assert!(lock.try_lock().is_some());
+    }
+
+    #[test_case]
+    fn test_debug_does_not_lock() {
+        let lock = SpinLock::new(1);
+        assert_eq!("SpinLock { data: 1 }", format!("{lock:?}"));
+
+        let _guard = lock.lock();
+        assert_eq!("SpinLock { data: <locked> }", format!("{lock:?}"));
     }
 }
@@ -0,0 +1,245 @@
+use core::cell::UnsafeCell;
+use core::fmt::{Debug, Formatter};
+use core::hint::spin_loop;
+use core::ops::{Deref, DerefMut};
+use core::panic::Location;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::debug::{forget_lock, LockDebug};
+
+/// The lock is held by a writer.
+const WRITER: usize = 1;
+/// A writer is waiting, new readers wait until it has had the lock.
+const WRITER_WAITING: usize = 1 << 1;
+/// Every reader adds one `READER` to the state.
+const READER: usize = 1 << 2;
+
+pub struct SpinRwLockReadGuard<'a, T> {
+    lock: &'a SpinRwLock<T>,
+}
+
+impl<T> Drop for SpinRwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.unlock_read();
+    }
+}
+
+impl<T> Deref for SpinRwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+pub struct SpinRwLockWriteGuard<'a, T> {
+    lock: &'a SpinRwLock<T>,
+}
+
+impl<T> Drop for SpinRwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.unlock_write();
+    }
+}
+
+impl<T> Deref for SpinRwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for SpinRwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+/// The spin locking equivalent of `std::sync::RwLock`.
+///
+/// Writers are preferred, once a writer waits no new readers get the lock, so a processor that
+/// already holds a read lock must not wait for it again.
+pub struct SpinRwLock<T> {
+    data: UnsafeCell<T>,
+    state: AtomicUsize,
+    debug: LockDebug,
+}
+
+impl<T> SpinRwLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            data: UnsafeCell::new(data),
+            state: AtomicUsize::new(0),
+            debug: LockDebug::new(),
+        }
+    }
+
+    #[cfg_attr(feature = "lock_debug", track_caller)]
+    pub fn read(&self) -> SpinRwLockReadGuard<'_, T> {
+        self.debug.acquiring(self.addr(), Location::caller());
+
+        while !self.acquire_read() {
+            spin_loop();
+        }
+
+        self.debug.acquired_shared(self.addr(), Location::caller());
+
+        SpinRwLockReadGuard { lock: self }
+    }
+
+    /// Lock for reading without spinning, returns `None` when a writer holds or waits for the
+    /// lock.
+    #[cfg_attr(feature = "lock_debug", track_caller)]
+    pub fn try_read(&self) -> Option<SpinRwLockReadGuard<'_, T>> {
+        if !self.acquire_read() {
+            return None;
+        }
+
+        self.debug.acquired_shared(self.addr(), Location::caller());
+
+        Some(SpinRwLockReadGuard { lock: self })
+    }
+
+    #[cfg_attr(feature = "lock_debug", track_caller)]
+    pub fn write(&self) -> SpinRwLockWriteGuard<'_, T> {
+        self.debug.acquiring(self.addr(), Location::caller());
+
+        loop {
+            let state = self.state.load(Ordering::Relaxed);
+
+            if state & !WRITER_WAITING == 0 {
+                // Other waiting writers set the flag again.
+                if self
+                    .state
+                    .compare_exchange_weak(state, WRITER, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    break;
+                }
+            } else if state & WRITER_WAITING == 0 {
+                self.state.fetch_or(WRITER_WAITING, Ordering::Relaxed);
+            }
+
+            spin_loop();
+        }
+
+        self.debug.acquired(self.addr(), Location::caller());
+
+        SpinRwLockWriteGuard { lock: self }
+    }
+
+    /// Lock for writing without spinning, returns `None` when the lock is held.
+    #[cfg_attr(feature = "lock_debug", track_caller)]
+    pub fn try_write(&self) -> Option<SpinRwLockWriteGuard<'_, T>> {
+        let state = self.state.load(Ordering::Relaxed);
+
+        if state & !WRITER_WAITING != 0 {
+            return None;
+        }
+
+        self.state
+            .compare_exchange(state, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+
+        self.debug.acquired(self.addr(), Location::caller());
+
+        Some(SpinRwLockWriteGuard { lock: self })
+    }
+
+    fn acquire_read(&self) -> bool {
+        let state = self.state.load(Ordering::Relaxed);
+
+        if state & (WRITER | WRITER_WAITING) != 0 {
+            return false;
+        }
+
+        self.state
+            .compare_exchange_weak(state, state + READER, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    fn unlock_read(&self) {
+        self.debug.releasing_shared(self.addr());
+        self.state.fetch_sub(READER, Ordering::Release);
+    }
+
+    fn unlock_write(&self) {
+        self.debug.releasing(self.addr());
+        self.state.fetch_and(!WRITER, Ordering::Release);
+    }
+
+    fn addr(&self) -> usize {
+        self as *const Self as usize
+    }
+}
+
+impl<T> AsMut<T> for SpinRwLock<T> {
+    fn as_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T> Drop for SpinRwLock<T> {
+    fn drop(&mut self) {
+        forget_lock(self.addr());
+    }
+}
+
+unsafe impl<T: Send + Sync> Sync for SpinRwLock<T> {}
+unsafe impl<T: Send> Send for SpinRwLock<T> {}
+
+impl<T> Debug for SpinRwLock<T>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        // Waiting for the lock would deadlock when the caller holds it for writing.
+        let mut debug = f.debug_struct("SpinRwLock");
+
+        match self.try_read() {
+            Some(guard) => debug.field("data", &*guard),
+            None => debug.field("data", &format_args!("<locked>")),
+        };
+
+        debug.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_readers_exclude_writer() {
+        let lock = SpinRwLock::new(1);
+
+        let first = lock.read();
+        let second = lock.try_read().unwrap();
+        assert!(lock.try_write().is_none());
+
+        drop(first);
+        drop(second);
+
+        let mut writer = lock.try_write().unwrap();
+        assert!(lock.try_read().is_none());
+        *writer = 2;
+        drop(writer);
+
+        assert_eq!(2, *lock.read());
+        assert_eq!("SpinRwLock { data: 2 }", format!("{lock:?}"));
+    }
+
+    #[test_case]
+    fn test_waiting_writer_blocks_readers() {
+        let lock = SpinRwLock::new(());
+        let reader = lock.read();
+
+        lock.state.fetch_or(WRITER_WAITING, Ordering::Relaxed);
+        assert!(lock.try_read().is_none());
+
+        drop(reader);
+        drop(lock.try_write().unwrap());
+        assert!(lock.try_read().is_some());
+    }
+}
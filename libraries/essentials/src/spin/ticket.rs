@@ -0,0 +1,176 @@
+use core::cell::UnsafeCell;
+use core::fmt::{Debug, Formatter};
+use core::hint::spin_loop;
+use core::ops::{Deref, DerefMut};
+use core::panic::Location;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::debug::{forget_lock, LockDebug};
+
+pub struct TicketLockGuard<'a, T> {
+    lock: &'a TicketLock<T>,
+}
+
+impl<T> Drop for TicketLockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.unlock();
+    }
+}
+
+impl<T> Deref for TicketLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for TicketLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+/// A fair spin lock, processors get the lock in the order in which they started waiting for it.
+///
+/// Every processor takes a ticket and waits until its ticket is served. A waiting processor that
+/// stops spinning, because it is interrupted, holds up every processor behind it, so the lock
+/// is best used with interrupts disabled.
+pub struct TicketLock<T> {
+    data: UnsafeCell<T>,
+    next_ticket: AtomicUsize,
+    serving: AtomicUsize,
+    debug: LockDebug,
+}
+
+impl<T> TicketLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            data: UnsafeCell::new(data),
+            next_ticket: AtomicUsize::new(0),
+            serving: AtomicUsize::new(0),
+            debug: LockDebug::new(),
+        }
+    }
+
+    #[cfg_attr(feature = "lock_debug", track_caller)]
+    pub fn lock(&self) -> TicketLockGuard<'_, T> {
+        self.debug.acquiring(self.addr(), Location::caller());
+
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+
+        while self.serving.load(Ordering::Acquire) != ticket {
+            spin_loop();
+        }
+
+        self.debug.acquired(self.addr(), Location::caller());
+
+        TicketLockGuard { lock: self }
+    }
+
+    /// Lock without waiting, returns `None` when the lock is held or processors are waiting.
+    #[cfg_attr(feature = "lock_debug", track_caller)]
+    pub fn try_lock(&self) -> Option<TicketLockGuard<'_, T>> {
+        let serving = self.serving.load(Ordering::Relaxed);
+
+        self.next_ticket
+            .compare_exchange(serving, serving + 1, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+
+        self.debug.acquired(self.addr(), Location::caller());
+
+        Some(TicketLockGuard { lock: self })
+    }
+
+    /// Whether the lock is held, or processors are waiting for it.
+    pub fn is_contended(&self) -> bool {
+        self.next_ticket.load(Ordering::Relaxed) != self.serving.load(Ordering::Relaxed)
+    }
+
+    fn unlock(&self) {
+        self.debug.releasing(self.addr());
+        self.serving.fetch_add(1, Ordering::Release);
+    }
+
+    fn addr(&self) -> usize {
+        self as *const Self as usize
+    }
+}
+
+impl<T> AsMut<T> for TicketLock<T> {
+    fn as_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T> Drop for TicketLock<T> {
+    fn drop(&mut self) {
+        forget_lock(self.addr());
+    }
+}
+
+unsafe impl<T: Send> Sync for TicketLock<T> {}
+unsafe impl<T: Send> Send for TicketLock<T> {}
+
+impl<T> Debug for TicketLock<T>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        // Waiting for the lock would deadlock when the caller holds it.
+        let mut debug = f.debug_struct("TicketLock");
+
+        match self.try_lock() {
+            Some(guard) => debug.field("data", &*guard),
+            None => debug.field("data", &format_args!("<locked>")),
+        };
+
+        debug.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_try_lock() {
+        let lock = TicketLock::new(1);
+
+        let guard = lock.lock();
+        assert!(lock.is_contended());
+        assert!(lock.try_lock().is_none());
+        drop(guard);
+
+        assert!(!lock.is_contended());
+        *lock.try_lock().unwrap() = 2;
+        assert_eq!(2, *lock.lock());
+    }
+
+    // Every thread of the host is processor 0 for the lock debugging.
+    #[cfg(not(feature = "lock_debug"))]
+    #[test_case]
+    fn test_contended_lock() {
+        use std::{sync::Arc, thread, vec::Vec};
+
+        let lock = Arc::new(TicketLock::new(0));
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        *lock.lock() += 1;
+                    }
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(4000, *lock.lock());
+    }
+}
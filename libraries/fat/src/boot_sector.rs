@@ -0,0 +1,271 @@
+use crate::{FatError, Storage};
+
+pub const BOOT_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+
+const FAT12_MAX_CLUSTERS: u32 = 4085;
+const FAT16_MAX_CLUSTERS: u32 = 65525;
+/// FAT32 entries are 28 bits wide, and the highest values are reserved.
+pub(crate) const FAT32_MAX_CLUSTERS: u32 = 0x0FFF_FFF5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatType {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+impl FatType {
+    /// The FAT type is determined by the number of clusters and nothing else, as the
+    /// specification demands.
+    pub const fn from_cluster_count(clusters: u32) -> Self {
+        if clusters < FAT12_MAX_CLUSTERS {
+            FatType::Fat12
+        } else if clusters < FAT16_MAX_CLUSTERS {
+            FatType::Fat16
+        } else {
+            FatType::Fat32
+        }
+    }
+
+    /// The smallest value of an entry that marks the end of a cluster chain.
+    pub const fn end_of_chain(&self) -> u32 {
+        match self {
+            FatType::Fat12 => 0xFF8,
+            FatType::Fat16 => 0xFFF8,
+            FatType::Fat32 => 0x0FFF_FFF8,
+        }
+    }
+
+    pub const fn bad_cluster(&self) -> u32 {
+        self.end_of_chain() - 1
+    }
+
+    pub const fn fs_type_label(&self) -> &'static [u8; 8] {
+        match self {
+            FatType::Fat12 => b"FAT12   ",
+            FatType::Fat16 => b"FAT16   ",
+            FatType::Fat32 => b"FAT32   ",
+        }
+    }
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+/// The parsed BIOS Parameter Block (BPB) of a FAT volume, together with the values derived from
+/// it.
+#[derive(Debug, Clone)]
+pub struct BootSector {
+    pub bytes_per_sector: u16,
+    pub sectors_per_cluster: u8,
+    pub reserved_sectors: u16,
+    pub fat_count: u8,
+    pub root_entry_count: u16,
+    pub total_sectors: u32,
+    pub fat_size: u32,
+    /// Only valid for FAT32, for FAT12/16 the root directory is a fixed region.
+    pub root_cluster: u32,
+    /// Only valid for FAT32.
+    pub fs_info_sector: u16,
+    pub fat_type: FatType,
+    pub cluster_count: u32,
+    /// The sector of the first data cluster, after the reserved sectors, the FATs and the
+    /// FAT12/16 root directory.
+    pub first_data_sector: u32,
+}
+
+impl BootSector {
+    pub const SIZE: usize = 512;
+
+    pub fn parse(bytes: &[u8; Self::SIZE]) -> Result<Self, FatError> {
+        if bytes[510..512] != BOOT_SIGNATURE {
+            return Err(FatError::InvalidBootSector);
+        }
+
+        let bytes_per_sector = u16_at(bytes, 11);
+        let sectors_per_cluster = bytes[13];
+        let reserved_sectors = u16_at(bytes, 14);
+        let fat_count = bytes[16];
+        let root_entry_count = u16_at(bytes, 17);
+        let total_sectors_16 = u16_at(bytes, 19);
+        let fat_size_16 = u16_at(bytes, 22);
+        let total_sectors_32 = u32_at(bytes, 32);
+
+        if !matches!(bytes_per_sector, 512 | 1024 | 2048 | 4096) {
+            return Err(FatError::UnsupportedSectorSize);
+        }
+
+        if !sectors_per_cluster.is_power_of_two() || reserved_sectors == 0 || fat_count == 0 {
+            return Err(FatError::InvalidBootSector);
+        }
+
+        let fat_size = match fat_size_16 {
+            0 => u32_at(bytes, 36),
+            size => size as u32,
+        };
+
+        let total_sectors = match total_sectors_16 {
+            0 => total_sectors_32,
+            sectors => sectors as u32,
+        };
+
+        let root_dir_sectors = (root_entry_count as u32 * 32).div_ceil(bytes_per_sector as u32);
+        // The values come from the volume, so a corrupt boot sector must not overflow.
+        let first_data_sector = (fat_count as u32)
+            .checked_mul(fat_size)
+            .and_then(|fats| fats.checked_add(reserved_sectors as u32))
+            .and_then(|sectors| sectors.checked_add(root_dir_sectors))
+            .ok_or(FatError::InvalidBootSector)?;
+
+        let data_sectors = total_sectors
+            .checked_sub(first_data_sector)
+            .ok_or(FatError::InvalidBootSector)?;
+
+        let cluster_count = data_sectors / sectors_per_cluster as u32;
+        if cluster_count == 0 || cluster_count > FAT32_MAX_CLUSTERS {
+            return Err(FatError::InvalidBootSector);
+        }
+
+        let fat_type = FatType::from_cluster_count(cluster_count);
+
+        let (root_cluster, fs_info_sector) = match fat_type {
+            FatType::Fat32 => (u32_at(bytes, 44), u16_at(bytes, 48)),
+            _ => (0, 0),
+        };
+
+        if fat_type == FatType::Fat32 && (root_entry_count != 0 || root_cluster < 2) {
+            return Err(FatError::InvalidBootSector);
+        }
+
+        let boot_sector = Self {
+            bytes_per_sector,
+            sectors_per_cluster,
+            reserved_sectors,
+            fat_count,
+            root_entry_count,
+            total_sectors,
+            fat_size,
+            root_cluster,
+            fs_info_sector,
+            fat_type,
+            cluster_count,
+            first_data_sector,
+        };
+
+        // The FAT must be large enough to describe every cluster.
+        let needed_fat_bytes = boot_sector.fat_entry_offset(cluster_count + 1) + 4;
+        if needed_fat_bytes > fat_size as u64 * bytes_per_sector as u64 {
+            return Err(FatError::InvalidBootSector);
+        }
+
+        Ok(boot_sector)
+    }
+
+    pub fn read(storage: &impl Storage) -> Result<Self, FatError> {
+        let mut bytes = [0u8; Self::SIZE];
+        storage.read_at(0, &mut bytes)?;
+
+        let boot_sector = Self::parse(&bytes)?;
+
+        if boot_sector.total_sectors as u64 * boot_sector.bytes_per_sector as u64 > storage.size() {
+            return Err(FatError::InvalidVolumeSize);
+        }
+
+        Ok(boot_sector)
+    }
+
+    pub fn bytes_per_cluster(&self) -> u32 {
+        self.bytes_per_sector as u32 * self.sectors_per_cluster as u32
+    }
+
+    fn sector_offset(&self, sector: u32) -> u64 {
+        sector as u64 * self.bytes_per_sector as u64
+    }
+
+    /// The byte offset of the first FAT.
+    pub fn fat_offset(&self, fat_index: u8) -> u64 {
+        self.sector_offset(self.reserved_sectors as u32 + fat_index as u32 * self.fat_size)
+    }
+
+    /// The byte offset of an entry within a FAT.
+    pub fn fat_entry_offset(&self, cluster: u32) -> u64 {
+        let cluster = cluster as u64;
+
+        match self.fat_type {
+            FatType::Fat12 => cluster + cluster / 2,
+            FatType::Fat16 => cluster * 2,
+            FatType::Fat32 => cluster * 4,
+        }
+    }
+
+    /// The byte offset and size of the FAT12/16 root directory.
+    pub fn root_dir_region(&self) -> (u64, u64) {
+        let offset = self.fat_offset(self.fat_count);
+        (offset, self.root_entry_count as u64 * 32)
+    }
+
+    /// The byte offset of a data cluster.
+    pub fn cluster_offset(&self, cluster: u32) -> u64 {
+        debug_assert!(self.is_valid_cluster(cluster));
+
+        let sector = (cluster - 2) * self.sectors_per_cluster as u32 + self.first_data_sector;
+        self.sector_offset(sector)
+    }
+
+    /// Data clusters are numbered from 2 up to and including `cluster_count + 1`.
+    pub fn is_valid_cluster(&self, cluster: u32) -> bool {
+        (2..self.cluster_count + 2).contains(&cluster)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::{format, FormatOptions};
+
+    fn floppy_boot_sector() -> [u8; BootSector::SIZE] {
+        let mut storage = vec![0u8; 1440 * 1024];
+        format(&mut storage, &FormatOptions::default()).unwrap();
+
+        storage[..BootSector::SIZE].try_into().unwrap()
+    }
+
+    #[test_case]
+    fn test_parse_without_clusters() {
+        let mut bytes = floppy_boot_sector();
+        let boot = BootSector::parse(&bytes).unwrap();
+
+        // Only the reserved sectors, the FATs and the root directory fit in the volume.
+        bytes[19..21].copy_from_slice(&(boot.first_data_sector as u16).to_le_bytes());
+
+        assert_eq!(
+            FatError::InvalidBootSector,
+            BootSector::parse(&bytes).unwrap_err()
+        );
+    }
+
+    #[test_case]
+    fn test_parse_overflowing_fat_size() {
+        let mut bytes = floppy_boot_sector();
+
+        bytes[22..24].copy_from_slice(&0u16.to_le_bytes());
+        bytes[36..40].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        assert_eq!(
+            FatError::InvalidBootSector,
+            BootSector::parse(&bytes).unwrap_err()
+        );
+    }
+}
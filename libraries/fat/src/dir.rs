@@ -0,0 +1,522 @@
+//! Directory entries, including long file names (LFN).
+//!
+//! A directory is a list of 32 byte entries. Every file has one short ("8.3") entry, which may be
+//! preceded by a number of long name entries that together hold the UTF-16 encoded name.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::{FatError, FileKind, InodeId};
+
+pub const ENTRY_SIZE: usize = 32;
+
+pub const ATTR_READ_ONLY: u8 = 0x01;
+pub const ATTR_HIDDEN: u8 = 0x02;
+pub const ATTR_SYSTEM: u8 = 0x04;
+pub const ATTR_VOLUME_ID: u8 = 0x08;
+pub const ATTR_DIRECTORY: u8 = 0x10;
+pub const ATTR_ARCHIVE: u8 = 0x20;
+pub const ATTR_LONG_NAME: u8 = ATTR_READ_ONLY | ATTR_HIDDEN | ATTR_SYSTEM | ATTR_VOLUME_ID;
+
+const END_MARKER: u8 = 0x00;
+const DELETED_MARKER: u8 = 0xE5;
+/// A name starting with 0xE5 is stored as 0x05 to not be confused with the deleted marker.
+const KANJI_MARKER: u8 = 0x05;
+
+const LAST_LONG_ENTRY: u8 = 0x40;
+const LONG_ORDER_MASK: u8 = 0x1F;
+const CHARS_PER_LONG_ENTRY: usize = 13;
+const LONG_NAME_CHAR_OFFSETS: [usize; CHARS_PER_LONG_ENTRY] =
+    [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+pub const MAX_NAME_LEN: usize = 255;
+pub const MAX_SHORT_NAME_TAIL: u32 = 999_999;
+
+/// Windows NT (and Linux) store the case of a short name in these reserved bits.
+const NT_LOWER_BASE: u8 = 0x08;
+const NT_LOWER_EXT: u8 = 0x10;
+
+/// 1980-01-01, the earliest date that can be stored.
+const DEFAULT_DATE: u16 = (1 << 5) | 1;
+
+/// A public view of a directory entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    pub name: String,
+    pub inode: InodeId,
+    pub kind: FileKind,
+    pub size: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RawEntry {
+    bytes: [u8; ENTRY_SIZE],
+}
+
+impl RawEntry {
+    pub const fn from_bytes(bytes: [u8; ENTRY_SIZE]) -> Self {
+        Self { bytes }
+    }
+
+    pub fn new(short_name: [u8; 11], nt_flags: u8, attr: u8, first_cluster: u32) -> Self {
+        let mut entry = Self {
+            bytes: [0; ENTRY_SIZE],
+        };
+
+        entry.bytes[0..11].copy_from_slice(&short_name);
+        entry.bytes[11] = attr;
+        entry.bytes[12] = nt_flags;
+
+        for offset in [16, 18, 24] {
+            entry.bytes[offset..offset + 2].copy_from_slice(&DEFAULT_DATE.to_le_bytes());
+        }
+
+        entry.set_first_cluster(first_cluster);
+        entry
+    }
+
+    pub fn as_bytes(&self) -> &[u8; ENTRY_SIZE] {
+        &self.bytes
+    }
+
+    pub fn is_end(&self) -> bool {
+        self.bytes[0] == END_MARKER
+    }
+
+    pub fn is_free(&self) -> bool {
+        self.is_end() || self.bytes[0] == DELETED_MARKER
+    }
+
+    pub fn attr(&self) -> u8 {
+        self.bytes[11]
+    }
+
+    pub fn is_long_name(&self) -> bool {
+        self.attr() & ATTR_LONG_NAME == ATTR_LONG_NAME
+    }
+
+    pub fn is_volume_label(&self) -> bool {
+        !self.is_long_name() && self.attr() & ATTR_VOLUME_ID != 0
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.attr() & ATTR_DIRECTORY != 0
+    }
+
+    pub fn kind(&self) -> FileKind {
+        if self.is_dir() {
+            FileKind::Directory
+        } else {
+            FileKind::File
+        }
+    }
+
+    pub fn short_name(&self) -> &[u8; 11] {
+        self.bytes[0..11].try_into().unwrap()
+    }
+
+    pub fn first_cluster(&self) -> u32 {
+        let high = u16::from_le_bytes([self.bytes[20], self.bytes[21]]) as u32;
+        let low = u16::from_le_bytes([self.bytes[26], self.bytes[27]]) as u32;
+
+        (high << 16) | low
+    }
+
+    pub fn set_first_cluster(&mut self, cluster: u32) {
+        self.bytes[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+        self.bytes[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+    }
+
+    pub fn size(&self) -> u32 {
+        u32::from_le_bytes(self.bytes[28..32].try_into().unwrap())
+    }
+
+    pub fn set_size(&mut self, size: u32) {
+        self.bytes[28..32].copy_from_slice(&size.to_le_bytes());
+    }
+
+    /// The name as stored in the short entry, with the NT case bits applied.
+    pub fn decoded_short_name(&self) -> String {
+        let flags = self.bytes[12];
+        let mut name = String::new();
+
+        let decode = |name: &mut String, bytes: &[u8], lower: bool| {
+            for (i, &byte) in bytes.iter().enumerate() {
+                let byte = if i == 0 && byte == KANJI_MARKER && bytes.len() == 8 {
+                    DELETED_MARKER
+                } else {
+                    byte
+                };
+
+                let char = char::from(byte);
+
+                if lower {
+                    name.push(char.to_ascii_lowercase());
+                } else {
+                    name.push(char);
+                }
+            }
+        };
+
+        let base = self.short_name()[0..8].trim_ascii_end();
+        let ext = self.short_name()[8..11].trim_ascii_end();
+
+        decode(&mut name, base, flags & NT_LOWER_BASE != 0);
+
+        if !ext.is_empty() {
+            name.push('.');
+            decode(&mut name, ext, flags & NT_LOWER_EXT != 0);
+        }
+
+        name
+    }
+
+    pub fn mark_deleted(&mut self) {
+        self.bytes[0] = DELETED_MARKER;
+    }
+
+    fn long_order(&self) -> u8 {
+        self.bytes[0]
+    }
+
+    fn long_checksum(&self) -> u8 {
+        self.bytes[13]
+    }
+
+    fn long_chars(&self) -> impl Iterator<Item = u16> + '_ {
+        LONG_NAME_CHAR_OFFSETS
+            .iter()
+            .map(|&offset| u16::from_le_bytes([self.bytes[offset], self.bytes[offset + 1]]))
+    }
+}
+
+/// The checksum of a short name that every long name entry refers to.
+pub fn short_name_checksum(short_name: &[u8; 11]) -> u8 {
+    short_name
+        .iter()
+        .fold(0u8, |sum, &byte| sum.rotate_right(1).wrapping_add(byte))
+}
+
+/// Assembles a long name from the entries preceding a short entry.
+#[derive(Default)]
+pub struct LongNameBuilder {
+    chars: Vec<u16>,
+    checksum: u8,
+    next_order: u8,
+    valid: bool,
+}
+
+impl LongNameBuilder {
+    pub fn push(&mut self, entry: &RawEntry) {
+        let order = entry.long_order();
+
+        if order & LAST_LONG_ENTRY != 0 {
+            let count = order & LONG_ORDER_MASK;
+
+            self.chars.clear();
+            self.chars
+                .resize(count as usize * CHARS_PER_LONG_ENTRY, 0xFFFF);
+            self.checksum = entry.long_checksum();
+            self.next_order = count;
+            self.valid = count > 0;
+        } else if !self.valid || order != self.next_order || entry.long_checksum() != self.checksum
+        {
+            self.reset();
+            return;
+        }
+
+        let index = (self.next_order - 1) as usize * CHARS_PER_LONG_ENTRY;
+
+        for (i, char) in entry.long_chars().enumerate() {
+            self.chars[index + i] = char;
+        }
+
+        self.next_order -= 1;
+    }
+
+    /// Get the long name belonging to `short`, if there is a valid one.
+    pub fn finish(&mut self, short: &RawEntry) -> Option<String> {
+        let complete = self.valid
+            && self.next_order == 0
+            && self.checksum == short_name_checksum(short.short_name());
+
+        let name = complete
+            .then(|| {
+                let len = self
+                    .chars
+                    .iter()
+                    .position(|&c| c == 0x0000 || c == 0xFFFF)
+                    .unwrap_or(self.chars.len());
+
+                char::decode_utf16(self.chars[..len].iter().copied())
+                    .collect::<Result<String, _>>()
+                    .ok()
+            })
+            .flatten();
+
+        self.reset();
+        name
+    }
+
+    pub fn reset(&mut self) {
+        self.valid = false;
+        self.next_order = 0;
+    }
+}
+
+/// Check whenever a name can be stored in a directory.
+pub fn validate_name(name: &str) -> Result<(), FatError> {
+    const ILLEGAL: &[char] = &['"', '*', '/', ':', '<', '>', '?', '\\', '|'];
+
+    if name.is_empty()
+        || name == "."
+        || name == ".."
+        || name.encode_utf16().count() > MAX_NAME_LEN
+        || name.ends_with(['.', ' '])
+        || name.chars().any(|c| c < ' ' || ILLEGAL.contains(&c))
+    {
+        return Err(FatError::InvalidName);
+    }
+
+    Ok(())
+}
+
+fn is_short_name_char(byte: u8) -> bool {
+    matches!(byte,
+        b'A'..=b'Z' | b'0'..=b'9'
+        | b'!' | b'#' | b'$' | b'%' | b'&' | b'\'' | b'(' | b')'
+        | b'-' | b'@' | b'^' | b'_' | b'`' | b'{' | b'}' | b'~')
+}
+
+/// Encode one part of a short name, if it can be stored without a long name.
+///
+/// Returns whenever the part is lowercase.
+fn encode_short_part(part: &str, out: &mut [u8]) -> Option<bool> {
+    if part.len() > out.len() {
+        return None;
+    }
+
+    let has_lower = part.bytes().any(|b| b.is_ascii_lowercase());
+    let has_upper = part.bytes().any(|b| b.is_ascii_uppercase());
+
+    if has_lower && has_upper {
+        return None;
+    }
+
+    for (i, byte) in part.bytes().enumerate() {
+        let byte = byte.to_ascii_uppercase();
+
+        if !is_short_name_char(byte) {
+            return None;
+        }
+
+        out[i] = byte;
+    }
+
+    Some(has_lower)
+}
+
+/// Encode `name` as a short name when no long name is needed to store it.
+pub fn exact_short_name(name: &str) -> Option<([u8; 11], u8)> {
+    let (base, ext) = match name.split_once('.') {
+        Some((base, ext)) => (base, ext),
+        None => (name, ""),
+    };
+
+    if base.is_empty() || ext.contains('.') {
+        return None;
+    }
+
+    let mut short_name = [b' '; 11];
+    let mut flags = 0;
+
+    if encode_short_part(base, &mut short_name[0..8])? {
+        flags |= NT_LOWER_BASE;
+    }
+
+    if encode_short_part(ext, &mut short_name[8..11])? {
+        flags |= NT_LOWER_EXT;
+    }
+
+    Some((short_name, flags))
+}
+
+/// Generate a short name like `LONGFI~1.TXT` for a name that needs a long name.
+///
+/// `tail` is the number after the tilde (at most [`MAX_SHORT_NAME_TAIL`]), and should be
+/// incremented until the short name is unique within the directory.
+pub fn generated_short_name(name: &str, tail: u32) -> [u8; 11] {
+    let sanitize = |part: &str| {
+        part.chars()
+            .filter(|&c| c != ' ' && c != '.')
+            .map(|c| {
+                let byte = if c.is_ascii() { c as u8 } else { b'_' };
+                let byte = byte.to_ascii_uppercase();
+
+                if is_short_name_char(byte) {
+                    byte
+                } else {
+                    b'_'
+                }
+            })
+            .collect::<Vec<u8>>()
+    };
+
+    let name = name.trim_start_matches('.');
+    let (base, ext) = match name.rsplit_once('.') {
+        Some((base, ext)) if !base.is_empty() => (sanitize(base), sanitize(ext)),
+        _ => (sanitize(name), Vec::new()),
+    };
+
+    let mut digits = [0u8; 10];
+    let mut digit_count = 0;
+    let mut value = tail;
+
+    loop {
+        digits[digit_count] = b'0' + (value % 10) as u8;
+        digit_count += 1;
+        value /= 10;
+
+        if value == 0 {
+            break;
+        }
+    }
+
+    let mut short_name = [b' '; 11];
+    let base_len = base.len().min(8 - 1 - digit_count);
+
+    short_name[..base_len].copy_from_slice(&base[..base_len]);
+
+    if base_len == 0 {
+        short_name[0] = b'_';
+    }
+
+    let mut index = base_len.max(1);
+    short_name[index] = b'~';
+    index += 1;
+
+    for i in (0..digit_count).rev() {
+        short_name[index] = digits[i];
+        index += 1;
+    }
+
+    let ext_len = ext.len().min(3);
+    short_name[8..8 + ext_len].copy_from_slice(&ext[..ext_len]);
+
+    short_name
+}
+
+/// Create the long name entries for `name`, in the order they are stored on disk.
+pub fn long_name_entries(name: &str, short_name: &[u8; 11]) -> Vec<RawEntry> {
+    let chars: Vec<u16> = name.encode_utf16().collect();
+    let count = chars.len().div_ceil(CHARS_PER_LONG_ENTRY);
+    let checksum = short_name_checksum(short_name);
+
+    (1..=count)
+        .rev()
+        .map(|order| {
+            let mut bytes = [0u8; ENTRY_SIZE];
+
+            bytes[0] = order as u8;
+            if order == count {
+                bytes[0] |= LAST_LONG_ENTRY;
+            }
+
+            bytes[11] = ATTR_LONG_NAME;
+            bytes[13] = checksum;
+
+            let start = (order - 1) * CHARS_PER_LONG_ENTRY;
+
+            for (i, &offset) in LONG_NAME_CHAR_OFFSETS.iter().enumerate() {
+                let char = match chars.get(start + i) {
+                    Some(&c) => c,
+                    None if start + i == chars.len() => 0x0000,
+                    None => 0xFFFF,
+                };
+
+                bytes[offset..offset + 2].copy_from_slice(&char.to_le_bytes());
+            }
+
+            RawEntry::from_bytes(bytes)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_exact_short_name_upper() {
+        let (short, flags) = exact_short_name("README.TXT").unwrap();
+        assert_eq!(b"README  TXT", &short);
+        assert_eq!(0, flags);
+    }
+
+    #[test_case]
+    fn test_exact_short_name_lower() {
+        let (short, flags) = exact_short_name("kernel.elf").unwrap();
+        assert_eq!(b"KERNEL  ELF", &short);
+        assert_eq!(NT_LOWER_BASE | NT_LOWER_EXT, flags);
+    }
+
+    #[test_case]
+    fn test_exact_short_name_rejects_long_and_mixed() {
+        assert!(exact_short_name("longfilename.txt").is_none());
+        assert!(exact_short_name("Readme.txt").is_none());
+        assert!(exact_short_name("a.tar.gz").is_none());
+        assert!(exact_short_name("file.html").is_none());
+    }
+
+    #[test_case]
+    fn test_generated_short_name() {
+        assert_eq!(b"LONGFI~1TXT", &generated_short_name("longfilename.txt", 1));
+        assert_eq!(b"ATAR~12 GZ ", &generated_short_name("a tar.gz", 12));
+        assert_eq!(b"BASHRC~1   ", &generated_short_name(".bashrc", 1));
+    }
+
+    #[test_case]
+    fn test_long_name_round_trip() {
+        let name = "a rather long file name.txt";
+        let short = generated_short_name(name, 1);
+        let entries = long_name_entries(name, &short);
+        assert_eq!(3, entries.len());
+
+        let mut builder = LongNameBuilder::default();
+        for entry in &entries {
+            assert!(entry.is_long_name());
+            builder.push(entry);
+        }
+
+        let short_entry = RawEntry::new(short, 0, ATTR_ARCHIVE, 0);
+        assert_eq!(Some(String::from(name)), builder.finish(&short_entry));
+    }
+
+    #[test_case]
+    fn test_long_name_checksum_mismatch() {
+        let name = "mismatching name";
+        let entries = long_name_entries(name, &generated_short_name(name, 1));
+
+        let mut builder = LongNameBuilder::default();
+        for entry in &entries {
+            builder.push(entry);
+        }
+
+        let short_entry = RawEntry::new(generated_short_name(name, 2), 0, ATTR_ARCHIVE, 0);
+        assert_eq!(None, builder.finish(&short_entry));
+    }
+
+    #[test_case]
+    fn test_decoded_short_name() {
+        let (short, flags) = exact_short_name("boot.CFG").unwrap();
+        let entry = RawEntry::new(short, flags, ATTR_ARCHIVE, 0);
+        assert_eq!("boot.CFG", entry.decoded_short_name());
+    }
+
+    #[test_case]
+    fn test_validate_name() {
+        assert!(validate_name("hello world.txt").is_ok());
+        assert_eq!(Err(FatError::InvalidName), validate_name(""));
+        assert_eq!(Err(FatError::InvalidName), validate_name(".."));
+        assert_eq!(Err(FatError::InvalidName), validate_name("a/b"));
+        assert_eq!(Err(FatError::InvalidName), validate_name("trailing."));
+    }
+}
@@ -0,0 +1,45 @@
+use crate::StorageError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatError {
+    Storage(StorageError),
+    InvalidBootSector,
+    UnsupportedSectorSize,
+    /// The volume is too small or too large for the requested FAT type.
+    InvalidVolumeSize,
+    /// The on-disk structures are inconsistent, for example a cluster chain that loops.
+    Corrupt,
+    NotFound,
+    NotADirectory,
+    IsADirectory,
+    AlreadyExists,
+    NotEmpty,
+    InvalidName,
+    NoSpace,
+}
+
+impl FatError {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FatError::Storage(StorageError::OutOfBounds) => "Access outside of the storage bounds",
+            FatError::Storage(StorageError::Io) => "The storage device reported an error",
+            FatError::InvalidBootSector => "The boot sector does not describe a FAT volume",
+            FatError::UnsupportedSectorSize => "The sector size is not supported",
+            FatError::InvalidVolumeSize => "The volume size does not fit the FAT type",
+            FatError::Corrupt => "The filesystem structures are inconsistent",
+            FatError::NotFound => "No such file or directory",
+            FatError::NotADirectory => "Not a directory",
+            FatError::IsADirectory => "Is a directory",
+            FatError::AlreadyExists => "The file already exists",
+            FatError::NotEmpty => "The directory is not empty",
+            FatError::InvalidName => "The name cannot be stored in a FAT directory",
+            FatError::NoSpace => "No space left on the volume",
+        }
+    }
+}
+
+impl From<StorageError> for FatError {
+    fn from(value: StorageError) -> Self {
+        FatError::Storage(value)
+    }
+}
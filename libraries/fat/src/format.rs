@@ -0,0 +1,304 @@
+//! Creating a new, empty FAT volume.
+
+use crate::{boot_sector::FAT32_MAX_CLUSTERS, BootSector, FatError, FatType, Storage};
+
+const BYTES_PER_SECTOR: u32 = 512;
+const FAT_COUNT: u32 = 2;
+const MEDIA_FIXED_DISK: u8 = 0xF8;
+/// The largest cluster that is supported by most implementations is 32 KiB.
+const MAX_SECTORS_PER_CLUSTER: u8 = 64;
+
+const FS_INFO_SECTOR: u16 = 1;
+const BACKUP_BOOT_SECTOR: u16 = 6;
+const FS_INFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+const FS_INFO_STRUCT_SIGNATURE: u32 = 0x6141_7272;
+const FS_INFO_TRAIL_SIGNATURE: u32 = 0xAA55_0000;
+
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// Picked based on the volume size when `None`.
+    pub fat_type: Option<FatType>,
+    /// Picked based on the volume size when `None`.
+    pub sectors_per_cluster: Option<u8>,
+    pub volume_label: [u8; 11],
+    pub volume_id: u32,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            fat_type: None,
+            sectors_per_cluster: None,
+            volume_label: *b"NO NAME    ",
+            volume_id: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    fat_type: FatType,
+    sectors_per_cluster: u8,
+    total_sectors: u32,
+    reserved_sectors: u32,
+    root_entry_count: u32,
+    fat_size: u32,
+}
+
+impl Layout {
+    fn new(fat_type: FatType, sectors_per_cluster: u8, total_sectors: u32) -> Option<Self> {
+        let (reserved_sectors, root_entry_count): (u32, u32) = match fat_type {
+            FatType::Fat32 => (32, 0),
+            _ => (1, 512),
+        };
+
+        let root_dir_sectors = (root_entry_count * 32).div_ceil(BYTES_PER_SECTOR);
+        let mut fat_size = 1;
+
+        // A larger FAT leaves fewer clusters, so this converges after a few iterations.
+        let cluster_count = loop {
+            let meta_sectors = reserved_sectors + FAT_COUNT * fat_size + root_dir_sectors;
+            let cluster_count =
+                total_sectors.checked_sub(meta_sectors)? / sectors_per_cluster as u32;
+
+            let fat_bytes = match fat_type {
+                FatType::Fat12 => (cluster_count + 2) * 3 / 2 + 4,
+                FatType::Fat16 => (cluster_count + 2) * 2 + 4,
+                FatType::Fat32 => (cluster_count + 2) * 4 + 4,
+            };
+
+            let needed = fat_bytes.div_ceil(BYTES_PER_SECTOR);
+
+            if needed <= fat_size {
+                break cluster_count;
+            }
+
+            fat_size = needed;
+        };
+
+        let valid = cluster_count > 0
+            && cluster_count <= FAT32_MAX_CLUSTERS
+            && FatType::from_cluster_count(cluster_count) == fat_type;
+
+        valid.then_some(Self {
+            fat_type,
+            sectors_per_cluster,
+            total_sectors,
+            reserved_sectors,
+            root_entry_count,
+            fat_size,
+        })
+    }
+
+    /// Use the smallest cluster size possible, and for that cluster size the smallest FAT type.
+    fn choose(options: &FormatOptions, total_sectors: u32) -> Result<Self, FatError> {
+        let cluster_sizes = match options.sectors_per_cluster {
+            Some(spc) if !spc.is_power_of_two() => return Err(FatError::InvalidBootSector),
+            Some(spc) => spc..=spc,
+            None => 1..=MAX_SECTORS_PER_CLUSTER,
+        };
+
+        let fat_types = [FatType::Fat12, FatType::Fat16, FatType::Fat32];
+
+        cluster_sizes
+            .filter(|spc| spc.is_power_of_two())
+            .flat_map(|spc| fat_types.iter().map(move |&fat_type| (fat_type, spc)))
+            .filter(|(fat_type, _)| options.fat_type.is_none_or(|t| t == *fat_type))
+            .find_map(|(fat_type, spc)| Self::new(fat_type, spc, total_sectors))
+            .ok_or(FatError::InvalidVolumeSize)
+    }
+
+    fn boot_sector(&self, options: &FormatOptions) -> [u8; BootSector::SIZE] {
+        let mut bytes = [0u8; BootSector::SIZE];
+        let is_fat32 = self.fat_type == FatType::Fat32;
+
+        bytes[0..3].copy_from_slice(match is_fat32 {
+            true => &[0xEB, 0x58, 0x90],
+            false => &[0xEB, 0x3C, 0x90],
+        });
+        bytes[3..11].copy_from_slice(b"ZENIX   ");
+        bytes[11..13].copy_from_slice(&(BYTES_PER_SECTOR as u16).to_le_bytes());
+        bytes[13] = self.sectors_per_cluster;
+        bytes[14..16].copy_from_slice(&(self.reserved_sectors as u16).to_le_bytes());
+        bytes[16] = FAT_COUNT as u8;
+        bytes[17..19].copy_from_slice(&(self.root_entry_count as u16).to_le_bytes());
+
+        match u16::try_from(self.total_sectors) {
+            Ok(total) if !is_fat32 => bytes[19..21].copy_from_slice(&total.to_le_bytes()),
+            _ => bytes[32..36].copy_from_slice(&self.total_sectors.to_le_bytes()),
+        }
+
+        bytes[21] = MEDIA_FIXED_DISK;
+
+        if !is_fat32 {
+            bytes[22..24].copy_from_slice(&(self.fat_size as u16).to_le_bytes());
+        }
+
+        // Sectors per track and number of heads, meaningless for anything but floppies.
+        bytes[24..26].copy_from_slice(&32u16.to_le_bytes());
+        bytes[26..28].copy_from_slice(&64u16.to_le_bytes());
+
+        let extended = match is_fat32 {
+            true => {
+                bytes[36..40].copy_from_slice(&self.fat_size.to_le_bytes());
+                bytes[44..48].copy_from_slice(&2u32.to_le_bytes());
+                bytes[48..50].copy_from_slice(&FS_INFO_SECTOR.to_le_bytes());
+                bytes[50..52].copy_from_slice(&BACKUP_BOOT_SECTOR.to_le_bytes());
+                64
+            }
+            false => 36,
+        };
+
+        bytes[extended] = 0x80;
+        bytes[extended + 2] = 0x29;
+        bytes[extended + 3..extended + 7].copy_from_slice(&options.volume_id.to_le_bytes());
+        bytes[extended + 7..extended + 18].copy_from_slice(&options.volume_label);
+        bytes[extended + 18..extended + 26].copy_from_slice(self.fat_type.fs_type_label());
+
+        bytes[510..512].copy_from_slice(&crate::boot_sector::BOOT_SIGNATURE);
+
+        bytes
+    }
+
+    fn fs_info(&self) -> [u8; BootSector::SIZE] {
+        let mut bytes = [0u8; BootSector::SIZE];
+
+        bytes[0..4].copy_from_slice(&FS_INFO_LEAD_SIGNATURE.to_le_bytes());
+        bytes[484..488].copy_from_slice(&FS_INFO_STRUCT_SIGNATURE.to_le_bytes());
+        // The free count and next free hints are unknown.
+        bytes[488..496].fill(0xFF);
+        bytes[508..512].copy_from_slice(&FS_INFO_TRAIL_SIGNATURE.to_le_bytes());
+
+        bytes
+    }
+
+    /// The first entries of the FAT, these hold the media type and the end of chain marker. On
+    /// FAT32 the root directory occupies cluster 2.
+    fn fat_start(&self) -> &'static [u8] {
+        match self.fat_type {
+            FatType::Fat12 => &[MEDIA_FIXED_DISK, 0xFF, 0xFF],
+            FatType::Fat16 => &[MEDIA_FIXED_DISK, 0xFF, 0xFF, 0xFF],
+            FatType::Fat32 => &[
+                MEDIA_FIXED_DISK,
+                0xFF,
+                0xFF,
+                0x0F,
+                0xFF,
+                0xFF,
+                0xFF,
+                0x0F,
+                0xFF,
+                0xFF,
+                0xFF,
+                0x0F,
+            ],
+        }
+    }
+
+    /// The amount of sectors that has to be cleared, up to and including the root directory.
+    fn meta_sectors(&self) -> u32 {
+        let root_dir_sectors = match self.fat_type {
+            FatType::Fat32 => self.sectors_per_cluster as u32,
+            _ => (self.root_entry_count * 32).div_ceil(BYTES_PER_SECTOR),
+        };
+
+        self.reserved_sectors + FAT_COUNT * self.fat_size + root_dir_sectors
+    }
+}
+
+/// Create an empty FAT volume that spans the entire storage.
+pub fn format<S: Storage>(storage: &mut S, options: &FormatOptions) -> Result<(), FatError> {
+    const ZEROS: [u8; BYTES_PER_SECTOR as usize] = [0; BYTES_PER_SECTOR as usize];
+
+    let total_sectors = u32::try_from(storage.size() / BYTES_PER_SECTOR as u64)
+        .map_err(|_| FatError::InvalidVolumeSize)?;
+
+    let layout = Layout::choose(options, total_sectors)?;
+
+    for sector in 0..layout.meta_sectors() {
+        storage.write_at(sector as u64 * BYTES_PER_SECTOR as u64, &ZEROS)?;
+    }
+
+    let boot_sector = layout.boot_sector(options);
+    storage.write_at(0, &boot_sector)?;
+
+    if layout.fat_type == FatType::Fat32 {
+        let fs_info = layout.fs_info();
+
+        for base in [0, BACKUP_BOOT_SECTOR as u64] {
+            let offset = base * BYTES_PER_SECTOR as u64;
+            storage.write_at(offset, &boot_sector)?;
+            storage.write_at(offset + BYTES_PER_SECTOR as u64, &fs_info)?;
+        }
+    }
+
+    for fat in 0..FAT_COUNT {
+        let sector = layout.reserved_sectors + fat * layout.fat_size;
+        storage.write_at(sector as u64 * BYTES_PER_SECTOR as u64, layout.fat_start())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    fn formatted(size: usize, options: &FormatOptions) -> BootSector {
+        let mut storage = vec![0u8; size];
+        format(&mut storage, options).unwrap();
+        BootSector::read(&storage).unwrap()
+    }
+
+    #[test_case]
+    fn test_format_floppy_is_fat12() {
+        let boot = formatted(1440 * 1024, &FormatOptions::default());
+
+        assert_eq!(FatType::Fat12, boot.fat_type);
+        assert_eq!(1, boot.sectors_per_cluster);
+        assert_eq!(2880, boot.total_sectors);
+    }
+
+    #[test_case]
+    fn test_format_forced_fat16() {
+        let options = FormatOptions {
+            fat_type: Some(FatType::Fat16),
+            ..Default::default()
+        };
+
+        let boot = formatted(8 * 1024 * 1024, &options);
+
+        assert_eq!(FatType::Fat16, boot.fat_type);
+        assert_eq!(512, boot.root_entry_count);
+    }
+
+    #[test_case]
+    fn test_format_forced_fat32() {
+        let options = FormatOptions {
+            fat_type: Some(FatType::Fat32),
+            ..Default::default()
+        };
+
+        let boot = formatted(40 * 1024 * 1024, &options);
+
+        assert_eq!(FatType::Fat32, boot.fat_type);
+        assert_eq!(2, boot.root_cluster);
+        assert_eq!(FS_INFO_SECTOR, boot.fs_info_sector);
+    }
+
+    #[test_case]
+    fn test_format_too_small() {
+        let mut storage = vec![0u8; 4 * 1024];
+        let options = FormatOptions {
+            fat_type: Some(FatType::Fat32),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            Err(FatError::InvalidVolumeSize),
+            format(&mut storage, &options)
+        );
+    }
+}
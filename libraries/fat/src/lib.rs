@@ -0,0 +1,33 @@
+//! A FAT12/16/32 filesystem implementation.
+//!
+//! The crate only depends on `core` and `alloc`, and reads and writes the volume through the
+//! [`Storage`] trait. This makes it usable in the kernel as well as on the host, where it can be
+//! tested against images created by `mkfs.fat`.
+//!
+//! Resources:
+//!  - [Microsoft FAT Specification](https://academy.cba.mit.edu/classes/networking_communications/SD/FAT.pdf).
+//!  - [osdev wiki](https://wiki.osdev.org/FAT).
+
+#![cfg_attr(not(test), no_std)]
+#![feature(custom_test_frameworks)]
+#![test_runner(test_runner::runner)]
+
+extern crate alloc;
+
+mod boot_sector;
+mod dir;
+mod error;
+mod format;
+mod storage;
+mod table;
+mod volume;
+
+#[cfg(test)]
+mod test_utils;
+
+pub use boot_sector::{BootSector, FatType};
+pub use dir::DirEntry;
+pub use error::FatError;
+pub use format::{format, FormatOptions};
+pub use storage::{Storage, StorageError};
+pub use volume::{FatVolume, FileKind, InodeId, Metadata};
@@ -0,0 +1,49 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageError {
+    OutOfBounds,
+    Io,
+}
+
+/// Byte addressable storage that holds a FAT volume, like a disk partition or a ramdisk.
+pub trait Storage {
+    /// The size of the storage in bytes.
+    fn size(&self) -> u64;
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), StorageError>;
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<(), StorageError>;
+}
+
+fn range(offset: u64, len: usize, size: usize) -> Result<core::ops::Range<usize>, StorageError> {
+    let start = usize::try_from(offset).map_err(|_| StorageError::OutOfBounds)?;
+    let end = start.checked_add(len).ok_or(StorageError::OutOfBounds)?;
+
+    if end > size {
+        return Err(StorageError::OutOfBounds);
+    }
+
+    Ok(start..end)
+}
+
+/// In-memory storage, for example `Vec<u8>`, `Box<[u8]>` or `&mut [u8]`.
+impl<T> Storage for T
+where
+    T: AsRef<[u8]> + AsMut<[u8]>,
+{
+    fn size(&self) -> u64 {
+        self.as_ref().len() as u64
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), StorageError> {
+        let bytes = self.as_ref();
+        buf.copy_from_slice(&bytes[range(offset, buf.len(), bytes.len())?]);
+        Ok(())
+    }
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<(), StorageError> {
+        let bytes = self.as_mut();
+        let range = range(offset, buf.len(), bytes.len())?;
+        bytes[range].copy_from_slice(buf);
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+//! Access to the File Allocation Table, the linked lists of clusters that make up files.
+
+use alloc::vec::Vec;
+
+use crate::{FatError, FatType, FatVolume, Storage};
+
+const FAT32_ENTRY_MASK: u32 = 0x0FFF_FFFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatEntry {
+    Free,
+    Next(u32),
+    EndOfChain,
+    Bad,
+}
+
+impl<S: Storage> FatVolume<S> {
+    pub(crate) fn read_fat_entry(&self, cluster: u32) -> Result<FatEntry, FatError> {
+        let boot = &self.boot;
+        let offset = boot.fat_offset(0) + boot.fat_entry_offset(cluster);
+
+        let value = match boot.fat_type {
+            FatType::Fat12 => {
+                let mut bytes = [0u8; 2];
+                self.storage.read_at(offset, &mut bytes)?;
+                let value = u16::from_le_bytes(bytes);
+
+                if cluster % 2 == 1 {
+                    (value >> 4) as u32
+                } else {
+                    (value & 0x0FFF) as u32
+                }
+            }
+            FatType::Fat16 => {
+                let mut bytes = [0u8; 2];
+                self.storage.read_at(offset, &mut bytes)?;
+                u16::from_le_bytes(bytes) as u32
+            }
+            FatType::Fat32 => {
+                let mut bytes = [0u8; 4];
+                self.storage.read_at(offset, &mut bytes)?;
+                u32::from_le_bytes(bytes) & FAT32_ENTRY_MASK
+            }
+        };
+
+        Ok(match value {
+            0 => FatEntry::Free,
+            v if v >= boot.fat_type.end_of_chain() => FatEntry::EndOfChain,
+            v if v == boot.fat_type.bad_cluster() => FatEntry::Bad,
+            v if boot.is_valid_cluster(v) => FatEntry::Next(v),
+            _ => return Err(FatError::Corrupt),
+        })
+    }
+
+    /// Write an entry into every copy of the FAT.
+    pub(crate) fn write_fat_entry(
+        &mut self,
+        cluster: u32,
+        entry: FatEntry,
+    ) -> Result<(), FatError> {
+        let fat_type = self.boot.fat_type;
+
+        let value = match entry {
+            FatEntry::Free => 0,
+            FatEntry::Next(next) => next,
+            FatEntry::EndOfChain => fat_type.end_of_chain() | 0x7,
+            FatEntry::Bad => fat_type.bad_cluster(),
+        };
+
+        for fat_index in 0..self.boot.fat_count {
+            let offset = self.boot.fat_offset(fat_index) + self.boot.fat_entry_offset(cluster);
+
+            match fat_type {
+                FatType::Fat12 => {
+                    let mut bytes = [0u8; 2];
+                    self.storage.read_at(offset, &mut bytes)?;
+                    let old = u16::from_le_bytes(bytes);
+
+                    let new = if cluster % 2 == 1 {
+                        (old & 0x000F) | ((value as u16) << 4)
+                    } else {
+                        (old & 0xF000) | (value as u16 & 0x0FFF)
+                    };
+
+                    self.storage.write_at(offset, &new.to_le_bytes())?;
+                }
+                FatType::Fat16 => {
+                    self.storage
+                        .write_at(offset, &(value as u16).to_le_bytes())?;
+                }
+                FatType::Fat32 => {
+                    // The upper 4 bits are reserved and must be preserved.
+                    let mut bytes = [0u8; 4];
+                    self.storage.read_at(offset, &mut bytes)?;
+                    let old = u32::from_le_bytes(bytes);
+                    let new = (old & !FAT32_ENTRY_MASK) | value;
+
+                    self.storage.write_at(offset, &new.to_le_bytes())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Collect all clusters in a chain starting at `first`.
+    pub(crate) fn cluster_chain(&self, first: u32) -> Result<Vec<u32>, FatError> {
+        let mut chain = Vec::new();
+
+        if first == 0 {
+            return Ok(chain);
+        }
+
+        if !self.boot.is_valid_cluster(first) {
+            return Err(FatError::Corrupt);
+        }
+
+        let mut current = first;
+
+        loop {
+            chain.push(current);
+
+            // A chain can never be longer than the amount of clusters, if it is there is a loop.
+            if chain.len() > self.boot.cluster_count as usize {
+                return Err(FatError::Corrupt);
+            }
+
+            match self.read_fat_entry(current)? {
+                FatEntry::Next(next) => current = next,
+                FatEntry::EndOfChain => return Ok(chain),
+                FatEntry::Free | FatEntry::Bad => return Err(FatError::Corrupt),
+            }
+        }
+    }
+
+    /// Allocate a zeroed cluster, and append it to the chain ending with `prev` if given.
+    pub(crate) fn allocate_cluster(&mut self, prev: Option<u32>) -> Result<u32, FatError> {
+        let count = self.boot.cluster_count;
+        let start = self.next_free.clamp(2, count + 1);
+
+        let mut found = None;
+
+        for i in 0..count {
+            let cluster = 2 + (start - 2 + i) % count;
+
+            if self.read_fat_entry(cluster)? == FatEntry::Free {
+                found = Some(cluster);
+                break;
+            }
+        }
+
+        let cluster = found.ok_or(FatError::NoSpace)?;
+
+        self.write_fat_entry(cluster, FatEntry::EndOfChain)?;
+        self.zero_cluster(cluster)?;
+
+        if let Some(prev) = prev {
+            self.write_fat_entry(prev, FatEntry::Next(cluster))?;
+        }
+
+        self.next_free = cluster + 1;
+        self.invalidate_fs_info()?;
+
+        Ok(cluster)
+    }
+
+    /// Mark every cluster in the chain starting at `first` as free.
+    pub(crate) fn free_chain(&mut self, first: u32) -> Result<(), FatError> {
+        for cluster in self.cluster_chain(first)? {
+            self.write_fat_entry(cluster, FatEntry::Free)?;
+        }
+
+        self.invalidate_fs_info()
+    }
+
+    fn zero_cluster(&mut self, cluster: u32) -> Result<(), FatError> {
+        const ZEROS: [u8; 512] = [0; 512];
+
+        let offset = self.boot.cluster_offset(cluster);
+
+        for i in 0..self.boot.bytes_per_cluster() as u64 / ZEROS.len() as u64 {
+            self.storage
+                .write_at(offset + i * ZEROS.len() as u64, &ZEROS)?;
+        }
+
+        Ok(())
+    }
+
+    /// The FAT32 FSInfo sector contains hints about the free cluster count. Instead of keeping them
+    /// up to date, they are marked as unknown after the first modification.
+    fn invalidate_fs_info(&mut self) -> Result<(), FatError> {
+        const UNKNOWN: [u8; 8] = [0xFF; 8];
+
+        let sector = self.boot.fs_info_sector;
+
+        if self.fs_info_invalidated
+            || self.boot.fat_type != FatType::Fat32
+            || sector == 0
+            || sector == u16::MAX
+        {
+            return Ok(());
+        }
+
+        let offset = sector as u64 * self.boot.bytes_per_sector as u64;
+        self.storage.write_at(offset + 488, &UNKNOWN)?;
+        self.fs_info_invalidated = true;
+
+        Ok(())
+    }
+}
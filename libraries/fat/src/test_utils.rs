@@ -0,0 +1,63 @@
+//! Helpers for creating volumes in host tests.
+
+extern crate std;
+
+use alloc::vec::Vec;
+use std::{fs, io::ErrorKind, process::Command, string::ToString};
+
+use crate::{format, FatType, FatVolume, FormatOptions};
+
+/// Large enough to be formatted as any FAT type.
+pub const FAT32_IMAGE_SIZE: usize = 40 * 1024 * 1024;
+
+/// A freshly formatted in-memory volume.
+pub fn volume(fat_type: FatType) -> FatVolume<Vec<u8>> {
+    let size = match fat_type {
+        FatType::Fat12 => 1440 * 1024,
+        FatType::Fat16 => 8 * 1024 * 1024,
+        FatType::Fat32 => FAT32_IMAGE_SIZE,
+    };
+
+    let mut storage = alloc::vec![0u8; size];
+    let options = FormatOptions {
+        fat_type: Some(fat_type),
+        ..Default::default()
+    };
+
+    format(&mut storage, &options).unwrap();
+    FatVolume::open(storage).unwrap()
+}
+
+/// Create an image with the `mkfs.fat` from dosfstools, returns `None` when it is not installed so
+/// the calling test can skip the image. Any other failure fails the test.
+pub fn mkfs_image(fat_bits: u8, size_kib: usize) -> Option<Vec<u8>> {
+    let path = std::env::temp_dir().join(std::format!(
+        "zenix-fat{fat_bits}-{}.img",
+        std::process::id()
+    ));
+
+    let _ = fs::remove_file(&path);
+
+    let output = Command::new("mkfs.fat")
+        .arg("-C")
+        .arg("-F")
+        .arg(fat_bits.to_string())
+        .arg(&path)
+        .arg(size_kib.to_string())
+        .output();
+
+    let status = match output {
+        Ok(output) => output.status,
+        Err(err) if err.kind() == ErrorKind::NotFound => return None,
+        Err(err) => panic!("failed to run mkfs.fat: {err}"),
+    };
+
+    assert!(
+        status.success(),
+        "mkfs.fat failed to create a FAT{fat_bits} image"
+    );
+
+    let image = fs::read(&path).unwrap();
+    let _ = fs::remove_file(&path);
+    Some(image)
+}
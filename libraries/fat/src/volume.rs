@@ -0,0 +1,863 @@
+use alloc::{string::String, vec::Vec};
+use core::ops::Range;
+
+use crate::{dir::*, table::FatEntry, BootSector, FatError, FatType, Storage};
+
+/// Identifies a file or directory on the volume.
+///
+/// FAT has no inodes, instead the byte offset of a file's short directory entry is used. The root
+/// directory has no entry and uses [`FatVolume::ROOT`].
+pub type InodeId = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    File,
+    Directory,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata {
+    pub inode: InodeId,
+    pub kind: FileKind,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DirLocation {
+    /// The fixed size root directory of FAT12/16.
+    FixedRoot,
+    Chain(u32),
+}
+
+/// A directory entry together with where it is stored.
+struct Located {
+    name: String,
+    entry: RawEntry,
+    /// The offsets of the long name entries followed by the short entry.
+    slots: Vec<u64>,
+}
+
+impl Located {
+    fn inode(&self) -> InodeId {
+        *self.slots.last().expect("there is always a short entry")
+    }
+}
+
+/// A mounted FAT volume.
+pub struct FatVolume<S> {
+    pub(crate) storage: S,
+    pub(crate) boot: BootSector,
+    pub(crate) next_free: u32,
+    pub(crate) fs_info_invalidated: bool,
+}
+
+impl<S: Storage> FatVolume<S> {
+    pub const ROOT: InodeId = 0;
+
+    pub fn open(storage: S) -> Result<Self, FatError> {
+        let boot = BootSector::read(&storage)?;
+
+        Ok(Self {
+            storage,
+            boot,
+            next_free: 2,
+            fs_info_invalidated: false,
+        })
+    }
+
+    pub fn boot_sector(&self) -> &BootSector {
+        &self.boot
+    }
+
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+
+    pub fn lookup(&self, dir: InodeId, name: &str) -> Result<InodeId, FatError> {
+        let location = self.dir_location(dir)?;
+
+        if location == self.root_location() && (name == "." || name == "..") {
+            return Ok(Self::ROOT);
+        }
+
+        self.find(location, name)?
+            .map(|found| found.inode())
+            .ok_or(FatError::NotFound)
+    }
+
+    pub fn stat(&self, inode: InodeId) -> Result<Metadata, FatError> {
+        if inode == Self::ROOT {
+            return Ok(Metadata {
+                inode,
+                kind: FileKind::Directory,
+                size: 0,
+            });
+        }
+
+        let entry = self.entry_at(inode)?;
+
+        Ok(Metadata {
+            inode,
+            kind: entry.kind(),
+            size: entry.size() as u64,
+        })
+    }
+
+    /// List the entries of a directory, without `.` and `..`.
+    pub fn readdir(&self, dir: InodeId) -> Result<Vec<DirEntry>, FatError> {
+        let location = self.dir_location(dir)?;
+
+        Ok(self
+            .scan(location)?
+            .into_iter()
+            .filter(|found| found.name != "." && found.name != "..")
+            .map(|found| DirEntry {
+                inode: found.inode(),
+                kind: found.entry.kind(),
+                size: found.entry.size(),
+                name: found.name,
+            })
+            .collect())
+    }
+
+    /// Read from a file, returns the amount of bytes read which is only less than `buf.len()` at
+    /// the end of the file.
+    pub fn read(&self, inode: InodeId, offset: u64, buf: &mut [u8]) -> Result<usize, FatError> {
+        let entry = self.file_entry_at(inode)?;
+        let size = entry.size() as u64;
+
+        if offset >= size {
+            return Ok(0);
+        }
+
+        let len = buf.len().min((size - offset) as usize);
+        let chain = self.cluster_chain(entry.first_cluster())?;
+
+        for (disk_offset, range) in self.segments(&chain, offset, len)? {
+            self.storage.read_at(disk_offset, &mut buf[range])?;
+        }
+
+        Ok(len)
+    }
+
+    /// Write to a file, growing it when writing past its end. A gap between the end of the file
+    /// and `offset` is filled with zeros.
+    pub fn write(&mut self, inode: InodeId, offset: u64, buf: &[u8]) -> Result<usize, FatError> {
+        let mut entry = self.file_entry_at(inode)?;
+
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let end = offset
+            .checked_add(buf.len() as u64)
+            .filter(|&end| end <= u32::MAX as u64)
+            .ok_or(FatError::NoSpace)?;
+
+        let bytes_per_cluster = self.boot.bytes_per_cluster() as u64;
+        let mut chain = self.cluster_chain(entry.first_cluster())?;
+        let allocated = chain.len() as u64 * bytes_per_cluster;
+
+        let needed_clusters = end.div_ceil(bytes_per_cluster) as usize;
+
+        while chain.len() < needed_clusters {
+            let cluster = match self.allocate_cluster(chain.last().copied()) {
+                Ok(cluster) => cluster,
+                Err(e) => {
+                    self.write_entry(inode, &entry)?;
+                    return Err(e);
+                }
+            };
+
+            if chain.is_empty() {
+                entry.set_first_cluster(cluster);
+            }
+
+            chain.push(cluster);
+        }
+
+        // Newly allocated clusters are zeroed, but the end of the last cluster might not be.
+        let size = entry.size() as u64;
+        if offset > size && size < allocated {
+            let gap = (offset.min(allocated) - size) as usize;
+            let zeros = alloc::vec![0u8; gap];
+
+            for (disk_offset, range) in self.segments(&chain, size, gap)? {
+                self.storage.write_at(disk_offset, &zeros[range])?;
+            }
+        }
+
+        for (disk_offset, range) in self.segments(&chain, offset, buf.len())? {
+            self.storage.write_at(disk_offset, &buf[range])?;
+        }
+
+        entry.set_size(entry.size().max(end as u32));
+        self.write_entry(inode, &entry)?;
+
+        Ok(buf.len())
+    }
+
+    pub fn create(
+        &mut self,
+        dir: InodeId,
+        name: &str,
+        kind: FileKind,
+    ) -> Result<InodeId, FatError> {
+        validate_name(name)?;
+
+        let location = self.dir_location(dir)?;
+
+        if self.find(location, name)?.is_some() {
+            return Err(FatError::AlreadyExists);
+        }
+
+        let (attr, first_cluster) = match kind {
+            FileKind::File => (ATTR_ARCHIVE, 0),
+            FileKind::Directory => (ATTR_DIRECTORY, self.create_dir_cluster(location)?),
+        };
+
+        let result = self.insert(location, name, attr, first_cluster);
+
+        if result.is_err() && first_cluster != 0 {
+            self.free_chain(first_cluster)?;
+        }
+
+        result
+    }
+
+    /// Remove a file or an empty directory.
+    pub fn unlink(&mut self, dir: InodeId, name: &str) -> Result<(), FatError> {
+        if name == "." || name == ".." {
+            return Err(FatError::InvalidName);
+        }
+
+        let location = self.dir_location(dir)?;
+        let found = self.find(location, name)?.ok_or(FatError::NotFound)?;
+        let first_cluster = found.entry.first_cluster();
+
+        if found.entry.is_dir() {
+            let not_empty = self
+                .scan(DirLocation::Chain(first_cluster))?
+                .iter()
+                .any(|child| child.name != "." && child.name != "..");
+
+            if not_empty {
+                return Err(FatError::NotEmpty);
+            }
+        }
+
+        for slot in found.slots {
+            let mut entry = self.read_slot(slot)?;
+            entry.mark_deleted();
+            self.storage.write_at(slot, entry.as_bytes())?;
+        }
+
+        if first_cluster != 0 {
+            self.free_chain(first_cluster)?;
+        }
+
+        Ok(())
+    }
+
+    fn root_location(&self) -> DirLocation {
+        match self.boot.fat_type {
+            FatType::Fat32 => DirLocation::Chain(self.boot.root_cluster),
+            _ => DirLocation::FixedRoot,
+        }
+    }
+
+    fn dir_location(&self, inode: InodeId) -> Result<DirLocation, FatError> {
+        if inode == Self::ROOT {
+            return Ok(self.root_location());
+        }
+
+        let entry = self.entry_at(inode)?;
+
+        if !entry.is_dir() {
+            return Err(FatError::NotADirectory);
+        }
+
+        // The `..` entry of a directory within the root refers to cluster 0.
+        match entry.first_cluster() {
+            0 => Ok(self.root_location()),
+            cluster => Ok(DirLocation::Chain(cluster)),
+        }
+    }
+
+    fn read_slot(&self, offset: u64) -> Result<RawEntry, FatError> {
+        let mut bytes = [0u8; ENTRY_SIZE];
+        self.storage.read_at(offset, &mut bytes)?;
+        Ok(RawEntry::from_bytes(bytes))
+    }
+
+    /// Check that `inode` is the offset of an entry slot, either in the root directory of
+    /// FAT12/16 or in an allocated data cluster, so that it never refers to other structures.
+    fn check_inode(&self, inode: InodeId) -> Result<(), FatError> {
+        if !inode.is_multiple_of(ENTRY_SIZE as u64) {
+            return Err(FatError::NotFound);
+        }
+
+        if self.root_location() == DirLocation::FixedRoot {
+            let (offset, size) = self.boot.root_dir_region();
+
+            if (offset..offset + size).contains(&inode) {
+                return Ok(());
+            }
+        }
+
+        let data_offset = self.boot.cluster_offset(2);
+        let cluster_size = self.boot.bytes_per_cluster() as u64;
+
+        let cluster = inode
+            .checked_sub(data_offset)
+            .and_then(|offset| u32::try_from(offset / cluster_size + 2).ok())
+            .filter(|cluster| self.boot.is_valid_cluster(*cluster))
+            .ok_or(FatError::NotFound)?;
+
+        match self.read_fat_entry(cluster)? {
+            FatEntry::Next(_) | FatEntry::EndOfChain => Ok(()),
+            FatEntry::Free | FatEntry::Bad => Err(FatError::NotFound),
+        }
+    }
+
+    fn entry_at(&self, inode: InodeId) -> Result<RawEntry, FatError> {
+        self.check_inode(inode)?;
+
+        let entry = self.read_slot(inode)?;
+
+        // The entry might have been removed since the inode was handed out.
+        if entry.is_free() || entry.is_long_name() || entry.is_volume_label() {
+            return Err(FatError::NotFound);
+        }
+
+        Ok(entry)
+    }
+
+    fn file_entry_at(&self, inode: InodeId) -> Result<RawEntry, FatError> {
+        if inode == Self::ROOT {
+            return Err(FatError::IsADirectory);
+        }
+
+        let entry = self.entry_at(inode)?;
+
+        if entry.is_dir() {
+            return Err(FatError::IsADirectory);
+        }
+
+        Ok(entry)
+    }
+
+    fn write_entry(&mut self, inode: InodeId, entry: &RawEntry) -> Result<(), FatError> {
+        self.check_inode(inode)?;
+
+        self.storage.write_at(inode, entry.as_bytes())?;
+        Ok(())
+    }
+
+    /// The offset of every entry slot in a directory.
+    fn slots(&self, location: DirLocation) -> Result<Vec<u64>, FatError> {
+        let entry_size = ENTRY_SIZE as u64;
+
+        match location {
+            DirLocation::FixedRoot => {
+                let (offset, size) = self.boot.root_dir_region();
+                Ok((0..size / entry_size)
+                    .map(|i| offset + i * entry_size)
+                    .collect())
+            }
+            DirLocation::Chain(first) => {
+                let per_cluster = self.boot.bytes_per_cluster() as u64 / entry_size;
+
+                Ok(self
+                    .cluster_chain(first)?
+                    .into_iter()
+                    .flat_map(|cluster| {
+                        let offset = self.boot.cluster_offset(cluster);
+                        (0..per_cluster).map(move |i| offset + i * entry_size)
+                    })
+                    .collect())
+            }
+        }
+    }
+
+    /// All entries in a directory, except volume labels.
+    fn scan(&self, location: DirLocation) -> Result<Vec<Located>, FatError> {
+        let mut entries = Vec::new();
+        let mut long_name = LongNameBuilder::default();
+        let mut pending_slots = Vec::new();
+
+        for slot in self.slots(location)? {
+            let entry = self.read_slot(slot)?;
+
+            if entry.is_end() {
+                break;
+            }
+
+            if entry.is_free() {
+                long_name.reset();
+                pending_slots.clear();
+                continue;
+            }
+
+            if entry.is_long_name() {
+                long_name.push(&entry);
+                pending_slots.push(slot);
+                continue;
+            }
+
+            if entry.is_volume_label() {
+                long_name.reset();
+                pending_slots.clear();
+                continue;
+            }
+
+            let (name, mut slots) = match long_name.finish(&entry) {
+                Some(name) => (name, core::mem::take(&mut pending_slots)),
+                None => {
+                    pending_slots.clear();
+                    (entry.decoded_short_name(), Vec::new())
+                }
+            };
+
+            slots.push(slot);
+            entries.push(Located { name, entry, slots });
+        }
+
+        Ok(entries)
+    }
+
+    fn find(&self, location: DirLocation, name: &str) -> Result<Option<Located>, FatError> {
+        Ok(self.scan(location)?.into_iter().find(|found| {
+            found.name.eq_ignore_ascii_case(name)
+                || found.entry.decoded_short_name().eq_ignore_ascii_case(name)
+        }))
+    }
+
+    /// Add a new entry to a directory, returns the offset of the short entry.
+    fn insert(
+        &mut self,
+        location: DirLocation,
+        name: &str,
+        attr: u8,
+        first_cluster: u32,
+    ) -> Result<InodeId, FatError> {
+        let entries = match exact_short_name(name) {
+            Some((short_name, flags)) => {
+                alloc::vec![RawEntry::new(short_name, flags, attr, first_cluster)]
+            }
+            None => {
+                let short_name = self.unique_short_name(location, name)?;
+                let mut entries = long_name_entries(name, &short_name);
+                entries.push(RawEntry::new(short_name, 0, attr, first_cluster));
+                entries
+            }
+        };
+
+        let slots = self.free_slots(location, entries.len())?;
+
+        for (slot, entry) in slots.iter().zip(entries.iter()) {
+            self.storage.write_at(*slot, entry.as_bytes())?;
+        }
+
+        Ok(*slots.last().expect("there is always a short entry"))
+    }
+
+    fn unique_short_name(&self, location: DirLocation, name: &str) -> Result<[u8; 11], FatError> {
+        let existing: Vec<[u8; 11]> = self
+            .scan(location)?
+            .iter()
+            .map(|found| *found.entry.short_name())
+            .collect();
+
+        (1..=MAX_SHORT_NAME_TAIL)
+            .map(|tail| generated_short_name(name, tail))
+            .find(|short_name| !existing.contains(short_name))
+            .ok_or(FatError::NoSpace)
+    }
+
+    /// Find `count` consecutive free slots, growing the directory if needed.
+    fn free_slots(&mut self, location: DirLocation, count: usize) -> Result<Vec<u64>, FatError> {
+        loop {
+            let slots = self.slots(location)?;
+            let mut run_start = 0;
+            let mut run_len = 0;
+
+            for (i, &slot) in slots.iter().enumerate() {
+                let entry = self.read_slot(slot)?;
+
+                // Everything after the end marker is free.
+                if entry.is_end() {
+                    let start = i - run_len;
+
+                    if slots.len() - start >= count {
+                        return Ok(slots[start..start + count].to_vec());
+                    }
+
+                    break;
+                }
+
+                if entry.is_free() {
+                    if run_len == 0 {
+                        run_start = i;
+                    }
+
+                    run_len += 1;
+
+                    if run_len == count {
+                        return Ok(slots[run_start..run_start + count].to_vec());
+                    }
+                } else {
+                    run_len = 0;
+                }
+            }
+
+            let DirLocation::Chain(first) = location else {
+                return Err(FatError::NoSpace);
+            };
+
+            let last = *self
+                .cluster_chain(first)?
+                .last()
+                .expect("chain is not empty");
+            self.allocate_cluster(Some(last))?;
+        }
+    }
+
+    /// Allocate the first cluster of a new directory containing the `.` and `..` entries.
+    fn create_dir_cluster(&mut self, parent: DirLocation) -> Result<u32, FatError> {
+        let cluster = self.allocate_cluster(None)?;
+
+        let parent_cluster = match parent {
+            DirLocation::Chain(cluster) if parent != self.root_location() => cluster,
+            _ => 0,
+        };
+
+        let dot = RawEntry::new(*b".          ", 0, ATTR_DIRECTORY, cluster);
+        let dot_dot = RawEntry::new(*b"..         ", 0, ATTR_DIRECTORY, parent_cluster);
+
+        let offset = self.boot.cluster_offset(cluster);
+        self.storage.write_at(offset, dot.as_bytes())?;
+        self.storage
+            .write_at(offset + ENTRY_SIZE as u64, dot_dot.as_bytes())?;
+
+        Ok(cluster)
+    }
+
+    /// Split a byte range of a file over its clusters, returns the offset on disk and the range
+    /// within the buffer of each piece.
+    fn segments(
+        &self,
+        chain: &[u32],
+        offset: u64,
+        len: usize,
+    ) -> Result<Vec<(u64, Range<usize>)>, FatError> {
+        let bytes_per_cluster = self.boot.bytes_per_cluster() as u64;
+        let mut segments = Vec::new();
+        let mut done = 0;
+
+        while done < len {
+            let position = offset + done as u64;
+            let cluster = *chain
+                .get((position / bytes_per_cluster) as usize)
+                .ok_or(FatError::Corrupt)?;
+            let within = position % bytes_per_cluster;
+            let amount = (len - done).min((bytes_per_cluster - within) as usize);
+
+            segments.push((
+                self.boot.cluster_offset(cluster) + within,
+                done..done + amount,
+            ));
+            done += amount;
+        }
+
+        Ok(segments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec};
+
+    use super::*;
+    use crate::test_utils::*;
+
+    const ALL_TYPES: [FatType; 3] = [FatType::Fat12, FatType::Fat16, FatType::Fat32];
+
+    fn names(entries: &[DirEntry]) -> Vec<String> {
+        let mut names: Vec<String> = entries.iter().map(|e| e.name.clone()).collect();
+        names.sort();
+        names
+    }
+
+    #[test_case]
+    fn test_empty_root() {
+        for fat_type in ALL_TYPES {
+            let volume = volume(fat_type);
+
+            assert_eq!(fat_type, volume.boot_sector().fat_type);
+            assert!(volume
+                .readdir(FatVolume::<Vec<u8>>::ROOT)
+                .unwrap()
+                .is_empty());
+        }
+    }
+
+    #[test_case]
+    fn test_create_write_read() {
+        for fat_type in ALL_TYPES {
+            let mut volume = volume(fat_type);
+            let root = FatVolume::<Vec<u8>>::ROOT;
+
+            let file = volume.create(root, "hello.txt", FileKind::File).unwrap();
+            assert_eq!(5, volume.write(file, 0, b"hello").unwrap());
+
+            let mut buf = [0u8; 16];
+            assert_eq!(5, volume.read(file, 0, &mut buf).unwrap());
+            assert_eq!(b"hello", &buf[..5]);
+
+            assert_eq!(file, volume.lookup(root, "HELLO.TXT").unwrap());
+            assert_eq!(5, volume.stat(file).unwrap().size);
+        }
+    }
+
+    #[test_case]
+    fn test_invalid_inode() {
+        for fat_type in ALL_TYPES {
+            let mut volume = volume(fat_type);
+            let root = FatVolume::<Vec<u8>>::ROOT;
+
+            let file = volume.create(root, "hello.txt", FileKind::File).unwrap();
+            volume.write(file, 0, b"hello").unwrap();
+
+            // Within the slot of the entry, the boot sector and a free cluster.
+            let free_cluster = volume.boot_sector().cluster_count + 1;
+            let free = volume.boot_sector().cluster_offset(free_cluster);
+
+            for inode in [file + 1, ENTRY_SIZE as u64, free] {
+                assert_eq!(Err(FatError::NotFound), volume.stat(inode));
+                assert_eq!(Err(FatError::NotFound), volume.write(inode, 0, b"x"));
+            }
+
+            assert_eq!(5, volume.stat(file).unwrap().size);
+        }
+    }
+
+    #[test_case]
+    fn test_write_across_clusters() {
+        for fat_type in ALL_TYPES {
+            let mut volume = volume(fat_type);
+            let root = FatVolume::<Vec<u8>>::ROOT;
+            let cluster_size = volume.boot_sector().bytes_per_cluster() as usize;
+
+            let data: Vec<u8> = (0..cluster_size * 3 + 17).map(|i| i as u8).collect();
+            let file = volume.create(root, "data.bin", FileKind::File).unwrap();
+
+            // Write in two parts to grow an existing chain.
+            volume.write(file, 0, &data[..100]).unwrap();
+            volume.write(file, 100, &data[100..]).unwrap();
+
+            let mut buf = vec![0u8; data.len()];
+            assert_eq!(data.len(), volume.read(file, 0, &mut buf).unwrap());
+            assert_eq!(data, buf);
+        }
+    }
+
+    #[test_case]
+    fn test_write_gap_is_zeroed() {
+        let mut volume = volume(FatType::Fat16);
+        let root = FatVolume::<Vec<u8>>::ROOT;
+
+        let file = volume.create(root, "sparse", FileKind::File).unwrap();
+        volume.write(file, 0, b"abc").unwrap();
+        volume.write(file, 0, b"x").unwrap();
+
+        // Leave garbage past the end of the file within the cluster.
+        let cluster = volume.entry_at(file).unwrap().first_cluster();
+        let offset = volume.boot.cluster_offset(cluster);
+        volume.storage.write_at(offset + 3, b"garbage").unwrap();
+
+        volume.write(file, 10, b"z").unwrap();
+
+        let mut buf = [0xFFu8; 11];
+        assert_eq!(11, volume.read(file, 0, &mut buf).unwrap());
+        assert_eq!(b"xbc\0\0\0\0\0\0\0z", &buf);
+    }
+
+    #[test_case]
+    fn test_long_names() {
+        for fat_type in ALL_TYPES {
+            let mut volume = volume(fat_type);
+            let root = FatVolume::<Vec<u8>>::ROOT;
+
+            let long_a = "A file with a rather long name.txt";
+            let long_b = "A file with a rather long name, too.txt";
+
+            let a = volume.create(root, long_a, FileKind::File).unwrap();
+            let b = volume.create(root, long_b, FileKind::File).unwrap();
+
+            assert_ne!(a, b);
+            assert_eq!(a, volume.lookup(root, long_a).unwrap());
+            assert_eq!(b, volume.lookup(root, long_b).unwrap());
+            assert_eq!(
+                vec![long_b.to_string(), long_a.to_string()],
+                names(&volume.readdir(root).unwrap())
+            );
+        }
+    }
+
+    #[test_case]
+    fn test_create_existing() {
+        let mut volume = volume(FatType::Fat12);
+        let root = FatVolume::<Vec<u8>>::ROOT;
+
+        volume.create(root, "file", FileKind::File).unwrap();
+
+        assert_eq!(
+            Err(FatError::AlreadyExists),
+            volume.create(root, "FILE", FileKind::File)
+        );
+    }
+
+    #[test_case]
+    fn test_directories() {
+        for fat_type in ALL_TYPES {
+            let mut volume = volume(fat_type);
+            let root = FatVolume::<Vec<u8>>::ROOT;
+
+            let dir = volume.create(root, "dir", FileKind::Directory).unwrap();
+            let nested = volume.create(dir, "nested", FileKind::Directory).unwrap();
+            let file = volume.create(nested, "file", FileKind::File).unwrap();
+
+            assert_eq!(FileKind::Directory, volume.stat(dir).unwrap().kind);
+            assert_eq!(file, volume.lookup(nested, "file").unwrap());
+
+            let dot_dot = volume.lookup(nested, "..").unwrap();
+            assert_eq!(
+                volume.readdir(dir).unwrap(),
+                volume.readdir(dot_dot).unwrap()
+            );
+
+            let root_again = volume.lookup(dir, "..").unwrap();
+            assert_eq!(
+                volume.readdir(root).unwrap(),
+                volume.readdir(root_again).unwrap()
+            );
+
+            assert_eq!(Err(FatError::NotADirectory), volume.readdir(file));
+            assert_eq!(Err(FatError::IsADirectory), volume.write(dir, 0, b"x"));
+        }
+    }
+
+    #[test_case]
+    fn test_directory_grows() {
+        let mut volume = volume(FatType::Fat32);
+        let root = FatVolume::<Vec<u8>>::ROOT;
+        let per_cluster = volume.boot_sector().bytes_per_cluster() as usize / ENTRY_SIZE;
+
+        let dir = volume.create(root, "many", FileKind::Directory).unwrap();
+
+        for i in 0..per_cluster * 2 {
+            volume
+                .create(dir, &std::format!("file number {i}"), FileKind::File)
+                .unwrap();
+        }
+
+        assert_eq!(per_cluster * 2, volume.readdir(dir).unwrap().len());
+    }
+
+    #[test_case]
+    fn test_fixed_root_is_full() {
+        let mut volume = volume(FatType::Fat16);
+        let root = FatVolume::<Vec<u8>>::ROOT;
+        let capacity = volume.boot_sector().root_entry_count as usize;
+
+        for i in 0..capacity {
+            volume
+                .create(root, &std::format!("F{i}"), FileKind::File)
+                .unwrap();
+        }
+
+        assert_eq!(
+            Err(FatError::NoSpace),
+            volume.create(root, "ONEMORE", FileKind::File)
+        );
+    }
+
+    #[test_case]
+    fn test_unlink() {
+        for fat_type in ALL_TYPES {
+            let mut volume = volume(fat_type);
+            let root = FatVolume::<Vec<u8>>::ROOT;
+
+            let dir = volume
+                .create(root, "directory", FileKind::Directory)
+                .unwrap();
+            let file = volume
+                .create(dir, "some long file name", FileKind::File)
+                .unwrap();
+            volume.write(file, 0, &[1; 5000]).unwrap();
+
+            assert_eq!(Err(FatError::NotEmpty), volume.unlink(root, "directory"));
+
+            volume.unlink(dir, "some long file name").unwrap();
+            assert_eq!(Err(FatError::NotFound), volume.stat(file));
+            volume.unlink(root, "directory").unwrap();
+
+            assert!(volume.readdir(root).unwrap().is_empty());
+
+            // The freed slots and clusters can be reused.
+            let file = volume.create(root, "again", FileKind::File).unwrap();
+            volume.write(file, 0, &[2; 5000]).unwrap();
+        }
+    }
+
+    #[test_case]
+    fn test_out_of_space() {
+        let mut volume = volume(FatType::Fat12);
+        let root = FatVolume::<Vec<u8>>::ROOT;
+
+        let file = volume.create(root, "big", FileKind::File).unwrap();
+        let too_large = vec![0xAB; 2 * 1024 * 1024];
+
+        assert_eq!(Err(FatError::NoSpace), volume.write(file, 0, &too_large));
+
+        // The clusters that were allocated are still part of the file, and can be freed.
+        volume.unlink(root, "big").unwrap();
+
+        let file = volume.create(root, "small", FileKind::File).unwrap();
+        assert_eq!(5, volume.write(file, 0, b"fits!").unwrap());
+    }
+
+    #[test_case]
+    fn test_reopen() {
+        let mut volume = volume(FatType::Fat32);
+        let root = FatVolume::<Vec<u8>>::ROOT;
+
+        let file = volume.create(root, "persist.txt", FileKind::File).unwrap();
+        volume.write(file, 0, b"still here").unwrap();
+
+        let volume = FatVolume::open(volume.into_storage()).unwrap();
+        let file = volume.lookup(root, "persist.txt").unwrap();
+
+        let mut buf = [0u8; 10];
+        volume.read(file, 0, &mut buf).unwrap();
+        assert_eq!(b"still here", &buf);
+    }
+
+    #[test_case]
+    fn test_mkfs_images() {
+        for (bits, size_kib) in [(12, 1440), (16, 16 * 1024), (32, FAT32_IMAGE_SIZE / 1024)] {
+            let Some(image) = mkfs_image(bits, size_kib) else {
+                std::println!("skipped FAT{bits}, mkfs.fat is not installed");
+                continue;
+            };
+
+            let mut volume = FatVolume::open(image).unwrap();
+            let root = FatVolume::<Vec<u8>>::ROOT;
+
+            let file = volume.create(root, "from zenix", FileKind::File).unwrap();
+            volume.write(file, 0, b"data").unwrap();
+            assert_eq!(file, volume.lookup(root, "FROM ZENIX").unwrap());
+        }
+    }
+}
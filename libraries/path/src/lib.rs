@@ -2,6 +2,15 @@
 #![feature(custom_test_frameworks)]
 #![test_runner(test_runner::runner)]
 
+extern crate alloc;
+
+mod path_buf;
+
+use alloc::{borrow::ToOwned, boxed::Box, string::String, sync::Arc, vec::Vec};
+use core::fmt::{Debug, Display, Formatter};
+
+pub use path_buf::PathBuf;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PathError {
     InvalidChar(usize, char),
@@ -10,17 +19,23 @@ pub enum PathError {
     Empty,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(transparent)]
 pub struct Path {
     bytes: str,
 }
 
 impl Path {
     pub const ROOT: &'static Path = unsafe { Path::new_unchecked("/") };
+    pub const CURRENT: &'static Path = unsafe { Path::new_unchecked(".") };
 
     pub fn new<S: AsRef<str> + ?Sized>(value: &S) -> Result<&Self, PathError> {
         let str = value.as_ref();
 
+        if str.is_empty() {
+            return Err(PathError::Empty);
+        }
+
         let mut dot_count: u8 = 0;
         for (index, byte) in str.chars().enumerate() {
             match (dot_count, byte) {
@@ -57,24 +72,40 @@ impl Path {
         self.bytes.as_bytes()[0] != b'/'
     }
 
+    pub fn is_root(&self) -> bool {
+        self.bytes.bytes().all(|b| b == b'/')
+    }
+
     pub fn as_str(&self) -> &str {
         &self.bytes
     }
 
     pub fn starts_with(&self, base: &Path) -> bool {
+        self.strip_prefix(base).is_some()
+    }
+
+    /// Get the remainder of the path after the components of `base`.
+    ///
+    /// The comparison is done component wise, so `/ab` does not start with `/a`. The remainder is
+    /// always relative, and is `.` when both paths are equal.
+    pub fn strip_prefix(&self, base: &Path) -> Option<&Path> {
         let mut components = self.components();
 
         for base_component in base.components() {
-            let Some(component) = components.next() else {
-                return false;
-            };
+            let component = components.next()?;
 
             if base_component != component {
-                return false;
+                return None;
             }
         }
 
-        true
+        let rest = components.as_str();
+
+        if rest.is_empty() {
+            return Some(Self::CURRENT);
+        }
+
+        Some(unsafe { Self::new_unchecked(rest) })
     }
 
     pub fn components(&self) -> Components<'_> {
@@ -84,26 +115,207 @@ impl Path {
         }
     }
 
-    pub fn combine(&self, _other: &Path) -> (&Path, &Path) {
-        todo!()
+    /// The final component of the path, if it is a [`Component::Name`].
+    pub fn file_name(&self) -> Option<&str> {
+        match self.components().last()? {
+            Component::Name(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The path without its final component.
+    ///
+    /// Returns `None` when the path is the root or [`Path::CURRENT`]. The parent of any other
+    /// relative path consisting of a single component is [`Path::CURRENT`].
+    pub fn parent(&self) -> Option<&Path> {
+        let trimmed = self.bytes.trim_end_matches('/');
+
+        if trimmed.is_empty() || trimmed == Self::CURRENT.as_str() {
+            return None;
+        }
+
+        let parent = match trimmed.rfind('/') {
+            Some(index) => {
+                let parent = trimmed[..index].trim_end_matches('/');
+
+                if parent.is_empty() {
+                    return Some(Self::ROOT);
+                }
+
+                parent
+            }
+            None => return Some(Self::CURRENT),
+        };
+
+        Some(unsafe { Self::new_unchecked(parent) })
+    }
+
+    /// Append `other` to the path, when `other` is absolute it replaces the current path.
+    ///
+    /// No normalization is performed, see [`Path::combine`] for that.
+    pub fn join(&self, other: &Path) -> PathBuf {
+        let mut buf = self.to_path_buf();
+        buf.push(other);
+        buf
+    }
+
+    /// Join `other` onto the path and [`Path::normalize`] the result.
+    pub fn combine(&self, other: &Path) -> PathBuf {
+        self.join(other).normalize()
+    }
+
+    /// Lexically normalize the path.
+    ///
+    /// Repeated slashes and `.` components are removed and `..` removes the previous name. An
+    /// absolute path can never escape the root, so `/..` normalizes to `/`. Leading `..`
+    /// components of a relative path are kept since there is nothing to remove.
+    ///
+    /// The filesystem is not consulted, so symbolic links are not taken into account.
+    pub fn normalize(&self) -> PathBuf {
+        let mut names: Vec<Component<'_>> = Vec::new();
+        let absolute = self.is_absolute();
+
+        for component in self.components() {
+            match component {
+                Component::RootDir | Component::CurrentDir => {}
+                Component::Parent => match names.last() {
+                    Some(Component::Name(_)) => {
+                        names.pop();
+                    }
+                    _ if absolute => {}
+                    _ => names.push(Component::Parent),
+                },
+                Component::Name(_) => names.push(component),
+            }
+        }
+
+        let mut out = String::with_capacity(self.bytes.len());
+
+        if absolute {
+            out.push('/');
+        }
+
+        for (i, component) in names.iter().enumerate() {
+            if i > 0 {
+                out.push('/');
+            }
+
+            out.push_str(component.as_str());
+        }
+
+        if out.is_empty() {
+            out.push('.');
+        }
+
+        unsafe { PathBuf::from_string_unchecked(out) }
+    }
+
+    pub fn to_path_buf(&self) -> PathBuf {
+        unsafe { PathBuf::from_string_unchecked(String::from(&self.bytes)) }
+    }
+}
+
+impl Debug for Path {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", &self.bytes)
+    }
+}
+
+impl Display for Path {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", &self.bytes)
+    }
+}
+
+impl AsRef<Path> for Path {
+    fn as_ref(&self) -> &Path {
+        self
+    }
+}
+
+impl AsRef<str> for Path {
+    fn as_ref(&self) -> &str {
+        &self.bytes
+    }
+}
+
+impl ToOwned for Path {
+    type Owned = PathBuf;
+
+    fn to_owned(&self) -> Self::Owned {
+        self.to_path_buf()
+    }
+}
+
+impl From<&Path> for Arc<Path> {
+    fn from(value: &Path) -> Self {
+        let arc: Arc<str> = Arc::from(&value.bytes);
+
+        // Safety: `Path` is a transparent wrapper around `str`.
+        unsafe { Arc::from_raw(Arc::into_raw(arc) as *const Path) }
+    }
+}
+
+impl From<&Path> for Box<Path> {
+    fn from(value: &Path) -> Self {
+        let boxed: Box<str> = Box::from(&value.bytes);
+
+        // Safety: `Path` is a transparent wrapper around `str`.
+        unsafe { Box::from_raw(Box::into_raw(boxed) as *mut Path) }
     }
 }
 
-#[allow(dead_code)]
+/// An iterator over the [`Component`]s of a [`Path`].
+///
+/// Repeated slashes are skipped, and only a leading slash yields a [`Component::RootDir`].
 pub struct Components<'a> {
     path: &'a Path,
     index: usize,
 }
 
+impl<'a> Components<'a> {
+    /// The part of the path that has not been iterated yet.
+    pub fn as_str(&self) -> &'a str {
+        let rest = &self.path.bytes[self.index..];
+
+        if self.index == 0 {
+            return rest;
+        }
+
+        rest.trim_start_matches('/')
+    }
+}
+
 impl<'a> Iterator for Components<'a> {
     type Item = Component<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        todo!()
+        let bytes = &self.path.bytes;
+
+        if self.index == 0 && bytes.starts_with('/') {
+            self.index = bytes.len() - bytes.trim_start_matches('/').len();
+            return Some(Component::RootDir);
+        }
+
+        let rest = bytes[self.index..].trim_start_matches('/');
+        self.index = bytes.len() - rest.len();
+
+        if rest.is_empty() {
+            return None;
+        }
+
+        let len = rest.find('/').unwrap_or(rest.len());
+        self.index += len;
+
+        Some(match &rest[..len] {
+            "." => Component::CurrentDir,
+            ".." => Component::Parent,
+            name => Component::Name(name),
+        })
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Component<'a> {
     RootDir,
     CurrentDir,
@@ -111,9 +323,21 @@ pub enum Component<'a> {
     Name(&'a str),
 }
 
+impl<'a> Component<'a> {
+    pub fn as_str(&self) -> &'a str {
+        match self {
+            Component::RootDir => "/",
+            Component::CurrentDir => ".",
+            Component::Parent => "..",
+            Component::Name(name) => name,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::vec;
 
     #[test_case]
     fn test_root_path() {
@@ -193,4 +417,122 @@ mod tests {
             );
         }
     }
+
+    fn path(value: &str) -> &Path {
+        Path::new(value).unwrap()
+    }
+
+    fn components(value: &str) -> Vec<Component<'_>> {
+        path(value).components().collect()
+    }
+
+    #[test_case]
+    fn test_empty() {
+        assert_eq!(Err(PathError::Empty), Path::new(""));
+    }
+
+    #[test_case]
+    fn test_components_root() {
+        assert_eq!(vec![Component::RootDir], components("/"));
+        assert_eq!(vec![Component::RootDir], components("/////"));
+    }
+
+    #[test_case]
+    fn test_components_absolute() {
+        assert_eq!(
+            vec![
+                Component::RootDir,
+                Component::Name("home"),
+                Component::Name("anon"),
+            ],
+            components("/home//anon/")
+        );
+    }
+
+    #[test_case]
+    fn test_components_relative() {
+        assert_eq!(
+            vec![
+                Component::CurrentDir,
+                Component::Parent,
+                Component::Name("config"),
+            ],
+            components("./../config")
+        );
+    }
+
+    #[test_case]
+    fn test_starts_with() {
+        assert!(path("/home/anon").starts_with(path("/home")));
+        assert!(path("/home/anon").starts_with(path("/")));
+        assert!(path("/home").starts_with(path("/home/")));
+        assert!(!path("/homes").starts_with(path("/home")));
+        assert!(!path("/home").starts_with(path("/home/anon")));
+        assert!(!path("home").starts_with(path("/home")));
+    }
+
+    #[test_case]
+    fn test_strip_prefix() {
+        assert_eq!(
+            Some(path("anon/config")),
+            path("/home/anon/config").strip_prefix(path("/home"))
+        );
+        assert_eq!(Some(Path::CURRENT), path("/home").strip_prefix(path("/home")));
+        assert_eq!(None, path("/etc").strip_prefix(path("/home")));
+    }
+
+    #[test_case]
+    fn test_file_name() {
+        assert_eq!(Some("anon"), path("/home/anon/").file_name());
+        assert_eq!(None, path("/").file_name());
+        assert_eq!(None, path("/home/..").file_name());
+    }
+
+    #[test_case]
+    fn test_parent() {
+        assert_eq!(Some(path("/home")), path("/home/anon").parent());
+        assert_eq!(Some(Path::ROOT), path("/home").parent());
+        assert_eq!(Some(Path::CURRENT), path("config").parent());
+        assert_eq!(None, path("/").parent());
+        assert_eq!(None, path(".").parent());
+        assert_eq!(None, path("./").parent());
+    }
+
+    #[test_case]
+    fn test_normalize() {
+        assert_eq!("/a/c", path("/a/./b/../c/").normalize().as_str());
+        assert_eq!("/", path("/a/..").normalize().as_str());
+        assert_eq!(".", path("./a/..").normalize().as_str());
+    }
+
+    #[test_case]
+    fn test_normalize_does_not_escape_root() {
+        assert_eq!("/etc", path("/../../etc").normalize().as_str());
+    }
+
+    #[test_case]
+    fn test_normalize_keeps_leading_parents() {
+        assert_eq!("../../b", path("../a/../../b").normalize().as_str());
+    }
+
+    #[test_case]
+    fn test_join() {
+        assert_eq!("/home/anon", path("/home").join(path("anon")).as_str());
+        assert_eq!("/etc", path("/home").join(path("/etc")).as_str());
+    }
+
+    #[test_case]
+    fn test_combine() {
+        assert_eq!(
+            "/home/config",
+            path("/home/anon").combine(path("../config")).as_str()
+        );
+        assert_eq!("/", path("/home").combine(path("../../..")).as_str());
+    }
+
+    #[test_case]
+    fn test_arc_from_path() {
+        let arc: Arc<Path> = Arc::from(path("/home/anon"));
+        assert_eq!(path("/home/anon"), &*arc);
+    }
 }
@@ -0,0 +1,178 @@
+use alloc::string::String;
+use core::{
+    borrow::Borrow,
+    fmt::{Debug, Display, Formatter},
+    ops::Deref,
+    str::FromStr,
+};
+
+use crate::{Path, PathError};
+
+/// An owned, mutable [`Path`] (akin to [`String`] for [`str`]).
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PathBuf {
+    inner: String,
+}
+
+impl PathBuf {
+    /// Create a `PathBuf` from a string, with the same validation as [`Path::new`].
+    pub fn new(value: String) -> Result<Self, PathError> {
+        Path::new(&value)?;
+        Ok(Self { inner: value })
+    }
+
+    /// # Safety
+    ///
+    /// The string must be a valid path according to [`Path::new`].
+    pub(crate) unsafe fn from_string_unchecked(inner: String) -> Self {
+        Self { inner }
+    }
+
+    pub fn as_path(&self) -> &Path {
+        unsafe { Path::new_unchecked(&self.inner) }
+    }
+
+    /// Extend the path with `other`, when `other` is absolute it replaces the current path.
+    pub fn push(&mut self, other: &Path) {
+        if other.is_absolute() {
+            self.inner.clear();
+        } else if !self.inner.ends_with('/') {
+            self.inner.push('/');
+        }
+
+        self.inner.push_str(other.as_str());
+    }
+
+    /// Truncate the path to its [`Path::parent`].
+    ///
+    /// Returns `false` and leaves the path untouched when there is no parent.
+    pub fn pop(&mut self) -> bool {
+        let Some(parent) = self.as_path().parent() else {
+            return false;
+        };
+
+        // The parent is either a prefix of the path or one of the `Path` constants.
+        if parent.as_str().as_ptr() == self.inner.as_ptr() {
+            let len = parent.as_str().len();
+            self.inner.truncate(len);
+        } else {
+            self.inner = String::from(parent.as_str());
+        }
+
+        true
+    }
+
+    pub fn into_string(self) -> String {
+        self.inner
+    }
+}
+
+impl Deref for PathBuf {
+    type Target = Path;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_path()
+    }
+}
+
+impl Borrow<Path> for PathBuf {
+    fn borrow(&self) -> &Path {
+        self.as_path()
+    }
+}
+
+impl AsRef<Path> for PathBuf {
+    fn as_ref(&self) -> &Path {
+        self.as_path()
+    }
+}
+
+impl From<&Path> for PathBuf {
+    fn from(value: &Path) -> Self {
+        value.to_path_buf()
+    }
+}
+
+impl TryFrom<String> for PathBuf {
+    type Error = PathError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl FromStr for PathBuf {
+    type Err = PathError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(Path::new(value)?.to_path_buf())
+    }
+}
+
+impl Debug for PathBuf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Debug::fmt(self.as_path(), f)
+    }
+}
+
+impl Display for PathBuf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(self.as_path(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buf(value: &str) -> PathBuf {
+        value.parse().unwrap()
+    }
+
+    #[test_case]
+    fn test_new_validates() {
+        assert_eq!(Err(PathError::Empty), PathBuf::new(String::new()));
+        assert!(PathBuf::new(String::from("/a/b")).is_ok());
+    }
+
+    #[test_case]
+    fn test_push_relative() {
+        let mut path = buf("/home");
+        path.push(Path::new("anon").unwrap());
+        assert_eq!("/home/anon", path.as_str());
+    }
+
+    #[test_case]
+    fn test_push_onto_trailing_slash() {
+        let mut path = buf("/");
+        path.push(Path::new("exe").unwrap());
+        assert_eq!("/exe", path.as_str());
+    }
+
+    #[test_case]
+    fn test_push_absolute_replaces() {
+        let mut path = buf("/home/anon");
+        path.push(Path::new("/etc").unwrap());
+        assert_eq!("/etc", path.as_str());
+    }
+
+    #[test_case]
+    fn test_pop() {
+        let mut path = buf("/home/anon");
+        assert!(path.pop());
+        assert_eq!("/home", path.as_str());
+        assert!(path.pop());
+        assert_eq!("/", path.as_str());
+        assert!(!path.pop());
+        assert_eq!("/", path.as_str());
+    }
+
+    #[test_case]
+    fn test_pop_relative() {
+        let mut path = buf("config");
+        assert!(path.pop());
+        assert_eq!(".", path.as_str());
+        assert!(!path.pop());
+        assert_eq!(".", path.as_str());
+    }
+}
@@ -5,7 +5,7 @@ use essentials::address::PhysicalAddress;
 use crate::acpi::SDTHeader;
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct MADTEntry {
     kind: u8,
     length: u8,
@@ -28,11 +28,83 @@ impl MADTProcessorLocalApic {
     pub fn processor_id(&self) -> u8 {
         self.processor_id
     }
+
+    pub fn apic_id(&self) -> u8 {
+        self.apic_id
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Debug)]
+pub struct MADTIoApic {
+    entry: MADTEntry,
+    io_apic_id: u8,
+    _reserved: u8,
+    address: u32,
+    gsi_base: u32,
+}
+
+impl MADTIoApic {
+    pub fn io_apic_id(&self) -> u8 {
+        self.io_apic_id
+    }
+
+    pub fn address(&self) -> PhysicalAddress {
+        (self.address as usize).into()
+    }
+
+    /// The first global system interrupt that is handled by the I/O APIC.
+    pub fn gsi_base(&self) -> u32 {
+        self.gsi_base
+    }
+}
+
+/// Describes how an ISA interrupt is connected to the I/O APIC, when it differs from the identity
+/// mapping or the ISA polarity and trigger mode.
+#[repr(C, packed)]
+#[derive(Debug)]
+pub struct MADTInterruptSourceOverride {
+    entry: MADTEntry,
+    bus: u8,
+    source: u8,
+    gsi: u32,
+    flags: u16,
+}
+
+impl MADTInterruptSourceOverride {
+    /// The ISA interrupt.
+    pub fn source(&self) -> u8 {
+        self.source
+    }
+
+    pub fn gsi(&self) -> u32 {
+        self.gsi
+    }
+
+    /// Whether the interrupt is active low, `None` when it conforms to the bus.
+    pub fn active_low(&self) -> Option<bool> {
+        match self.flags & 0b11 {
+            0b01 => Some(false),
+            0b11 => Some(true),
+            _ => None,
+        }
+    }
+
+    /// Whether the interrupt is level triggered, `None` when it conforms to the bus.
+    pub fn level_triggered(&self) -> Option<bool> {
+        match (self.flags >> 2) & 0b11 {
+            0b01 => Some(false),
+            0b11 => Some(true),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum MADTEntryKind {
     ProcessorLocal(&'static MADTProcessorLocalApic),
+    IoApic(&'static MADTIoApic),
+    InterruptSourceOverride(&'static MADTInterruptSourceOverride),
     Other(&'static MADTEntry),
 }
 
@@ -86,6 +158,12 @@ impl Iterator for MADTIter {
             0 => Some(MADTEntryKind::ProcessorLocal(unsafe {
                 &*(current_ptr as *const _)
             })),
+            1 => Some(MADTEntryKind::IoApic(unsafe {
+                &*(current_ptr as *const _)
+            })),
+            2 => Some(MADTEntryKind::InterruptSourceOverride(unsafe {
+                &*(current_ptr as *const _)
+            })),
             _ => Some(MADTEntryKind::Other(entry_header)),
         }
     }
@@ -4,9 +4,11 @@ pub mod qemu;
 mod uart_16550;
 
 mod apic;
+mod io_apic;
 mod vga;
 
 pub use apic::*;
+pub use io_apic::*;
 pub use pic_8259::*;
 pub use pit::*;
 pub use uart_16550::*;
@@ -10,6 +10,8 @@ enum Reg {
     InitialCount = 0x380,
     CurrentCount = 0x390,
     Priority = 0x80,
+    InterruptCommandLow = 0x300,
+    InterruptCommandHigh = 0x310,
 }
 
 /// APIC ("Advanced Programmable Interrupt Controller") is the updated Intel standard for the
@@ -77,7 +79,39 @@ impl Apic {
     ///
     /// > In MP systems, the local APIC ID is also used as a processor ID by the BIOS and the operating system.
     pub fn id(&self) -> u32 {
-        unsafe { self.read_register(Reg::Id) }
+        unsafe { self.read_register(Reg::Id) >> 24 }
+    }
+
+    /// Send an INIT inter-processor interrupt, which puts the processor with `apic_id` in the
+    /// wait-for-SIPI state.
+    pub fn send_init(&self, apic_id: u32) {
+        const INIT: u32 = 0b101 << 8;
+        const ASSERT: u32 = 1 << 14;
+
+        self.send_ipi(apic_id, INIT | ASSERT);
+    }
+
+    /// Send a startup inter-processor interrupt, the processor with `apic_id` starts executing in
+    /// real mode at `page * 4096`.
+    pub fn send_startup(&self, apic_id: u32, page: u8) {
+        const STARTUP: u32 = 0b110 << 8;
+        const ASSERT: u32 = 1 << 14;
+
+        self.send_ipi(apic_id, STARTUP | ASSERT | page as u32);
+    }
+
+    /// Write the interrupt command register and wait until the interrupt is delivered.
+    fn send_ipi(&self, apic_id: u32, command: u32) {
+        const DELIVERY_PENDING: u32 = 1 << 12;
+
+        unsafe {
+            self.write_register(Reg::InterruptCommandHigh, apic_id << 24);
+            self.write_register(Reg::InterruptCommandLow, command);
+
+            while self.read_register(Reg::InterruptCommandLow) & DELIVERY_PENDING != 0 {
+                core::hint::spin_loop();
+            }
+        }
     }
 
     pub fn end_of_interrupt(&self) {
@@ -0,0 +1,136 @@
+use essentials::address::PhysicalAddress;
+
+const REGISTER_SELECT: usize = 0x00;
+const REGISTER_WINDOW: usize = 0x10;
+
+#[repr(u32)]
+enum Reg {
+    Id = 0x00,
+    Version = 0x01,
+    RedirectionTable = 0x10,
+}
+
+/// Where and how an interrupt of the I/O APIC is delivered, always with the fixed delivery mode
+/// to the local APIC with the (physical) id `destination`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RedirectionEntry {
+    pub vector: u8,
+    pub destination: u8,
+    pub active_low: bool,
+    pub level_triggered: bool,
+    pub masked: bool,
+}
+
+impl RedirectionEntry {
+    const ACTIVE_LOW: u64 = 1 << 13;
+    const LEVEL_TRIGGERED: u64 = 1 << 15;
+    const MASKED: u64 = 1 << 16;
+
+    pub const fn from_u64(value: u64) -> Self {
+        Self {
+            vector: value as u8,
+            destination: (value >> 56) as u8,
+            active_low: value & Self::ACTIVE_LOW != 0,
+            level_triggered: value & Self::LEVEL_TRIGGERED != 0,
+            masked: value & Self::MASKED != 0,
+        }
+    }
+
+    pub const fn as_u64(&self) -> u64 {
+        let mut value = self.vector as u64 | (self.destination as u64) << 56;
+
+        if self.active_low {
+            value |= Self::ACTIVE_LOW;
+        }
+
+        if self.level_triggered {
+            value |= Self::LEVEL_TRIGGERED;
+        }
+
+        if self.masked {
+            value |= Self::MASKED;
+        }
+
+        value
+    }
+}
+
+/// The I/O APIC routes the interrupts of devices, identified by their global system interrupt
+/// (GSI), to the local APICs.
+///
+/// Resources:
+///  - [82093AA I/O APIC datasheet](https://pdos.csail.mit.edu/6.828/2016/readings/ia32/ioapic.pdf).
+///  - [osdev wiki](https://wiki.osdev.org/IOAPIC).
+pub struct IoApic {
+    addr: PhysicalAddress,
+}
+
+impl IoApic {
+    /// # Safety
+    ///
+    /// `addr` has to be the (identity mapped) address of the registers of an I/O APIC.
+    pub const unsafe fn new(addr: PhysicalAddress) -> Self {
+        Self { addr }
+    }
+
+    unsafe fn read_register(&mut self, reg: u32) -> u32 {
+        core::ptr::write_volatile((self.addr + REGISTER_SELECT).as_usize() as *mut u32, reg);
+        core::ptr::read_volatile((self.addr + REGISTER_WINDOW).as_usize() as *const u32)
+    }
+
+    unsafe fn write_register(&mut self, reg: u32, value: u32) {
+        core::ptr::write_volatile((self.addr + REGISTER_SELECT).as_usize() as *mut u32, reg);
+        core::ptr::write_volatile((self.addr + REGISTER_WINDOW).as_usize() as *mut u32, value)
+    }
+
+    pub fn id(&mut self) -> u8 {
+        unsafe { (self.read_register(Reg::Id as u32) >> 24) as u8 & 0xf }
+    }
+
+    /// The number of interrupts the I/O APIC handles.
+    pub fn redirection_count(&mut self) -> u32 {
+        unsafe { ((self.read_register(Reg::Version as u32) >> 16) & 0xff) + 1 }
+    }
+
+    pub fn redirection(&mut self, index: u32) -> RedirectionEntry {
+        let reg = Reg::RedirectionTable as u32 + index * 2;
+
+        unsafe {
+            let low = self.read_register(reg) as u64;
+            let high = self.read_register(reg + 1) as u64;
+
+            RedirectionEntry::from_u64(high << 32 | low)
+        }
+    }
+
+    pub fn set_redirection(&mut self, index: u32, entry: RedirectionEntry) {
+        let reg = Reg::RedirectionTable as u32 + index * 2;
+        let value = entry.as_u64();
+
+        unsafe {
+            // Masking first, so the interrupt is never delivered with half of the entry.
+            self.write_register(reg, RedirectionEntry::MASKED as u32);
+            self.write_register(reg + 1, (value >> 32) as u32);
+            self.write_register(reg, value as u32);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_redirection_entry_encoding() {
+        let entry = RedirectionEntry {
+            vector: 0x24,
+            destination: 3,
+            active_low: true,
+            level_triggered: false,
+            masked: true,
+        };
+
+        assert_eq!(0x0300_0000_0001_2024, entry.as_u64());
+        assert_eq!(entry, RedirectionEntry::from_u64(entry.as_u64()));
+    }
+}
@@ -19,8 +19,10 @@ impl Pit {
         }
     }
 
+    const FREQUENCY: u32 = 1193182;
+
     pub fn set_freq(&mut self, freq: u32) {
-        let divisor = (1193182 / freq) as u16;
+        let divisor = (Self::FREQUENCY / freq) as u16;
         let [lower, higher] = divisor.to_ne_bytes();
 
         unsafe {
@@ -34,4 +36,36 @@ impl Pit {
     pub fn set_interval(&mut self, interval: Duration) {
         self.set_freq(1000_000 / interval.as_micros() as u32);
     }
+
+    /// Busy wait for `duration`, which is at most ~55ms, by counting down channel 0 once.
+    ///
+    /// This reprograms channel 0, so it cannot be used while the PIT is the timer.
+    pub fn sleep(&mut self, duration: Duration) {
+        let ticks = (duration.as_nanos() * Self::FREQUENCY as u128 / 1_000_000_000)
+            .clamp(1, u16::MAX as u128) as u16;
+        let [lower, higher] = ticks.to_le_bytes();
+
+        unsafe {
+            // channel 0, low and high byte, interrupt on terminal count.
+            self.command.write(0x30);
+
+            self.channel0.write(lower);
+            self.channel0.write(higher);
+
+            let mut previous = ticks;
+
+            loop {
+                // latch the count of channel 0.
+                self.command.write(0x00);
+                let count = u16::from_le_bytes([self.channel0.read(), self.channel0.read()]);
+
+                // The counter wraps around after reaching zero.
+                if count == 0 || count > previous {
+                    break;
+                }
+
+                previous = count;
+            }
+        }
+    }
 }
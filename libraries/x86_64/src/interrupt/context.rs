@@ -85,6 +85,18 @@ impl InterruptedContext {
     pub fn interrupt_stack_frame(&self) -> &InterruptStackFrame {
         &self.interrupt_stack_frame
     }
+
+    pub fn interrupt_stack_frame_mut(&mut self) -> &mut InterruptStackFrame {
+        &mut self.interrupt_stack_frame
+    }
+
+    pub fn registers(&self) -> &RegisterContext {
+        &self.registers
+    }
+
+    pub fn registers_mut(&mut self) -> &mut RegisterContext {
+        &mut self.registers
+    }
 }
 
 #[derive(Clone, Debug, Default)]
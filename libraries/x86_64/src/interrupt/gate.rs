@@ -178,6 +178,16 @@ impl GateDescriptor<Isr> {
         self.set_handler_address(VirtualAddress::from(handler as usize));
         self.enable_as_normal(selector);
     }
+
+    /// Set a handler that is not a Rust function, like an assembly stub.
+    ///
+    /// # Safety
+    ///
+    /// `addr` has to point to code that handles the interrupt like an [`Isr`].
+    pub unsafe fn set_handler_at(&mut self, selector: SegmentSelector, addr: VirtualAddress) {
+        self.set_handler_address(addr);
+        self.enable_as_normal(selector);
+    }
 }
 
 impl GateDescriptor<ErrorIsr> {
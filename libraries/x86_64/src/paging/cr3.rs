@@ -1,5 +1,5 @@
 use core::arch::asm;
-use essentials::address::PhysicalAddress;
+use essentials::address::{PhysicalAddress, VirtualAddress};
 
 const ADDR_MASK: u64 = 0x_000f_ffff_ffff_f000;
 
@@ -15,17 +15,45 @@ pub fn active_page() -> PhysicalAddress {
     PhysicalAddress::from(value & ADDR_MASK)
 }
 
+/// Make the level 4 table at `page_addr` the active one.
+///
+/// # Safety
+///
+/// The table has to map the code, the stack and every other memory that is in use, in the same
+/// way as the active table does.
 #[cfg(target_arch = "x86_64")]
 #[doc(cfg(target_arch = "x86_64"))]
 pub unsafe fn set_active_page(page_addr: PhysicalAddress) {
-    let value = page_addr.as_u64() | (!ADDR_MASK);
-    // clears out the add, without removing the flags.
+    let current: u64;
+    asm!("mov {}, cr3", out(reg) current, options(nomem, nostack, preserves_flags));
+
+    // replaces the address, without removing the flags.
+    let value = (current & !ADDR_MASK) | (page_addr.as_u64() & ADDR_MASK);
     asm!("mov cr3, {}", in(reg) value, options(nostack, preserves_flags));
 }
 
+/// Remove the page at `page_addr` from the TLB.
+///
+/// # Safety
+///
+/// The address is used as a virtual address, so it has to be the address of the page as it is
+/// mapped.
 #[cfg(target_arch = "x86_64")]
 #[doc(cfg(target_arch = "x86_64"))]
 pub unsafe fn flush_page(page_addr: PhysicalAddress) {
     let addr = page_addr.as_usize();
     asm!("invlpg [{}]", in(reg) addr, options(nostack, preserves_flags));
 }
+
+/// Remove the page that contains `addr` from the TLB.
+///
+/// # Safety
+///
+/// The entry of the page in the active table has to be up to date, since the processor walks the
+/// table again on the next access.
+#[cfg(target_arch = "x86_64")]
+#[doc(cfg(target_arch = "x86_64"))]
+pub unsafe fn flush_virtual_page(addr: VirtualAddress) {
+    let addr = addr.as_usize();
+    asm!("invlpg [{}]", in(reg) addr, options(nostack, preserves_flags));
+}
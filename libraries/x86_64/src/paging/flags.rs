@@ -99,7 +99,7 @@ impl PageTableEntryFlags {
     }
 
     pub const fn custom<const INDEX: u64>(&self) -> bool {
-        self.value & (1 << 9) != 0
+        self.value & (1 << Self::map_custom_bit::<INDEX>()) != 0
     }
 
     const fn map_custom_bit<const INDEX: u64>() -> u64 {
@@ -209,6 +209,18 @@ mod tests {
         assert_eq!(53, PageTableEntryFlags::map_custom_bit::<4>());
     }
 
+    #[test_case]
+    fn test_custom_bits_are_independent() {
+        let flags = PageTableEntryFlags::null()
+            .set_custom::<1>(true)
+            .set_custom::<3>(true);
+
+        assert!(!flags.custom::<0>());
+        assert!(flags.custom::<1>());
+        assert!(!flags.custom::<2>());
+        assert!(flags.custom::<3>());
+    }
+
     #[test_case]
     fn test_bitwise_and_eq() {
         let some_flags = PageTableEntryFlags::null()
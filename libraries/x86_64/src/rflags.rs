@@ -11,7 +11,22 @@ impl RFlags {
         value: Self::INTERRUPTS_ENABLED_BIT,
     };
 
+    pub const TRAP: RFlags = Self {
+        value: Self::TRAP_BIT,
+    };
+
+    pub const DIRECTION: RFlags = Self {
+        value: Self::DIRECTION_BIT,
+    };
+
+    pub const ALIGNMENT_CHECK: RFlags = Self {
+        value: Self::ALIGNMENT_CHECK_BIT,
+    };
+
+    const TRAP_BIT: u64 = 1 << 8;
     const INTERRUPTS_ENABLED_BIT: u64 = 1 << 9;
+    const DIRECTION_BIT: u64 = 1 << 10;
+    const ALIGNMENT_CHECK_BIT: u64 = 1 << 18;
 
     #[cfg(target_arch = "x86_64")]
     #[doc(cfg(target_arch = "x86_64"))]
@@ -33,3 +48,13 @@ impl RFlags {
         self.value & Self::INTERRUPTS_ENABLED_BIT != 0
     }
 }
+
+impl core::ops::BitOr for RFlags {
+    type Output = RFlags;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self {
+            value: self.value | rhs.value,
+        }
+    }
+}
@@ -1,6 +1,14 @@
 use core::arch::asm;
 
-use essentials::address::PhysicalAddress;
+use essentials::address::{PhysicalAddress, VirtualAddress};
+
+use crate::{rdmsr::rdmsr, segmentation::SegmentSelector, RFlags};
+
+const EFER: u64 = 0xC000_0080;
+const STAR: u64 = 0xC000_0081;
+const LSTAR: u64 = 0xC000_0082;
+const SFMASK: u64 = 0xC000_0084;
+const KERNEL_GS_BASE: u64 = 0xC000_0102;
 
 /// WRMSR — Write to Model Specific Register
 ///
@@ -42,3 +50,61 @@ pub unsafe fn write_apic_base(addr: PhysicalAddress) {
         (addr.as_u64() & 0xfffff0000) | APIC_ENABLE,
     )
 }
+
+fn wrmsr_u64(ecx: u64, value: u64) {
+    // Safety: only called with MSRs that are defined for every x86_64 processor.
+    unsafe { wrmsr(ecx, value >> 32, value & 0xffff_ffff) }
+}
+
+/// Enable the `syscall` and `sysret` instructions, by setting the SCE bit in the `EFER` MSR.
+///
+/// # Safety
+///
+/// The `STAR`, `LSTAR` and `SFMASK` MSRs should be written before user code can execute
+/// `syscall`.
+pub unsafe fn enable_syscall() {
+    const SYSCALL_ENABLE: u64 = 1;
+
+    let (edx, eax) = rdmsr(EFER);
+    wrmsr(EFER, edx, eax | SYSCALL_ENABLE);
+}
+
+/// Write the segments used by `syscall` and `sysret` to the `STAR` MSR.
+///
+/// `syscall` loads `syscall` into CS and the next entry in the GDT into SS. In 64-bit mode,
+/// `sysret` loads the entry after `sysret` into SS, and the entry after that into CS.
+///
+/// # Safety
+///
+/// Both selectors must point to the segments as described above, in the active GDT.
+pub unsafe fn write_star(syscall: SegmentSelector, sysret: SegmentSelector) {
+    let value = (sysret.as_u16() as u64) << 48 | (syscall.as_u16() as u64) << 32;
+    wrmsr_u64(STAR, value);
+}
+
+/// Write the address that `syscall` jumps to in 64-bit mode to the `LSTAR` MSR.
+///
+/// # Safety
+///
+/// The address must point to a valid system call entry, that never trusts the user stack.
+pub unsafe fn write_lstar(entry: VirtualAddress) {
+    wrmsr_u64(LSTAR, entry.as_u64());
+}
+
+/// Write the flags that are cleared from RFLAGS on `syscall` to the `SFMASK` MSR.
+///
+/// # Safety
+///
+/// The system call entry must be able to deal with the flags that are left as is.
+pub unsafe fn write_sfmask(mask: RFlags) {
+    wrmsr_u64(SFMASK, mask.as_u64());
+}
+
+/// Write the value that `swapgs` exchanges with the GS base to the `KERNEL_GS_BASE` MSR.
+///
+/// # Safety
+///
+/// The system call entry expects this to point at its data for the current processor.
+pub unsafe fn write_kernel_gs_base(addr: VirtualAddress) {
+    wrmsr_u64(KERNEL_GS_BASE, addr.as_u64());
+}
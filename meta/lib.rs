@@ -1,4 +1,7 @@
-use std::process::{Command, Stdio};
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
 
 #[derive(Debug, Default)]
 pub struct RunnerOptions {
@@ -6,6 +9,27 @@ pub struct RunnerOptions {
     pub verbose: bool,
     pub n_proc: Option<usize>,
     pub hide: bool,
+    /// A directory that is archived and loaded as the initial ramdisk.
+    pub initrd_dir: Option<PathBuf>,
+}
+
+/// Archive `dir` as a ustar file that the kernel unpacks into its root filesystem.
+fn create_initrd(dir: &Path) -> PathBuf {
+    let archive = std::env::temp_dir().join(format!("zenix-initrd-{}.tar", std::process::id()));
+
+    let status = Command::new("tar")
+        .arg("--format=ustar")
+        .arg("-cf")
+        .arg(&archive)
+        .arg("-C")
+        .arg(dir)
+        .arg(".")
+        .status()
+        .expect("failed to run tar");
+
+    assert!(status.success(), "failed to archive {}", dir.display());
+
+    archive
 }
 
 pub fn run(opts: &RunnerOptions) {
@@ -21,7 +45,13 @@ pub fn run(opts: &RunnerOptions) {
     cmd.args(["-serial", "stdio"]);
     cmd.args(["-device", "isa-debug-exit"]);
     cmd.args(["-kernel", pre_kernel_path]);
-    cmd.args(["-initrd", kernel_path]);
+
+    // Multiboot modules are passed as a comma separated list, the kernel must be the first one.
+    let initrd = opts.initrd_dir.as_deref().map(create_initrd);
+    match &initrd {
+        Some(archive) => cmd.args(["-initrd", &format!("{kernel_path},{}", archive.display())]),
+        None => cmd.args(["-initrd", kernel_path]),
+    };
 
     // debug options
     cmd.args(["-d", "guest_errors"]);
@@ -70,4 +100,8 @@ pub fn run(opts: &RunnerOptions) {
 
     qemu_proc.wait().unwrap();
     debugger_proc.and_then(|mut proc| proc.wait().ok());
+
+    if let Some(archive) = initrd {
+        let _ = std::fs::remove_file(archive);
+    }
 }
@@ -5,12 +5,14 @@ use runner_common::RunnerOptions;
 
 pub enum CliError {
     UnexpectedArg(String),
+    MissingValue(String),
 }
 
 impl Debug for CliError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             CliError::UnexpectedArg(got) => write!(f, "unexpected argument \"{got}\""),
+            CliError::MissingValue(arg) => write!(f, "missing value for argument \"{arg}\""),
         }
     }
 }
@@ -18,11 +20,17 @@ impl Debug for CliError {
 fn main() -> Result<(), CliError> {
     let mut opts = RunnerOptions::default();
 
-    for arg in env::args().skip(1) {
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
         match arg.as_str() {
             "--verbose" | "-v" => opts.verbose = true,
             "--gdb" | "-d" => opts.gdb = true,
             "--hide" | "-h" => opts.hide = true,
+            "--initrd" | "-i" => {
+                let dir = args.next().ok_or(CliError::MissingValue(arg))?;
+                opts.initrd_dir = Some(dir.into());
+            }
             _ => {
                 return Err(CliError::UnexpectedArg(arg));
             }
@@ -5,11 +5,13 @@ use essentials::FixedVec;
 
 use crate::{
     bump_memory::BumpMemory,
-    multiboot::{MultibootInfo, MultibootMMapEntry},
+    multiboot::{MultibootInfo, MultibootMMapEntry, MultibootModule},
     paging::{align_down, align_up, PHYS_MEM_OFFSET},
     regions::{known_regions, pre_kernel, stack},
 };
 
+const MAX_MODULES: usize = 32;
+
 pub fn finalize_boot_info(bump_memory: BumpMemory, kernel_boot_info: &mut BootInfoData) {
     kernel_boot_info.bump_memory = bump_memory.used_memory();
 
@@ -26,7 +28,11 @@ pub fn setup_boot_info<'a>(
     multiboot_info: &'static MultibootInfo,
     rsdp: Option<u64>,
 ) -> &'static mut BootInfoData {
-    let usable_memory = setup_mmap_info(&mut bump_memory, mmap, kernel_module_region);
+    // The kernel module has already been taken, everything that's left is forwarded to the kernel.
+    let modules = multiboot_info.mods().unwrap_or_default();
+
+    let usable_memory = setup_mmap_info(&mut bump_memory, mmap, kernel_module_region, modules);
+    let (modules_addr, modules_len) = setup_modules(bump_memory, modules);
 
     let boot_info = bump_memory.alloc_struct::<BootInfoData>();
 
@@ -51,9 +57,38 @@ pub fn setup_boot_info<'a>(
         bootloader_name_addr,
         bootloader_name_len,
         rsdp_addr: rsdp.unwrap_or_default(),
+        modules_addr,
+        modules_len,
     })
 }
 
+fn setup_modules(bump_memory: &mut BumpMemory, modules: &[MultibootModule]) -> (u64, u64) {
+    // The command lines are copied first, so that the module structs end up next to each other.
+    let mut cmdlines = FixedVec::<MAX_MODULES, _>::new();
+
+    for module in modules.iter().take(MAX_MODULES) {
+        cmdlines.push(setup_str(bump_memory, module.cmdline()));
+    }
+
+    let mut first_addr: *const BootModule = null();
+
+    for (module, &(cmdline_addr, cmdline_len)) in modules.iter().zip(cmdlines.iter()) {
+        let slot = bump_memory.alloc_struct::<BootModule>();
+
+        if first_addr.is_null() {
+            first_addr = slot.as_ptr();
+        }
+
+        slot.write(BootModule {
+            region: module.as_info_region(),
+            cmdline_addr,
+            cmdline_len,
+        });
+    }
+
+    (first_addr as u64, cmdlines.len() as u64)
+}
+
 fn setup_str(bump_memory: &mut BumpMemory, input: Option<&'static str>) -> (u64, u64) {
     let Some(input) = input else {
         return (0, 0);
@@ -76,9 +111,14 @@ fn setup_mmap_info<'a>(
     bump_memory: &mut BumpMemory,
     mmap: impl Iterator<Item = &'a MultibootMMapEntry> + Copy,
     kernel_module_region: MemoryRegion,
+    modules: &[MultibootModule],
 ) -> &'static [MemoryRegion] {
     let protected_regions_iter = [(kernel_module_region.start, kernel_module_region.size)]
         .into_iter()
+        .chain(modules.iter().map(|module| {
+            let region = module.as_info_region();
+            (region.start, region.size)
+        }))
         .chain(known_regions().map(|entry| (entry.start, entry.size)));
 
     let mut entries = FixedVec::<128, _>::new();
@@ -14,6 +14,19 @@ pub struct MultibootModule {
 }
 
 impl MultibootModule {
+    pub fn cmdline(&self) -> Option<&'static str> {
+        if self.cmdline == 0 {
+            return None;
+        }
+
+        let buff = unsafe {
+            let len = MultibootInfo::str_len(self.cmdline as *const _);
+            core::slice::from_raw_parts(self.cmdline as *const u8, len)
+        };
+
+        core::str::from_utf8(buff).ok()
+    }
+
     pub fn addr(&self) -> u32 {
         self.start
     }
@@ -149,6 +162,10 @@ impl MultibootInfo {
     }
 
     pub fn mods(&self) -> Option<&[MultibootModule]> {
+        if self.flags & (1 << 3) == 0 {
+            return None;
+        }
+
         Some(unsafe {
             core::slice::from_raw_parts(self.mods_addr as *const _, self.mods_len as usize)
         })
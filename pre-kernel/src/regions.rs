@@ -1,4 +1,4 @@
-use bootinfo::MemoryRegion;
+use bootinfo::{MemoryRegion, AP_TRAMPOLINE_ADDR};
 
 use crate::{
     rsdp_detection::{RSDP_ADDR_END, RSDP_ADDR_START},
@@ -66,6 +66,13 @@ pub fn known_regions() -> impl Iterator<Item = KnownRegion> + Clone {
             executable: false,
             mmio: true,
         },
+        KnownRegion {
+            start: AP_TRAMPOLINE_ADDR,
+            size: 0x1000,
+            writable: true,
+            executable: true,
+            mmio: false,
+        },
         KnownRegion {
             start: RSDP_ADDR_START as u64,
             size: (RSDP_ADDR_END - RSDP_ADDR_START) as u64,